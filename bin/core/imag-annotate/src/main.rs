@@ -53,9 +53,11 @@ use libimagentryannotation::error::AnnotationError as AE;
 use libimagentryedit::edit::*;
 use libimagerror::trace::MapErrTrace;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::IntoStoreId;
+use libimagstore::storeid::StoreId;
 use libimagutil::warn_exit::warn_exit;
 
 mod ui;
@@ -110,25 +112,25 @@ fn remove(rt: &Runtime) {
     let annotation_name = scmd.value_of("annotation_name").unwrap(); // safed by clap
     let delete          = scmd.is_present("delete-annotation");
 
-    let mut entry = rt.store()
-        .get(PathBuf::from(entry_name).into_storeid().map_err_trace_exit(1).unwrap())
-        .map_err_trace_exit(1)
-        .unwrap()
-        .ok_or(AE::from("Entry does not exist".to_owned()))
-        .map_err_trace_exit(1)
-        .unwrap();
+    let id = PathBuf::from(entry_name).into_storeid().map_err_trace_exit(1).unwrap();
+
+    // Removing the annotation changes the entry and the annotation, they are written in one
+    // transaction so a crash cannot leave a link which exists on one side only
+    let annotation = rt
+        .store()
+        .transaction(|tx| -> Result<Option<StoreId>, AE> {
+            let mut entry = tx.get(id)?.ok_or(AE::from("Entry does not exist".to_owned()))?;
 
-    let annotation = entry
-        .denotate(rt.store(), annotation_name)
+            entry
+                .denotate(tx.store(), annotation_name)
+                .map(|an| an.map(|an| tx.attach(an).get_location().clone()))
+        })
         .map_err_trace_exit(1)
         .unwrap();
 
     if delete {
         debug!("Deleting annotation object");
-        if let Some(an) = annotation {
-            let loc = an.get_location().clone();
-            drop(an);
-
+        if let Some(loc) = annotation {
             let _ = rt
                 .store()
                 .delete(loc)
@@ -161,7 +163,7 @@ fn list(rt: &Runtime) {
                 .map_err_trace_exit(1)
                 .unwrap()
                 .enumerate()
-                .map(|(i, a)| list_annotation(i, a.map_err_trace_exit(1).unwrap(), with_text))
+                .map(|(i, a)| list_annotation(rt, i, a.map_err_trace_exit(1).unwrap(), with_text))
                 .collect::<Vec<_>>();
         }
 
@@ -173,7 +175,7 @@ fn list(rt: &Runtime) {
                 .map_err_trace_exit(1)
                 .unwrap()
                 .enumerate()
-                .map(|(i, a)| list_annotation(i, a.map_err_trace_exit(1).unwrap(), with_text))
+                .map(|(i, a)| list_annotation(rt, i, a.map_err_trace_exit(1).unwrap(), with_text))
                 .collect::<Vec<_>>();
         }
     }
@@ -181,14 +183,22 @@ fn list(rt: &Runtime) {
     info!("Ok");
 }
 
-fn list_annotation<'a>(i: usize, a: FileLockEntry<'a>, with_text: bool) {
-    if with_text {
-        println!("--- {i: >5} | {id}\n{text}\n\n",
-                 i = i,
-                 id = a.get_location(),
-                 text = a.get_content());
+fn list_annotation<'a>(rt: &Runtime, i: usize, a: FileLockEntry<'a>, with_text: bool) {
+    let record = if with_text {
+        let text = format!("--- {i: >5} | {id}\n{text}\n\n",
+                           i = i,
+                           id = a.get_location(),
+                           text = a.get_content());
+
+        Record::new(text).with("text", a.get_content().clone())
     } else {
-        println!("{: >5} | {}", i, a.get_location());
-    }
+        Record::new(format!("{: >5} | {}", i, a.get_location()))
+    };
+
+    let record = record
+        .with("index", i)
+        .with("id", a.get_location().to_string());
+
+    rt.output().emit(record).map_err_trace_exit(1).unwrap();
 }
 
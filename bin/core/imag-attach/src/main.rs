@@ -0,0 +1,189 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+#[macro_use] extern crate version;
+extern crate clap;
+
+extern crate libimagstore;
+extern crate libimagrt;
+extern crate libimagerror;
+extern crate libimagentryattachment;
+extern crate libimagutil;
+
+mod ui;
+use ui::build_ui;
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Duration;
+
+use libimagentryattachment::attachable::Attachable;
+use libimagentryattachment::blob::collect_garbage;
+use libimagentryattachment::blob::DEFAULT_GRACE_PERIOD_SECS;
+use libimagerror::trace::MapErrTrace;
+use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
+use libimagrt::setup::generate_runtime_setup;
+use libimagstore::store::FileLockEntry;
+use libimagstore::storeid::StoreId;
+
+fn main() {
+    let rt = generate_runtime_setup("imag-attach",
+                                    &version!()[..],
+                                    "Attach files to entries",
+                                    build_ui);
+
+    rt.cli()
+        .subcommand_name()
+        .map(|name| {
+            debug!("Call: {}", name);
+            match name {
+                "add"    => add(&rt),
+                "list"   => list(&rt),
+                "get"    => get(&rt),
+                "remove" => remove(&rt),
+                "gc"     => gc(&rt),
+                _        => {
+                    debug!("Unknown command"); // More error handling
+                },
+            };
+        });
+}
+
+/// Get the entry named by the "id" argument of the subcommand `cmd`, exit if there is none
+fn get_entry<'a>(rt: &'a Runtime, cmd: &str) -> FileLockEntry<'a> {
+    let id = rt.cli()
+        .subcommand_matches(cmd)
+        .and_then(|scmd| scmd.value_of("id"))
+        .map(PathBuf::from)
+        .unwrap(); // saved by clap
+
+    let sid = StoreId::new_baseless(id.clone()).map_err_trace_exit_unwrap(1);
+    match rt.store().get(sid).map_err_trace_exit_unwrap(1) {
+        Some(entry) => entry,
+        None        => {
+            error!("No entry '{}' found", id.display());
+            exit(1)
+        },
+    }
+}
+
+fn add(rt: &Runtime) {
+    let cmd       = rt.cli().subcommand_matches("add").unwrap();
+    let mime      = cmd.value_of("mime").map(String::from);
+    let mut entry = get_entry(rt, "add");
+
+    for path in cmd.values_of("files").unwrap() { // saved by clap
+        let attachment = entry
+            .attach(rt.store(), &PathBuf::from(path), mime.clone())
+            .map_err_trace_exit_unwrap(1);
+        info!("Attached '{}' ({})", attachment.name(), attachment.hash());
+    }
+}
+
+fn list(rt: &Runtime) {
+    let entry = get_entry(rt, "list");
+
+    for attachment in entry.attachments().map_err_trace_exit_unwrap(1) {
+        let text = format!("{} - {} - {} bytes - {}",
+                           attachment.name(),
+                           attachment.mime(),
+                           attachment.size(),
+                           attachment.hash());
+
+        let record = Record::new(text)
+            .with("name", attachment.name().clone())
+            .with("mime", attachment.mime().clone())
+            .with("size", attachment.size())
+            .with("hash", attachment.hash().clone());
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    }
+}
+
+fn get(rt: &Runtime) {
+    let cmd   = rt.cli().subcommand_matches("get").unwrap();
+    let name  = cmd.value_of("name").unwrap(); // saved by clap
+    let entry = get_entry(rt, "get");
+
+    let attachment = match entry.attachment(name).map_err_trace_exit_unwrap(1) {
+        Some(attachment) => attachment,
+        None             => {
+            error!("No attachment '{}' on '{}'", name, entry.get_location());
+            exit(1)
+        },
+    };
+
+    let mut content = attachment.open(rt.store()).map_err_trace_exit_unwrap(1);
+    match cmd.value_of("to") {
+        Some(path) => {
+            let mut file = File::create(path).map_err_trace_exit_unwrap(1);
+            io::copy(&mut content, &mut file).map_err_trace_exit_unwrap(1);
+        },
+        None => {
+            let stdout = io::stdout();
+            io::copy(&mut content, &mut stdout.lock()).map_err_trace_exit_unwrap(1);
+        },
+    }
+}
+
+fn remove(rt: &Runtime) {
+    let cmd       = rt.cli().subcommand_matches("remove").unwrap();
+    let name      = cmd.value_of("name").unwrap(); // saved by clap
+    let mut entry = get_entry(rt, "remove");
+
+    if entry.detach(name).map_err_trace_exit_unwrap(1).is_none() {
+        error!("No attachment '{}' on '{}'", name, entry.get_location());
+        exit(1)
+    }
+}
+
+fn gc(rt: &Runtime) {
+    let dry_run = rt.cli().subcommand_matches("gc").unwrap().is_present("dry-run");
+    let grace   = Duration::from_secs(DEFAULT_GRACE_PERIOD_SECS);
+
+    for hash in collect_garbage(rt.store(), grace, dry_run).map_err_trace_exit_unwrap(1) {
+        if dry_run {
+            let record = Record::new(format!("Would remove {}", hash)).with("hash", hash);
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        } else {
+            info!("Removed {}", hash);
+        }
+    }
+}
+
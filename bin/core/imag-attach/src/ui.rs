@@ -0,0 +1,116 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use clap::{Arg, App, SubCommand};
+
+use libimagutil::cli_validators::is_file;
+
+pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app
+        .subcommand(SubCommand::with_name("add")
+                    .about("Attach files to an entry")
+                    .version("0.1")
+                    .arg(Arg::with_name("id")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The entry to attach the files to")
+                         .value_name("ID"))
+                    .arg(Arg::with_name("files")
+                         .index(2)
+                         .takes_value(true)
+                         .required(true)
+                         .multiple(true)
+                         .help("The files to attach")
+                         .validator(is_file)
+                         .value_name("FILE"))
+                    .arg(Arg::with_name("mime")
+                         .long("mime")
+                         .short("m")
+                         .takes_value(true)
+                         .required(false)
+                         .help("The MIME type of the files. Guessed from the file extension if not passed")
+                         .value_name("MIME"))
+                    )
+
+        .subcommand(SubCommand::with_name("list")
+                    .about("List the attachments of an entry")
+                    .version("0.1")
+                    .arg(Arg::with_name("id")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The entry to list the attachments of")
+                         .value_name("ID"))
+                    )
+
+        .subcommand(SubCommand::with_name("get")
+                    .about("Get the content of an attachment")
+                    .version("0.1")
+                    .arg(Arg::with_name("id")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The entry the file is attached to")
+                         .value_name("ID"))
+                    .arg(Arg::with_name("name")
+                         .index(2)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The name of the attachment")
+                         .value_name("NAME"))
+                    .arg(Arg::with_name("to")
+                         .long("to")
+                         .short("t")
+                         .takes_value(true)
+                         .required(false)
+                         .help("Write the content to this file instead of stdout")
+                         .value_name("PATH"))
+                    )
+
+        .subcommand(SubCommand::with_name("remove")
+                    .about("Remove an attachment from an entry")
+                    .version("0.1")
+                    .arg(Arg::with_name("id")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The entry the file is attached to")
+                         .value_name("ID"))
+                    .arg(Arg::with_name("name")
+                         .index(2)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The name of the attachment")
+                         .value_name("NAME"))
+                    )
+
+        .subcommand(SubCommand::with_name("gc")
+                    .about("Remove the content of files which are not attached to any entry anymore")
+                    .version("0.1")
+                    .arg(Arg::with_name("dry-run")
+                         .long("dry-run")
+                         .short("n")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Only print what would be removed"))
+                    )
+}
+
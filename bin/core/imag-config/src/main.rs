@@ -0,0 +1,402 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+#[macro_use] extern crate version;
+extern crate clap;
+extern crate toml;
+extern crate toml_query;
+extern crate serde_json;
+
+extern crate libimagstore;
+extern crate libimagrt;
+extern crate libimagerror;
+extern crate libimagdiary;
+extern crate libimagentryview;
+
+mod ui;
+use ui::build_ui;
+
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::exit;
+
+use clap::ArgMatches;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::delete::TomlValueDeleteExt;
+
+use libimagerror::trace::MapErrTrace;
+use libimagrt::configuration::{find_user_config, parse_value, ConfigSource, STORE_CONFIG_NAME};
+use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
+use libimagrt::setup::generate_runtime_setup;
+use libimagstore::schema::Schema;
+
+fn main() {
+    let rt = generate_runtime_setup("imag-config",
+                                    &version!()[..],
+                                    "Show, change and validate the configuration",
+                                    build_ui);
+
+    rt.cli()
+        .subcommand_name()
+        .map(|name| {
+            debug!("Call: {}", name);
+            match name {
+                "get"      => get(&rt),
+                "set"      => set(&rt),
+                "unset"    => unset(&rt),
+                "dump"     => dump(&rt),
+                "validate" => validate(&rt),
+                _          => {
+                    debug!("Unknown command"); // More error handling
+                },
+            };
+        });
+}
+
+/// The schemas of the configuration sections, contributed by the crates which read them
+fn schemas() -> Vec<Schema> {
+    vec![
+        libimagrt::configuration::config_schema(),
+        libimagstore::configuration::config_schema(),
+        libimagdiary::config::config_schema(),
+        libimagentryview::config::config_schema(),
+    ]
+}
+
+fn get(rt: &Runtime) {
+    let cmd    = rt.cli().subcommand_matches("get").unwrap();
+    let key    = cmd.value_of("key").unwrap(); // saved by clap
+    let config = effective_config(rt);
+
+    let value = match config.read(key).map_err_trace_exit_unwrap(1) {
+        Some(value) => value,
+        None        => {
+            error!("'{}' is not configured", key);
+            exit(1)
+        },
+    };
+
+    if cmd.is_present("source") {
+        for (path, source) in sources_below(rt, key) {
+            let record = Record::new(format!("{}: {}", path, source))
+                .with("path", path.clone())
+                .with("source", source.to_string());
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        }
+    } else {
+        let text = match *value {
+            Value::String(ref s) => s.clone(),
+            Value::Table(_)      => without_trailing_newline(toml::ser::to_string_pretty(value)
+                                                             .map_err_trace_exit_unwrap(1)),
+            _                    => format!("{}", value),
+        };
+
+        let record = Record::new(text)
+            .with("key", key)
+            .with("value", to_json(value));
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    }
+}
+
+fn set(rt: &Runtime) {
+    let cmd   = rt.cli().subcommand_matches("set").unwrap();
+    let key   = cmd.value_of("key").unwrap(); // saved by clap
+    let value = parse_value(cmd.value_of("value").unwrap()); // saved by clap
+    let path  = config_file(rt, cmd);
+
+    let mut config = read_config_file(&path);
+    let _ = config.insert(key, value).map_err_trace_exit_unwrap(1);
+    write_config_file(&path, &config);
+    info!("Set '{}' in {}", key, path.display());
+}
+
+fn unset(rt: &Runtime) {
+    let cmd  = rt.cli().subcommand_matches("unset").unwrap();
+    let key  = cmd.value_of("key").unwrap(); // saved by clap
+    let path = config_file(rt, cmd);
+
+    let mut config = read_config_file(&path);
+    match config.delete(key).map_err_trace_exit_unwrap(1) {
+        Some(_) => write_config_file(&path, &config),
+        None    => {
+            error!("'{}' is not set in {}", key, path.display());
+            exit(1)
+        },
+    }
+}
+
+fn dump(rt: &Runtime) {
+    let cmd = rt.cli().subcommand_matches("dump").unwrap();
+
+    if !cmd.is_present("effective") {
+        let path = config_file(rt, cmd);
+        let mut content = String::new();
+        let _ = File::open(&path)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err_trace_exit_unwrap(1);
+        let config = read_config_file(&path);
+
+        let record = Record::new(without_trailing_newline(content))
+            .with("path", path.display().to_string())
+            .with("config", to_json(&config));
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    } else if cmd.is_present("sources") {
+        let config = effective_config(rt);
+        for (path, source) in rt.config_sources() {
+            if let Ok(Some(value)) = config.read(path) {
+                let record = Record::new(format!("{} = {} # {}", path, value, source))
+                    .with("key", path.clone())
+                    .with("value", to_json(value))
+                    .with("source", source.to_string());
+
+                rt.output().emit(record).map_err_trace_exit_unwrap(1);
+            }
+        }
+    } else {
+        let config = effective_config(rt);
+        let text   = toml::ser::to_string_pretty(config).map_err_trace_exit_unwrap(1);
+        let record = Record::new(without_trailing_newline(text)).with("config", to_json(config));
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    }
+}
+
+fn validate(rt: &Runtime) {
+    let config  = effective_config(rt);
+    let schemas = schemas();
+
+    let violations = schemas
+        .iter()
+        .flat_map(|schema| schema.verify(config))
+        .collect::<Vec<_>>();
+
+    for violation in violations.iter() {
+        let source = sources_below(rt, violation.path())
+            .into_iter()
+            .next()
+            .map(|(_, source)| source.to_string());
+
+        let text = match source {
+            Some(ref source) => format!("{} (from {})", violation, source),
+            None             => format!("{}", violation),
+        };
+
+        let record = Record::new(text)
+            .with("path", violation.path())
+            .with("violation", violation.to_string())
+            .with("source", source);
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    }
+
+    if let Some(table) = config.as_table() {
+        for section in table.keys().filter(|k| !schemas.iter().any(|s| s.section() == k.as_str())) {
+            let text   = format!("Section '{}' is not checked, there is no schema for it", section);
+            let record = Record::new(text).with("unchecked", section.clone());
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        }
+    }
+
+    if !violations.is_empty() {
+        error!("Found {} problems in the configuration", violations.len());
+        exit(1)
+    }
+}
+
+/// Convert a configuration value for the machine readable output modes
+fn to_json(value: &Value) -> serde_json::Value {
+    serde_json::to_value(value).map_err_trace_exit_unwrap(1)
+}
+
+/// Records are printed with a newline, the serialized toml already ends with one
+fn without_trailing_newline(mut s: String) -> String {
+    if s.ends_with('\n') {
+        let _ = s.pop();
+    }
+    s
+}
+
+/// Get the effective configuration, exit if there is none
+fn effective_config<'a>(rt: &'a Runtime) -> &'a Value {
+    match rt.config() {
+        Some(config) => config,
+        None         => {
+            error!("No configuration found");
+            exit(1)
+        },
+    }
+}
+
+/// Get the sources of the values at and below `path`
+fn sources_below<'a>(rt: &'a Runtime, path: &str) -> Vec<(&'a String, &'a ConfigSource)> {
+    let below = format!("{}.", path);
+    rt.config_sources()
+        .iter()
+        .filter(|&(p, _)| p == path || p.starts_with(&below))
+        .collect()
+}
+
+/// Get the configuration file `set`, `unset` and `dump` work on: the one of the store if `--store`
+/// is passed, the one of the user otherwise
+fn config_file(rt: &Runtime, cmd: &ArgMatches) -> PathBuf {
+    if cmd.is_present("store") {
+        return rt.store().path().join(STORE_CONFIG_NAME);
+    }
+
+    let searchpath = rt
+        .cli()
+        .value_of(Runtime::arg_config_name())
+        .map_or_else(|| rt.rtp().clone(), PathBuf::from);
+
+    find_user_config(&searchpath)
+        .map(|(path, _)| path)
+        .unwrap_or_else(|| rt.rtp().join("imagrc.toml"))
+}
+
+/// Read the configuration file at `path`, an empty configuration if there is no file
+fn read_config_file(path: &PathBuf) -> Value {
+    if !path.exists() {
+        return Value::Table(Default::default());
+    }
+
+    let mut content = String::new();
+    let _ = File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .map_err_trace_exit_unwrap(1);
+
+    toml::de::from_str(&content).map_err_trace_exit_unwrap(1)
+}
+
+/// Write `config` to the configuration file at `path`
+///
+/// The file is serialized from `config`, so comments in it would be lost. Files with comments are
+/// not changed, the user has to edit them by hand.
+///
+/// The content is written to a temporary file next to the configuration file first, which then
+/// replaces it. So the configuration file is never left half-written.
+fn write_config_file(path: &PathBuf, config: &Value) {
+    // The configuration might be a symlink into a dotfile repository, which must stay one
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+
+    if path.exists() {
+        let mut content = String::new();
+        let _ = File::open(&path)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err_trace_exit_unwrap(1);
+
+        if has_comments(&content) {
+            error!("{} contains comments, which would be lost", path.display());
+            error!("Please edit it by hand");
+            exit(1)
+        }
+    }
+
+    let content = toml::ser::to_string_pretty(config).map_err_trace_exit_unwrap(1);
+    let tmp     = {
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        PathBuf::from(tmp)
+    };
+
+    let _ = File::create(&tmp)
+        .and_then(|f| match fs::metadata(&path) {
+            Ok(meta) => f.set_permissions(meta.permissions()).map(|_| f),
+            Err(_)   => Ok(f), // There is no configuration file yet
+        })
+        .and_then(|mut f| f.write_all(content.as_bytes()).and_then(|_| f.sync_all()))
+        .and_then(|_| fs::rename(&tmp, &path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            e
+        })
+        .map_err_trace_exit_unwrap(1);
+}
+
+/// Check whether the TOML document `content` contains a comment, that is a `#` outside of strings
+fn has_comments(content: &str) -> bool {
+    let chars = content.chars().collect::<Vec<char>>();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '#' => return true,
+
+            quote @ '"' | quote @ '\'' => {
+                let len   = if chars[i..].starts_with(&[quote, quote, quote]) { 3 } else { 1 };
+                let delim = vec![quote; len];
+
+                i += len;
+                while i < chars.len() && !chars[i..].starts_with(&delim) {
+                    // Basic strings can contain escaped quotes, literal strings cannot
+                    i += if quote == '"' && chars[i] == '\\' { 2 } else { 1 };
+                }
+                i += len;
+            },
+
+            _ => i += 1,
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::has_comments;
+
+    #[test]
+    fn test_has_comments() {
+        assert!(has_comments("# A comment\na = 1"));
+        assert!(has_comments("a = 1 # A comment"));
+        assert!(has_comments("a = \"\" # A comment"));
+        assert!(has_comments(r##"a = "\"#" # A comment"##));
+
+        assert!(!has_comments("a = 1"));
+        assert!(!has_comments("a = \"#1\"\nb = '#2'"));
+        assert!(!has_comments("a = \"\"\"\n# not a comment\n\"\"\"\nb = '''#'''"));
+    }
+
+}
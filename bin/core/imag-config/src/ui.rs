@@ -0,0 +1,102 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use clap::{Arg, App, SubCommand};
+
+pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app
+        .subcommand(SubCommand::with_name("get")
+                    .about("Print the effective value of a configuration key")
+                    .version("0.1")
+                    .arg(Arg::with_name("key")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The key, for example 'store.trash.enabled'")
+                         .value_name("KEY"))
+                    .arg(Arg::with_name("source")
+                         .long("source")
+                         .short("s")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Print where the value (or each value below the key) comes from instead"))
+                    )
+
+        .subcommand(SubCommand::with_name("set")
+                    .about("Set a configuration key in the configuration file")
+                    .version("0.1")
+                    .arg(Arg::with_name("key")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The key, for example 'store.trash.enabled'")
+                         .value_name("KEY"))
+                    .arg(Arg::with_name("value")
+                         .index(2)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The value, read as TOML value if possible ('true', '5', '[\"a\"]'), as string otherwise")
+                         .value_name("VALUE"))
+                    .arg(store_arg())
+                    )
+
+        .subcommand(SubCommand::with_name("unset")
+                    .about("Remove a configuration key from the configuration file")
+                    .version("0.1")
+                    .arg(Arg::with_name("key")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The key, for example 'store.trash.enabled'")
+                         .value_name("KEY"))
+                    .arg(store_arg())
+                    )
+
+        .subcommand(SubCommand::with_name("dump")
+                    .about("Print the configuration file")
+                    .version("0.1")
+                    .arg(Arg::with_name("effective")
+                         .long("effective")
+                         .short("e")
+                         .takes_value(false)
+                         .required(false)
+                         .conflicts_with("store")
+                         .help("Print the effective configuration, merged from all files, the environment and the overrides"))
+                    .arg(Arg::with_name("sources")
+                         .long("sources")
+                         .takes_value(false)
+                         .required(false)
+                         .requires("effective")
+                         .help("Print each value with where it comes from"))
+                    .arg(store_arg())
+                    )
+
+        .subcommand(SubCommand::with_name("validate")
+                    .about("Check the effective configuration for unknown keys and wrong values")
+                    .version("0.1")
+                    )
+}
+
+fn store_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("store")
+        .long("store-config")
+        .takes_value(false)
+        .required(false)
+        .help("Use the configuration file of the store instead of the one of the user")
+}
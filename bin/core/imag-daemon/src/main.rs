@@ -0,0 +1,111 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+#[macro_use] extern crate version;
+extern crate clap;
+
+extern crate libimagrt;
+extern crate libimagstore;
+extern crate libimagerror;
+extern crate libimagfulltext;
+
+mod ui;
+use ui::build_ui;
+
+use std::process::exit;
+use std::time::Duration;
+
+use libimagrt::setup::generate_runtime_setup;
+use libimagrt::output::Record;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::trace::trace_error;
+use libimagstore::watch::DEFAULT_INTERVAL_MS;
+use libimagstore::watch::StoreEventKind;
+
+fn main() {
+    let mut rt = generate_runtime_setup("imag-daemon",
+                                        &version!()[..],
+                                        "Watch the store and keep indexes up to date",
+                                        build_ui);
+
+    // The full-text index is kept up to date by the daemon, for the changes of all processes
+    if libimagfulltext::hook::is_enabled(rt.config()) {
+        debug!("Registering full-text index hook");
+        libimagfulltext::hook::register(rt.store_mut()).map_err_trace_exit_unwrap(1);
+    }
+
+    let interval = match rt.cli().value_of("interval").map(|i| i.parse::<u64>()) {
+        Some(Ok(ms)) => ms,
+        Some(Err(e)) => {
+            error!("Cannot parse interval: {}", e);
+            exit(1)
+        },
+        None => DEFAULT_INTERVAL_MS,
+    };
+    let print = rt.cli().is_present("print");
+
+    let watcher = rt
+        .store()
+        .watch()
+        .map_err_trace_exit_unwrap(1)
+        .with_interval(Duration::from_millis(interval));
+
+    info!("Watching store at {:?}", rt.store().path());
+    for event in watcher {
+        let event = event.map_err_trace_exit_unwrap(1);
+        debug!("Store event: {:?}", event);
+
+        if print {
+            let kind = match event.kind() {
+                StoreEventKind::Created  => "created",
+                StoreEventKind::Modified => "modified",
+                StoreEventKind::Removed  => "removed",
+            };
+            let record = Record::new(format!("{} {}", kind, event.id()))
+                .with("kind", kind)
+                .with("id", event.id().to_string());
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        }
+
+        // Changes made by this process already went through the store
+        if event.is_external() {
+            if let Err(e) = rt.store().apply_external_event(&event) {
+                trace_error(&e);
+            }
+        }
+    }
+}
@@ -35,22 +35,27 @@
 extern crate clap;
 extern crate toml;
 extern crate toml_query;
+extern crate serde_json;
 #[macro_use] extern crate version;
 
 extern crate libimagrt;
 extern crate libimagerror;
 extern crate libimagentrylink;
 extern crate libimagstore;
+extern crate libimagmodules;
 
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::output::Record;
 use libimagerror::trace::MapErrTrace;
-use libimagstore::store::FileLockEntry;
+use libimagstore::store::Entry;
 use libimagstore::storeid::StoreId;
-use libimagstore::iter::get::StoreIdGetIteratorExtension;
-use libimagstore::error::StoreError as Error;
+use libimagstore::migration::MigrationRegistry;
+use libimagstore::schema::SchemaViolation;
 use libimagentrylink::internal::*;
 
 use toml::Value;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
 use toml_query::read::TomlValueReadExt;
 
 use std::collections::BTreeMap;
@@ -65,11 +70,13 @@ struct Diagnostic {
     pub overall_byte_size: usize,
     pub verified: bool,
     pub num_internal_links: usize,
+    pub needs_migration: bool,
+    pub schema_violations: Vec<SchemaViolation>,
 }
 
-impl<'a> From<FileLockEntry<'a>> for Diagnostic {
+impl Diagnostic {
 
-    fn from(entry: FileLockEntry<'a>) -> Diagnostic {
+    fn new(entry: Entry, registry: &MigrationRegistry) -> Diagnostic {
         Diagnostic {
             id: entry.get_location().clone(),
             entry_store_version: entry
@@ -89,6 +96,8 @@ impl<'a> From<FileLockEntry<'a>> for Diagnostic {
             overall_byte_size: entry.to_str().as_str().len(),
             verified: entry.verify().is_ok(),
             num_internal_links: entry.get_internal_links().map(Iterator::count).unwrap_or(0),
+            needs_migration: registry.needs_migration(&entry).unwrap_or(false),
+            schema_violations: entry.schema_violations().map_err_trace_exit_unwrap(1),
         }
     }
 }
@@ -99,17 +108,16 @@ fn main() {
                                     "Print diagnostics about imag and the imag store",
                                     ui::build_ui);
 
+    let registry = libimagmodules::migration_registry().map_err_trace_exit_unwrap(1);
+    libimagmodules::register_schemas().map_err_trace_exit_unwrap(1);
+
+    // Copies, so the entries are neither locked nor written back
     let diags = rt.store()
         .entries()
         .map_err_trace_exit(1)
         .unwrap()
-        .into_get_iter(rt.store())
-        .map(|e| {
-            e.map_err_trace_exit_unwrap(1)
-                .ok_or(Error::from("Unable to get entry".to_owned()))
-                .map_err_trace_exit_unwrap(1)
-        })
-        .map(Diagnostic::from)
+        .map(|id| rt.store().get_copy(id).map_err_trace_exit_unwrap(1))
+        .map(|e| Diagnostic::new(e, &registry))
         .collect::<Vec<_>>();
 
     let mut version_counts        : BTreeMap<String, usize> = BTreeMap::new();
@@ -121,6 +129,8 @@ fn main() {
     let mut unverified_count      = 0;
     let mut num_internal_links    = 0;
     let mut max_internal_links : Option<(usize, StoreId)> = None;
+    let mut needs_migration       : Vec<StoreId> = vec![];
+    let mut schema_violations     : Vec<(StoreId, Vec<SchemaViolation>)> = vec![];
 
     for diag in diags.iter() {
         sum_header_sections     += diag.header_sections;
@@ -149,43 +159,96 @@ fn main() {
                 max_internal_links = Some((diag.num_internal_links, diag.id.clone()));
             }
         }
+
+        if diag.needs_migration {
+            needs_migration.push(diag.id.clone());
+        }
+
+        if !diag.schema_violations.is_empty() {
+            schema_violations.push((diag.id.clone(), diag.schema_violations.clone()));
+        }
     }
 
     let n = diags.len();
 
-    println!("imag version {}", version!());
-    println!("");
-    println!("{} entries", n);
+    // Lines for the text output mode, the same data goes into the fields of the record
+    let mut lines  = vec![format!("imag version {}", version!()), String::new()];
+    let mut fields = Map::new();
+    let _ = fields.insert(String::from("version"), JsonValue::from(version!()));
+    let _ = fields.insert(String::from("entries"), JsonValue::from(n));
+
+    lines.push(format!("{} entries", n));
+    let mut versions = Map::new();
     for (k, v) in version_counts {
-        println!("{} entries with store version '{}'", v, k);
+        lines.push(format!("{} entries with store version '{}'", v, k));
+        let _ = versions.insert(k, JsonValue::from(v));
     }
+    let _ = fields.insert(String::from("store_versions"), JsonValue::from(versions));
+
     if n != 0 {
-        println!("{} header sections in the average entry", sum_header_sections / n);
-        println!("{} average content bytecount", sum_bytecount_content / n);
-        println!("{} average overall bytecount", sum_overall_byte_size / n);
+        lines.push(format!("{} header sections in the average entry", sum_header_sections / n));
+        lines.push(format!("{} average content bytecount", sum_bytecount_content / n));
+        lines.push(format!("{} average overall bytecount", sum_overall_byte_size / n));
+        let _ = fields.insert(String::from("average_header_sections"), JsonValue::from(sum_header_sections / n));
+        let _ = fields.insert(String::from("average_content_bytecount"), JsonValue::from(sum_bytecount_content / n));
+        let _ = fields.insert(String::from("average_overall_bytecount"), JsonValue::from(sum_overall_byte_size / n));
+
         if let Some((num, path)) = max_overall_byte_size {
-            println!("Largest Entry ({bytes} bytes): {path}",
-                bytes = num,
-                path = path
-                    .into_pathbuf()
-                    .map_err_trace_exit_unwrap(1)
-                    .to_str()
-                    .unwrap_or("Failed converting path to string")
-            );
+            let path = path_to_string(path);
+            lines.push(format!("Largest Entry ({bytes} bytes): {path}", bytes = num, path = path));
+            let _ = fields.insert(String::from("largest_entry"), JsonValue::from(path));
+            let _ = fields.insert(String::from("largest_entry_bytecount"), JsonValue::from(num));
         }
-        println!("{} average internal link count per entry", num_internal_links / n);
+
+        lines.push(format!("{} average internal link count per entry", num_internal_links / n));
+        let _ = fields.insert(String::from("average_internal_links"), JsonValue::from(num_internal_links / n));
+
         if let Some((num, path)) = max_internal_links {
-            println!("Entry with most internal links ({count}): {path}",
-                     count = num,
-                     path = path
-                        .into_pathbuf()
-                        .map_err_trace_exit_unwrap(1)
-                        .to_str()
-                        .unwrap_or("Failed converting path to string")
-            );
+            let path = path_to_string(path);
+            lines.push(format!("Entry with most internal links ({count}): {path}", count = num, path = path));
+            let _ = fields.insert(String::from("most_internal_links_entry"), JsonValue::from(path));
+            let _ = fields.insert(String::from("most_internal_links"), JsonValue::from(num));
         }
-        println!("{} verified entries", verified_count);
-        println!("{} unverified entries", unverified_count);
+
+        lines.push(format!("{} verified entries", verified_count));
+        lines.push(format!("{} unverified entries", unverified_count));
+        let _ = fields.insert(String::from("verified"), JsonValue::from(verified_count));
+        let _ = fields.insert(String::from("unverified"), JsonValue::from(unverified_count));
+
+        lines.push(format!("{} entries need migration (see 'imag-store migrate')", needs_migration.len()));
+        let mut migrations = vec![];
+        for id in needs_migration {
+            lines.push(format!("    {}", id));
+            migrations.push(JsonValue::from(id.to_string()));
+        }
+        let _ = fields.insert(String::from("needs_migration"), JsonValue::from(migrations));
+
+        lines.push(format!("{} entries violate the header schemas of their modules", schema_violations.len()));
+        let mut violating = Map::new();
+        for (id, violations) in schema_violations {
+            lines.push(format!("    {}", id));
+            let mut descriptions = vec![];
+            for violation in violations {
+                lines.push(format!("        {}", violation));
+                descriptions.push(JsonValue::from(violation.to_string()));
+            }
+            let _ = violating.insert(id.to_string(), JsonValue::from(descriptions));
+        }
+        let _ = fields.insert(String::from("schema_violations"), JsonValue::from(violating));
     }
+
+    let record = fields
+        .into_iter()
+        .fold(Record::new(lines.join("\n")), |record, (key, value)| record.with(key, value));
+
+    rt.output().emit(record).map_err_trace_exit_unwrap(1);
+}
+
+fn path_to_string(id: StoreId) -> String {
+    id.into_pathbuf()
+        .map_err_trace_exit_unwrap(1)
+        .to_str()
+        .map(String::from)
+        .unwrap_or_else(|| String::from("Failed converting path to string"))
 }
 
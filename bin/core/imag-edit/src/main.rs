@@ -38,6 +38,7 @@ extern crate clap;
 
 extern crate libimagentryedit;
 extern crate libimagerror;
+extern crate libimaginteraction;
 extern crate libimagrt;
 extern crate libimagstore;
 extern crate libimagutil;
@@ -47,8 +48,15 @@ use std::path::PathBuf;
 use libimagentryedit::edit::*;
 use libimagentryedit::error::EditError as EE;
 use libimagerror::trace::MapErrTrace;
+use libimagerror::trace::trace_error_exit;
+use libimaginteraction::ask::ask_bool;
+use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagstore::error::StoreErrorKind as SEK;
+use libimagstore::store::Entry;
+use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::IntoStoreId;
+use libimagutil::merge::three_way_merge;
 
 mod ui;
 
@@ -78,10 +86,48 @@ fn main() {
         ::std::process::exit(1);
     }
 
+    let base = entry.get_content().clone();
+
     let _ = entry
         .edit_content(&rt)
         .map_err_trace_exit_unwrap(1);
 
+    match rt.store().update(&mut entry) {
+        Ok(_) => {},
+        Err(e) => match *e.kind() {
+            SEK::EntryConflict(_, _, ref theirs) => merge(&rt, &mut entry, &base, theirs),
+            _ => trace_error_exit(&e, 1),
+        },
+    }
+
     info!("Ok");
 }
 
+/// Merge our changes with the version of the entry which was written while we were editing it
+fn merge<'a>(rt: &'a Runtime, entry: &mut FileLockEntry<'a>, base: &str, theirs: &Entry) {
+    let question = format!("{} was changed while it was edited. Merge the changes?",
+                           entry.get_location());
+    if !ask_bool(&question, Some(true)) {
+        info!("Keeping the other version, discarding changes");
+        ::std::process::exit(1);
+    }
+
+    let merge = three_way_merge(base, entry.get_content(), theirs.get_content());
+    let conflicts = merge.conflicts();
+    *entry.get_content_mut() = merge.into_text();
+
+    // The header is not edited by imag-edit, so we take theirs
+    *entry.get_header_mut() = theirs.get_header().clone();
+
+    if conflicts != 0 {
+        warn!("{} conflicting changes, please resolve them", conflicts);
+        let _ = entry
+            .edit_content(rt)
+            .map_err_trace_exit_unwrap(1);
+    }
+
+    let _ = rt.store()
+        .update_resolved(entry, theirs)
+        .map_err_trace_exit_unwrap(1);
+}
+
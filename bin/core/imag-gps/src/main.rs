@@ -52,6 +52,7 @@ use libimagentrygps::types::*;
 use libimagentrygps::entry::*;
 use libimagrt::setup::generate_runtime_setup;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagutil::warn_exit::warn_exit;
 use libimagerror::trace::MapErrTrace;
 use libimagstore::storeid::IntoStoreId;
@@ -139,7 +140,7 @@ fn remove(rt: &Runtime) {
         .map_err_trace_exit_unwrap(1); // The parsing of the deleted values failed
 
     if scmd.is_present("print-removed") {
-        println!("{}", removed_value);
+        rt.output().emit(coordinates_record(&removed_value)).map_err_trace_exit_unwrap(1);
     }
 
     info!("Ok");
@@ -168,7 +169,18 @@ fn get(rt: &Runtime) {
             exit(1)
         });
 
-    println!("{}", value);
+    rt.output().emit(coordinates_record(&value)).map_err_trace_exit_unwrap(1);
     info!("Ok");
 }
 
+/// The output record for `coordinates`, with degree, minutes and seconds as array per field
+fn coordinates_record(coordinates: &Coordinates) -> Record {
+    fn parts(value: &GPSValue) -> Vec<i8> {
+        vec![value.degree(), value.minutes(), value.seconds()]
+    }
+
+    Record::new(format!("{}", coordinates))
+        .with("longitude", parts(coordinates.longitude()))
+        .with("latitude", parts(coordinates.latitude()))
+}
+
@@ -39,10 +39,18 @@ extern crate regex;
 extern crate libimagstore;
 extern crate libimagrt;
 extern crate libimagerror;
+extern crate libimagfulltext;
+
+use std::path::PathBuf;
 
 use regex::Regex;
 
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
+use libimagfulltext::index::FullTextIndex;
+use libimagfulltext::query::Query;
+use libimagstore::storeid::StoreId;
 use libimagstore::iter::get::StoreIdGetIteratorExtension;
 use libimagstore::store::Entry;
 use libimagerror::trace::MapErrTrace;
@@ -60,11 +68,19 @@ fn main() {
                                     "grep through entries text",
                                     ui::build_ui);
 
+    if let Some("reindex") = rt.cli().subcommand_name() {
+        return reindex(&rt);
+    }
+
     let opts = Options {
         files_with_matches    : rt.cli().is_present("files-with-matches"),
         count                 : rt.cli().is_present("count"),
     };
 
+    if rt.cli().is_present("index") {
+        return query_index(&rt, &opts);
+    }
+
     let mut count : usize = 0;
 
     let pattern = rt
@@ -81,35 +97,97 @@ fn main() {
         .into_get_iter(rt.store())
         .filter_map(|res| res.map_err_trace_exit_unwrap(1))
         .filter(|entry| pattern.is_match(entry.get_content()))
-        .map(|entry| show(&entry, &pattern, &opts, &mut count))
+        .map(|entry| show(&rt, &entry, &pattern, &opts, &mut count))
         .count();
 
-    if opts.count {
-        println!("{}", count);
+    let record = if opts.count {
+        Record::new(format!("{}", count)).with("count", count)
     } else {
-        println!("Processed {} files, {} matches, {} nonmatches",
-                 overall_count,
-                 count,
-                 overall_count - count);
+        let text = format!("Processed {} files, {} matches, {} nonmatches",
+                           overall_count,
+                           count,
+                           overall_count - count);
+
+        Record::new(text)
+            .with("files", overall_count)
+            .with("matches", count)
+            .with("nonmatches", overall_count - count)
+    };
+
+    rt.output().emit(record).map_err_trace_exit_unwrap(1);
+}
+
+fn reindex(rt: &Runtime) {
+    let index = FullTextIndex::build(rt.store()).map_err_trace_exit_unwrap(1);
+    index.save(rt.store()).map_err_trace_exit_unwrap(1);
+    let record = Record::new(format!("Indexed {} entries", index.len())).with("entries", index.len());
+    rt.output().emit(record).map_err_trace_exit_unwrap(1);
+}
+
+fn query_index(rt: &Runtime, opts: &Options) {
+    let query = rt
+        .cli()
+        .value_of("pattern")
+        .map(Query::parse)
+        .unwrap() // ensured by clap
+        .map_err_trace_exit_unwrap(1);
+
+    let results = FullTextIndex::load(rt.store())
+        .map_err_trace_exit_unwrap(1)
+        .search(&query);
+
+    if opts.count {
+        let record = Record::new(format!("{}", results.len())).with("count", results.len());
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        return;
+    }
+
+    for result in results {
+        let id = StoreId::new_baseless(PathBuf::from(result.id))
+            .map_err_trace_exit_unwrap(1)
+            .with_base(rt.store().path().clone());
+
+        let record = if opts.files_with_matches {
+            Record::new(format!("{}", id))
+        } else {
+            Record::new(format!("{:.3} {}", result.score, id)).with("score", result.score)
+        };
+
+        let record = record.with("id", id.to_string());
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        rt.report_touched(&id).map_err_trace_exit_unwrap(1);
     }
 }
 
-fn show(e: &Entry, re: &Regex, opts: &Options, count: &mut usize) {
+fn show(rt: &Runtime, e: &Entry, re: &Regex, opts: &Options, count: &mut usize) {
+    rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
+    let id = e.get_location().to_string();
+
     if opts.files_with_matches {
-        println!("{}", e.get_location());
+        let record = Record::new(id.clone()).with("id", id);
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
     } else if opts.count {
         *count += 1;
     } else {
-        println!("{}:", e.get_location());
-        for capture in re.captures_iter(e.get_content()) {
-            for mtch in capture.iter() {
-                if let Some(m) = mtch {
-                    println!(" '{}'", m.as_str());
-                }
-            }
-        }
-
-        println!("");
+        let matches : Vec<String> = re
+            .captures_iter(e.get_content())
+            .flat_map(|capture| {
+                capture
+                    .iter()
+                    .filter_map(|mtch| mtch.map(|m| String::from(m.as_str())))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let text = matches
+            .iter()
+            .fold(format!("{}:\n", id), |text, m| format!("{} '{}'\n", text, m));
+
+        let record = Record::new(text)
+            .with("id", id)
+            .with("matches", matches);
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
     }
 }
 
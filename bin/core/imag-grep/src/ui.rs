@@ -17,10 +17,23 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use clap::{Arg, App};
+use clap::{Arg, App, AppSettings, SubCommand};
+
+use libimagrt::runtime::Runtime;
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(Runtime::arg_ids_out())
+
+        .arg(Arg::with_name("index")
+             .long("index")
+             .short("i")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .help("Query the full-text index instead of matching a regex. The pattern is a query: terms, \"phrases\", prefix*, AND, OR, NOT/-term and parentheses. Results are ranked."))
+
         .arg(Arg::with_name("files-with-matches")
              .long("files-with-matches")
              .short("l")
@@ -44,4 +57,8 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .multiple(false)
              .value_name("PATTERN")
              .help("Pattern to search for. Regex is supported, multiple patterns are not."))
+
+        .subcommand(SubCommand::with_name("reindex")
+                   .about("Rebuild the full-text index from all entries in the store")
+                   .version("0.1"))
 }
@@ -57,11 +57,13 @@ use libimagentrylink::external::ExternalLinker;
 use libimagentrylink::internal::InternalLinker;
 use libimagentrylink::internal::store_check::StoreLinkConsistentExt;
 use libimagentrylink::error::LinkError as LE;
-use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
+use libimagerror::trace::{MapErrTrace, trace_error};
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagstore::error::StoreError;
 use libimagstore::store::FileLockEntry;
+use libimagstore::store::Transaction;
 use libimagutil::warn_exit::warn_exit;
 use libimagutil::warn_result::*;
 
@@ -110,59 +112,60 @@ fn main() {
         .map_err_trace_exit(1);
 }
 
-fn get_entry_by_name<'a>(rt: &'a Runtime, name: &str) -> Result<Option<FileLockEntry<'a>>, StoreError> {
+fn get_entry_by_name<'a>(tx: &Transaction<'a>, name: &str) -> Result<Option<FileLockEntry<'a>>, StoreError> {
     use libimagstore::storeid::StoreId;
 
-    StoreId::new(Some(rt.store().path().clone()), PathBuf::from(name))
-        .and_then(|id| rt.store().get(id))
+    StoreId::new(Some(tx.store().path().clone()), PathBuf::from(name))
+        .and_then(|id| tx.get(id))
 }
 
 fn link_from_to<'a, I>(rt: &'a Runtime, from: &'a str, to: I)
     where I: Iterator<Item = &'a str>
 {
-    let mut from_entry = match get_entry_by_name(rt, from) {
-        Ok(Some(e)) => e,
-        Ok(None)    => warn_exit("No 'from' entry", 1),
-        Err(e)      => trace_error_exit(&e, 1),
-    };
-
-    for entry in to {
-        if PathBuf::from(entry).exists() {
-            debug!("Linking externally: {:?} -> {:?}", from, entry);
-            let url = Url::parse(entry).map_err_trace_exit_unwrap(1);
-            let _ = from_entry
-                .add_external_link(rt.store(), url)
-                .map_err_trace_exit_unwrap(1);
-        } else {
-            debug!("Linking internally: {:?} -> {:?}", from, entry);
-            let mut to_entry = match get_entry_by_name(rt, entry) {
-                Ok(Some(e)) => e,
-                Ok(None)    => {
-                    warn!("No 'to' entry: {}", entry);
-                    ::std::process::exit(1)
-                },
-                Err(e)      => trace_error_exit(&e, 1),
-            };
-            let _ = from_entry
-                .add_internal_link(&mut to_entry)
-                .map_err_trace_exit_unwrap(1);
+    // A link changes both entries, they are written in one transaction so a crash cannot leave
+    // a link which exists on one side only
+    rt.store().transaction(|tx| -> Result<(), LE> {
+        let mut from_entry = match get_entry_by_name(tx, from)? {
+            Some(e) => e,
+            None    => warn_exit("No 'from' entry", 1),
+        };
+
+        for entry in to {
+            if PathBuf::from(entry).exists() {
+                debug!("Linking externally: {:?} -> {:?}", from, entry);
+                let url = Url::parse(entry)?;
+                let _   = from_entry.add_external_link(tx.store(), url)?;
+            } else {
+                debug!("Linking internally: {:?} -> {:?}", from, entry);
+                let mut to_entry = match get_entry_by_name(tx, entry)? {
+                    Some(e) => e,
+                    None    => {
+                        warn!("No 'to' entry: {}", entry);
+                        ::std::process::exit(1)
+                    },
+                };
+                let _ = from_entry.add_internal_link(&mut to_entry)?;
+            }
+
+            info!("Ok: {} -> {}", from, entry);
         }
 
-        info!("Ok: {} -> {}", from, entry);
-    }
+        Ok(())
+    })
+    .map_err_trace_exit_unwrap(1);
 
     info!("Ok");
 }
 
 fn remove_linking(rt: &Runtime) {
 
-    fn get_from_entry<'a>(rt: &'a Runtime) -> Option<FileLockEntry<'a>> {
+    fn get_from_entry<'a>(rt: &Runtime, tx: &Transaction<'a>) -> Option<FileLockEntry<'a>> {
         rt.cli()
             .subcommand_matches("remove")
             .unwrap() // safe, we know there is an "remove" subcommand
             .value_of("from")
             .and_then(|from_name| {
-                match get_entry_by_name(rt, from_name) {
+                match get_entry_by_name(tx, from_name) {
                     Err(e) => {
                         debug!("We couldn't get the entry from name: '{:?}'", from_name);
                         trace_error(&e); None
@@ -174,38 +177,41 @@ fn remove_linking(rt: &Runtime) {
             })
     }
 
-    let mut from = match get_from_entry(&rt) {
-        None => warn_exit("No 'from' entry", 1),
-        Some(s) => s,
-    };
-
-    rt.cli()
-        .subcommand_matches("remove")
-        .unwrap()
-        .values_of("to")
-        .map(|values| {
-            for (entry, value) in values.map(|v| (get_entry_by_name(rt, v), v)) {
-                match entry {
-                    Err(e) => trace_error(&e),
-                    Ok(Some(mut to_entry)) => {
-                        if let Err(e) = to_entry.remove_internal_link(&mut from) {
-                            trace_error_exit(&e, 1);
-                        }
-                    },
-                    Ok(None) => {
-                        // looks like this is not an entry, but a filesystem URI and therefor an
-                        // external link...?
-                        if PathBuf::from(value).is_file() {
-                            let url = Url::parse(value).map_err_trace_exit_unwrap(1);
-                            from.remove_external_link(rt.store(), url).map_err_trace_exit_unwrap(1);
-                            info!("Ok: {}", value);
-                        } else {
-                            warn!("Entry not found: {:?}", value);
-                        }
+    // Like linking, unlinking changes both entries, see `link_from_to()`
+    rt.store().transaction(|tx| -> Result<(), LE> {
+        let mut from = match get_from_entry(rt, tx) {
+            None => warn_exit("No 'from' entry", 1),
+            Some(s) => s,
+        };
+
+        let values = match rt.cli().subcommand_matches("remove").unwrap().values_of("to") {
+            Some(values) => values,
+            None         => return Ok(()),
+        };
+
+        for (entry, value) in values.map(|v| (get_entry_by_name(tx, v), v)) {
+            match entry {
+                Err(e) => trace_error(&e),
+                Ok(Some(mut to_entry)) => {
+                    let _ = to_entry.remove_internal_link(&mut from)?;
+                },
+                Ok(None) => {
+                    // looks like this is not an entry, but a filesystem URI and therefor an
+                    // external link...?
+                    if PathBuf::from(value).is_file() {
+                        let url = Url::parse(value)?;
+                        let _   = from.remove_external_link(tx.store(), url)?;
+                        info!("Ok: {}", value);
+                    } else {
+                        warn!("Entry not found: {:?}", value);
                     }
                 }
             }
-        });
+        }
+
+        Ok(())
+    })
+    .map_err_trace_exit_unwrap(1);
 }
 
 fn list_linkings(rt: &Runtime) {
@@ -227,7 +233,13 @@ fn list_linkings(rt: &Runtime) {
                         .ok();
 
                     if let Some(link) = link {
-                        println!("{: <3}: {}", i, link);
+                        let record = Record::new(format!("{: <3}: {}", i, link))
+                            .with("index", i)
+                            .with("entry", entry.get_location().to_string())
+                            .with("link", link)
+                            .with("external", false);
+
+                        rt.output().emit(record).map_err_trace_exit_unwrap(1);
                         i += 1;
                     }
                 }
@@ -238,7 +250,13 @@ fn list_linkings(rt: &Runtime) {
                             .map_err_trace_exit_unwrap(1)
                             .into_string();
 
-                        println!("{: <3}: {}", i, link);
+                        let record = Record::new(format!("{: <3}: {}", i, link))
+                            .with("index", i)
+                            .with("entry", entry.get_location().to_string())
+                            .with("link", link)
+                            .with("external", true);
+
+                        rt.output().emit(record).map_err_trace_exit_unwrap(1);
                         i += 1;
                     }
                 }
@@ -55,6 +55,7 @@ use libimagerror::trace::trace_error;
 use libimagerror::trace::MapErrTrace;
 use libimagrt::setup::generate_runtime_setup;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::{OutputMode, Record};
 
 fn main() {
     let rt = generate_runtime_setup("imag-ref",
@@ -148,12 +149,31 @@ fn list(rt: &Runtime) {
         }
     };
 
-    RefLister::new()
-        .check_dead(do_check_dead)
-        .check_changed(do_check_changed)
-        .check_changed_content(do_check_changed_content)
-        .check_changed_permiss(do_check_changed_permiss)
-        .list(iter.filter_map(Into::into))
-        .ok();
+    let iter = iter.filter_map(Into::into);
+
+    if rt.output().mode() == OutputMode::Text {
+        RefLister::new()
+            .check_dead(do_check_dead)
+            .check_changed(do_check_changed)
+            .check_changed_content(do_check_changed_content)
+            .check_changed_permiss(do_check_changed_permiss)
+            .list(iter)
+            .ok();
+    } else {
+        use libimagentryref::reference::Ref;
+
+        for entry in iter {
+            let id         = entry.get_location().to_string();
+            let mut record = Record::new(id.clone())
+                .with("id", id)
+                .with("hash", entry.get_path_hash().map_err_trace_exit_unwrap(1));
+
+            if do_check_dead {
+                record = record.with("link_exists", entry.fs_link_exists().map_err_trace_exit_unwrap(1));
+            }
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        }
+    }
 }
 
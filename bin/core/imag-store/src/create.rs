@@ -31,6 +31,7 @@ use libimagrt::runtime::Runtime;
 use libimagstore::store::Entry;
 use libimagstore::storeid::StoreId;
 use libimagerror::trace::trace_error_exit;
+use libimagerror::trace::MapErrTrace;
 use libimagutil::debug_result::*;
 
 use error::StoreError;
@@ -68,7 +69,9 @@ pub fn create(rt: &Runtime) {
     .unwrap_or_else(|e| {
         error!("Error building Entry");
         trace_error_exit(&e, 1);
-    })
+    });
+
+    rt.report_touched(&path).map_err_trace_exit_unwrap(1);
 }
 
 fn create_from_cli_spec(rt: &Runtime, matches: &ArgMatches, path: &StoreId) -> Result<()> {
@@ -0,0 +1,186 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use chrono::NaiveDateTime;
+use clap::ArgMatches;
+
+use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
+use libimagerror::trace::MapErrTrace;
+use libimagstore::store::Revision;
+use libimagstore::storeid::StoreId;
+
+fn store_id(rt: &Runtime, scmd: &ArgMatches) -> StoreId {
+    let id    = scmd.value_of("id").unwrap(); // safe by clap
+    let store = Some(rt.store().path().clone());
+    StoreId::new(store, PathBuf::from(id)).map_err_trace_exit_unwrap(1)
+}
+
+fn revision_number(scmd: &ArgMatches, name: &str) -> Option<u64> {
+    scmd.value_of(name).map(|r| match r.parse::<u64>() {
+        Ok(r)  => r,
+        Err(e) => {
+            error!("Cannot parse revision '{}': {}", r, e);
+            exit(1)
+        },
+    })
+}
+
+fn find_revision(revisions: &[Revision], number: u64) -> &Revision {
+    match revisions.iter().find(|r| r.number() == number) {
+        Some(r) => r,
+        None    => {
+            error!("No revision {}", number);
+            exit(1)
+        },
+    }
+}
+
+/// List the revisions of an entry
+pub fn history(rt: &Runtime) {
+    let scmd = rt.cli().subcommand_matches("history").unwrap(); // safe by main()
+    let id   = store_id(rt, scmd);
+
+    for revision in rt.store().revisions(id).map_err_trace_exit_unwrap(1) {
+        let time = NaiveDateTime::from_timestamp(revision.timestamp() as i64, 0);
+        let record = Record::new(format!("{}\t{}", revision.number(), time))
+            .with("revision", revision.number())
+            .with("timestamp", revision.timestamp());
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    }
+}
+
+/// Print the difference between two revisions, or a revision and the current entry
+///
+/// Without revisions, the newest revision is compared to the current entry.
+pub fn diff(rt: &Runtime) {
+    let scmd      = rt.cli().subcommand_matches("diff").unwrap(); // safe by main()
+    let id        = store_id(rt, scmd);
+    let revisions = rt.store().revisions(id.clone()).map_err_trace_exit_unwrap(1);
+
+    let old = match revision_number(scmd, "revision") {
+        Some(n) => find_revision(&revisions, n),
+        None    => match revisions.last() {
+            Some(r) => r,
+            None    => {
+                info!("No revisions of {}", id);
+                return;
+            },
+        },
+    };
+
+    let (new_name, new_text) = match revision_number(scmd, "other-revision") {
+        Some(n) => (format!("revision {}", n), find_revision(&revisions, n).entry().to_str()),
+        None    => {
+            let text = rt.store()
+                .get_copy(id.clone())
+                .map(|e| e.to_str())
+                .unwrap_or_else(|_| String::new()); // the entry might have been deleted
+            (String::from("current"), text)
+        },
+    };
+
+    let mut lines = vec![
+        format!("--- {} (revision {})", id, old.number()),
+        format!("+++ {} ({})", id, new_name),
+    ];
+    lines.extend(diff_lines(&old.entry().to_str(), &new_text));
+
+    let record = Record::new(lines.join("\n"))
+        .with("id", id.to_string())
+        .with("old", old.number())
+        .with("new", new_name)
+        .with("diff", lines[2..].to_vec());
+
+    rt.output().emit(record).map_err_trace_exit_unwrap(1);
+}
+
+/// Restore an entry to a revision
+pub fn restore(rt: &Runtime) {
+    let scmd     = rt.cli().subcommand_matches("restore").unwrap(); // safe by main()
+    let id       = store_id(rt, scmd);
+    let revision = revision_number(scmd, "revision").unwrap(); // safe by clap
+
+    rt.store().restore(id.clone(), revision).map_err_trace_exit_unwrap(1);
+    rt.report_touched(&id).map_err_trace_exit_unwrap(1);
+}
+
+/// A line based diff of two texts
+///
+/// Each line of the result is prefixed with "-" if it was removed, "+" if it was added and " "
+/// if it is in both texts.
+fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            result.push(format!("+{}", new[j]));
+            j += 1;
+        } else {
+            result.push(format!("-{}", old[i]));
+            i += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_lines;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "a\nb\nc\nd";
+        let new = "a\nc\nd\ne";
+        assert_eq!(diff_lines(old, new), vec![" a", "-b", " c", " d", "+e"]);
+    }
+
+    #[test]
+    fn test_diff_lines_identical_and_empty() {
+        assert_eq!(diff_lines("a\nb", "a\nb"), vec![" a", " b"]);
+        assert_eq!(diff_lines("", "a"), vec!["+a"]);
+        assert_eq!(diff_lines("a", ""), vec!["-a"]);
+    }
+
+}
@@ -18,24 +18,53 @@
 //
 
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagerror::trace::*;
+use libimagstore::store::Entry;
+use libimagstore::store::Result as StoreResult;
+use libimagstore::storeid::StoreId;
+
+use chrono::NaiveDateTime;
 
 pub fn ids(rt: &Runtime) {
-    let full = rt.cli().subcommand_matches("ids").unwrap() //secured by main
-        .is_present("full");
+    let scmd = rt.cli().subcommand_matches("ids").unwrap(); //secured by main
+    let full = scmd.is_present("full");
     let base = rt.store().path();
-    let _ :Vec<_> = rt
-        .store()
-        .entries()
-        .map_err_trace_exit_unwrap(1)
-        .map(|e| if full {
-            e.with_base(base.clone())
+
+    let mut ids : Vec<StoreId> = rt.store().entries().map_err_trace_exit_unwrap(1).collect();
+    let ids = match scmd.value_of("sort-by") {
+        Some("id")       => { ids.sort(); ids },
+        Some("created")  => sort_by_time(rt, ids, Entry::created),
+        Some("modified") => sort_by_time(rt, ids, Entry::modified),
+        _                => ids,
+    };
+
+    for id in ids {
+        rt.report_touched(&id).map_err_trace_exit_unwrap(1);
+
+        let id = if full {
+            id.with_base(base.clone())
         } else {
-           e.without_base()
+            id.without_base()
+        };
+        let id = id.to_str().map_err_trace_exit_unwrap(1);
+
+        rt.output().emit(Record::new(id.clone()).with("id", id)).map_err_trace_exit_unwrap(1);
+    }
+}
+
+/// Sort `ids` by the time `time` reads from the entries, entries without time first
+fn sort_by_time<F>(rt: &Runtime, ids: Vec<StoreId>, time: F) -> Vec<StoreId>
+    where F: Fn(&Entry) -> StoreResult<Option<NaiveDateTime>>
+{
+    let mut timed : Vec<(Option<NaiveDateTime>, StoreId)> = ids
+        .into_iter()
+        .map(|id| {
+            let t = rt.store().get_copy(id.clone()).and_then(|e| time(&e));
+            (t.map_err_trace_exit_unwrap(1), id)
         })
-        .map(|i| i.to_str())
-        .map(|elem| elem.map_err_trace_exit_unwrap(1))
-        .map(|i| println!("{}", i))
         .collect();
-}
 
+    timed.sort();
+    timed.into_iter().map(|(_, id)| id).collect()
+}
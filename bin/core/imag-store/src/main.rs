@@ -35,6 +35,8 @@
 extern crate clap;
 #[macro_use] extern crate log;
 extern crate toml;
+extern crate serde_json;
+extern crate chrono;
 #[cfg(test)] extern crate toml_query;
 #[macro_use] extern crate version;
 #[macro_use] extern crate error_chain;
@@ -42,6 +44,7 @@ extern crate toml;
 extern crate libimagrt;
 extern crate libimagstore;
 extern crate libimagerror;
+extern crate libimagmodules;
 
 #[cfg(test)]
 #[macro_use]
@@ -57,7 +60,10 @@ mod delete;
 mod dump;
 mod error;
 mod get;
+mod history;
+mod migrate;
 mod retrieve;
+mod trash;
 mod ui;
 mod update;
 mod verify;
@@ -70,7 +76,10 @@ use create::create;
 use delete::delete;
 use dump::dump;
 use get::get;
+use history::{history, diff, restore};
+use migrate::migrate;
 use retrieve::retrieve;
+use trash::trash;
 use ui::build_ui;
 use update::update;
 use verify::verify;
@@ -95,6 +104,11 @@ fn main() {
             "verify"   => verify(&rt),
             "dump"     => dump(&mut rt),
             "ids"      => ids(&rt),
+            "migrate"  => migrate(&rt),
+            "history"  => history(&rt),
+            "diff"     => diff(&rt),
+            "restore"  => restore(&rt),
+            "trash"    => trash(&rt),
             _ => {
                 debug!("Unknown command");
                 // More error handling
@@ -0,0 +1,56 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
+use libimagerror::trace::MapErrTrace;
+use libimagmodules::migration_registry;
+
+/// Migrate all entries of the store, or only print what would be done if `--apply` is not passed
+pub fn migrate(rt: &Runtime) {
+    let scmd     = rt.cli().subcommand_matches("migrate").unwrap(); // safe by main()
+    let dry_run  = !scmd.is_present("apply");
+    let registry = migration_registry().map_err_trace_exit_unwrap(1);
+
+    let reports = registry
+        .migrate_store(rt.store(), dry_run)
+        .map_err_trace_exit_unwrap(1);
+
+    for report in reports.iter() {
+        let steps = report
+            .migrations
+            .iter()
+            .map(|m| format!("{}: {} -> {}", m.module(), m.from(), m.to()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let record = Record::new(format!("{} ({})", report.id, steps))
+            .with("id", report.id.to_string())
+            .with("migrations", steps);
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        rt.report_touched(&report.id).map_err_trace_exit_unwrap(1);
+    }
+
+    if dry_run {
+        info!("{} entries need to be migrated. Pass --apply to migrate them", reports.len());
+    } else {
+        info!("{} entries migrated", reports.len());
+    }
+}
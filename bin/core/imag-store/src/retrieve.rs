@@ -24,6 +24,7 @@ use clap::ArgMatches;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagerror::trace::MapErrTrace;
 use libimagutil::debug_result::*;
 
@@ -48,9 +49,11 @@ pub fn retrieve(rt: &Runtime) {
 }
 
 pub fn print_entry(rt: &Runtime, scmd: &ArgMatches, e: FileLockEntry) {
-    if do_print_raw(scmd) {
+    rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
+
+    let text = if do_print_raw(scmd) {
         debug!("Printing raw content...");
-        println!("{}", e.to_str());
+        e.to_str()
     } else if do_filter(scmd) {
         debug!("Filtering...");
         warn!("Filtering via header specs is currently now supported.");
@@ -58,25 +61,34 @@ pub fn print_entry(rt: &Runtime, scmd: &ArgMatches, e: FileLockEntry) {
         unimplemented!()
     } else {
         debug!("Printing structured...");
+        let mut lines = vec![];
+
         if do_print_header(scmd) {
             debug!("Printing header...");
             if do_print_header_as_json(rt.cli()) {
                 debug!("Printing header as json...");
-                warn!("Printing as JSON currently not supported.");
-                warn!("Will fail now!");
-                unimplemented!()
+                lines.push(serde_json::to_string(e.get_header()).map_err_trace_exit_unwrap(1));
             } else {
                 debug!("Printing header as TOML...");
-                println!("{}", e.get_header())
+                lines.push(format!("{}", e.get_header()));
             }
         }
 
         if do_print_content(scmd) {
             debug!("Printing content...");
-            println!("{}", e.get_content());
+            lines.push(e.get_content().clone());
         }
 
-    }
+        lines.join("\n")
+    };
+
+    let header = serde_json::to_value(e.get_header()).map_err_trace_exit_unwrap(1);
+    let record = Record::new(text)
+        .with("id", e.get_location().to_string())
+        .with("header", header)
+        .with("content", e.get_content().clone());
+
+    rt.output().emit(record).map_err_trace_exit_unwrap(1);
 }
 
 fn do_print_header(m: &ArgMatches) -> bool {
@@ -0,0 +1,85 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use clap::ArgMatches;
+
+use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
+use libimagerror::trace::MapErrTrace;
+use libimagstore::storeid::StoreId;
+
+/// Work with the trash of the store
+pub fn trash(rt: &Runtime) {
+    let scmd = rt.cli().subcommand_matches("trash").unwrap(); // safe by main()
+
+    match scmd.subcommand() {
+        ("list", _)              => list(rt),
+        ("restore", Some(scmd))  => restore(rt, scmd),
+        ("empty", Some(scmd))    => empty(rt, scmd),
+        _ => {
+            debug!("Unknown command");
+            exit(1)
+        },
+    }
+}
+
+/// List the deleted entries, the most recently deleted last
+fn list(rt: &Runtime) {
+    for trashed in rt.store().deleted_entries().map_err_trace_exit_unwrap(1) {
+        let time = NaiveDateTime::from_timestamp(trashed.deleted() as i64, 0);
+        let record = Record::new(format!("{}\t{}", time, trashed.id()))
+            .with("id", trashed.id().to_string())
+            .with("deleted", trashed.deleted());
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+    }
+}
+
+/// Restore the most recently deleted version of an entry
+fn restore(rt: &Runtime, scmd: &ArgMatches) {
+    let id    = scmd.value_of("id").unwrap(); // safe by clap
+    let store = Some(rt.store().path().clone());
+    let id    = StoreId::new(store, PathBuf::from(id)).map_err_trace_exit_unwrap(1);
+
+    let entry = rt.store().restore_deleted(id).map_err_trace_exit_unwrap(1);
+    rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
+}
+
+/// Remove entries from the trash for good
+fn empty(rt: &Runtime, scmd: &ArgMatches) {
+    let days = match scmd.value_of("older-than").map(|d| d.parse::<u64>()) {
+        Some(Ok(days)) => days,
+        Some(Err(e))   => {
+            error!("Cannot parse number of days: {}", e);
+            exit(1)
+        },
+        None => 0,
+    };
+
+    let removed = rt.store()
+        .empty_trash(Duration::from_secs(days * 24 * 60 * 60))
+        .map_err_trace_exit_unwrap(1);
+    info!("Removed {} entries from the trash", removed);
+}
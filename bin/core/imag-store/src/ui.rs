@@ -17,10 +17,13 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use clap::{Arg, App, ArgGroup, SubCommand};
+use clap::{Arg, App, AppSettings, ArgGroup, SubCommand};
+
+use libimagrt::runtime::Runtime;
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
-    app.subcommand(SubCommand::with_name("create")
+    app.arg(Runtime::arg_ids_out())
+       .subcommand(SubCommand::with_name("create")
                    .about("Create an entry from the store")
                    .version("0.1")
                    .arg(Arg::with_name("path")
@@ -205,5 +208,103 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .multiple(false)
                         .required(false)
                         .help("Print full filepath instead of storeid part"))
+                   .arg(Arg::with_name("sort-by")
+                        .long("sort-by")
+                        .takes_value(true)
+                        .multiple(false)
+                        .required(false)
+                        .possible_values(&["id", "created", "modified"])
+                        .value_name("FIELD")
+                        .help("Sort the ids by id or by the time the entries were created or modified last, oldest first. Entries without the time come first"))
+                   )
+
+       .subcommand(SubCommand::with_name("migrate")
+                   .about("Migrate entries written by older versions of imag. Dry run by default")
+                   .version("0.1")
+                   .arg(Arg::with_name("apply")
+                        .long("apply")
+                        .short("a")
+                        .takes_value(false)
+                        .multiple(false)
+                        .required(false)
+                        .help("Apply the migrations instead of only listing the entries to migrate"))
+                   )
+
+       .subcommand(SubCommand::with_name("history")
+                   .about("List the previous revisions of an entry (see store.history.revisions)")
+                   .version("0.1")
+                   .arg(Arg::with_name("id")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help("The entry to list the revisions of")
+                        .value_name("PATH"))
+                   )
+
+       .subcommand(SubCommand::with_name("diff")
+                   .about("Show the changes between revisions of an entry. Compares the newest revision to the entry by default")
+                   .version("0.1")
+                   .arg(Arg::with_name("id")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help("The entry to show the changes of")
+                        .value_name("PATH"))
+                   .arg(Arg::with_name("revision")
+                        .index(2)
+                        .takes_value(true)
+                        .required(false)
+                        .help("The revision to compare")
+                        .value_name("REVISION"))
+                   .arg(Arg::with_name("other-revision")
+                        .index(3)
+                        .takes_value(true)
+                        .required(false)
+                        .help("The revision to compare to. The current entry if not passed")
+                        .value_name("REVISION"))
+                   )
+
+       .subcommand(SubCommand::with_name("restore")
+                   .about("Restore an entry to a previous revision")
+                   .version("0.1")
+                   .arg(Arg::with_name("id")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help("The entry to restore")
+                        .value_name("PATH"))
+                   .arg(Arg::with_name("revision")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .help("The revision to restore")
+                        .value_name("REVISION"))
+                   )
+
+       .subcommand(SubCommand::with_name("trash")
+                   .about("Work with deleted entries")
+                   .version("0.1")
+                   .setting(AppSettings::SubcommandRequiredElseHelp)
+                   .subcommand(SubCommand::with_name("list")
+                               .about("List the deleted entries")
+                               .version("0.1"))
+                   .subcommand(SubCommand::with_name("restore")
+                               .about("Restore a deleted entry")
+                               .version("0.1")
+                               .arg(Arg::with_name("id")
+                                    .index(1)
+                                    .takes_value(true)
+                                    .required(true)
+                                    .help("The entry to restore. If it was deleted more than once, the most recently deleted version is restored")
+                                    .value_name("PATH")))
+                   .subcommand(SubCommand::with_name("empty")
+                               .about("Remove deleted entries for good")
+                               .version("0.1")
+                               .arg(Arg::with_name("older-than")
+                                    .long("older-than")
+                                    .takes_value(true)
+                                    .required(false)
+                                    .help("Only remove entries which were deleted more than this many days ago")
+                                    .value_name("DAYS")))
                    )
 }
@@ -22,6 +22,7 @@ use std::path::PathBuf;
 
 use libimagrt::runtime::Runtime;
 use libimagerror::trace::trace_error_exit;
+use libimagerror::trace::MapErrTrace;
 use libimagstore::storeid::StoreId;
 
 use util::build_toml_header;
@@ -36,6 +37,7 @@ pub fn update(rt: &Runtime) {
     let _ = rt.store()
         .retrieve(path)
         .map(|mut locked_e| {
+            rt.report_touched(locked_e.get_location()).map_err_trace_exit_unwrap(1);
             let e = locked_e.deref_mut();
 
             scmd.value_of("content")
@@ -21,15 +21,22 @@ use std::ops::Deref;
 
 use libimagrt::runtime::Runtime;
 use libimagutil::warn_exit::warn_exit;
+use libimagerror::trace::MapErrTrace;
 use libimagstore::store::Header;
 use libimagstore::store::StoreObject;
+use libimagmodules::register_schemas;
 
 /// Verify the store.
 ///
+/// The header of each entry is checked against the schemas of the modules, the fields which
+/// violate them are listed below the entry.
+///
 /// This function is not intended to be called by normal programs but only by `imag-store`.
 pub fn verify(rt: &Runtime) {
     use libimagerror::trace::trace_error_dbg;
 
+    register_schemas().map_err_trace_exit_unwrap(1);
+
     info!("Header | Content length | Path");
     info!("-------+----------------+-----");
     let result = rt
@@ -39,22 +46,24 @@ pub fn verify(rt: &Runtime) {
         .all(|res| match res {
             StoreObject::Collection(_) => true,
             StoreObject::Id(id) => {
-                match rt.store().get(id.clone()) {
-                    Ok(Some(fle)) => {
-                        let p           = fle.get_location();
-                        let content_len = fle.get_content().len();
-                        let header      = if fle.get_header().verify().is_ok() {
-                            "ok"
-                        } else {
+                // A copy, so the entry is neither locked nor written back
+                match rt.store().get_copy(id.clone()) {
+                    Ok(entry) => {
+                        let p           = entry.get_location();
+                        let content_len = entry.get_content().len();
+                        let violations  = entry.schema_violations().map_err_trace_exit_unwrap(1);
+                        let header      = if entry.get_header().verify().is_err() {
                             "broken"
+                        } else if !violations.is_empty() {
+                            "schema"
+                        } else {
+                            "ok"
                         };
 
                         info!("{: >6} | {: >14} | {:?}", header, content_len, p.deref());
-                        true
-                    },
-
-                    Ok(None) => {
-                        info!("{: >6} | {: >14} | {:?}", "?", "couldn't load", id.local());
+                        for violation in violations {
+                            info!("{: >6} | {: >14} |     {}", "", "", violation);
+                        }
                         true
                     },
 
@@ -45,9 +45,10 @@ use std::path::PathBuf;
 
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::output::{Output, OutputMode, Record};
 use libimagentrytag::tagable::Tagable;
 use libimagentrytag::tag::Tag;
-use libimagerror::trace::{trace_error, trace_error_exit};
+use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
 use libimagstore::storeid::StoreId;
 use libimagutil::warn_exit::warn_exit;
 
@@ -63,27 +64,37 @@ fn main() {
                                     "Direct interface to the store. Use with great care!",
                                     build_ui);
 
-    let id = rt.cli().value_of("id").unwrap(); // enforced by clap
-    rt.cli()
-        .subcommand_name()
-        .map_or_else(
-            || {
-                let id = PathBuf::from(id);
-                let add = get_add_tags(rt.cli());
-                let rem = get_remove_tags(rt.cli());
-                alter(&rt, id, add, rem);
-            },
-            |name| {
-                let id = PathBuf::from(id);
-                debug!("Call: {}", name);
-                match name {
-                    "list" => list(id, &rt),
-                    _ => {
-                        warn!("Unknown command");
-                        // More error handling
-                    },
-                };
-            });
+    let ids = match rt.cli().value_of("id") {
+        Some(id) => vec![PathBuf::from(id)],
+        None     => rt
+            .ids_from_stdin()
+            .map_err_trace_exit_unwrap(1)
+            .unwrap_or_else(|| warn_exit("No id passed, neither as argument nor on stdin", 1))
+            .into_iter()
+            .map(|id| id.local().clone())
+            .collect(),
+    };
+
+    for id in ids {
+        rt.cli()
+            .subcommand_name()
+            .map_or_else(
+                || {
+                    let add = get_add_tags(rt.cli());
+                    let rem = get_remove_tags(rt.cli());
+                    alter(&rt, id.clone(), add, rem);
+                },
+                |name| {
+                    debug!("Call: {}", name);
+                    match name {
+                        "list" => list(id.clone(), &rt),
+                        _ => {
+                            warn!("Unknown command");
+                            // More error handling
+                        },
+                    };
+                });
+    }
 }
 
 fn alter(rt: &Runtime, id: PathBuf, add: Option<Vec<Tag>>, rem: Option<Vec<Tag>>) {
@@ -125,6 +136,7 @@ fn alter(rt: &Runtime, id: PathBuf, add: Option<Vec<Tag>>, rem: Option<Vec<Tag>>
 
             debug!("Entry header now = {:?}", e.get_header());
 
+            rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
         },
 
         Ok(None) => {
@@ -160,12 +172,6 @@ fn list(id: PathBuf, rt: &Runtime) {
     let json_out = scmd.is_present("json");
     let line_out = scmd.is_present("linewise");
     let sepp_out = scmd.is_present("sep");
-    let mut comm_out = scmd.is_present("commasep");
-
-    if !vec![json_out, line_out, comm_out, sepp_out].iter().any(|v| *v) {
-        // None of the flags passed, go to default
-        comm_out = true;
-    }
 
     let tags = entry.get_tags();
     if tags.is_err() {
@@ -173,23 +179,29 @@ fn list(id: PathBuf, rt: &Runtime) {
     }
     let tags = tags.unwrap();
 
-    if json_out {
-        unimplemented!()
-    }
+    let text = if line_out {
+        tags.join("\n")
+    } else if sepp_out {
+        let sepp = scmd.value_of("sep").unwrap(); // we checked before
+        tags.join(sepp)
+    } else {
+        // "commasep" is the default
+        tags.join(", ")
+    };
 
-    if line_out {
-        for tag in &tags {
-            println!("{}", tag);
-        }
-    }
+    rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
 
-    if sepp_out {
-        let sepp = scmd.value_of("sep").unwrap(); // we checked before
-        println!("{}", tags.join(sepp));
-    }
+    let record = Record::new(text)
+        .with("id", entry.get_location().to_string())
+        .with("tags", tags);
 
-    if comm_out {
-        println!("{}", tags.join(", "));
+    if json_out {
+        // `--json` is a shorthand for the global `--output json`
+        Output::new(OutputMode::Json, None)
+            .and_then(|output| output.emit(record))
+            .map_err_trace_exit_unwrap(1);
+    } else {
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
     }
 }
 
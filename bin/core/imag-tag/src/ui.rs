@@ -17,22 +17,26 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use clap::{Arg, App, ArgGroup, SubCommand};
+use clap::{Arg, App, SubCommand};
 
 use libimagentrytag::tag::is_tag;
+use libimagrt::runtime::Runtime;
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
-    app.arg(Arg::with_name("id")
+    app.arg(Runtime::arg_ids_out())
+
+        .arg(Arg::with_name("id")
                 .index(1)
                 .takes_value(true)
-                .required(true)
+                .required(false)
                 .multiple(false)
                 .value_name("ID")
-                .help("Entry to use"))
+                .help("Entry to use. If not passed, the ids are read from stdin, one per line"))
 
         .arg(Arg::with_name("add-tags")
                 .short("a")
                 .long("add")
+                .alias("add-tags")
                 .takes_value(true)
                 .value_name("tags")
                 .multiple(true)
@@ -42,6 +46,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
         .arg(Arg::with_name("remove-tags")
                 .short("r")
                 .long("remove")
+                .alias("remove-tags")
                 .takes_value(true)
                 .multiple(true)
                 .validator(is_tag)
@@ -56,35 +61,30 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .short("j")
                         .takes_value(false)
                         .required(false)
+                        .conflicts_with_all(&["linewise", "commasep", "sep"])
                         .help("List as JSON"))
                    .arg(Arg::with_name("linewise")
                         .long("linewise")
                         .short("l")
                         .takes_value(false)
                         .required(false)
+                        .conflicts_with_all(&["json", "commasep", "sep"])
                         .help("One tag per line"))
                    .arg(Arg::with_name("commasep")
                         .long("comma")
                         .short("c")
                         .takes_value(false)
                         .required(false)
+                        .conflicts_with_all(&["json", "linewise", "sep"])
                         .help("Commaseperated (default)"))
                    .arg(Arg::with_name("sep")
                         .long("sep")
                         .short("s")
                         .takes_value(true)
                         .required(false)
+                        .conflicts_with_all(&["json", "linewise", "commasep"])
                         .help("Separated by string")
                         .value_name("SEP"))
-
-                   .group(ArgGroup::with_name("list-group")
-                          .args(&[
-                                "json",
-                                "linewise",
-                                "commasep",
-                                "sep",
-                          ])
-                          .required(true))
                    )
 
 }
@@ -0,0 +1,276 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Command aliases
+//!
+//! An alias is a name for one or more imag command lines, configured in `imag.aliases`:
+//!
+//! ```toml
+//! [imag.aliases]
+//! standup = "timetrack start standup && diary create --no-edit"
+//! note    = "notes create --name $1"
+//! store   = [ "s", "st" ]
+//! ```
+//!
+//! `imag standup` then runs `imag timetrack start standup` and, if that succeeded,
+//! `imag diary create --no-edit`.
+//!
+//! A value which is a single word (or an array of them) gives the command of the key other
+//! names, as it always did: `imag s` calls `imag store`.
+
+use std::collections::BTreeMap;
+
+use toml::Value;
+
+/// Get the definitions of the aliases in the `imag.aliases` table, by name
+///
+/// A command line (a string containing whitespace) is the definition of the alias named by the
+/// key. A single word or an array of words are names for the command named by the key, which is
+/// their definition then.
+pub fn from_table(table: &BTreeMap<String, Value>) -> Result<BTreeMap<String, String>, String> {
+    let mut aliases = BTreeMap::new();
+
+    for (k, v) in table {
+        match *v {
+            Value::String(ref s) if s.trim().contains(char::is_whitespace) => {
+                aliases.insert(k.clone(), s.clone());
+            },
+            Value::String(ref alias) => {
+                aliases.insert(alias.clone(), k.clone());
+            },
+            Value::Array(ref names) => for name in names {
+                match *name {
+                    Value::String(ref s) => {
+                        aliases.insert(s.clone(), k.clone());
+                    },
+                    _ => return Err(format!("Not all values are a String in 'imag.aliases.{}'", k)),
+                }
+            },
+            _ => return Err(format!("Type Error: 'imag.aliases.{}' is not an array or string", k)),
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// Expand the alias `name` with the `definition`, called with `args`, to the command lines to run
+///
+/// The definition is split into words at whitespace, quotes (`"..."` or `'...'`) make one word of
+/// their content. An unquoted `&&` word separates two commands, a quoted one (`"&&"`) is passed
+/// on as argument. In each word, `$1` to `$9` are replaced by
+/// the arguments, a `$@` word is replaced by all arguments. If the definition uses none of these,
+/// the arguments are appended to the last command.
+///
+/// Each command line starts with the command, without the `imag-` prefix.
+pub fn expand(name: &str, definition: &str, args: &[String]) -> Result<Vec<Vec<String>>, String> {
+    let tokens = tokenize(definition).map_err(|e| format!("Alias '{}': {}", name, e))?;
+
+    let has_placeholders = tokens.iter().any(|t| match *t {
+        Token::Word(ref w) => w == "$@" || !positionals(w).is_empty(),
+        Token::And         => false,
+    });
+
+    let mut commands = vec![];
+    for command in tokens.split(|t| *t == Token::And) {
+        if command.is_empty() {
+            return Err(format!("Alias '{}' has an empty command", name));
+        }
+
+        let mut line = vec![];
+        for token in command {
+            if let Token::Word(ref word) = *token {
+                if word == "$@" {
+                    line.extend(args.iter().cloned());
+                } else {
+                    line.push(replace_positionals(name, word, args)?);
+                }
+            }
+        }
+
+        if line.is_empty() {
+            return Err(format!("Alias '{}' expands to an empty command", name));
+        }
+
+        commands.push(line);
+    }
+
+    if !has_placeholders {
+        if let Some(last) = commands.last_mut() {
+            last.extend(args.iter().cloned());
+        }
+    }
+
+    Ok(commands)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    And,
+}
+
+fn tokenize(definition: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut word   = None;
+    let mut quote  = None;
+    let mut quoted = false; // whether the current word contains a quote
+
+    for c in definition.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c)           => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote  = Some(c);
+                quoted = true;
+                word.get_or_insert_with(String::new);
+            },
+            (None, c) if c.is_whitespace() => if let Some(w) = word.take() {
+                tokens.push(to_token(w, quoted));
+                quoted = false;
+            },
+            (None, c)              => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(String::from("unterminated quote"));
+    }
+
+    if let Some(w) = word {
+        tokens.push(to_token(w, quoted));
+    }
+
+    Ok(tokens)
+}
+
+fn to_token(word: String, quoted: bool) -> Token {
+    if word == "&&" && !quoted { Token::And } else { Token::Word(word) }
+}
+
+/// Get the numbers of the positional placeholders (`$1` to `$9`) in `word`
+fn positionals(word: &str) -> Vec<usize> {
+    let chars = word.chars().collect::<Vec<_>>();
+    chars
+        .windows(2)
+        .filter(|w| w[0] == '$')
+        .filter_map(|w| w[1].to_digit(10))
+        .filter(|n| *n > 0)
+        .map(|n| n as usize)
+        .collect()
+}
+
+fn replace_positionals(name: &str, word: &str, args: &[String]) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars  = word.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let n = match (c, chars.peek().and_then(|d| d.to_digit(10))) {
+            ('$', Some(n)) if n > 0 => n as usize,
+            _ => {
+                result.push(c);
+                continue;
+            },
+        };
+
+        let _ = chars.next();
+        let arg = args
+            .get(n - 1)
+            .ok_or_else(|| format!("Alias '{}' needs at least {} arguments", name, n))?;
+        result.push_str(arg);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use toml::Value;
+
+    use super::expand;
+    use super::from_table;
+
+    fn args(a: &[&str]) -> Vec<String> {
+        a.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn test_chained_commands() {
+        let commands = expand("standup", "timetrack start standup && diary create --no-edit", &[]);
+        assert_eq!(Ok(vec![
+            args(&["timetrack", "start", "standup"]),
+            args(&["diary", "create", "--no-edit"]),
+        ]), commands);
+    }
+
+    #[test]
+    fn test_arguments_are_appended() {
+        let commands = expand("ls", "store ids", &args(&["--sort-by", "created"]));
+        assert_eq!(Ok(vec![args(&["store", "ids", "--sort-by", "created"])]), commands);
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let commands = expand("note", "notes create --name=$1 && tag add $2 '$1 note' $@",
+                              &args(&["todo", "work"]));
+        assert_eq!(Ok(vec![
+            args(&["notes", "create", "--name=todo"]),
+            args(&["tag", "add", "work", "todo note", "todo", "work"]),
+        ]), commands);
+    }
+
+    #[test]
+    fn test_quoted_separator_is_an_argument() {
+        let commands = expand("note", "notes create --name \"&&\" && log '&&'", &[]);
+        assert_eq!(Ok(vec![
+            args(&["notes", "create", "--name", "&&"]),
+            args(&["log", "&&"]),
+        ]), commands);
+    }
+
+    #[test]
+    fn test_from_table() {
+        let table = ::toml::de::from_str::<BTreeMap<String, Value>>(r#"
+            standup = "timetrack start standup && diary create --no-edit"
+            store   = [ "s", "st" ]
+            notes   = "n"
+        "#).unwrap();
+        let aliases = from_table(&table).unwrap();
+
+        let get = |name: &str| aliases.get(name).map(String::as_str);
+        assert_eq!(Some("timetrack start standup && diary create --no-edit"), get("standup"));
+        assert_eq!(Some("store"), get("s"));
+        assert_eq!(Some("store"), get("st"));
+        assert_eq!(Some("notes"), get("n"));
+        assert_eq!(None, get("store"));
+
+        // A single word alias expands to the command with the arguments appended
+        let commands = expand("s", get("s").unwrap(), &args(&["ids"]));
+        assert_eq!(Ok(vec![args(&["store", "ids"])]), commands);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(expand("note", "notes create $2", &args(&["a"])).is_err());
+        assert!(expand("note", "notes create && && diary", &[]).is_err());
+        assert!(expand("note", "notes create \"unterminated", &[]).is_err());
+    }
+}
@@ -27,6 +27,8 @@ extern crate toml_query;
 extern crate libimagrt;
 extern crate libimagerror;
 
+mod alias;
+
 use std::env;
 use std::process::exit;
 use std::process::Command;
@@ -136,6 +138,7 @@ fn main() {
              .required(false)
              .multiple(false)
              .help("Get the versions of the imag commands"))
+        .arg(Runtime::arg_ids_out())
         .subcommand(SubCommand::with_name("help").help("Show help"))
         .after_help(helptext.as_str());
     let rt = Runtime::new(app)
@@ -197,54 +200,32 @@ fn main() {
             // Get all given arguments and further subcommands to pass to
             // the imag-<> binary
             // Providing no arguments is OK, and is therefore ignored here
-            let mut subcommand_args : Vec<String> = match scmd.values_of("") {
+            let subcommand_args : Vec<String> = match scmd.values_of("") {
                 Some(values) => values.map(String::from).collect(),
                 None => Vec::new()
             };
 
-            forward_commandline_arguments(&matches, &mut subcommand_args);
-
             let subcommand = String::from(subcommand);
-            let subcommand = aliases.get(&subcommand).cloned().unwrap_or(subcommand);
-
-            debug!("Calling 'imag-{}' with args: {:?}", subcommand, subcommand_args);
-
-            // Create a Command, and pass it the gathered arguments
-            match Command::new(format!("imag-{}", subcommand))
-                .stdin(Stdio::inherit())
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .args(&subcommand_args[..])
-                .spawn()
-                .and_then(|mut c| c.wait())
-            {
-                Ok(exit_status) => {
-                    if !exit_status.success() {
-                        debug!("{} exited with non-zero exit code: {:?}", subcommand, exit_status);
-                        println!("{} exited with non-zero exit code", subcommand);
-                        exit(exit_status.code().unwrap_or(1));
-                    }
-                    debug!("Successful exit!");
+
+            // An alias expands to one or more command lines, everything else is one
+            let commands = match aliases.get(&subcommand) {
+                Some(definition) => alias::expand(&subcommand, definition, &subcommand_args)
+                    .unwrap_or_else(|e| {
+                        println!("{}", e);
+                        exit(1)
+                    }),
+                None => {
+                    let mut command = vec![subcommand];
+                    command.extend(subcommand_args);
+                    vec![command]
                 },
+            };
 
-                Err(e) => {
-                    debug!("Error calling the subcommand");
-                    match e.kind() {
-                        ErrorKind::NotFound => {
-                            println!("No such command: 'imag-{}'", subcommand);
-                            println!("See 'imag --help' for available subcommands");
-                            exit(1);
-                        },
-                        ErrorKind::PermissionDenied => {
-                            println!("No permission to execute: 'imag-{}'", subcommand);
-                            exit(1);
-                        },
-                        _ => {
-                            println!("Error spawning: {:?}", e);
-                            exit(1);
-                        }
-                    }
-                }
+            for mut command in commands {
+                let subcommand = command.remove(0);
+
+                forward_commandline_arguments(&matches, &mut command);
+                call_subcommand(&subcommand, &command);
             }
         },
         // Calling for example 'imag --versions' will lead here, as this option does not exit.
@@ -253,6 +234,49 @@ fn main() {
     }
 }
 
+/// Call `imag-<subcommand>` with `args`, exit if it fails
+fn call_subcommand(subcommand: &str, args: &[String]) {
+    debug!("Calling 'imag-{}' with args: {:?}", subcommand, args);
+
+    // Create a Command, and pass it the gathered arguments
+    match Command::new(format!("imag-{}", subcommand))
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .args(args)
+        .spawn()
+        .and_then(|mut c| c.wait())
+    {
+        Ok(exit_status) => {
+            if !exit_status.success() {
+                debug!("{} exited with non-zero exit code: {:?}", subcommand, exit_status);
+                println!("{} exited with non-zero exit code", subcommand);
+                exit(exit_status.code().unwrap_or(1));
+            }
+            debug!("Successful exit!");
+        },
+
+        Err(e) => {
+            debug!("Error calling the subcommand");
+            match e.kind() {
+                ErrorKind::NotFound => {
+                    println!("No such command: 'imag-{}'", subcommand);
+                    println!("See 'imag --help' for available subcommands");
+                    exit(1);
+                },
+                ErrorKind::PermissionDenied => {
+                    println!("No permission to execute: 'imag-{}'", subcommand);
+                    exit(1);
+                },
+                _ => {
+                    println!("Error spawning: {:?}", e);
+                    exit(1);
+                }
+            }
+        }
+    }
+}
+
 fn fetch_aliases(rt: &Runtime) -> Result<BTreeMap<String, String>, String> {
     let cfg   = rt.config().ok_or_else(|| String::from("No configuration found"))?;
     let value = cfg
@@ -261,38 +285,7 @@ fn fetch_aliases(rt: &Runtime) -> Result<BTreeMap<String, String>, String> {
 
     match value? {
         None                         => Ok(BTreeMap::new()),
-        Some(&Value::Table(ref tbl)) => {
-            let mut alias_mappings = BTreeMap::new();
-
-            for (k, v) in tbl {
-                match v {
-                    &Value::String(ref alias)      => {
-                        alias_mappings.insert(alias.clone(), k.clone());
-                    },
-                    &Value::Array(ref aliases) => {
-                        for alias in aliases {
-                            match alias {
-                                &Value::String(ref s) => {
-                                    alias_mappings.insert(s.clone(), k.clone());
-                                },
-                                _ => {
-                                    let e = format!("Not all values are a String in 'imag.aliases.{}'", k);
-                                    return Err(e);
-                                }
-                            }
-                        }
-                    },
-
-                    _ => {
-                        let msg = format!("Type Error: 'imag.aliases.{}' is not a table or string", k);
-                        return Err(msg);
-                    },
-                }
-            }
-
-            Ok(alias_mappings)
-        },
-
+        Some(&Value::Table(ref tbl)) => alias::from_table(tbl),
         Some(_) => Err(String::from("Type Error: 'imag.aliases' is not a table")),
     }
 }
@@ -335,7 +328,17 @@ fn forward_commandline_arguments(m: &ArgMatches, scmd: &mut Vec<String>) {
     push(Some("generate-commandline-completion"),
          Runtime::arg_generate_compl(), m , scmd);
 
+    push(Some("output"),
+         Runtime::arg_output_name(), m , scmd);
+
+    push(Some("output-template"),
+         Runtime::arg_output_template_name(), m , scmd);
+
     push(None , Runtime::arg_logdest_name()                         , m , scmd);
 
+    if m.is_present(Runtime::arg_ids_out_name()) {
+        scmd.insert(0, String::from("--ids-out"));
+    }
+
 }
 
@@ -49,6 +49,7 @@ use toml::Value;
 use toml_query::read::TomlValueReadExt;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagbookmark::collection::BookmarkCollection;
 use libimagbookmark::collection::BookmarkCollectionStore;
@@ -137,7 +138,13 @@ fn list(rt: &Runtime) {
             debug!("Listing...");
             for (i, link) in links.enumerate() {
                 match link {
-                    Ok(link) => println!("{: >3}: {}", i, link),
+                    Ok(link) => {
+                        let record = Record::new(format!("{: >3}: {}", i, link))
+                            .with("index", i)
+                            .with("link", link.to_string());
+
+                        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+                    },
                     Err(e)   => trace_error(&e)
                 }
             };
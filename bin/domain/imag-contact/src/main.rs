@@ -62,6 +62,7 @@ use toml::Value;
 use walkdir::WalkDir;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagerror::trace::MapErrTrace;
 use libimagcontact::store::ContactStore;
@@ -141,7 +142,11 @@ fn list(rt: &Runtime) {
             let s = list_format.render("format", &data)
                 .map_err_trace_exit(1)
                 .unwrap();
-            println!("{}", s);
+            let record = data
+                .into_iter()
+                .fold(Record::new(s), |record, (key, value)| record.with(key, value));
+
+            rt.output().emit(record).map_err_trace_exit(1).unwrap();
         })
         .collect::<Vec<_>>();
 }
@@ -209,7 +214,11 @@ fn show(rt: &Runtime) {
     let s = show_format.render("format", &data)
         .map_err_trace_exit(1)
         .unwrap();
-    println!("{}", s);
+    let record = data
+        .into_iter()
+        .fold(Record::new(s), |record, (key, value)| record.with(key, value));
+
+    rt.output().emit(record).map_err_trace_exit(1).unwrap();
     info!("Ok");
 }
 
@@ -40,6 +40,7 @@ pub fn create(rt: &Runtime) {
         .unwrap_or_else( || warn_exit("No diary selected. Use either the configuration file or the commandline option", 1));
 
     let mut entry = create_entry(rt.store(), &diaryname, rt);
+    rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
 
     let res = if rt.cli().subcommand_matches("create").unwrap().is_present("no-edit") {
         debug!("Not editing new diary entry");
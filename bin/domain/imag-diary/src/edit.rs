@@ -60,7 +60,10 @@ pub fn edit(rt: &Runtime) {
         })
         .and_then(|id| rt.store().get(id))
         .map(|opte| match opte {
-            Some(mut e) => e.edit_content(rt).chain_err(|| DEK::IOError),
+            Some(mut e) => {
+                rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
+                e.edit_content(rt).chain_err(|| DEK::IOError)
+            },
             None        => Err(DE::from_kind(DEK::EntryNotInDiary)),
         })
         .map_err_trace()
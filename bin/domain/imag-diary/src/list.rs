@@ -18,11 +18,8 @@
 //
 
 use libimagdiary::diary::Diary;
-use libimagdiary::error::DiaryErrorKind as DEK;
-use libimagdiary::error::ResultExt;
-use libimagentrylist::listers::core::CoreLister;
-use libimagentrylist::lister::Lister;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagstore::store::Entry;
 use libimagutil::warn_exit::warn_exit;
 use libimagerror::trace::MapErrTrace;
@@ -43,21 +40,23 @@ pub fn list(rt: &Runtime) {
     }
 
     Diary::entries(rt.store(), &diaryname)
-        .and_then(|es| {
+        .map(|es| {
             debug!("Iterator for listing: {:?}", es);
 
-            let es = es
+            let records = es
                 .filter_map(|entry| {
                     entry
                         .map_dbg(|e| format!("Filtering: {:?}", e))
                         .map_err_trace() // error tracing here
                         .ok() // so we can ignore errors here
                 })
-                .map(|e| e.into());
+                .map(|e| {
+                    rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
+                    let id = entry_to_location_listing_string(&e);
+                    Record::new(id.clone()).with("id", id)
+                });
 
-            CoreLister::new(&entry_to_location_listing_string)
-                .list(es)
-                .chain_err(|| DEK::IOError)
+            rt.output().emit_all(records).map_err_trace_exit_unwrap(1);
         })
         .map_dbg_str("Ok")
         .map_err_trace()
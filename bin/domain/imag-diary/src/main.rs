@@ -41,7 +41,6 @@ extern crate toml_query;
 
 extern crate libimagdiary;
 extern crate libimagentryedit;
-extern crate libimagentrylist;
 extern crate libimagerror;
 extern crate libimaginteraction;
 extern crate libimagrt;
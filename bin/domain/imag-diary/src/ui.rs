@@ -19,8 +19,11 @@
 
 use clap::{Arg, ArgGroup, App, SubCommand};
 
+use libimagrt::runtime::Runtime;
+
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
+       .arg(Runtime::arg_ids_out())
        .arg(Arg::with_name("diaryname")
             .long("diary")
             .short("d")
@@ -30,7 +30,23 @@ pub fn view(rt: &Runtime) {
     let hdr       = rt.cli().subcommand_matches("view").unwrap().is_present("show-header");
 
     Diary::entries(rt.store(), &diaryname)
-        .and_then(|entries| DV::new(hdr).view_entries(entries.into_iter().filter_map(Result::ok)))
+        .and_then(|entries| {
+            let entries = entries
+                .into_iter()
+                .filter_map(Result::ok)
+                .map(|e| {
+                    rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
+                    e
+                });
+
+            if rt.output().is_quiet() {
+                // The viewer prints to stdout, which only gets the ids of the entries now
+                for _ in entries {}
+                Ok(())
+            } else {
+                DV::new(hdr).view_entries(entries)
+            }
+        })
         .map_err_trace()
         .ok();
 }
@@ -52,6 +52,7 @@ use std::process::exit;
 
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::output::{OutputMode, Record};
 use libimagerror::trace::{MapErrTrace, trace_error};
 use libimaghabit::store::HabitStore;
 use libimaghabit::habit::builder::HabitBuilder;
@@ -307,12 +308,7 @@ fn today(rt: &Runtime, future: bool) {
                 .iter().map(|x| String::from(*x)).collect()
         }
 
-        TableLister::new(lister_fn)
-            .with_header(lister_header())
-            .with_idx(true)
-            .print_empty(false)
-            .list(relevant.into_iter())
-            .map_err_trace_exit_unwrap(1);
+        list_table(rt, lister_header(), lister_fn, relevant.into_iter());
     }
 }
 
@@ -353,12 +349,7 @@ fn list(rt: &Runtime) {
         });
 
 
-    TableLister::new(lister_fn)
-        .with_header(lister_header())
-        .with_idx(true)
-        .print_empty(false)
-        .list(iter)
-        .map_err_trace_exit_unwrap(1);
+    list_table(rt, lister_header(), lister_fn, iter);
 }
 
 fn show(rt: &Runtime) {
@@ -396,12 +387,21 @@ fn show(rt: &Runtime) {
             let recur    = habit.habit_recur_spec().map_err_trace_exit_unwrap(1);
             let comm     = habit.habit_comment().map_err_trace_exit_unwrap(1);
 
-            println!("{i} - {name}\nBase      : {b},\nRecurrence: {r}\nComment   : {c}\n",
-                     i    = i,
-                     name = name,
-                     b    = basedate,
-                     r    = recur,
-                     c    = comm);
+            let text = format!("{i} - {name}\nBase      : {b},\nRecurrence: {r}\nComment   : {c}\n",
+                               i    = i,
+                               name = name,
+                               b    = basedate,
+                               r    = recur,
+                               c    = comm);
+
+            let record = Record::new(text)
+                .with("index", i)
+                .with("name", name)
+                .with("basedate", basedate)
+                .with("recurrence", recur)
+                .with("comment", comm);
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
 
             let instances_iter = habit
                 .linked_instances()
@@ -411,12 +411,7 @@ fn show(rt: &Runtime) {
                     rt.store().get(instance_id).map_err_trace_exit_unwrap(1)
                 });
 
-            TableLister::new(instance_lister_fn)
-                .with_header(instance_lister_header())
-                .with_idx(true)
-                .print_empty(false)
-                .list(instances_iter)
-                .map_err_trace_exit_unwrap(1);
+            list_table(rt, instance_lister_header(), instance_lister_fn, instances_iter);
         })
         .collect::<Vec<_>>();
 }
@@ -483,6 +478,37 @@ fn get_from_store<'a>(store: &'a Store, id: StoreId) -> Option<FileLockEntry<'a>
     }
 }
 
+/// List the `entries` as table in the text output mode and as one record per entry otherwise
+///
+/// The fields of the records are named after the `header`.
+fn list_table<'a, I>(rt: &Runtime,
+                     header: Vec<String>,
+                     lister_fn: fn(&FileLockEntry) -> Vec<String>,
+                     entries: I)
+    where I: Iterator<Item = FileLockEntry<'a>>
+{
+    if rt.output().mode() == OutputMode::Text {
+        TableLister::new(lister_fn)
+            .with_header(header)
+            .with_idx(true)
+            .print_empty(false)
+            .list(entries)
+            .map_err_trace_exit_unwrap(1);
+    } else {
+        for entry in entries {
+            let row    = lister_fn(&entry);
+            let record = header
+                .iter()
+                .zip(row.iter())
+                .fold(Record::new(row.join(" | ")), |record, (key, value)| {
+                    record.with(key.to_lowercase().replace(' ', "_"), value.clone())
+                });
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
+        }
+    }
+}
+
 fn date_to_string_helper(d: chrono::NaiveDate) -> String {
     libimaghabit::util::date_to_string(&d)
 }
@@ -45,6 +45,7 @@ extern crate libimagerror;
 extern crate libimagdiary;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagerror::trace::MapErrTrace;
 use libimagdiary::diary::Diary;
@@ -121,14 +122,23 @@ fn show(rt: &Runtime) {
         for element in iter {
             let e  = element.map_err_trace_exit_unwrap(1);
             let id = e.diary_id().map_err_trace_exit_unwrap(1);
-            println!("{dname: >10} - {y: >4}-{m:0>2}-{d:0>2}T{H:0>2}:{M:0>2} - {text}",
-                     dname = id.diary_name(),
-                     y = id.year(),
-                     m = id.month(),
-                     d = id.day(),
-                     H = id.hour(),
-                     M = id.minute(),
-                     text = e.get_content());
+            let datetime = format!("{y: >4}-{m:0>2}-{d:0>2}T{H:0>2}:{M:0>2}",
+                                   y = id.year(),
+                                   m = id.month(),
+                                   d = id.day(),
+                                   H = id.hour(),
+                                   M = id.minute());
+            let text = format!("{dname: >10} - {datetime} - {text}",
+                               dname    = id.diary_name(),
+                               datetime = datetime,
+                               text     = e.get_content());
+
+            let record = Record::new(text)
+                .with("diary", id.diary_name().clone())
+                .with("datetime", datetime)
+                .with("text", e.get_content().clone());
+
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
         }
     }
 
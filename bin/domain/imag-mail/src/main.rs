@@ -29,6 +29,7 @@ extern crate libimagutil;
 use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
 use libimagmail::mail::Mail;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagutil::info_result::*;
 
@@ -81,7 +82,7 @@ fn list(rt: &Runtime) {
         Err(e)   => trace_error_exit(&e, 1),
     };
 
-    fn list_mail(m: Mail) {
+    fn list_mail(rt: &Runtime, m: Mail) {
         let id = match m.get_message_id() {
             Ok(Some(f)) => f,
             Ok(None) => "<no id>".to_owned(),
@@ -118,17 +119,25 @@ fn list(rt: &Runtime) {
             },
         };
 
-        println!("Mail: {id}\n\tFrom: {from}\n\tTo: {to}\n\t{subj}\n",
-                 from = from,
-                 id   = id,
-                 subj = subject,
-                 to   = to
+        let text = format!("Mail: {id}\n\tFrom: {from}\n\tTo: {to}\n\t{subj}\n",
+                           from = from,
+                           id   = id,
+                           subj = subject,
+                           to   = to
         );
+
+        let record = Record::new(text)
+            .with("id", id)
+            .with("from", from)
+            .with("to", to)
+            .with("subject", subject);
+
+        rt.output().emit(record).map_err_trace_exit_unwrap(1);
     }
 
     // TODO: Implement lister type in libimagmail for this
     for mail in iter {
-        list_mail(mail)
+        list_mail(rt, mail)
     }
 }
 
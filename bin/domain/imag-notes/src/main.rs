@@ -31,6 +31,7 @@ extern crate libimagutil;
 use itertools::Itertools;
 
 use libimagentryedit::edit::Edit;
+use libimagrt::output::Record;
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
 use libimagnotes::note::Note;
@@ -76,6 +77,8 @@ fn create(rt: &Runtime) {
         .new_note(name.clone(), String::new())
         .map_err_trace_exit_unwrap(1);
 
+    rt.report_touched(note.get_location()).map_err_trace_exit_unwrap(1);
+
     if rt.cli().subcommand_matches("create").unwrap().is_present("edit") {
         let _ = note
             .edit_content(rt)
@@ -98,6 +101,7 @@ fn edit(rt: &Runtime) {
         .get_note(name.clone())
         .map_err_trace_exit_unwrap(1)
         .map(|mut note| {
+            rt.report_touched(note.get_location()).map_err_trace_exit_unwrap(1);
             let _ = note
                 .edit_content(rt)
                 .map_warn_err_str("Editing failed")
@@ -127,7 +131,14 @@ fn list(rt: &Runtime) {
 
             for note in notes.iter() {
                 note.get_name()
-                    .map(|name| println!("{}", name))
+                    .map(|name| {
+                        let record = Record::new(name.clone())
+                            .with("name", name)
+                            .with("id", note.get_location().to_string());
+
+                        rt.output().emit(record).map_err_trace_exit_unwrap(1);
+                        rt.report_touched(note.get_location()).map_err_trace_exit_unwrap(1);
+                    })
                     .map_err_trace()
                     .ok();
             }
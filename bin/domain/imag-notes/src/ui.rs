@@ -19,8 +19,11 @@
 
 use clap::{Arg, App, SubCommand};
 
+use libimagrt::runtime::Runtime;
+
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
+        .arg(Runtime::arg_ids_out())
         .subcommand(SubCommand::with_name("create")
                    .about("Create a note")
                    .version("0.1")
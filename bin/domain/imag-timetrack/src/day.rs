@@ -33,6 +33,8 @@ use libimagtimetrack::iter::filter::*;
 
 use libimagrt::runtime::Runtime;
 
+use util::timetracking_record;
+
 
 pub fn day(rt: &Runtime) -> i32 {
     let (_, cmd) = rt.cli().subcommand();
@@ -97,11 +99,8 @@ pub fn day(rt: &Runtime) -> i32 {
                         let end   = e.get_end_datetime()?;
                         debug!(" -> end = {:?}", end);
 
-                        match (start, end) {
-                            (None, _)          => println!("{} has no start time.", tag),
-                            (Some(s), None)    => println!("{} | {} - ...", tag, s),
-                            (Some(s), Some(e)) => println!("{} | {} - {}", tag, s, e),
-                        }
+                        let record = timetracking_record(&tag, start, end);
+                        rt.output().emit(record).map_err_trace_exit_unwrap(1);
 
                         Ok(())
                     })
@@ -41,6 +41,7 @@ mod start;
 mod stop;
 mod track;
 mod ui;
+mod util;
 mod week;
 mod year;
 
@@ -0,0 +1,47 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use chrono::NaiveDateTime;
+
+use libimagrt::output::Record;
+use libimagtimetrack::tag::TimeTrackingTag;
+
+const DATETIME_FORMAT : &'static str = "%Y-%m-%dT%H:%M:%S";
+
+/// Build the output record for one timetracking
+///
+/// The text is what the listing commands always printed, the fields carry the tag and the start
+/// and end time (or null if there is none) for the machine readable output modes.
+pub fn timetracking_record(tag: &TimeTrackingTag,
+                           start: Option<NaiveDateTime>,
+                           end: Option<NaiveDateTime>)
+    -> Record
+{
+    let text = match (start, end) {
+        (None, _)          => format!("{} has no start time.", tag),
+        (Some(s), None)    => format!("{} | {} - ...", tag, s),
+        (Some(s), Some(e)) => format!("{} | {} - {}", tag, s, e),
+    };
+
+    Record::new(text)
+        .with("tag", tag.to_string())
+        .with("start", start.map(|s| s.format(DATETIME_FORMAT).to_string()))
+        .with("end", end.map(|e| e.format(DATETIME_FORMAT).to_string()))
+}
+
@@ -33,6 +33,8 @@ use libimagtimetrack::iter::filter::*;
 
 use libimagrt::runtime::Runtime;
 
+use util::timetracking_record;
+
 pub fn week(rt: &Runtime) -> i32 {
     let cmd = rt.cli().subcommand().1.unwrap(); // checked in main
 
@@ -104,11 +106,8 @@ pub fn week(rt: &Runtime) -> i32 {
                         let end   = e.get_end_datetime()?;
                         debug!(" -> end = {:?}", end);
 
-                        match (start, end) {
-                            (None, _)          => println!("{} has no start time.", tag),
-                            (Some(s), None)    => println!("{} | {} - ...", tag, s),
-                            (Some(s), Some(e)) => println!("{} | {} - {}", tag, s, e),
-                        }
+                        let record = timetracking_record(&tag, start, end);
+                        rt.output().emit(record).map_err_trace_exit_unwrap(1);
 
                         Ok(())
                     })
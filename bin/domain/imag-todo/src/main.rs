@@ -34,6 +34,7 @@ use std::io::stdin;
 use toml::Value;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Record;
 use libimagrt::setup::generate_runtime_setup;
 use libimagtodo::taskstore::TaskStore;
 use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
@@ -147,7 +148,8 @@ fn list(rt: &Runtime) {
             };
 
             // and then print that
-            println!("{}", outstring);
+            let record = Record::new(outstring).with("uuids", uuids);
+            rt.output().emit(record).map_err_trace_exit_unwrap(1);
         });
 
     res.map_err_trace().ok();
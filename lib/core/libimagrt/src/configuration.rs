@@ -17,16 +17,295 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fmt::Error as FmtError;
 use std::path::PathBuf;
 
 use toml::Value;
 use clap::App;
 
+use libimagstore::schema::{Schema, FieldType};
+
 use error::RuntimeError as RE;
 use error::RuntimeErrorKind as REK;
 use error::Result;
 use error::ResultExt;
 
+/// The system-wide configuration file, the lowest layer of the configuration
+pub const SYSTEM_CONFIG_PATH : &'static str = "/etc/imag/imagrc.toml";
+
+/// The name of the configuration file of a store, which lives in the store directory
+pub const STORE_CONFIG_NAME : &'static str = ".imagrc.toml";
+
+/// The prefix of the environment variables which set configuration values
+pub const ENV_CONFIG_PREFIX : &'static str = "IMAG_";
+
+/// Where a value of the configuration came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The system-wide configuration file
+    System(PathBuf),
+
+    /// The configuration file of the user
+    User(PathBuf),
+
+    /// The configuration file of the store
+    Store(PathBuf),
+
+    /// An `IMAG_*` environment variable, by name
+    Environment(String),
+
+    /// A `--override-config` argument
+    CommandLine,
+}
+
+impl Display for ConfigSource {
+
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        match *self {
+            ConfigSource::System(ref path) => write!(fmt, "system config {}", path.display()),
+            ConfigSource::User(ref path)   => write!(fmt, "user config {}", path.display()),
+            ConfigSource::Store(ref path)  => write!(fmt, "store config {}", path.display()),
+            ConfigSource::Environment(ref var) => write!(fmt, "environment variable {}", var),
+            ConfigSource::CommandLine      => write!(fmt, "commandline override"),
+        }
+    }
+
+}
+
+/// The configuration, merged from several layers
+///
+/// Each layer overrides the values of the layers merged before it. Tables are merged key by key,
+/// all other values (arrays included) are replaced as a whole. For each value which is not a
+/// table, the layer it came from is remembered.
+#[derive(Debug, Clone, Default)]
+pub struct LayeredConfig {
+    value: Option<Value>,
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl LayeredConfig {
+
+    pub fn new() -> LayeredConfig {
+        LayeredConfig::default()
+    }
+
+    /// Merge `layer` over the configuration
+    pub fn merge(&mut self, layer: Value, source: ConfigSource) {
+        let mut value = self.value.take().unwrap_or_else(|| Value::Table(BTreeMap::new()));
+        merge_value(&mut value, layer, "", &source, &mut self.sources);
+        self.value = Some(value);
+    }
+
+    /// Set the value at the dotted `path`, creating the tables on the way
+    pub fn set(&mut self, path: &str, value: Value, source: ConfigSource) {
+        let layer = path.rsplit('.').fold(value, |value, key| {
+            let mut table = BTreeMap::new();
+            table.insert(String::from(key), value);
+            Value::Table(table)
+        });
+
+        self.merge(layer, source)
+    }
+
+    /// Set the values from the `IMAG_*` environment variables in `vars`
+    ///
+    /// A variable sets a value if its name has the form `IMAG_<SECTION>__<KEY>`, where each `__`
+    /// separates the keys of the path to the value: `IMAG_STORE__TRASH__ENABLED` sets
+    /// `store.trash.enabled`. The keys are lowercased, and a `_` in a key matches a `-` if the
+    /// configuration has the key with a `-` only (`IMAG_STORE__LOCK_TIMEOUT` sets
+    /// `store.lock-timeout`). Variables without `__` (like `IMAG_RTP`) are left alone.
+    ///
+    /// The value is read as TOML value if possible (`true`, `5`, `["a", "b"]`), as string
+    /// otherwise. The variables are applied ordered by name.
+    pub fn apply_env<I>(&mut self, vars: I)
+        where I: IntoIterator<Item = (String, String)>
+    {
+        let mut vars = vars
+            .into_iter()
+            .filter(|&(ref name, _)| name.starts_with(ENV_CONFIG_PREFIX))
+            .collect::<Vec<_>>();
+        vars.sort();
+
+        for (name, value) in vars {
+            if let Some(path) = self.env_var_path(&name[ENV_CONFIG_PREFIX.len()..]) {
+                debug!("Setting '{}' from ${}", path, name);
+                self.set(&path, parse_value(&value), ConfigSource::Environment(name.clone()));
+            }
+        }
+    }
+
+    /// Get the configuration path a variable name (without the prefix) refers to
+    fn env_var_path(&self, name: &str) -> Option<String> {
+        if !name.contains("__") {
+            return None;
+        }
+
+        let mut table = self.value.as_ref().and_then(Value::as_table);
+        let mut path  = vec![];
+
+        for key in name.split("__") {
+            if key.is_empty() {
+                return None;
+            }
+
+            let key = key.to_lowercase();
+            let key = match table {
+                Some(t) if !t.contains_key(&key) && t.contains_key(&key.replace("_", "-")) => {
+                    key.replace("_", "-")
+                },
+                _ => key,
+            };
+
+            table = table.and_then(|t| t.get(&key)).and_then(Value::as_table);
+            path.push(key);
+        }
+
+        Some(path.join("."))
+    }
+
+    /// Apply the `key=value` overrides from the commandline
+    ///
+    /// The keys must be present in the configuration and the values convertible to their type,
+    /// see `override_config()`.
+    pub fn apply_overrides(&mut self, v: Vec<String>) -> Result<()> {
+        for (k, v) in split_overrides(v) {
+            let value = {
+                let config = self.value
+                    .as_ref()
+                    .ok_or_else(|| RE::from_kind(REK::ConfigOverrideKeyNotAvailable))
+                    .chain_err(|| REK::ConfigOverrideError)?;
+
+                overridden_value(config, &k, v).chain_err(|| REK::ConfigOverrideError)?
+            };
+
+            info!("Successfully overridden: {} = {}", k, value);
+            self.set(&k, value, ConfigSource::CommandLine);
+        }
+
+        Ok(())
+    }
+
+    /// Get the merged configuration, `None` if no layer contained anything
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+
+    /// Get where the value at the dotted `path` came from
+    pub fn source(&self, path: &str) -> Option<&ConfigSource> {
+        self.sources.get(path)
+    }
+
+    /// Get the sources of all values, by their dotted path
+    pub fn sources(&self) -> &BTreeMap<String, ConfigSource> {
+        &self.sources
+    }
+
+    pub fn into_parts(self) -> (Option<Value>, BTreeMap<String, ConfigSource>) {
+        (self.value, self.sources)
+    }
+
+}
+
+fn merge_value(base: &mut Value,
+               layer: Value,
+               path: &str,
+               source: &ConfigSource,
+               sources: &mut BTreeMap<String, ConfigSource>)
+{
+    match (base, layer) {
+        (&mut Value::Table(ref mut base), Value::Table(layer)) => {
+            for (key, value) in layer {
+                let path = join_path(path, &key);
+                if let Some(existing) = base.get_mut(&key) {
+                    merge_value(existing, value, &path, source, sources);
+                    continue;
+                }
+
+                record_sources(&value, &path, source, sources);
+                base.insert(key, value);
+            }
+        },
+
+        (base, layer) => {
+            let below = format!("{}.", path);
+            let stale = sources
+                .keys()
+                .filter(|p| *p == path || p.starts_with(&below))
+                .cloned()
+                .collect::<Vec<_>>();
+            for p in stale {
+                sources.remove(&p);
+            }
+
+            record_sources(&layer, path, source, sources);
+            *base = layer;
+        },
+    }
+}
+
+fn record_sources(value: &Value, path: &str, source: &ConfigSource, sources: &mut BTreeMap<String, ConfigSource>) {
+    match *value {
+        Value::Table(ref table) => for (key, value) in table {
+            record_sources(value, &join_path(path, key), source, sources);
+        },
+        _ => {
+            sources.insert(String::from(path), source.clone());
+        },
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        String::from(key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Read `s` as TOML value (`true`, `5`, `["a", "b"]`), as string if it is none
+pub fn parse_value(s: &str) -> Value {
+    ::toml::de::from_str::<BTreeMap<String, Value>>(&format!("value = {}", s))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(String::from(s)))
+}
+
+/// Load the configuration from all layers but the commandline
+///
+/// The layers are, from the lowest to the highest:
+///
+///  1. the system-wide configuration file, `SYSTEM_CONFIG_PATH`
+///  2. the configuration file of the user, searched like `fetch_config()` does
+///  3. the configuration file of the store, `<store>/.imagrc.toml`
+///  4. the `IMAG_*` environment variables, see `LayeredConfig::apply_env()`
+///
+/// The overrides from the commandline are applied on top with `LayeredConfig::apply_overrides()`.
+/// Missing configuration files are skipped, so is a file which cannot be parsed (after printing
+/// the error).
+pub fn fetch_layered_config(searchpath: &PathBuf, storepath: &PathBuf) -> LayeredConfig {
+    let mut config = LayeredConfig::new();
+
+    let system = PathBuf::from(SYSTEM_CONFIG_PATH);
+    if let Some(value) = read_config_file(&system) {
+        config.merge(value, ConfigSource::System(system));
+    }
+
+    if let Some((path, value)) = find_user_config(searchpath) {
+        config.merge(value, ConfigSource::User(path));
+    }
+
+    let store = storepath.join(STORE_CONFIG_NAME);
+    if let Some(value) = read_config_file(&store) {
+        config.merge(value, ConfigSource::Store(store));
+    }
+
+    config.apply_env(env::vars());
+    config
+}
+
 /// Get a new configuration object.
 ///
 /// The passed runtimepath is used for searching the configuration file, whereas several file
@@ -34,18 +313,21 @@ use error::ResultExt;
 /// with all variants.
 ///
 /// If that doesn't work either, an error is returned.
+///
+/// This is the configuration of the user only, see `fetch_layered_config()` for the complete
+/// configuration.
 pub fn fetch_config(searchpath: &PathBuf) -> Result<Value> {
-    use std::env;
-    use std::fs::File;
-    use std::io::Read;
-    use std::io::Write;
-    use std::io::stderr;
+    find_user_config(searchpath)
+        .map(|(_, value)| value)
+        .ok_or(RE::from_kind(REK::ConfigNoConfigFileFound))
+}
 
+/// Search the configuration file of the user like `fetch_config()`, get its path and content
+pub fn find_user_config(searchpath: &PathBuf) -> Option<(PathBuf, Value)> {
     use xdg_basedir;
     use itertools::Itertools;
 
     use libimagutil::variants::generate_variants as gen_vars;
-    use libimagerror::trace::trace_error;
 
     let variants = vec!["config", "config.toml", "imagrc", "imagrc.toml"];
     let modifier = |base: &PathBuf, v: &'static str| {
@@ -65,36 +347,48 @@ pub fn fetch_config(searchpath: &PathBuf) -> Result<Value> {
                                     .unwrap_or(vec![]),
     ].iter()
         .flatten()
-        .filter(|path| path.exists() && path.is_file())
-        .filter_map(|path| {
-            let content = {
-                let f = File::open(path);
-                if f.is_err() {
-                    let _ = write!(stderr(), "Error opening file: {:?}", f);
-                    return None
-                }
-                let mut f = f.unwrap();
+        .filter_map(|path| read_config_file(path).map(|value| (path.clone(), value)))
+        .nth(0)
+}
 
-                let mut s = String::new();
-                f.read_to_string(&mut s).ok();
-                s
-            };
+/// Read the configuration file at `path`, `None` if there is none or it cannot be parsed
+fn read_config_file(path: &PathBuf) -> Option<Value> {
+    use std::fs::File;
+    use std::io::Read;
+    use std::io::Write;
+    use std::io::stderr;
 
-            ::toml::de::from_str::<::toml::Value>(&content[..])
-                .map(Some)
-                .unwrap_or_else(|e| {
-                    let line_col = e
-                        .line_col()
-                        .map(|(line, col)| format!("Line {}, Column {}", line, col))
-                        .unwrap_or_else(|| String::from("Line unknown, Column unknown"));
-
-                    let _ = write!(stderr(), "Config file parser error at {}", line_col);
-                    trace_error(&e);
-                    None
-                })
+    use libimagerror::trace::trace_error;
+
+    if !(path.exists() && path.is_file()) {
+        return None;
+    }
+
+    let content = {
+        let f = File::open(path);
+        if f.is_err() {
+            let _ = write!(stderr(), "Error opening file: {:?}", f);
+            return None
+        }
+        let mut f = f.unwrap();
+
+        let mut s = String::new();
+        f.read_to_string(&mut s).ok();
+        s
+    };
+
+    ::toml::de::from_str::<::toml::Value>(&content[..])
+        .map(Some)
+        .unwrap_or_else(|e| {
+            let line_col = e
+                .line_col()
+                .map(|(line, col)| format!("Line {}, Column {}", line, col))
+                .unwrap_or_else(|| String::from("Line unknown, Column unknown"));
+
+            let _ = write!(stderr(), "Config file parser error at {}", line_col);
+            trace_error(&e);
+            None
         })
-        .nth(0)
-        .ok_or(RE::from_kind(REK::ConfigNoConfigFileFound))
 }
 
 /// Override the configuration.
@@ -106,31 +400,64 @@ pub fn fetch_config(searchpath: &PathBuf) -> Result<Value> {
 ///
 /// If `v` is empty, this is considered to be a successful `override_config()` call.
 pub fn override_config(val: &mut Value, v: Vec<String>) -> Result<()> {
+    use toml_query::insert::TomlValueInsertExt;
+
+    for (k, v) in split_overrides(v) {
+        let value = overridden_value(val, &k, v).chain_err(|| REK::ConfigOverrideError)?;
+        info!("Successfully overridden: {} = {}", k, value);
+        let _ = val
+            .insert(&k, value)
+            .chain_err(|| REK::ConfigTOMLParserError)
+            .chain_err(|| REK::ConfigOverrideError)?;
+    }
+
+    Ok(())
+}
+
+/// Split the 'key=value' pairs, ignoring the ones without a '='
+fn split_overrides(v: Vec<String>) -> Vec<(String, String)> {
     use libimagutil::key_value_split::*;
-    use toml_query::read::TomlValueReadExt;
 
-    let iter = v.into_iter()
+    v.into_iter()
         .map(|s| { debug!("Trying to process '{}'", s); s })
         .filter_map(|s| s.into_kv().map(Into::into).or_else(|| {
             warn!("Could split at '=' - will be ignore override");
             None
         }))
-        .map(|(k, v)| {
-            let value = val
-                .read(&k)
-                .chain_err(|| REK::ConfigTOMLParserError)?
-                .ok_or(RE::from_kind(REK::ConfigOverrideKeyNotAvailable))?;
-
-            into_value(value, v)
-                .map(|v| info!("Successfully overridden: {} = {}", k, v))
-                .ok_or_else(|| RE::from_kind(REK::ConfigOverrideTypeNotMatching))
-        });
+        .collect()
+}
 
-    for elem in iter {
-        let _ = try!(elem.chain_err(|| REK::ConfigOverrideError));
-    }
+/// Get the value `s` overrides the value at `key` in `config` with
+fn overridden_value(config: &Value, key: &str, s: String) -> Result<Value> {
+    use toml_query::read::TomlValueReadExt;
 
-    Ok(())
+    let value = config
+        .read(key)
+        .chain_err(|| REK::ConfigTOMLParserError)?
+        .ok_or(RE::from_kind(REK::ConfigOverrideKeyNotAvailable))?;
+
+    into_value(value, s).ok_or_else(|| RE::from_kind(REK::ConfigOverrideTypeNotMatching))
+}
+
+/// The schema of the "imag" section of the configuration: the logging and the aliases of the
+/// `imag` command
+pub fn config_schema() -> Schema {
+    let level   = || FieldType::OneOf(vec!["trace", "debug", "info", "warn", "error"]);
+    let strings = || FieldType::Array(Box::new(FieldType::String));
+
+    Schema::new("imag")
+        .required("logging.level", level())
+        .required("logging.destinations", strings())
+        .required("logging.format.trace", FieldType::String)
+        .required("logging.format.debug", FieldType::String)
+        .required("logging.format.info", FieldType::String)
+        .required("logging.format.warn", FieldType::String)
+        .required("logging.format.error", FieldType::String)
+        .optional("logging.modules.*.destinations", strings())
+        .optional("logging.modules.*.level", level())
+        .required("logging.modules.*.enabled", FieldType::Boolean)
+        .optional("aliases.*", FieldType::AnyOf(vec![FieldType::String, strings()]))
+        .deny_unknown_fields()
 }
 
 /// Tries to convert the String `s` into the same type as `value`.
@@ -167,3 +494,120 @@ pub trait InternalConfiguration {
 
 impl<'a> InternalConfiguration for App<'a, 'a> {}
 
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml::de::from_str as toml_from_str;
+    use toml_query::read::TomlValueReadExt;
+
+    use super::*;
+
+    fn layer(s: &str) -> Value {
+        toml_from_str(s).unwrap()
+    }
+
+    fn read<'a>(config: &'a LayeredConfig, path: &str) -> &'a Value {
+        config.value().unwrap().read(path).unwrap().unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|&(k, v)| (String::from(k), String::from(v))).collect()
+    }
+
+    fn system() -> ConfigSource { ConfigSource::System(PathBuf::from("/etc/imag/imagrc.toml")) }
+    fn user() -> ConfigSource { ConfigSource::User(PathBuf::from("/home/user/.imag/imagrc.toml")) }
+    fn store() -> ConfigSource { ConfigSource::Store(PathBuf::from("/store/.imagrc.toml")) }
+
+    #[test]
+    fn test_layer_precedence() {
+        let mut config = LayeredConfig::new();
+        config.merge(layer("[x]\na = 1\nb = 1\nc = 1\nd = 1\ne = 1"), system());
+        config.merge(layer("[x]\nb = 2\nc = 2\nd = 2\ne = 2"), user());
+        config.merge(layer("[x]\nc = 3\nd = 3\ne = 3"), store());
+        config.apply_env(env(&[("IMAG_X__D", "4"), ("IMAG_X__E", "4")]));
+        config.apply_overrides(vec![String::from("x.e=5")]).unwrap();
+
+        for (key, value) in vec![("x.a", 1), ("x.b", 2), ("x.c", 3), ("x.d", 4), ("x.e", 5)] {
+            assert_eq!(&Value::Integer(value), read(&config, key), "{}", key);
+        }
+
+        assert_eq!(Some(&system()), config.source("x.a"));
+        assert_eq!(Some(&user()), config.source("x.b"));
+        assert_eq!(Some(&store()), config.source("x.c"));
+        assert_eq!(Some(&ConfigSource::Environment(String::from("IMAG_X__D"))), config.source("x.d"));
+        assert_eq!(Some(&ConfigSource::CommandLine), config.source("x.e"));
+    }
+
+    #[test]
+    fn test_arrays_are_replaced() {
+        let mut config = LayeredConfig::new();
+        config.merge(layer("[x]\nlist = [\"a\", \"b\"]"), system());
+        config.merge(layer("[x]\nlist = [\"c\"]"), user());
+
+        let expected = Value::Array(vec![Value::String(String::from("c"))]);
+        assert_eq!(&expected, read(&config, "x.list"));
+        assert_eq!(Some(&user()), config.source("x.list"));
+    }
+
+    #[test]
+    fn test_sources_are_removed_when_a_table_is_replaced() {
+        let mut config = LayeredConfig::new();
+        config.merge(layer("[x.t]\na = 1\nb = 2"), system());
+        config.merge(layer("[x]\nt = \"flat\""), user());
+
+        assert_eq!(&Value::String(String::from("flat")), read(&config, "x.t"));
+        assert_eq!(Some(&user()), config.source("x.t"));
+        assert!(config.source("x.t.a").is_none());
+        assert!(config.source("x.t.b").is_none());
+
+        // and the other way round
+        config.merge(layer("[x.t]\nc = 3"), store());
+        assert_eq!(&Value::Integer(3), read(&config, "x.t.c"));
+        assert_eq!(Some(&store()), config.source("x.t.c"));
+        assert!(config.source("x.t").is_none());
+    }
+
+    #[test]
+    fn test_env_underscore_matches_dash() {
+        let mut config = LayeredConfig::new();
+        config.merge(layer("[store]\nlock-timeout = 5"), system());
+        config.apply_env(env(&[("IMAG_STORE__LOCK_TIMEOUT", "10")]));
+
+        assert_eq!(&Value::Integer(10), read(&config, "store.lock-timeout"));
+        assert!(config.value().unwrap().read("store.lock_timeout").unwrap().is_none());
+        assert_eq!(Some(&ConfigSource::Environment(String::from("IMAG_STORE__LOCK_TIMEOUT"))),
+                   config.source("store.lock-timeout"));
+    }
+
+    #[test]
+    fn test_env_values_are_parsed() {
+        let mut config = LayeredConfig::new();
+        config.apply_env(env(&[
+            ("IMAG_X__BOOL", "true"),
+            ("IMAG_X__LIST", "[\"a\", \"b\"]"),
+            ("IMAG_X__STRING", "some text"),
+        ]));
+
+        assert_eq!(&Value::Boolean(true), read(&config, "x.bool"));
+        assert_eq!(2, read(&config, "x.list").as_array().unwrap().len());
+        assert_eq!(&Value::String(String::from("some text")), read(&config, "x.string"));
+    }
+
+    #[test]
+    fn test_env_vars_without_separator_are_ignored() {
+        let mut config = LayeredConfig::new();
+        config.merge(layer("[x]\na = 1"), system());
+        config.apply_env(env(&[
+            ("IMAG_RTP", "/tmp/rtp"),
+            ("IMAG_X__", "2"),
+            ("OTHER_X__A", "3"),
+        ]));
+
+        assert_eq!(&Value::Integer(1), read(&config, "x.a"));
+        assert!(config.value().unwrap().read("rtp").unwrap().is_none());
+        assert_eq!(1, config.sources().len());
+    }
+
+}
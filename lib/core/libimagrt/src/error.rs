@@ -118,6 +118,26 @@ error_chain! {
             display("Configuration: Configuration Type not matching")
         }
 
+        OutputModeUnknown(mode: String) {
+            description("Output: Unknown output mode")
+            display("Output: Unknown output mode '{}', expected 'text', 'json' or 'template'", mode)
+        }
+
+        OutputTemplateMissing {
+            description("Output: No template for the 'template' output mode")
+            display("Output: No template for the 'template' output mode, pass one with --output-template")
+        }
+
+        OutputRenderError {
+            description("Output: Could not render record")
+            display("Output: Could not render record")
+        }
+
+        IdsFromStdinError {
+            description("Could not read ids from stdin")
+            display("Could not read ids from stdin")
+        }
+
     }
 }
 
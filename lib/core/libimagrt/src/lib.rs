@@ -46,7 +46,7 @@ extern crate handlebars;
 extern crate clap;
 extern crate toml;
 extern crate toml_query;
-#[macro_use] extern crate is_match;
+extern crate serde_json;
 
 extern crate libimagstore;
 extern crate libimagutil;
@@ -56,6 +56,7 @@ extern crate libimaginteraction;
 pub mod error;
 pub mod configuration;
 pub mod logger;
+pub mod output;
 pub mod runtime;
 pub mod setup;
 pub mod spec;
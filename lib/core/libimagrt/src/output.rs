@@ -0,0 +1,269 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The output of the commands
+//!
+//! The commands do not print what they output themselves, they emit it as `Record`s through the
+//! `Output` of the `Runtime`. A record is the text a human wants to read, together with the same
+//! information as fields for scripts. How the records are printed is chosen with the `--output`
+//! argument every command has:
+//!
+//! * `text` (the default) prints the text of each record
+//! * `json` prints the fields of each record as JSON object, one per line
+//! * `template` renders the fields of each record with the handlebars template passed with
+//!   `--output-template`, for example `--output-template '{{id}} - {{name}}'`
+//!
+//! Messages which are not output, like errors or progress, go to the log as before.
+
+use std::fmt::{Debug, Formatter};
+use std::fmt::Error as FmtError;
+use std::io::Write;
+use std::io::stdout;
+use std::str::FromStr;
+
+use handlebars::Handlebars;
+use serde_json::Map;
+use serde_json::Value;
+
+use error::RuntimeError as RE;
+use error::RuntimeErrorKind as REK;
+use error::Result;
+use error::ResultExt;
+
+/// How records are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// The text of the records
+    Text,
+
+    /// The fields of the records, as JSON object per line
+    Json,
+
+    /// The fields of the records, rendered with a template
+    Template,
+}
+
+impl FromStr for OutputMode {
+    type Err = RE;
+
+    fn from_str(s: &str) -> Result<OutputMode> {
+        match s {
+            "text"     => Ok(OutputMode::Text),
+            "json"     => Ok(OutputMode::Json),
+            "template" => Ok(OutputMode::Template),
+            other      => Err(RE::from_kind(REK::OutputModeUnknown(String::from(other)))),
+        }
+    }
+}
+
+/// One unit of output of a command, for example one entry of a listing
+///
+/// ```ignore
+/// Record::new(format!("{} - {}", id, name))
+///     .with("id", id)
+///     .with("name", name)
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    text: String,
+    fields: Map<String, Value>,
+}
+
+impl Record {
+
+    /// A record which is printed as `text` in the `text` mode
+    pub fn new<S: Into<String>>(text: S) -> Record {
+        Record {
+            text: text.into(),
+            fields: Map::new(),
+        }
+    }
+
+    /// Add the field `key`, replacing the field with the same key
+    pub fn with<K: Into<String>, V: Into<Value>>(mut self, key: K, value: V) -> Record {
+        let _ = self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+}
+
+/// Prints records to stdout, in the mode passed on the commandline
+pub struct Output {
+    mode: OutputMode,
+    handlebars: Handlebars,
+    quiet: bool,
+}
+
+impl Output {
+
+    /// Create an output for `mode`, the `template` is required for `OutputMode::Template`
+    pub fn new(mode: OutputMode, template: Option<&str>) -> Result<Output> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(::handlebars::no_escape);
+
+        ::libimaginteraction::format::register_all_color_helpers(&mut handlebars);
+        ::libimaginteraction::format::register_all_format_helpers(&mut handlebars);
+
+        if mode == OutputMode::Template {
+            let template = template.ok_or_else(|| RE::from_kind(REK::OutputTemplateMissing))?;
+            handlebars.register_template_string("record", template)?;
+        }
+
+        Ok(Output {
+            mode: mode,
+            handlebars: handlebars,
+            quiet: false,
+        })
+    }
+
+    /// Do not print any records, because stdout is used for something else (like `--ids-out`)
+    pub fn quiet(mut self, quiet: bool) -> Output {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Whether records are left out, because stdout is used for something else
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    /// Get the line(s) `record` is printed as
+    pub fn render(&self, record: &Record) -> Result<String> {
+        match self.mode {
+            OutputMode::Text     => Ok(record.text.clone()),
+            OutputMode::Json     => ::serde_json::to_string(&record.fields)
+                .chain_err(|| REK::OutputRenderError),
+            OutputMode::Template => self.handlebars
+                .render("record", &record.fields)
+                .chain_err(|| REK::OutputRenderError),
+        }
+    }
+
+    /// Print `record` to stdout, if the output is not quiet
+    pub fn emit(&self, record: Record) -> Result<()> {
+        self.write_to(&mut stdout(), &record)
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W, record: &Record) -> Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        let rendered = self.render(record)?;
+        writeln!(out, "{}", rendered).chain_err(|| REK::IOError)
+    }
+
+    /// Print all `records` to stdout
+    pub fn emit_all<I: IntoIterator<Item = Record>>(&self, records: I) -> Result<()> {
+        for record in records {
+            self.emit(record)?;
+        }
+
+        Ok(())
+    }
+
+}
+
+impl Debug for Output {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        write!(fmt, "Output {{ mode: {:?}, quiet: {:?} }}", self.mode, self.quiet)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record() -> Record {
+        Record::new("entry 1 - name").with("id", 1).with("name", "name")
+    }
+
+    fn written(output: &Output) -> String {
+        let mut buf = vec![];
+        output.write_to(&mut buf, &record()).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!(OutputMode::Text, "text".parse::<OutputMode>().unwrap());
+        assert_eq!(OutputMode::Json, "json".parse::<OutputMode>().unwrap());
+        assert_eq!(OutputMode::Template, "template".parse::<OutputMode>().unwrap());
+        assert!("yaml".parse::<OutputMode>().is_err());
+    }
+
+    #[test]
+    fn test_render_text() {
+        let output = Output::new(OutputMode::Text, None).unwrap();
+        assert_eq!("entry 1 - name", output.render(&record()).unwrap());
+    }
+
+    #[test]
+    fn test_render_json() {
+        let output   = Output::new(OutputMode::Json, None).unwrap();
+        let rendered = output.render(&record()).unwrap();
+        let parsed   = ::serde_json::from_str::<Value>(&rendered).unwrap();
+
+        assert!(!rendered.contains('\n'));
+        assert_eq!(Some(&Value::from(1)), parsed.get("id"));
+        assert_eq!(Some(&Value::from("name")), parsed.get("name"));
+    }
+
+    #[test]
+    fn test_render_template() {
+        let output = Output::new(OutputMode::Template, Some("{{id}}: {{name}}")).unwrap();
+        assert_eq!("1: name", output.render(&record()).unwrap());
+    }
+
+    #[test]
+    fn test_template_missing() {
+        let err = Output::new(OutputMode::Template, None).unwrap_err();
+        match *err.kind() {
+            REK::OutputTemplateMissing => {},
+            ref other => panic!("Unexpected error kind: {:?}", other),
+        }
+
+        // The template is only required for the template mode
+        assert!(Output::new(OutputMode::Text, None).is_ok());
+    }
+
+    #[test]
+    fn test_quiet_output_writes_nothing() {
+        let output = Output::new(OutputMode::Text, None).unwrap();
+        assert_eq!("entry 1 - name\n", written(&output));
+
+        let output = output.quiet(true);
+        assert!(output.is_quiet());
+        assert_eq!("", written(&output));
+    }
+
+}
@@ -17,10 +17,12 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::process::Command;
 use std::env;
 use std::process::exit;
+use std::str::FromStr;
 
 pub use clap::App;
 use toml::Value;
@@ -28,15 +30,18 @@ use toml::Value;
 use clap::{Arg, ArgMatches};
 use log;
 
-use configuration::{fetch_config, override_config, InternalConfiguration};
+use configuration::{fetch_layered_config, ConfigSource, InternalConfiguration};
 use error::RuntimeError;
 use error::RuntimeErrorKind;
 use error::ResultExt;
 use logger::ImagLogger;
+use output::{Output, OutputMode};
 
 use libimagerror::trace::*;
 use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
 use libimagstore::file_abstraction::InMemoryFileAbstraction;
+use libimagstore::file_abstraction::stdio::mapper::Format;
 use spec::CliSpec;
 
 /// The Runtime object
@@ -46,6 +51,8 @@ use spec::CliSpec;
 pub struct Runtime<'a> {
     rtp: PathBuf,
     configuration: Option<Value>,
+    config_sources: BTreeMap<String, ConfigSource>,
+    output: Output,
     cli_matches: ArgMatches<'a>,
     store: Store,
 }
@@ -56,6 +63,9 @@ impl<'a> Runtime<'a> {
     /// in $HOME/.imag/config, $XDG_CONFIG_DIR/imag/config or from env("$IMAG_CONFIG")
     /// and builds the Runtime object with it.
     ///
+    /// The configuration file is layered with the system-wide and the store configuration, the
+    /// environment and the commandline overrides, see `configuration::fetch_layered_config()`.
+    ///
     /// The cli_app object should be initially build with the ::get_default_cli_builder() function.
     pub fn new<C>(cli_app: C) -> Result<Runtime<'a>, RuntimeError>
         where C: Clone + CliSpec<'a> + InternalConfiguration
@@ -71,28 +81,20 @@ impl<'a> Runtime<'a> {
 
         debug!("Config path = {:?}", configpath);
 
-        let config = match fetch_config(&configpath) {
-            Err(e) => if !is_match!(e.kind(), &RuntimeErrorKind::ConfigNoConfigFileFound) {
-                return Err(e).chain_err(|| RuntimeErrorKind::Instantiate);
-            } else {
-                println!("No config file found.");
-                println!("Continuing without configuration file");
-                None
-            },
-
-            Ok(mut config) => {
-                if let Err(e) = override_config(&mut config, get_override_specs(&matches)) {
-                    error!("Could not apply config overrides");
-                    trace_error(&e);
+        let mut config = fetch_layered_config(&configpath, &get_store_path(&matches, &rtp));
 
-                    // TODO: continue question (interactive)
-                }
+        if config.value().is_none() {
+            println!("No config file found.");
+            println!("Continuing without configuration file");
+        } else if let Err(e) = config.apply_overrides(get_override_specs(&matches)) {
+            error!("Could not apply config overrides");
+            trace_error(&e);
 
-                Some(config)
-            }
-        };
+            // TODO: continue question (interactive)
+        }
 
-        Runtime::_new(cli_app, matches, config)
+        let (config, sources) = config.into_parts();
+        Runtime::_new(cli_app, matches, config, sources)
     }
 
     /// Builds the Runtime object using the given `config`.
@@ -101,10 +103,13 @@ impl<'a> Runtime<'a> {
         where C: Clone + CliSpec<'a> + InternalConfiguration
     {
         let matches = cli_app.clone().matches();
-        Runtime::_new(cli_app, matches, config)
+        Runtime::_new(cli_app, matches, config, BTreeMap::new())
     }
 
-    fn _new<C>(mut cli_app: C, matches: ArgMatches<'a>, config: Option<Value>)
+    fn _new<C>(mut cli_app: C,
+               matches: ArgMatches<'a>,
+               config: Option<Value>,
+               config_sources: BTreeMap<String, ConfigSource>)
                -> Result<Runtime<'a>, RuntimeError>
     where C: Clone + CliSpec<'a> + InternalConfiguration
     {
@@ -127,16 +132,14 @@ impl<'a> Runtime<'a> {
 
         let rtp = get_rtp_match(&matches);
 
-        let storepath = matches.value_of(Runtime::arg_storepath_name())
-                                .map_or_else(|| {
-                                    let mut spath = rtp.clone();
-                                    spath.push("store");
-                                    spath
-                                }, PathBuf::from);
+        let storepath = get_store_path(&matches, &rtp);
 
         debug!("RTP path    = {:?}", rtp);
         debug!("Store path  = {:?}", storepath);
 
+        let output = Runtime::output_from_matches(&matches)
+            .chain_err(|| RuntimeErrorKind::Instantiate)?;
+
         let store_result = if cli_app.use_inmemory_fs() {
             Store::new_with_backend(storepath,
                                     &config,
@@ -149,6 +152,8 @@ impl<'a> Runtime<'a> {
             Runtime {
                 cli_matches: matches,
                 configuration: config,
+                config_sources: config_sources,
+                output: output,
                 rtp: rtp,
                 store: store,
             }
@@ -156,6 +161,19 @@ impl<'a> Runtime<'a> {
         .chain_err(|| RuntimeErrorKind::Instantiate)
     }
 
+    /// Create the `Output` for the mode passed with `--output`, `text` if none is passed
+    ///
+    /// With `--ids-out`, stdout is for the ids only and the output is quiet.
+    fn output_from_matches(matches: &ArgMatches) -> Result<Output, RuntimeError> {
+        let mode = match matches.value_of(Runtime::arg_output_name()) {
+            Some(mode) => mode.parse::<OutputMode>()?,
+            None       => OutputMode::Text,
+        };
+
+        Output::new(mode, matches.value_of(Runtime::arg_output_template_name()))
+            .map(|output| output.quiet(matches.is_present(Runtime::arg_ids_out_name())))
+    }
+
     ///
     /// Get a commandline-interface builder object from `clap`
     ///
@@ -165,6 +183,8 @@ impl<'a> Runtime<'a> {
     ///   * -c <file> | --config <file> for alternative configuration file
     ///   * -r <path> | --rtp <path> for alternative runtimepath
     ///   * --store <path> for alternative store path
+    ///   * --stdin-format <format> | --stdout-format <format> for the format the store is read
+    ///     from stdin and written to stdout in, if a command pipes the store
     /// Each has the appropriate help text included.
     ///
     /// The `appname` shall be "imag-<command>".
@@ -244,8 +264,51 @@ impl<'a> Runtime<'a> {
                 .takes_value(true)
                 .value_name("LOGDESTS"))
 
+            .arg(Arg::with_name(Runtime::arg_stdin_format_name())
+                .long("stdin-format")
+                .help("The format of the store which is read from stdin, if the command reads the store from stdin: 'json' (default), 'ndjson', 'toml', 'yaml' or 'cbor'")
+                .required(false)
+                .takes_value(true)
+                .value_name("FORMAT"))
+
+            .arg(Arg::with_name(Runtime::arg_stdout_format_name())
+                .long("stdout-format")
+                .help("The format of the store which is written to stdout, if the command writes the store to stdout: 'json' (default), 'ndjson', 'toml', 'yaml' or 'cbor'")
+                .required(false)
+                .takes_value(true)
+                .value_name("FORMAT"))
+
+            .arg(Arg::with_name(Runtime::arg_output_name())
+                .long("output")
+                .help("How the output is printed: as 'text' for humans (default), as 'json' object per line or rendered with the template passed with --output-template")
+                .required(false)
+                .takes_value(true)
+                .possible_values(&["text", "json", "template"])
+                .value_name("MODE"))
+
+            .arg(Arg::with_name(Runtime::arg_output_template_name())
+                .long("output-template")
+                .help("The handlebars template the output is rendered with, if --output is 'template'. The fields of the output are available in the template, see 'imag <command> --output json'")
+                .required(false)
+                .takes_value(true)
+                .value_name("TEMPLATE"))
+
     }
 
+    /// Get the `--ids-out` argument
+    ///
+    /// This argument is not part of the default CLI: Commands add it if all their subcommands
+    /// which read, create or change entries report them with `Runtime::report_touched()`, all
+    /// other commands reject it.
+    pub fn arg_ids_out() -> Arg<'a, 'a> {
+        Arg::with_name(Runtime::arg_ids_out_name())
+            .long("ids-out")
+            .help("Print the ids of the entries the command touched to stdout, one per line, instead of the output. Other commands read these ids from stdin")
+            .required(false)
+            .takes_value(false)
+    }
+
+
     /// Get the argument names of the Runtime which are available
     pub fn arg_names() -> Vec<&'static str> {
         vec![
@@ -257,6 +320,10 @@ impl<'a> Runtime<'a> {
             Runtime::arg_runtimepath_name(),
             Runtime::arg_storepath_name(),
             Runtime::arg_editor_name(),
+            Runtime::arg_stdin_format_name(),
+            Runtime::arg_stdout_format_name(),
+            Runtime::arg_output_name(),
+            Runtime::arg_output_template_name(),
         ]
     }
 
@@ -300,6 +367,31 @@ impl<'a> Runtime<'a> {
         "editor"
     }
 
+    /// Get the argument name for the format of the store read from stdin
+    pub fn arg_stdin_format_name() -> &'static str {
+        "stdin-format"
+    }
+
+    /// Get the argument name for the format of the store written to stdout
+    pub fn arg_stdout_format_name() -> &'static str {
+        "stdout-format"
+    }
+
+    /// Get the argument name for the output mode
+    pub fn arg_output_name() -> &'static str {
+        "output"
+    }
+
+    /// Get the argument name for the template of the `template` output mode
+    pub fn arg_output_template_name() -> &'static str {
+        "output-template"
+    }
+
+    /// Get the argument name for printing the touched ids instead of the output
+    pub fn arg_ids_out_name() -> &'static str {
+        "ids-out"
+    }
+
     /// Get the argument name for generating the completion
     pub fn arg_generate_compl() -> &'static str {
         "generate-completion"
@@ -379,45 +471,106 @@ impl<'a> Runtime<'a> {
         self.configuration.as_ref()
     }
 
+    /// Get where the configuration value at the dotted `path` came from
+    ///
+    /// Only values which are not tables have a source, for example `"store.trash.enabled"`.
+    /// `None` if the value is not configured or the configuration was passed to
+    /// `Runtime::with_configuration()`.
+    pub fn config_source(&self, path: &str) -> Option<&ConfigSource> {
+        self.config_sources.get(path)
+    }
+
+    /// Get the sources of all configuration values, by their dotted path
+    pub fn config_sources(&self) -> &BTreeMap<String, ConfigSource> {
+        &self.config_sources
+    }
+
+    /// Get the output, which the command prints its output through
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Get the ids piped into the command, if stdin is not a terminal
+    ///
+    /// The ids are read one per line, as another command prints them with `--ids-out`. Returns
+    /// `None` if stdin is a terminal or nothing was piped in.
+    pub fn ids_from_stdin(&self) -> Result<Option<Vec<StoreId>>, RuntimeError> {
+        ::libimaginteraction::ui::ids_from_stdin().chain_err(|| RuntimeErrorKind::IdsFromStdinError)
+    }
+
+    /// Tell the runtime that the command touched the entry `id`
+    ///
+    /// With `--ids-out`, the id is printed to stdout, so the next command in a pipe can read it
+    /// with `Runtime::ids_from_stdin()`. Commands which call this must accept `--ids-out`, see
+    /// `Runtime::arg_ids_out()`.
+    pub fn report_touched(&self, id: &StoreId) -> Result<(), RuntimeError> {
+        use std::io::Write;
+        use std::io::stdout;
+
+        if self.cli_matches.is_present(Runtime::arg_ids_out_name()) {
+            // The local part of the id, which is what commands take as id
+            writeln!(stdout(), "{}", id).chain_err(|| RuntimeErrorKind::IOError)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Get the store object
     pub fn store(&self) -> &Store {
         &self.store
     }
 
+    /// Get the store object mutably, for example to register hooks
+    pub fn store_mut(&mut self) -> &mut Store {
+        &mut self.store
+    }
+
     /// Change the store backend to stdout
     ///
     /// For the documentation on purpose and cavecats, have a look at the documentation of the
     /// `Store::reset_backend()` function.
     ///
+    /// The store is read from stdin in the format passed with `--stdin-format` and written to
+    /// stdout in the format passed with `--stdout-format`, both default to JSON.
+    /// If both are `ndjson`, the entries are only parsed if they are used, all other entries are
+    /// passed through as they are.
+    ///
     pub fn store_backend_to_stdio(&mut self) -> Result<(), RuntimeError> {
+        use libimagstore::file_abstraction::FileAbstraction;
         use libimagstore::file_abstraction::stdio::*;
-        use libimagstore::file_abstraction::stdio::mapper::json::JsonMapper;
         use std::rc::Rc;
         use std::cell::RefCell;
 
+        let in_format  = self.stdio_format(Runtime::arg_stdin_format_name())?;
+        let out_format = self.stdio_format(Runtime::arg_stdout_format_name())?;
+
         let mut input = ::std::io::stdin();
         let output    = ::std::io::stdout();
         let output    = Rc::new(RefCell::new(output));
-        let mapper    = JsonMapper::new();
 
-        StdIoFileAbstraction::new(&mut input, output, mapper)
+        let backend : Box<FileAbstraction> = if in_format == Format::NdJson && out_format == Format::NdJson {
+            debug!("Using lazy ndjson backend");
+            Box::new(lazy::LazyStdIoFileAbstraction::new(&mut input, output)
+                     .chain_err(|| RuntimeErrorKind::Instantiate)?)
+        } else {
+            Box::new(StdIoFileAbstraction::with_input_mapper(&mut input, &in_format, output, out_format)
+                     .chain_err(|| RuntimeErrorKind::Instantiate)?)
+        };
+
+        self.store
+            .reset_backend(backend)
             .chain_err(|| RuntimeErrorKind::Instantiate)
-            .and_then(|backend| {
-                self.store
-                    .reset_backend(Box::new(backend))
-                    .chain_err(|| RuntimeErrorKind::Instantiate)
-            })
     }
 
+    /// Change the store backend to stdout, in the format passed with `--stdout-format`
     pub fn store_backend_to_stdout(&mut self) -> Result<(), RuntimeError> {
-        use libimagstore::file_abstraction::stdio::mapper::json::JsonMapper;
         use libimagstore::file_abstraction::stdio::out::StdoutFileAbstraction;
         use std::rc::Rc;
         use std::cell::RefCell;
 
         let output    = ::std::io::stdout();
         let output    = Rc::new(RefCell::new(output));
-        let mapper    = JsonMapper::new();
+        let mapper    = self.stdio_format(Runtime::arg_stdout_format_name())?;
 
         StdoutFileAbstraction::new(output, mapper)
             .chain_err(|| RuntimeErrorKind::Instantiate)
@@ -428,6 +581,15 @@ impl<'a> Runtime<'a> {
             })
     }
 
+    /// Get the stdio format passed with the argument `name`, JSON if it was not passed
+    fn stdio_format(&self, name: &str) -> Result<Format, RuntimeError> {
+        self.cli()
+            .value_of(name)
+            .map(Format::from_str)
+            .unwrap_or(Ok(Format::Json))
+            .chain_err(|| RuntimeErrorKind::Instantiate)
+    }
+
     /// Get a editor command object which can be called to open the $EDITOR
     pub fn editor(&self) -> Option<Command> {
         self.cli()
@@ -460,6 +622,15 @@ fn get_rtp_match<'a>(matches: &ArgMatches<'a>) -> PathBuf {
         }, PathBuf::from)
 }
 
+fn get_store_path<'a>(matches: &ArgMatches<'a>, rtp: &PathBuf) -> PathBuf {
+    matches.value_of(Runtime::arg_storepath_name())
+        .map_or_else(|| {
+            let mut spath = rtp.clone();
+            spath.push("store");
+            spath
+        }, PathBuf::from)
+}
+
 fn get_override_specs(matches: &ArgMatches) -> Vec<String> {
     matches
         .values_of("config-override")
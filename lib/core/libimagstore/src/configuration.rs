@@ -17,9 +17,13 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::path::PathBuf;
+use std::time::Duration;
+
 use toml::Value;
 
 use store::Result;
+use schema::{Schema, FieldType};
 use error::StoreError as SE;
 use error::StoreErrorKind as SEK;
 
@@ -40,6 +44,236 @@ pub fn config_implicit_store_create_allowed(config: &Option<Value>) -> Result<bo
     }
 }
 
+/// The backends the store can be configured to use
+#[derive(Debug, PartialEq, Eq)]
+pub enum StoreBackend {
+    Filesystem,
+    #[cfg(feature = "git")]
+    Git,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// Checks which backend is configured in "store.backend". If the key is not present, the
+/// filesystem backend is used.
+pub fn config_store_backend(config: &Option<Value>) -> Result<StoreBackend> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.backend";
+
+    let name = match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(v) => v.as_str().ok_or(SE::from_kind(SEK::ConfigTypeError(key, "String")))?,
+            None    => return Ok(StoreBackend::Filesystem),
+        },
+        None => return Ok(StoreBackend::Filesystem),
+    };
+
+    match name {
+        "filesystem" => Ok(StoreBackend::Filesystem),
+        #[cfg(feature = "git")]
+        "git"        => Ok(StoreBackend::Git),
+        #[cfg(feature = "sqlite")]
+        "sqlite"     => Ok(StoreBackend::Sqlite),
+        other        => Err(SE::from_kind(SEK::UnknownBackend(String::from(other)))),
+    }
+}
+
+/// Get the header paths configured in "store.index", which the store keeps indexes for. If the
+/// key is not present, no header path is indexed.
+pub fn config_header_indexes(config: &Option<Value>) -> Result<Vec<String>> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.index";
+
+    let paths = match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Array(ref paths)) => paths,
+            Some(_) => return Err(SE::from_kind(SEK::ConfigTypeError(key, "Array"))),
+            None    => return Ok(vec![]),
+        },
+        None => return Ok(vec![]),
+    };
+
+    paths.iter()
+        .map(|p| p.as_str()
+             .map(String::from)
+             .ok_or(SE::from_kind(SEK::ConfigTypeError(key, "Array of Strings"))))
+        .collect()
+}
+
+/// Get the collections which are encrypted, from "store.encryption.collections". If the key is not
+/// present, nothing is encrypted.
+pub fn config_encrypted_collections(config: &Option<Value>) -> Result<Vec<String>> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.encryption.collections";
+
+    let collections = match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Array(ref collections)) => collections,
+            Some(_) => return Err(SE::from_kind(SEK::ConfigTypeError(key, "Array"))),
+            None    => return Ok(vec![]),
+        },
+        None => return Ok(vec![]),
+    };
+
+    collections.iter()
+        .map(|c| c.as_str()
+             .map(String::from)
+             .ok_or(SE::from_kind(SEK::ConfigTypeError(key, "Array of Strings"))))
+        .collect()
+}
+
+/// Check whether entries in encrypted collections may be unencrypted, from
+/// "store.encryption.allow-plaintext". Defaults to true, so existing stores can be switched to
+/// encryption.
+pub fn config_encryption_allows_plaintext(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.encryption.allow-plaintext";
+
+    match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Boolean(b)) => Ok(b),
+            Some(_) => Err(SE::from_kind(SEK::ConfigTypeError(key, "Boolean"))),
+            None    => Ok(true),
+        },
+        None => Ok(true),
+    }
+}
+
+/// A store which is mounted into the store, as configured in "store.mounts"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountConfig {
+    /// The first component of the ids of the mounted entries
+    pub prefix: String,
+
+    /// The path of the mounted store
+    pub path: PathBuf,
+
+    pub read_only: bool,
+}
+
+/// Get the stores which are mounted into the store, from "store.mounts". Each mount is a table
+/// named after its prefix, with the path of the store in "path" and an optional "read-only" flag:
+///
+/// ```toml
+/// [store.mounts.team]
+/// path = "/srv/imag/team"
+/// read-only = true
+/// ```
+///
+/// If the key is not present, nothing is mounted.
+pub fn config_mounts(config: &Option<Value>) -> Result<Vec<MountConfig>> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.mounts";
+
+    let mounts = match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Table(ref mounts)) => mounts,
+            Some(_) => return Err(SE::from_kind(SEK::ConfigTypeError(key, "Table"))),
+            None    => return Ok(vec![]),
+        },
+        None => return Ok(vec![]),
+    };
+
+    mounts.iter()
+        .map(|(prefix, mount)| {
+            let path = match mount.get("path") {
+                Some(&Value::String(ref path)) => PathBuf::from(path),
+                Some(_) => return Err(SE::from_kind(SEK::ConfigTypeError(key, "String at 'path'"))),
+                None    => return Err(SE::from_kind(SEK::ConfigKeyMissingError("store.mounts.<prefix>.path"))),
+            };
+
+            let read_only = match mount.get("read-only") {
+                Some(&Value::Boolean(b)) => b,
+                Some(_) => return Err(SE::from_kind(SEK::ConfigTypeError(key, "Boolean at 'read-only'"))),
+                None    => false,
+            };
+
+            Ok(MountConfig {
+                prefix: prefix.clone(),
+                path: path,
+                read_only: read_only,
+            })
+        })
+        .collect()
+}
+
+/// Checks whether deleted entries are moved to the trash, from "store.trash.enabled". If the key
+/// is not present, the trash is enabled.
+pub fn config_trash_enabled(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.trash.enabled";
+
+    match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Boolean(b)) => Ok(b),
+            Some(_) => Err(SE::from_kind(SEK::ConfigTypeError(key, "Boolean"))),
+            None    => Ok(true),
+        },
+        None => Ok(true),
+    }
+}
+
+/// Get how long the store waits for an entry which is locked by another process, from
+/// "store.lock-timeout" (in milliseconds). If the key is not present or zero, the store does not
+/// wait but fails right away.
+pub fn config_lock_timeout(config: &Option<Value>) -> Result<Option<Duration>> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.lock-timeout";
+
+    match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Integer(0)) => Ok(None),
+            Some(&Value::Integer(i)) if i > 0 => Ok(Some(Duration::from_millis(i as u64))),
+            Some(_) => Err(SE::from_kind(SEK::ConfigTypeError(key, "positive Integer"))),
+            None    => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Get the number of previous revisions the store keeps of each entry, from
+/// "store.history.revisions". If the key is not present, no revisions are kept.
+pub fn config_history_revisions(config: &Option<Value>) -> Result<usize> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.history.revisions";
+
+    match *config {
+        Some(ref t) => match t.read(key)? {
+            Some(&Value::Integer(i)) if i >= 0 => Ok(i as usize),
+            Some(_) => Err(SE::from_kind(SEK::ConfigTypeError(key, "positive Integer"))),
+            None    => Ok(0),
+        },
+        None => Ok(0),
+    }
+}
+
+/// The schema of the "store" section of the configuration
+pub fn config_schema() -> Schema {
+    let strings = || FieldType::Array(Box::new(FieldType::String));
+
+    Schema::new("store")
+        .optional("implicit-create", FieldType::Boolean)
+        .optional("backend", FieldType::OneOf(vec!["filesystem", "git", "sqlite"]))
+        .optional("index", strings())
+        .optional("lock-timeout", FieldType::Integer)
+        .optional("encryption.collections", strings())
+        .optional("encryption.allow-plaintext", FieldType::Boolean)
+        .required("mounts.*.path", FieldType::String)
+        .optional("mounts.*.read-only", FieldType::Boolean)
+        .optional("trash.enabled", FieldType::Boolean)
+        .optional("history.revisions", FieldType::Integer)
+        .optional("hooks", FieldType::Table)
+        .deny_unknown_fields()
+}
+
 #[cfg(test)]
 mod tests {
     use toml::de::from_str as toml_from_str;
@@ -76,5 +310,147 @@ mod tests {
         assert!(config_implicit_store_create_allowed(&Some(config)).unwrap());
     }
 
+    #[test]
+    fn test_encrypted_collections() {
+        let config = toml_from_str(r#"
+        [store.encryption]
+            collections = ["diary", "contact"]
+        "#).unwrap();
+
+        let collections = config_encrypted_collections(&Some(config)).unwrap();
+        assert_eq!(vec![String::from("diary"), String::from("contact")], collections);
+        assert!(config_encrypted_collections(&None).unwrap().is_empty());
+
+        let config = toml_from_str("[store.encryption]\ncollections = \"diary\"").unwrap();
+        assert!(config_encrypted_collections(&Some(config)).is_err());
+
+        assert!(config_encryption_allows_plaintext(&None).unwrap());
+        let config = toml_from_str("[store.encryption]\nallow-plaintext = false").unwrap();
+        assert!(!config_encryption_allows_plaintext(&Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_trash_enabled() {
+        assert!(config_trash_enabled(&None).unwrap());
+
+        let config = toml_from_str("[store.trash]\nenabled = false").unwrap();
+        assert!(!config_trash_enabled(&Some(config)).unwrap());
+
+        let config = toml_from_str("[store.trash]\nenabled = 1").unwrap();
+        assert!(config_trash_enabled(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        use std::time::Duration;
+
+        let config = toml_from_str("[store]\nlock-timeout = 500").unwrap();
+        assert_eq!(Some(Duration::from_millis(500)), config_lock_timeout(&Some(config)).unwrap());
+
+        let config = toml_from_str("[store]\nlock-timeout = 0").unwrap();
+        assert_eq!(None, config_lock_timeout(&Some(config)).unwrap());
+        assert_eq!(None, config_lock_timeout(&None).unwrap());
+
+        let config = toml_from_str("[store]\nlock-timeout = -1").unwrap();
+        assert!(config_lock_timeout(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_header_indexes() {
+        let config = toml_from_str(r#"
+        [store]
+            index = ["todo.uuid", "timetrack.start"]
+        "#).unwrap();
+
+        let paths = config_header_indexes(&Some(config)).unwrap();
+        assert_eq!(vec![String::from("todo.uuid"), String::from("timetrack.start")], paths);
+        assert!(config_header_indexes(&None).unwrap().is_empty());
+
+        let config = toml_from_str("[store]\nindex = [1]").unwrap();
+        assert!(config_header_indexes(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_store_backend_default() {
+        let config = toml_from_str("[store]").unwrap();
+        assert_eq!(StoreBackend::Filesystem, config_store_backend(&Some(config)).unwrap());
+        assert_eq!(StoreBackend::Filesystem, config_store_backend(&None).unwrap());
+    }
+
+    #[test]
+    fn test_store_backend_unknown() {
+        let config = toml_from_str(r#"
+        [store]
+            backend = "unknown"
+        "#).unwrap();
+
+        assert!(config_store_backend(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_history_revisions() {
+        assert_eq!(0, config_history_revisions(&None).unwrap());
+
+        let config = toml_from_str("[store.history]\nrevisions = 5").unwrap();
+        assert_eq!(5, config_history_revisions(&Some(config)).unwrap());
+
+        let config = toml_from_str("[store.history]\nrevisions = -1").unwrap();
+        assert!(config_history_revisions(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_mounts() {
+        use std::path::PathBuf;
+
+        assert!(config_mounts(&None).unwrap().is_empty());
+
+        let config = toml_from_str(r#"
+        [store.mounts.team]
+            path = "/srv/imag/team"
+            read-only = true
+
+        [store.mounts.work]
+            path = "/home/user/work-store"
+        "#).unwrap();
+
+        let mounts = config_mounts(&Some(config)).unwrap();
+        assert_eq!(vec![
+            MountConfig { prefix: String::from("team"), path: PathBuf::from("/srv/imag/team"), read_only: true },
+            MountConfig { prefix: String::from("work"), path: PathBuf::from("/home/user/work-store"), read_only: false },
+        ], mounts);
+
+        let config = toml_from_str("[store.mounts.team]\nread-only = true").unwrap();
+        assert!(config_mounts(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let config = toml_from_str(r#"
+        [store]
+            implicit-create = false
+            backend = "filesystem"
+            lock-timeout = 100
+            implicit_create = true
+
+        [store.hooks.fulltext]
+            enabled = true
+
+        [store.mounts.team]
+            path = "/srv/imag/team"
+            read-only = "yes"
+        "#).unwrap();
+
+        let violations = config_schema()
+            .verify(&config)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![
+            "'store.mounts.team.read-only' should be Boolean, is String",
+            "'store.implicit_create' is not a known field",
+        ], violations);
+    }
+
 }
 
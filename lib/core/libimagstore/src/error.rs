@@ -20,6 +20,8 @@
 use std::path::PathBuf;
 
 use storeid::StoreId;
+use store::Entry;
+use schema::SchemaViolation;
 
 error_chain! {
     types {
@@ -50,6 +52,11 @@ error_chain! {
             display("Configuration Key missing: '{}'", key)
         }
 
+        UnknownBackend(name: String) {
+            description("Unknown or unavailable store backend")
+            display("Unknown or unavailable store backend: '{}'", name)
+        }
+
         VersionError            {
             description("Incompatible store versions detected")
             display("Incompatible store versions detected")
@@ -120,6 +127,56 @@ error_chain! {
             display("Directory/Directories could not be created")
         }
 
+        GitBackendError         {
+            description("Error in the git backend")
+            display("Error in the git backend")
+        }
+
+        SqliteBackendError      {
+            description("Error in the SQLite backend")
+            display("Error in the SQLite backend")
+        }
+
+        EncryptionError         {
+            description("Error while encrypting the store")
+            display("Error while encrypting the store")
+        }
+
+        DecryptionError(id: StoreId) {
+            description("Cannot decrypt entry")
+            display("Cannot decrypt entry: '{}'", id)
+        }
+
+        EntryLocked(id: StoreId) {
+            description("Entry is locked by another process")
+            display("Entry is locked by another process: '{}'", id)
+        }
+
+        WrongPassphrase         {
+            description("Wrong passphrase for the encrypted store")
+            display("Wrong passphrase for the encrypted store")
+        }
+
+        MissingPassphrase(var: &'static str) {
+            description("No passphrase for the encrypted store")
+            display("No passphrase for the encrypted store, set it in ${}", var)
+        }
+
+        InvalidMountPrefix(prefix: String) {
+            description("Invalid mount prefix")
+            display("Invalid mount prefix '{}', expected a single, non-hidden path component", prefix)
+        }
+
+        MountPathMissing(prefix: String, path: PathBuf) {
+            description("The store to mount does not exist")
+            display("The store to mount at '{}' does not exist: '{}'", prefix, path.display())
+        }
+
+        ReadOnlyMount(path: PathBuf) {
+            description("Path is on a read-only mount")
+            display("'{}' is on a read-only mount", path.display())
+        }
+
         StorePathExists(pb: PathBuf) {
             description("Store path exists")
             display("Store path exists: {:?}", pb)
@@ -140,6 +197,11 @@ error_chain! {
             display("The internal Store Lock has been poisoned")
         }
 
+        EntryConflict(id: StoreId, ours: Box<Entry>, theirs: Box<Entry>) {
+            description("Entry was changed by someone else since it was loaded")
+            display("Entry was changed by someone else since it was loaded: '{}'", id)
+        }
+
         EntryAlreadyBorrowed(id: StoreId) {
             description("Entry is already borrowed")
             display("Entry is already borrowed: {:?}", id)
@@ -240,6 +302,89 @@ error_chain! {
             display("Error when calling move_by_id()")
         }
 
+        TransactionCallError       {
+            description("Error when calling transaction()")
+            display("Error when calling transaction()")
+        }
+
+        JournalError               {
+            description("Error while handling the transaction journal")
+            display("Error while handling the transaction journal")
+        }
+
+        // Hook-related errors
+
+        HookRegisterError(name: String) {
+            description("Error while registering hook")
+            display("Error while registering hook: '{}'", name)
+        }
+
+        PreHookExecuteError(name: String) {
+            description("Pre-hook failed, action aborted")
+            display("Pre-hook '{}' failed, action aborted", name)
+        }
+
+        PostHookExecuteError(name: String) {
+            description("Post-hook failed")
+            display("Post-hook '{}' failed", name)
+        }
+
+        // Index-related errors
+
+        HeaderIndexMissing(path: String) {
+            description("No index for header path")
+            display("No index for header path: '{}'", path)
+        }
+
+        HeaderIndexError        {
+            description("Error while handling a header index")
+            display("Error while handling a header index")
+        }
+
+        // History-related errors
+
+        RevisionNotFound(id: StoreId, revision: u64) {
+            description("Revision not found")
+            display("Revision {} of '{}' not found", revision, id)
+        }
+
+        HistoryError            {
+            description("Error while handling the revision history")
+            display("Error while handling the revision history")
+        }
+
+        UnknownMapper(name: String) {
+            description("Unknown or unavailable stdio format")
+            display("Unknown or unavailable stdio format: '{}'", name)
+        }
+
+        NotInTrash(id: StoreId) {
+            description("Entry is not in the trash")
+            display("Entry is not in the trash: '{}'", id)
+        }
+
+        NotHidden(id: StoreId) {
+            description("Not in a hidden directory of the store")
+            display("Not in a hidden directory of the store: '{}'", id)
+        }
+
+        TrashError              {
+            description("Error while handling the trash")
+            display("Error while handling the trash")
+        }
+
+        // Migration-related errors
+
+        MigrationRegisterError(module: String) {
+            description("Error while registering migration")
+            display("Error while registering migration of module '{}'", module)
+        }
+
+        MigrationError(module: String) {
+            description("Migration failed")
+            display("Migration of module '{}' failed", module)
+        }
+
         // Parser-related errors
 
         MissingMainSection  {
@@ -261,6 +406,22 @@ error_chain! {
             description("The header is inconsistent")
             display("The header is inconsistent")
         }
+
+        HeaderSectionTypeError(path: String, expected: String) {
+            description("Header section has the wrong type")
+            display("Header section '{}' cannot be read as '{}'", path, expected)
+        }
+
+        SchemaViolations(id: StoreId, violations: Vec<SchemaViolation>) {
+            description("The header violates the schema of a module")
+            display("The header of '{}' violates the schema of a module: {}", id,
+                    violations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))
+        }
+
+        HeaderSectionWriteError(path: String, expected: String) {
+            description("Header section cannot be written")
+            display("Header section '{}' cannot be written from '{}'", path, expected)
+        }
     }
 }
 
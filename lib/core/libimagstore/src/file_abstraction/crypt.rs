@@ -0,0 +1,625 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! An encrypting FileAbstraction
+//!
+//! The `CryptFileAbstraction` wraps another backend and encrypts the entries of some collections
+//! (for example only `diary` and `contact`) before they are handed to the wrapped backend, and
+//! decrypts them when they are read. Entries are encrypted with ChaCha20-Poly1305, the key is
+//! derived from a passphrase with scrypt.
+//!
+//! An encrypted entry is an entry itself, so each backend can store it: its header contains the
+//! nonce and authentication tag in the `crypt` section, its content is the base64 encoded
+//! ciphertext of the complete plaintext entry.
+//! The salt for the key derivation is stored in the hidden `.crypt` entry of the store, together
+//! with an encrypted test value which is used to check the passphrase when the store is opened.
+//!
+//! The ciphertext is bound to the id of the file it is stored in (it is the additional
+//! authenticated data), so the ciphertext of one entry cannot be passed off as another one.
+//! Copying or moving an encrypted file therefore always re-encrypts it.
+//!
+//! Entries which are not encrypted are read as they are (and a warning is logged), so a store can
+//! be switched to encryption at any time: entries are encrypted when they are written the next
+//! time (or all at once with `Store::migrate_backend()`). Once all entries are encrypted, this
+//! should be turned off with `store.encryption.allow-plaintext = false`, so unencrypted entries
+//! are refused.
+//!
+//! Revisions of entries in the `.history` and deleted entries in the `.trash` are encrypted if their
+//! entry is: copying or moving a file into an encrypted location goes through this backend, so a
+//! copy of an entry which was not encrypted yet is encrypted on the way. The transaction journal,
+//! the header indexes in `.index` and the attachment blobs in `.blobs` contain data of all
+//! collections, they are always encrypted.
+//!
+//! The full-text index of `libimagfulltext` is not written through the store backend, it leaves
+//! out the encrypted collections instead (see `Store::is_encrypted()`).
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::fmt::Error as FmtError;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use base64;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use chacha20poly1305::aead::AeadInPlace;
+use getrandom::getrandom;
+use scrypt::{scrypt, Params as ScryptParams};
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadExt;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use store::Entry;
+use storeid::StoreId;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use super::EntryLock;
+use file_abstraction::iter::PathIterator;
+
+/// The environment variable the passphrase is read from by `Store::new()`
+pub const PASSPHRASE_ENV_VAR : &'static str = "IMAG_STORE_PASSPHRASE";
+
+/// The entry holding the salt and passphrase check, relative to the store path
+const CRYPT_ID : &'static str = ".crypt";
+
+/// The value encrypted in the `.crypt` entry to check the passphrase
+const CHECK_VALUE : &'static [u8] = b"imag";
+
+const KEY_LEN   : usize = 32;
+const NONCE_LEN : usize = 12;
+const SALT_LEN  : usize = 16;
+const TAG_LEN   : usize = 16;
+
+/// The key and the collections which are encrypted
+struct Cipher {
+    key: [u8; KEY_LEN],
+    store_location: PathBuf,
+    collections: Vec<String>,
+
+    /// Whether entries which are not encrypted are read from encrypted locations
+    allow_plaintext: bool,
+}
+
+impl Debug for Cipher {
+
+    /// Do not print the key
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        write!(fmt, "Cipher {{ collections: {:?} }}", self.collections)
+    }
+
+}
+
+struct Sealed {
+    nonce: Vec<u8>,
+    tag: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Cipher {
+
+    fn aead(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.key))
+    }
+
+    /// The path of the file `path` relative to the store, used as additional authenticated data
+    fn local<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.store_location).unwrap_or(path)
+    }
+
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Sealed, SE> {
+        let mut nonce = vec![0u8; NONCE_LEN];
+        getrandom(&mut nonce).chain_err(|| SEK::EncryptionError)?;
+
+        let mut ciphertext = plaintext.to_vec();
+        let tag = self.aead()
+            .encrypt_in_place_detached(Nonce::from_slice(&nonce), aad, &mut ciphertext)
+            .map_err(|_| SE::from_kind(SEK::EncryptionError))?;
+
+        Ok(Sealed { nonce: nonce, tag: tag.to_vec(), ciphertext: ciphertext })
+    }
+
+    fn open(&self, sealed: &Sealed, aad: &[u8]) -> Option<Vec<u8>> {
+        if sealed.nonce.len() != NONCE_LEN || sealed.tag.len() != TAG_LEN {
+            return None;
+        }
+
+        let mut plaintext = sealed.ciphertext.clone();
+        self.aead()
+            .decrypt_in_place_detached(Nonce::from_slice(&sealed.nonce),
+                                       aad,
+                                       &mut plaintext,
+                                       Tag::from_slice(&sealed.tag))
+            .ok()
+            .map(|_| plaintext)
+    }
+
+    /// Whether the file at `path` has to be encrypted
+    fn encrypts(&self, path: &Path) -> bool {
+        let mut components = self.local(path).components().filter_map(|c| match c {
+            Component::Normal(s) => s.to_str(),
+            _                    => None,
+        });
+
+        match components.next() {
+            Some(".journal") | Some(".index") | Some(".blobs") => !self.collections.is_empty(),
+            Some(".history") | Some(".trash") => components
+                .next()
+                .map(|c| self.is_collection(c))
+                .unwrap_or(false),
+            Some(first)      => self.is_collection(first),
+            None             => false,
+        }
+    }
+
+    fn is_collection(&self, name: &str) -> bool {
+        self.collections.iter().any(|c| c == name)
+    }
+
+    /// Encrypt an entry which is written to the file at `path`
+    fn encrypt_entry(&self, entry: &Entry, path: &Path) -> Result<Entry, SE> {
+        let aad           = self.local(path).to_string_lossy().into_owned();
+        let sealed        = self.seal(entry.to_str().as_bytes(), aad.as_bytes())?;
+        let mut encrypted = Entry::new(entry.get_location().clone());
+
+        {
+            let header = encrypted.get_header_mut();
+            let _ = header.insert("crypt.nonce", Value::String(base64::encode(&sealed.nonce)))?;
+            let _ = header.insert("crypt.tag", Value::String(base64::encode(&sealed.tag)))?;
+        }
+        *encrypted.get_content_mut() = base64::encode(&sealed.ciphertext);
+
+        Ok(encrypted)
+    }
+
+    /// Decrypt an entry which was read from the file at `path`
+    ///
+    /// Entries which are not encrypted are returned as they are, unless they were read from an
+    /// encrypted location and plaintext is not allowed there.
+    fn decrypt_entry(&self, entry: Entry, path: &Path) -> Result<Entry, SE> {
+        let id = entry.get_location().clone();
+
+        let sealed = {
+            let decode = |path: &str| -> Result<Option<Vec<u8>>, SE> {
+                match entry.get_header().read(path)? {
+                    Some(&Value::String(ref s)) => base64::decode(s)
+                        .map(Some)
+                        .chain_err(|| SEK::DecryptionError(id.clone())),
+                    Some(_) => Err(SE::from_kind(SEK::DecryptionError(id.clone()))),
+                    None    => Ok(None),
+                }
+            };
+
+            match (decode("crypt.nonce")?, decode("crypt.tag")?) {
+                (Some(nonce), Some(tag)) => Sealed {
+                    nonce: nonce,
+                    tag: tag,
+                    ciphertext: base64::decode(entry.get_content())
+                        .chain_err(|| SEK::DecryptionError(id.clone()))?,
+                },
+                (None, None) if !self.encrypts(path) => return Ok(entry),
+                (None, None) if self.allow_plaintext => {
+                    warn!("{} is not encrypted yet", path.display());
+                    return Ok(entry);
+                },
+                (None, None) => {
+                    error!("{} is not encrypted, refusing to read it", path.display());
+                    return Err(SE::from_kind(SEK::DecryptionError(id)));
+                },
+                _            => return Err(SE::from_kind(SEK::DecryptionError(id))),
+            }
+        };
+
+        let aad       = self.local(path).to_string_lossy().into_owned();
+        let plaintext = self.open(&sealed, aad.as_bytes())
+            .ok_or_else(|| SE::from_kind(SEK::DecryptionError(id.clone())))?;
+        let text = String::from_utf8(plaintext).chain_err(|| SEK::DecryptionError(id.clone()))?;
+
+        Entry::from_str(id, &text)
+    }
+
+}
+
+/// A FileAbstractionInstance which encrypts the entry before it is written by the wrapped instance
+#[derive(Debug)]
+pub struct CryptFileAbstractionInstance {
+    inner: Box<FileAbstractionInstance>,
+    cipher: Arc<Cipher>,
+    path: PathBuf,
+    encrypt: bool,
+}
+
+impl FileAbstractionInstance for CryptFileAbstractionInstance {
+
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
+        self.inner
+            .get_file_content(id)
+            .and_then(|entry| self.cipher.decrypt_entry(entry, &self.path))
+    }
+
+    fn write_file_content(&mut self, buf: &Entry) -> Result<(), SE> {
+        if self.encrypt {
+            let encrypted = self.cipher.encrypt_entry(buf, &self.path)?;
+            self.inner.write_file_content(&encrypted)
+        } else {
+            self.inner.write_file_content(buf)
+        }
+    }
+
+}
+
+/// A FileAbstraction which encrypts the entries of some collections, see the module documentation
+#[derive(Debug)]
+pub struct CryptFileAbstraction {
+    inner: Box<FileAbstraction>,
+    cipher: Arc<Cipher>,
+    crypt_path: PathBuf,
+}
+
+impl CryptFileAbstraction {
+
+    /// Wrap `inner`, encrypting the entries of `collections` with a key derived from `passphrase`
+    ///
+    /// If `allow_plaintext` is false, entries in `collections` which are not encrypted cannot be
+    /// read.
+    ///
+    /// If the store at `store_location` does not contain a `.crypt` entry yet, a new salt is
+    /// generated and the entry is written. Otherwise, the passphrase is checked against it.
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - WrongPassphrase() if the passphrase does not match the one the store was encrypted with
+    ///  - EncryptionError() if the `.crypt` entry cannot be read or written
+    ///
+    pub fn new(inner: Box<FileAbstraction>,
+               store_location: PathBuf,
+               passphrase: &str,
+               collections: Vec<String>,
+               allow_plaintext: bool)
+        -> Result<CryptFileAbstraction, SE>
+    {
+        let crypt_id = StoreId::new(Some(store_location.clone()), PathBuf::from(CRYPT_ID))?;
+        let crypt_pb = crypt_id.clone().into_pathbuf()?;
+
+        let existing = if inner.exists(&crypt_pb)? {
+            Some(inner.new_instance(crypt_pb.clone()).get_file_content(crypt_id.clone())?)
+        } else {
+            None
+        };
+
+        let salt = match existing {
+            Some(ref entry) => match entry.get_header().read("crypt.salt")? {
+                Some(&Value::String(ref s)) => base64::decode(s).chain_err(|| SEK::EncryptionError)?,
+                _ => return Err(SE::from_kind(SEK::EncryptionError)),
+            },
+            None => {
+                let mut salt = vec![0u8; SALT_LEN];
+                getrandom(&mut salt).chain_err(|| SEK::EncryptionError)?;
+                salt
+            },
+        };
+
+        debug!("Deriving store key");
+        let mut key = [0u8; KEY_LEN];
+        let _ = ScryptParams::new(14, 8, 1, KEY_LEN)
+            .map_err(|_| SE::from_kind(SEK::EncryptionError))
+            .and_then(|params| {
+                scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+                    .map_err(|_| SE::from_kind(SEK::EncryptionError))
+            })?;
+
+        let cipher = Cipher {
+            key: key,
+            store_location: store_location,
+            collections: collections,
+            allow_plaintext: allow_plaintext,
+        };
+
+        match existing {
+            Some(entry) => {
+                let decrypted = cipher
+                    .decrypt_entry(entry, &crypt_pb)
+                    .chain_err(|| SEK::WrongPassphrase)?;
+                if decrypted.get_content().as_bytes() != CHECK_VALUE {
+                    return Err(SE::from_kind(SEK::WrongPassphrase));
+                }
+            },
+            None => {
+                // The check value is encrypted like an entry with the content CHECK_VALUE, the
+                // salt is stored unencrypted next to it
+                let mut check = Entry::new(crypt_id);
+                *check.get_content_mut() = String::from_utf8_lossy(CHECK_VALUE).into_owned();
+
+                let mut entry = cipher.encrypt_entry(&check, &crypt_pb)?;
+                let _ = entry.get_header_mut().insert("crypt.salt", Value::String(base64::encode(&salt)))?;
+                let _ = inner
+                    .new_instance(crypt_pb.clone())
+                    .write_file_content(&entry)
+                    .chain_err(|| SEK::EncryptionError)?;
+            },
+        }
+
+        Ok(CryptFileAbstraction {
+            inner: inner,
+            cipher: Arc::new(cipher),
+            crypt_path: crypt_pb,
+        })
+    }
+
+    /// Read the file at `from` and write it to `to` through this backend, so it is decrypted and
+    /// encrypted as the locations require
+    fn copy_through_cipher(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let id    = StoreId::from_full_path(&self.cipher.store_location, from.as_path())?;
+        let entry = self.new_instance(from.clone()).get_file_content(id)?;
+        self.new_instance(to.clone()).write_file_content(&entry)
+    }
+
+}
+
+impl FileAbstraction for CryptFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        self.inner.remove_file(path)
+    }
+
+    /// Copy a file, re-encrypting it if it is copied from or into an encrypted location
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        if self.cipher.encrypts(from) || self.cipher.encrypts(to) {
+            self.copy_through_cipher(from, to)
+        } else {
+            self.inner.copy(from, to)
+        }
+    }
+
+    /// Move a file, re-encrypting it if it moves from or into an encrypted location
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        if self.cipher.encrypts(from) || self.cipher.encrypts(to) {
+            self.copy_through_cipher(from, to)
+                .and_then(|_| self.inner.remove_file(from))
+        } else {
+            self.inner.rename(from, to)
+        }
+    }
+
+    fn create_dir_all(&self, path: &PathBuf) -> Result<(), SE> {
+        self.inner.create_dir_all(path)
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.inner.exists(path)
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.inner.is_file(path)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(CryptFileAbstractionInstance {
+            encrypt: self.cipher.encrypts(&p),
+            inner: self.inner.new_instance(p.clone()),
+            cipher: self.cipher.clone(),
+            path: p,
+        })
+    }
+
+    /// Drain the wrapped backend, decrypting the entries
+    ///
+    /// The `.crypt` entry is not part of the drain, it belongs to this key only.
+    fn drain(&self) -> Result<Drain, SE> {
+        let mut drain = self.inner.drain()?;
+        let mut hm    = HashMap::new();
+        for (path, entry) in drain.iter() {
+            if path == self.crypt_path {
+                continue;
+            }
+            let entry = self.cipher.decrypt_entry(entry, &path)?;
+            hm.insert(path, entry);
+        }
+        Ok(Drain::new(hm))
+    }
+
+    /// Fill the wrapped backend, encrypting the entries of the encrypted collections
+    fn fill(&mut self, mut d: Drain) -> Result<(), SE> {
+        let mut hm = HashMap::new();
+        for (path, entry) in d.iter() {
+            if path == self.crypt_path {
+                continue;
+            }
+            let entry = if self.cipher.encrypts(&path) {
+                self.cipher.encrypt_entry(&entry, &path)?
+            } else {
+                entry
+            };
+            hm.insert(path, entry);
+        }
+        self.inner.fill(Drain::new(hm))
+    }
+
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE> {
+        self.inner.pathes_recursively(basepath)
+    }
+
+    fn modified(&self, path: &PathBuf) -> Result<Option<SystemTime>, SE> {
+        self.inner.modified(path)
+    }
+
+    fn lock(&self, store: &PathBuf, id: &StoreId, wait: Option<Duration>)
+        -> Result<Option<Box<EntryLock>>, SE>
+    {
+        self.inner.lock(store, id, wait)
+    }
+
+    fn is_read_only(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.inner.is_read_only(path)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use file_abstraction::InMemoryFileAbstraction;
+    use file_abstraction::FileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn open_crypt(inner: Box<FileAbstraction>, passphrase: &str) -> Result<CryptFileAbstraction, SE> {
+        CryptFileAbstraction::new(inner, PathBuf::from("/"), passphrase, vec![String::from("diary")], true)
+    }
+
+    fn write(backend: &FileAbstraction, id: &str, content: &str) {
+        let id        = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from(content);
+        backend.new_instance(id.into_pathbuf().unwrap()).write_file_content(&entry).unwrap();
+    }
+
+    fn read(backend: &FileAbstraction, id: &str) -> String {
+        let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(id)).unwrap();
+        backend
+            .new_instance(id.clone().into_pathbuf().unwrap())
+            .get_file_content(id)
+            .unwrap()
+            .get_content()
+            .clone()
+    }
+
+    #[test]
+    fn test_encrypts_configured_collections_only() {
+        let crypt = open_crypt(Box::new(InMemoryFileAbstraction::new()), "secret").unwrap();
+
+        write(&crypt, "diary/today", "dear diary");
+        write(&crypt, "notes/note", "not secret");
+
+        assert_eq!("dear diary", read(&crypt, "diary/today"));
+        assert_eq!("not secret", read(&crypt, "notes/note"));
+
+        // The wrapped backend only sees the ciphertext
+        assert!(!read(&*crypt.inner, "diary/today").contains("dear diary"));
+        assert_eq!("not secret", read(&*crypt.inner, "notes/note"));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_rejected() {
+        let crypt = open_crypt(Box::new(InMemoryFileAbstraction::new()), "secret").unwrap();
+        write(&crypt, "diary/today", "dear diary");
+
+        // Reopen the same backend with the same passphrase
+        let crypt = open_crypt(crypt.inner, "secret").unwrap();
+        assert_eq!("dear diary", read(&crypt, "diary/today"));
+
+        assert!(open_crypt(crypt.inner, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_plaintext_entries_are_readable() {
+        let plain = InMemoryFileAbstraction::new();
+        write(&plain, "diary/old", "written before encryption");
+
+        let crypt = open_crypt(Box::new(plain), "secret").unwrap();
+        assert_eq!("written before encryption", read(&crypt, "diary/old"));
+    }
+
+    #[test]
+    fn test_plaintext_entries_are_refused_if_not_allowed() {
+        let plain = InMemoryFileAbstraction::new();
+        write(&plain, "diary/old", "written before encryption");
+        write(&plain, "notes/note", "not secret");
+
+        let crypt = CryptFileAbstraction::new(Box::new(plain),
+                                              PathBuf::from("/"),
+                                              "secret",
+                                              vec![String::from("diary")],
+                                              false).unwrap();
+
+        let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("diary/old")).unwrap();
+        assert!(crypt.new_instance(PathBuf::from("/diary/old")).get_file_content(id).is_err());
+        assert_eq!("not secret", read(&crypt, "notes/note"));
+    }
+
+    #[test]
+    fn test_ciphertext_is_bound_to_its_id() {
+        let crypt = open_crypt(Box::new(InMemoryFileAbstraction::new()), "secret").unwrap();
+        write(&crypt, "diary/a", "entry a");
+        write(&crypt, "diary/b", "entry b");
+
+        // Copy the ciphertext of a over b, without going through the cipher
+        crypt.inner.copy(&PathBuf::from("/diary/a"), &PathBuf::from("/diary/b")).unwrap();
+
+        let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("diary/b")).unwrap();
+        assert!(crypt.new_instance(PathBuf::from("/diary/b")).get_file_content(id).is_err());
+
+        // Copies through the backend are re-encrypted for their new id
+        crypt.copy(&PathBuf::from("/diary/a"), &PathBuf::from("/diary/c")).unwrap();
+        assert_eq!("entry a", read(&crypt, "diary/c"));
+    }
+
+    #[test]
+    fn test_drain_decrypts_and_fill_encrypts() {
+        let crypt = open_crypt(Box::new(InMemoryFileAbstraction::new()), "secret").unwrap();
+        write(&crypt, "diary/today", "dear diary");
+
+        let drain     = crypt.drain().unwrap();
+        let mut other = open_crypt(Box::new(InMemoryFileAbstraction::new()), "other").unwrap();
+        let _         = other.fill(drain).unwrap();
+
+        assert_eq!("dear diary", read(&other, "diary/today"));
+        assert!(!read(&*other.inner, "diary/today").contains("dear diary"));
+    }
+
+    #[test]
+    fn test_history_of_encrypted_collection_is_encrypted() {
+        let crypt = open_crypt(Box::new(InMemoryFileAbstraction::new()), "secret").unwrap();
+
+        assert!(crypt.cipher.encrypts(&PathBuf::from("/.history/diary/today~1")));
+        assert!(crypt.cipher.encrypts(&PathBuf::from("/.journal")));
+        assert!(!crypt.cipher.encrypts(&PathBuf::from("/.history/notes/note~1")));
+        assert!(crypt.cipher.encrypts(&PathBuf::from("/.trash/diary/today~1514761200")));
+        assert!(crypt.cipher.encrypts(&PathBuf::from("/.index/todo.uuid")));
+        assert!(crypt.cipher.encrypts(&PathBuf::from("/.blobs/ab/cdef")));
+    }
+
+    #[test]
+    fn test_copy_into_encrypted_location_encrypts() {
+        let plain = InMemoryFileAbstraction::new();
+        write(&plain, "diary/old", "written before encryption");
+
+        let crypt = open_crypt(Box::new(plain), "secret").unwrap();
+        let from  = PathBuf::from("/diary/old");
+        let to    = PathBuf::from("/.trash/diary/old~1514761200");
+        crypt.copy(&from, &to).unwrap();
+
+        assert_eq!("written before encryption", read(&crypt, ".trash/diary/old~1514761200"));
+        assert!(!read(&*crypt.inner, ".trash/diary/old~1514761200").contains("before encryption"));
+
+        // Moving an entry out of an encrypted collection decrypts it
+        write(&crypt, "diary/today", "dear diary");
+        crypt.rename(&PathBuf::from("/diary/today"), &PathBuf::from("/notes/today")).unwrap();
+        assert_eq!("dear diary", read(&*crypt.inner, "notes/today"));
+        assert!(!crypt.exists(&PathBuf::from("/diary/today")).unwrap());
+    }
+
+}
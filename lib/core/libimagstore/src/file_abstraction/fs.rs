@@ -17,9 +17,12 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use std::fs::{File, OpenOptions, create_dir_all, remove_file, copy, rename};
+use std::fs::{File, OpenOptions, create_dir_all, remove_file, copy, rename, metadata};
 use std::io::{Seek, SeekFrom, Read};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+#[cfg(feature = "fs-locking")] use std::time::Duration;
 
 use error::{StoreError as SE, StoreErrorKind as SEK};
 use error::ResultExt;
@@ -27,6 +30,7 @@ use error::ResultExt;
 use super::FileAbstraction;
 use super::FileAbstractionInstance;
 use super::Drain;
+#[cfg(feature = "fs-locking")] use super::EntryLock;
 use store::Entry;
 use storeid::StoreId;
 use file_abstraction::iter::PathIterator;
@@ -154,6 +158,48 @@ impl FileAbstraction for FSFileAbstraction {
         Ok(path.is_file())
     }
 
+    fn modified(&self, path: &PathBuf) -> Result<Option<SystemTime>, SE> {
+        match metadata(path).and_then(|m| m.modified()) {
+            Ok(time)                                      => Ok(Some(time)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e)                                        => Err(e).chain_err(|| SEK::FileError),
+        }
+    }
+
+    /// Lock the entry with an advisory lock (`flock()`) on the file `<store>/.lock/<id>`
+    ///
+    /// The lock files are not removed when the lock is released, as removing them could race with
+    /// another process locking the same entry.
+    #[cfg(feature = "fs-locking")]
+    fn lock(&self, store: &PathBuf, id: &StoreId, wait: Option<Duration>)
+        -> Result<Option<Box<EntryLock>>, SE>
+    {
+        use std::thread;
+        use std::time::Instant;
+
+        let mut path = store.clone();
+        path.push(LOCK_DIR);
+        path.push(id.local());
+
+        let file     = create_file(&path).chain_err(|| SEK::FileError)?;
+        let deadline = wait.map(|w| Instant::now() + w);
+
+        loop {
+            if try_lock_exclusive(&file).chain_err(|| SEK::FileError)? {
+                debug!("Locked {:?}", path);
+                return Ok(Some(Box::new(FSEntryLock { file: file })));
+            }
+
+            match deadline {
+                Some(deadline) if Instant::now() < deadline => {
+                    debug!("{:?} is locked, waiting", path);
+                    thread::sleep(Duration::from_millis(LOCK_RETRY_INTERVAL_MS));
+                },
+                _ => return Err(SE::from_kind(SEK::EntryLocked(id.clone()))),
+            }
+        }
+    }
+
     fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
         Box::new(FSFileAbstractionInstance::Absent(p))
     }
@@ -175,6 +221,11 @@ impl FileAbstraction for FSFileAbstraction {
     fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE> {
         use walkdir::WalkDir;
 
+        if !basepath.exists() {
+            debug!("{:?} does not exist, no pathes there", basepath);
+            return Ok(PathIterator::new(Box::new(vec![].into_iter())));
+        }
+
         let i : Result<Vec<PathBuf>, SE> = WalkDir::new(basepath)
             .min_depth(1)
             .into_iter()
@@ -192,6 +243,79 @@ impl FileAbstraction for FSFileAbstraction {
     }
 }
 
+/// The directory the lock files live in, relative to the store path
+#[cfg(feature = "fs-locking")]
+const LOCK_DIR : &'static str = ".lock";
+
+/// How long to sleep between two attempts to lock a locked entry
+#[cfg(feature = "fs-locking")]
+const LOCK_RETRY_INTERVAL_MS : u64 = 100;
+
+/// An advisory lock on an entry, released when the lock file is closed
+#[cfg(feature = "fs-locking")]
+#[derive(Debug)]
+pub struct FSEntryLock {
+    file: File,
+}
+
+#[cfg(feature = "fs-locking")]
+impl EntryLock for FSEntryLock {
+}
+
+#[cfg(feature = "fs-locking")]
+impl Drop for FSEntryLock {
+
+    /// Closing the file would release the lock as well, but unlocking explicitely releases it
+    /// even if the file descriptor was inherited by a child process
+    fn drop(&mut self) {
+        if let Err(e) = unlock(&self.file) {
+            warn!("Could not release lock: {:?}", e);
+        }
+    }
+
+}
+
+/// Try to lock the file exclusively, without blocking. Returns false if it is locked already.
+#[cfg(all(feature = "fs-locking", unix))]
+fn try_lock_exclusive(file: &File) -> ::std::io::Result<bool> {
+    use std::io::Error;
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { ::libc::flock(file.as_raw_fd(), ::libc::LOCK_EX | ::libc::LOCK_NB) } == 0 {
+        Ok(true)
+    } else {
+        let err = Error::last_os_error();
+        if err.kind() == ErrorKind::WouldBlock {
+            Ok(false)
+        } else {
+            Err(err)
+        }
+    }
+}
+
+#[cfg(all(feature = "fs-locking", unix))]
+fn unlock(file: &File) -> ::std::io::Result<()> {
+    use std::io::Error;
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { ::libc::flock(file.as_raw_fd(), ::libc::LOCK_UN) } == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error())
+    }
+}
+
+/// Advisory locks are only implemented on unix, other platforms do not lock at all
+#[cfg(all(feature = "fs-locking", not(unix)))]
+fn try_lock_exclusive(_: &File) -> ::std::io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(all(feature = "fs-locking", not(unix)))]
+fn unlock(_: &File) -> ::std::io::Result<()> {
+    Ok(())
+}
+
 fn open_file<A: AsRef<Path>>(p: A) -> ::std::io::Result<File> {
     OpenOptions::new().write(true).read(true).open(p)
 }
@@ -206,3 +330,38 @@ fn create_file<A: AsRef<Path>>(p: A) -> ::std::io::Result<File> {
     OpenOptions::new().write(true).read(true).create(true).open(p)
 }
 
+
+#[cfg(all(test, feature = "fs-locking"))]
+mod test {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use super::FSFileAbstraction;
+    use file_abstraction::FileAbstraction;
+    use error::StoreErrorKind as SEK;
+    use storeid::StoreId;
+
+    #[test]
+    fn test_lock_excludes_other_locks() {
+        let dir   = TempDir::new("imag-store-lock").unwrap();
+        let store = dir.path().to_path_buf();
+        let fs    = FSFileAbstraction::new();
+        let id    = StoreId::new(Some(store.clone()), PathBuf::from("test/entry")).unwrap();
+        let other = StoreId::new(Some(store.clone()), PathBuf::from("test/other")).unwrap();
+
+        let lock = fs.lock(&store, &id, None).unwrap();
+        assert!(lock.is_some());
+
+        match fs.lock(&store, &id, Some(Duration::from_millis(200))) {
+            Err(e) => assert!(is_match!(e.kind(), &SEK::EntryLocked(_))),
+            Ok(_)  => panic!("Entry was locked twice"),
+        }
+        assert!(fs.lock(&store, &other, None).unwrap().is_some());
+
+        drop(lock);
+        assert!(fs.lock(&store, &id, None).unwrap().is_some());
+    }
+
+}
@@ -0,0 +1,343 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A git-backed FileAbstraction
+//!
+//! This backend writes entries to the filesystem just like the `FSFileAbstraction` does, but the
+//! store directory is also a git repository and each mutation of the store results in a commit.
+//!
+//! The repository is opened (or initialized, if the store directory is not a git repository yet)
+//! lazily when the first commit is made.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use git2::Repository;
+use git2::Signature;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use store::is_hidden_path;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use super::EntryLock;
+use super::fs::FSFileAbstraction;
+use super::fs::FSFileAbstractionInstance;
+use store::Entry;
+use storeid::StoreId;
+use file_abstraction::iter::PathIterator;
+
+/// Handle on the git repository the store lives in
+#[derive(Debug)]
+struct GitRepository {
+    location: PathBuf,
+    repo: Mutex<Option<Repository>>,
+}
+
+impl GitRepository {
+
+    fn new(location: PathBuf) -> GitRepository {
+        GitRepository {
+            location: location,
+            repo: Mutex::new(None),
+        }
+    }
+
+    /// Get a printable representation of the StoreId the path points to
+    fn id_for(&self, path: &Path) -> String {
+        StoreId::from_full_path(&self.location, path)
+            .map(|id| format!("{}", id))
+            .unwrap_or_else(|_| path.display().to_string())
+    }
+
+    /// Check whether `path` lies in a hidden, store-internal namespace (`.index`, `.journal`, ...)
+    fn is_internal(&self, path: &Path) -> bool {
+        is_hidden_path(&self.location, path)
+    }
+
+    /// Stage the changes on `added` and `removed` and commit them with `msg`
+    ///
+    /// Changes in the hidden namespaces of the store (indexes, the journal, the history, the
+    /// trash, ...) are store internals and are never committed.
+    ///
+    /// If the resulting tree does not differ from the tree of `HEAD`, no commit is made.
+    fn commit(&self, added: &[&Path], removed: &[&Path], msg: String) -> Result<(), SE> {
+        let added   = added.iter().filter(|p| !self.is_internal(p)).collect::<Vec<_>>();
+        let removed = removed.iter().filter(|p| !self.is_internal(p)).collect::<Vec<_>>();
+
+        if added.is_empty() && removed.is_empty() {
+            debug!("Only store internals changed, not committing");
+            return Ok(());
+        }
+
+        let mut guard = self.repo.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        if guard.is_none() {
+            debug!("Opening git repository at {:?}", self.location);
+            let repo = Repository::open(&self.location)
+                .or_else(|_| {
+                    debug!("Initializing git repository at {:?}", self.location);
+                    Repository::init(&self.location)
+                })
+                .chain_err(|| SEK::GitBackendError)?;
+            *guard = Some(repo);
+        }
+
+        let repo = guard.as_ref().unwrap(); // safe by above
+
+        let mut index = repo.index().chain_err(|| SEK::GitBackendError)?;
+        for path in added.iter() {
+            let path = path.strip_prefix(&self.location).chain_err(|| SEK::GitBackendError)?;
+            debug!("git add {:?}", path);
+            let _ = index.add_path(path).chain_err(|| SEK::GitBackendError)?;
+        }
+        for path in removed.iter() {
+            let path = path.strip_prefix(&self.location).chain_err(|| SEK::GitBackendError)?;
+            debug!("git rm {:?}", path);
+            let _ = index.remove_path(path).chain_err(|| SEK::GitBackendError)?;
+        }
+        let _ = index.write().chain_err(|| SEK::GitBackendError)?;
+
+        let tree_id = index.write_tree().chain_err(|| SEK::GitBackendError)?;
+        let tree    = repo.find_tree(tree_id).chain_err(|| SEK::GitBackendError)?;
+
+        let parent = match repo.head().ok().and_then(|head| head.target()) {
+            Some(oid) => Some(repo.find_commit(oid).chain_err(|| SEK::GitBackendError)?),
+            None      => None, // no commits yet
+        };
+
+        if parent.as_ref().map(|p| p.tree_id() == tree_id).unwrap_or(false) {
+            debug!("Nothing changed, not committing");
+            return Ok(());
+        }
+
+        let signature = repo
+            .signature()
+            .or_else(|_| Signature::now("imag", "imag@localhost"))
+            .chain_err(|| SEK::GitBackendError)?;
+
+        let parents = parent.iter().collect::<Vec<_>>();
+
+        debug!("git commit -m '{}'", msg);
+        repo.commit(Some("HEAD"), &signature, &signature, &msg, &tree, &parents)
+            .chain_err(|| SEK::GitBackendError)
+            .map(|_| ())
+    }
+
+}
+
+#[derive(Debug)]
+pub struct GitFileAbstractionInstance {
+    fs: FSFileAbstractionInstance,
+    path: PathBuf,
+    repo: Arc<GitRepository>,
+}
+
+impl FileAbstractionInstance for GitFileAbstractionInstance {
+
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
+        self.fs.get_file_content(id)
+    }
+
+    /// Write the content of this file and commit it
+    fn write_file_content(&mut self, buf: &Entry) -> Result<(), SE> {
+        let _ = self.fs.write_file_content(buf)?;
+        let msg = format!("Update {}", buf.get_location());
+        self.repo.commit(&[self.path.as_path()], &[], msg)
+    }
+}
+
+/// A FileAbstraction which writes to the filesystem and commits each change in a git repository
+#[derive(Debug)]
+pub struct GitFileAbstraction {
+    fs: FSFileAbstraction,
+    repo: Arc<GitRepository>,
+}
+
+impl GitFileAbstraction {
+
+    /// Create a new GitFileAbstraction for the store at `location`
+    pub fn new(location: PathBuf) -> GitFileAbstraction {
+        GitFileAbstraction {
+            fs: FSFileAbstraction::new(),
+            repo: Arc::new(GitRepository::new(location)),
+        }
+    }
+
+}
+
+impl FileAbstraction for GitFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        let _ = self.fs.remove_file(path)?;
+        let msg = format!("Delete {}", self.repo.id_for(path));
+        self.repo.commit(&[], &[path.as_path()], msg)
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let _ = self.fs.copy(from, to)?;
+        let msg = format!("Copy {} -> {}", self.repo.id_for(from), self.repo.id_for(to));
+        self.repo.commit(&[to.as_path()], &[], msg)
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let _ = self.fs.rename(from, to)?;
+        let msg = format!("Move {} -> {}", self.repo.id_for(from), self.repo.id_for(to));
+        self.repo.commit(&[to.as_path()], &[from.as_path()], msg)
+    }
+
+    fn create_dir_all(&self, path: &PathBuf) -> Result<(), SE> {
+        self.fs.create_dir_all(path)
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.fs.exists(path)
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.fs.is_file(path)
+    }
+
+    fn modified(&self, path: &PathBuf) -> Result<Option<SystemTime>, SE> {
+        self.fs.modified(path)
+    }
+
+    fn lock(&self, store: &PathBuf, id: &StoreId, wait: Option<Duration>)
+        -> Result<Option<Box<EntryLock>>, SE>
+    {
+        self.fs.lock(store, id, wait)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(GitFileAbstractionInstance {
+            fs: FSFileAbstractionInstance::Absent(p.clone()),
+            path: p,
+            repo: self.repo.clone(),
+        })
+    }
+
+    /// We return nothing from the FS here.
+    fn drain(&self) -> Result<Drain, SE> {
+        self.fs.drain()
+    }
+
+    /// Write everything to the filesystem, committing each entry
+    fn fill(&mut self, mut d: Drain) -> Result<(), SE> {
+        d.iter()
+            .fold(Ok(()), |acc, (path, element)| {
+                acc.and_then(|_| self.new_instance(path).write_file_content(&element))
+            })
+    }
+
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE> {
+        self.fs
+            .pathes_recursively(basepath)
+            .map(|iter| {
+                // Do not expose the internals of the git repository as entries
+                let iter = iter.filter(|p| !p.components().any(|c| c.as_os_str() == ".git"));
+                PathIterator::new(Box::new(iter))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use git2::Repository;
+    use tempdir::TempDir;
+
+    use super::GitFileAbstraction;
+    use store::Store;
+
+    fn commit_messages(path: &PathBuf) -> Vec<String> {
+        let repo     = Repository::open(path).unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        walk.map(|oid| repo.find_commit(oid.unwrap()).unwrap())
+            .map(|c| String::from(c.message().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_update_delete_move_commit() {
+        let dir     = TempDir::new("imag-git-backend").unwrap();
+        let path    = dir.path().to_path_buf();
+        let backend = Box::new(GitFileAbstraction::new(path.clone()));
+        let store   = Store::new_with_backend(path.clone(), &None, backend).unwrap();
+
+        {
+            let mut entry = store.create(PathBuf::from("test")).unwrap();
+            entry.get_content_mut().push_str("content");
+            assert!(store.update(&mut entry).is_ok());
+        }
+
+        {
+            let old = ::storeid::StoreId::new_baseless(PathBuf::from("test")).unwrap();
+            let new = ::storeid::StoreId::new_baseless(PathBuf::from("moved")).unwrap();
+            assert!(store.move_by_id(old, new).is_ok());
+        }
+
+        assert!(store.delete(PathBuf::from("moved")).is_ok());
+
+        let messages = commit_messages(&path);
+        assert_eq!(messages, vec![
+            String::from("Delete moved"),
+            String::from("Move test -> moved"),
+            String::from("Update test"),
+        ]);
+    }
+
+    #[test]
+    fn test_internal_pathes_are_not_committed() {
+        use file_abstraction::FileAbstraction;
+        use store::Entry;
+        use storeid::StoreId;
+
+        let dir     = TempDir::new("imag-git-backend").unwrap();
+        let path    = dir.path().to_path_buf();
+        let backend = GitFileAbstraction::new(path.clone());
+
+        let entry = Entry::new(StoreId::new(Some(path.clone()), PathBuf::from("test")).unwrap());
+        backend.new_instance(path.join("test")).write_file_content(&entry).unwrap();
+
+        let index = Entry::new(StoreId::new(Some(path.clone()), PathBuf::from(".index/test")).unwrap());
+        backend.create_dir_all(&path.join(".index")).unwrap();
+        backend.new_instance(path.join(".index/test")).write_file_content(&index).unwrap();
+
+        backend.create_dir_all(&path.join(".trash")).unwrap();
+        backend.rename(&path.join("test"), &path.join(".trash/test")).unwrap();
+
+        assert!(path.join(".index/test").exists());
+        assert_eq!(commit_messages(&path), vec![
+            String::from("Move test -> .trash/test"),
+            String::from("Update test"),
+        ]);
+
+        let repo = Repository::open(&path).unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert_eq!(0, tree.len());
+    }
+
+}
@@ -142,7 +142,7 @@ impl FileAbstraction for InMemoryFileAbstraction {
         let mut mtx = self.backend().lock().expect("Locking Mutex failed");
         let backend = mtx.get_mut();
 
-        let a = backend.get(from).cloned().ok_or(SE::from_kind(SEK::FileNotFound))?;
+        let a = backend.remove(from).ok_or(SE::from_kind(SEK::FileNotFound))?;
         backend.insert(to.clone(), a);
         debug!("Renaming: {:?} -> {:?} worked", from, to);
         Ok(())
@@ -20,6 +20,7 @@
 use std::path::PathBuf;
 use std::fmt::Debug;
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 use error::StoreError as SE;
 use store::Entry;
@@ -28,12 +29,30 @@ use storeid::StoreId;
 mod fs;
 mod inmemory;
 mod iter;
+mod mount;
 pub mod stdio;
+#[cfg(feature = "git")] mod git;
+#[cfg(feature = "sqlite")] mod sqlite;
+#[cfg(feature = "encryption")] mod crypt;
 
 pub use self::fs::FSFileAbstraction;
 pub use self::fs::FSFileAbstractionInstance;
+#[cfg(feature = "fs-locking")] pub use self::fs::FSEntryLock;
+#[cfg(feature = "git")] pub use self::git::GitFileAbstraction;
+#[cfg(feature = "git")] pub use self::git::GitFileAbstractionInstance;
+#[cfg(feature = "sqlite")] pub use self::sqlite::SqliteFileAbstraction;
+#[cfg(feature = "sqlite")] pub use self::sqlite::SqliteFileAbstractionInstance;
+#[cfg(feature = "encryption")] pub use self::crypt::CryptFileAbstraction;
+#[cfg(feature = "encryption")] pub use self::crypt::CryptFileAbstractionInstance;
+#[cfg(feature = "encryption")] pub use self::crypt::PASSPHRASE_ENV_VAR;
 pub use self::inmemory::InMemoryFileAbstraction;
 pub use self::inmemory::InMemoryFileAbstractionInstance;
+pub use self::mount::Mount;
+pub use self::mount::MountFileAbstraction;
+pub use self::mount::MountPoint;
+pub use self::mount::Origin;
+pub use self::mount::register_mounts;
+pub use self::mount::ReadOnlyFileAbstractionInstance;
 use self::iter::PathIterator;
 
 /// An abstraction trait over filesystem actions
@@ -52,6 +71,49 @@ pub trait FileAbstraction : Debug {
     fn fill<'a>(&'a mut self, d: Drain) -> Result<(), SE>;
 
     fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE>;
+
+    /// Get the time the file at `path` was modified last, if the backend knows it
+    ///
+    /// Backends which can be changed by other processes (like the filesystem) return the time, so
+    /// `Store::watch()` can notice these changes. The default implementation returns `None`.
+    fn modified(&self, _path: &PathBuf) -> Result<Option<SystemTime>, SE> {
+        Ok(None)
+    }
+
+    /// Lock the entry `id` of the store at `store` against other processes
+    ///
+    /// The store holds the returned lock as long as a `FileLockEntry` for the entry exists. If the
+    /// entry is locked by another process, the backend retries for `wait` (if any) before failing
+    /// with `EntryLocked`.
+    /// Backends which cannot be used by several processes at once do not need locking, the
+    /// default implementation returns `None`.
+    fn lock(&self, _store: &PathBuf, _id: &StoreId, _wait: Option<Duration>)
+        -> Result<Option<Box<EntryLock>>, SE>
+    {
+        Ok(None)
+    }
+
+    /// Check whether the file at `path` must not be changed
+    ///
+    /// The store checks this before it creates, writes, moves or removes an entry, so it can fail
+    /// early with `ReadOnlyMount`. The default implementation returns `false`.
+    fn is_read_only(&self, _path: &PathBuf) -> Result<bool, SE> {
+        Ok(false)
+    }
+
+    /// Get the stores which are mounted into the store
+    ///
+    /// The store registers them when it is opened, so `Origin` knows where an entry is kept. Only
+    /// the `MountFileAbstraction` mounts stores, the default implementation returns none.
+    fn mounts(&self) -> Vec<MountPoint> {
+        vec![]
+    }
+}
+
+/// A lock on an entry, as returned by `FileAbstraction::lock()`
+///
+/// The lock is released when the object is dropped.
+pub trait EntryLock : Debug {
 }
 
 /// An abstraction trait over actions on files
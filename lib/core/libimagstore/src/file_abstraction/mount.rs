@@ -0,0 +1,577 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A FileAbstraction which mounts other stores into the store
+//!
+//! The `MountFileAbstraction` wraps the backend of the store and mounts other stores under
+//! prefixes: with a store mounted at `team`, the entry `team/meeting/2018-01-31` is the entry
+//! `meeting/2018-01-31` of the mounted store. Everything else is handed to the wrapped backend.
+//!
+//! As the entries of mounted stores have ids in the mounting store, they can be linked with the
+//! entries of the store like any other entry.
+//!
+//! A mount can be read-only. Entries on a read-only mount can be read, but all attempts to write,
+//! move or remove them fail with `ReadOnlyMount`.
+//!
+//! Only the entries of the wrapped backend are drained and filled, the mounted stores keep their
+//! entries.
+//!
+//! Ids are only meaningful within the store which mounts the others. To refer to an entry from
+//! another store, use its `Origin`: the mounted store it is kept in and its id there.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use store::Entry;
+use storeid::StoreId;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use super::EntryLock;
+use file_abstraction::iter::PathIterator;
+
+/// A store mounted under a prefix
+#[derive(Debug)]
+pub struct Mount {
+    prefix: String,
+    location: PathBuf,
+    backend: Box<FileAbstraction>,
+    read_only: bool,
+}
+
+impl Mount {
+
+    /// Mount the store at `location`, which is accessed with `backend`, under `prefix`
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - InvalidMountPrefix() if `prefix` is not a single path component or starts with a dot, as
+    ///    hidden pathes belong to the store itself
+    ///
+    pub fn new(prefix: String, location: PathBuf, backend: Box<FileAbstraction>, read_only: bool)
+        -> Result<Mount, SE>
+    {
+        let valid = {
+            let mut components = Path::new(&prefix).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), None) => !prefix.starts_with("."),
+                _                                  => false,
+            }
+        };
+
+        if !valid {
+            return Err(SE::from_kind(SEK::InvalidMountPrefix(prefix)));
+        }
+
+        Ok(Mount {
+            prefix: prefix,
+            location: location,
+            backend: backend,
+            read_only: read_only,
+        })
+    }
+
+    pub fn prefix(&self) -> &String {
+        &self.prefix
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+}
+
+/// A store mounted into another one, as seen by code outside of the store
+///
+/// See `FileAbstraction::mounts()` and `Origin`.
+#[derive(Debug, Clone)]
+pub struct MountPoint {
+    /// The first component of the ids of the mounted entries
+    pub prefix: String,
+
+    /// The location of the mounted store
+    pub location: PathBuf,
+
+    pub read_only: bool,
+}
+
+lazy_static! {
+    /// The stores opened by this process, by the location they were opened with: their canonical
+    /// location and the stores mounted into them
+    static ref MOUNTS: RwLock<BTreeMap<PathBuf, (PathBuf, Vec<MountPoint>)>> =
+        RwLock::new(BTreeMap::new());
+}
+
+/// The canonical form of `path`, or `path` itself if it does not exist on the filesystem
+fn canonical(path: &PathBuf) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.clone())
+}
+
+/// Remember the stores mounted into the store at `store_location`, so `Origin` can find them
+///
+/// Called by the store when it is opened, replacing what was registered for the location before.
+pub fn register_mounts(store_location: &PathBuf, mounts: Vec<MountPoint>) -> Result<(), SE> {
+    let mounts = mounts
+        .into_iter()
+        .map(|m| MountPoint { location: canonical(&m.location), ..m })
+        .collect();
+
+    MOUNTS.write()
+        .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+        .map(|mut registry| {
+            registry.insert(store_location.clone(), (canonical(store_location), mounts));
+        })
+}
+
+/// Where an entry is kept: the store which holds it, and its id in that store
+///
+/// For entries on a mount, this is the mounted store, not the store which mounts it. So the origin
+/// does not depend on where (or whether) the store is mounted, which makes it suitable to refer to
+/// entries of other stores (libimagentrylink uses it for links between stores, for example).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The canonical location of the store which holds the entry
+    pub store: PathBuf,
+
+    /// The id of the entry in that store
+    pub id: PathBuf,
+
+    /// Whether the entry lies on a read-only mount
+    pub read_only: bool,
+}
+
+impl Origin {
+
+    /// Get where the entry `id` is kept
+    ///
+    /// The store is the one at the base of `id`. Entries of stores which were not opened by this
+    /// process are considered to be kept in the store itself.
+    pub fn of(id: &StoreId) -> Result<Origin, SE> {
+        let base     = id.base().cloned().unwrap_or_else(PathBuf::new);
+        let registry = MOUNTS.read().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        let (location, mounts) = match registry.get(&base) {
+            Some(&(ref location, ref mounts)) => (location.clone(), mounts.as_slice()),
+            None                              => (base.clone(), &[][..]),
+        };
+
+        let mut components = id.local().components();
+        let mounted = match components.next() {
+            Some(Component::Normal(first)) => mounts
+                .iter()
+                .find(|m| first == OsStr::new(&m.prefix))
+                .map(|m| (m, components.as_path().to_path_buf())),
+            _ => None,
+        };
+
+        Ok(match mounted {
+            Some((mount, rest)) => Origin {
+                store: mount.location.clone(),
+                id: rest,
+                read_only: mount.read_only,
+            },
+            None => Origin {
+                store: location,
+                id: id.local().clone(),
+                read_only: false,
+            },
+        })
+    }
+
+    /// Get the id of the entry in the store at `store_location`
+    ///
+    /// `None` if the entry is kept in a store which is neither the store itself nor mounted into
+    /// it.
+    pub fn id_in(&self, store_location: &PathBuf) -> Result<Option<StoreId>, SE> {
+        let registry = MOUNTS.read().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        let local = match registry.get(store_location) {
+            Some(&(ref location, _)) if *location == self.store => Some(self.id.clone()),
+            Some(&(_, ref mounts)) => mounts
+                .iter()
+                .find(|m| m.location == self.store)
+                .map(|m| Path::new(&m.prefix).join(&self.id)),
+            None if *store_location == self.store => Some(self.id.clone()),
+            None => None,
+        };
+
+        match local {
+            Some(id) => StoreId::new(Some(store_location.clone()), id).map(Some),
+            None     => Ok(None),
+        }
+    }
+
+}
+
+/// A FileAbstractionInstance for an entry on a read-only mount
+#[derive(Debug)]
+pub struct ReadOnlyFileAbstractionInstance {
+    inner: Box<FileAbstractionInstance>,
+    path: PathBuf,
+}
+
+impl FileAbstractionInstance for ReadOnlyFileAbstractionInstance {
+
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
+        self.inner.get_file_content(id)
+    }
+
+    fn write_file_content(&mut self, _: &Entry) -> Result<(), SE> {
+        Err(SE::from_kind(SEK::ReadOnlyMount(self.path.clone())))
+    }
+
+}
+
+/// A FileAbstraction which mounts other stores under prefixes, see the module documentation
+#[derive(Debug)]
+pub struct MountFileAbstraction {
+    inner: Box<FileAbstraction>,
+    store_location: PathBuf,
+    mounts: Vec<Mount>,
+}
+
+/// Where a path of the store lives: in the wrapped backend or on one of the mounts
+struct Route<'a> {
+    backend: &'a FileAbstraction,
+    path: PathBuf,
+    mount: Option<&'a Mount>,
+}
+
+impl<'a> Route<'a> {
+
+    fn is_read_only(&self) -> bool {
+        self.mount.map(|m| m.read_only).unwrap_or(false)
+    }
+
+    /// Whether `other` lives in the same backend
+    fn same_backend(&self, other: &Route) -> bool {
+        match (self.mount, other.mount) {
+            (Some(a), Some(b)) => a.prefix == b.prefix,
+            (None, None)       => true,
+            _                  => false,
+        }
+    }
+
+}
+
+impl MountFileAbstraction {
+
+    /// Wrap `inner`, the backend of the store at `store_location`, mounting `mounts`
+    pub fn new(inner: Box<FileAbstraction>, store_location: PathBuf, mounts: Vec<Mount>)
+        -> MountFileAbstraction
+    {
+        MountFileAbstraction {
+            inner: inner,
+            store_location: store_location,
+            mounts: mounts,
+        }
+    }
+
+    /// The mount the store-local path `local` lies on, and the path relative to the mounted store
+    fn mount_of<'a, 'b>(&'a self, local: &'b Path) -> Option<(&'a Mount, &'b Path)> {
+        let mut components = local.components();
+        match components.next() {
+            Some(Component::Normal(first)) => self.mounts
+                .iter()
+                .find(|m| first == OsStr::new(&m.prefix))
+                .map(|m| (m, components.as_path())),
+            _ => None,
+        }
+    }
+
+    fn route(&self, path: &Path) -> Route {
+        let mounted = path
+            .strip_prefix(&self.store_location)
+            .ok()
+            .and_then(|local| self.mount_of(local));
+
+        match mounted {
+            Some((mount, rest)) => Route {
+                backend: &*mount.backend,
+                path: mount.location.join(rest),
+                mount: Some(mount),
+            },
+            None => Route {
+                backend: &*self.inner,
+                path: path.to_path_buf(),
+                mount: None,
+            },
+        }
+    }
+
+    /// Route `path`, failing if it lies on a read-only mount
+    fn route_writable(&self, path: &Path) -> Result<Route, SE> {
+        let route = self.route(path);
+        if route.is_read_only() {
+            return Err(SE::from_kind(SEK::ReadOnlyMount(path.to_path_buf())));
+        }
+        Ok(route)
+    }
+
+    /// Copy the entry at `from` to `to`, which live in different backends
+    fn copy_between(&self, from: &Route, to: &Route, original: &PathBuf) -> Result<(), SE> {
+        let id    = StoreId::from_full_path(&self.store_location, original.as_path())?;
+        let entry = from.backend.new_instance(from.path.clone()).get_file_content(id)?;
+
+        if let Some(parent) = to.path.parent() {
+            let _ = to.backend.create_dir_all(&parent.to_path_buf())?;
+        }
+        to.backend.new_instance(to.path.clone()).write_file_content(&entry)
+    }
+
+}
+
+impl FileAbstraction for MountFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        let route = self.route_writable(path)?;
+        route.backend.remove_file(&route.path)
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let source = self.route(from);
+        let target = self.route_writable(to)?;
+
+        if source.same_backend(&target) {
+            source.backend.copy(&source.path, &target.path)
+        } else {
+            self.copy_between(&source, &target, from)
+        }
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let source = self.route_writable(from)?;
+        let target = self.route_writable(to)?;
+
+        if source.same_backend(&target) {
+            source.backend.rename(&source.path, &target.path)
+        } else {
+            self.copy_between(&source, &target, from)
+                .and_then(|_| source.backend.remove_file(&source.path))
+        }
+    }
+
+    fn create_dir_all(&self, path: &PathBuf) -> Result<(), SE> {
+        let route = self.route_writable(path)?;
+        route.backend.create_dir_all(&route.path)
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE> {
+        let route = self.route(path);
+        route.backend.exists(&route.path)
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE> {
+        let route = self.route(path);
+        route.backend.is_file(&route.path)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        let route    = self.route(&p);
+        let instance = route.backend.new_instance(route.path.clone());
+
+        if route.is_read_only() {
+            Box::new(ReadOnlyFileAbstractionInstance {
+                inner: instance,
+                path: p,
+            })
+        } else {
+            instance
+        }
+    }
+
+    fn drain(&self) -> Result<Drain, SE> {
+        self.inner.drain()
+    }
+
+    fn fill(&mut self, d: Drain) -> Result<(), SE> {
+        self.inner.fill(d)
+    }
+
+    /// List the pathes below `basepath`, the pathes of the mounted stores included
+    ///
+    /// Pathes of the wrapped backend which are hidden by a mount are left out.
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE> {
+        let mut pathes = vec![];
+
+        for path in self.inner.pathes_recursively(basepath.clone())? {
+            let mounted = path
+                .strip_prefix(&self.store_location)
+                .ok()
+                .and_then(|local| self.mount_of(local))
+                .is_some();
+
+            if !mounted {
+                pathes.push(path);
+            }
+        }
+
+        for mount in self.mounts.iter() {
+            let mountpoint = self.store_location.join(&mount.prefix);
+            let mount_base = if let Ok(rest) = basepath.strip_prefix(&mountpoint) {
+                mount.location.join(rest)
+            } else if mountpoint.starts_with(&basepath) {
+                mount.location.clone()
+            } else {
+                continue;
+            };
+
+            for path in mount.backend.pathes_recursively(mount_base)? {
+                // Not all backends limit the pathes to the basepath
+                if let Ok(rest) = path.strip_prefix(&mount.location) {
+                    pathes.push(mountpoint.join(rest));
+                }
+            }
+        }
+
+        Ok(PathIterator::new(Box::new(pathes.into_iter())))
+    }
+
+    fn modified(&self, path: &PathBuf) -> Result<Option<SystemTime>, SE> {
+        let route = self.route(path);
+        route.backend.modified(&route.path)
+    }
+
+    /// Lock entries on mounts in the mounted store, so processes using it directly see the lock
+    fn lock(&self, store: &PathBuf, id: &StoreId, wait: Option<Duration>)
+        -> Result<Option<Box<EntryLock>>, SE>
+    {
+        match self.mount_of(id.local()) {
+            Some((mount, rest)) => {
+                let id = StoreId::new(Some(mount.location.clone()), rest.to_path_buf())?;
+                mount.backend.lock(&mount.location, &id, wait)
+            },
+            None => self.inner.lock(store, id, wait),
+        }
+    }
+
+    fn is_read_only(&self, path: &PathBuf) -> Result<bool, SE> {
+        Ok(self.route(path).is_read_only())
+    }
+
+    fn mounts(&self) -> Vec<MountPoint> {
+        self.mounts
+            .iter()
+            .map(|m| MountPoint {
+                prefix: m.prefix.clone(),
+                location: m.location.clone(),
+                read_only: m.read_only,
+            })
+            .collect()
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use file_abstraction::InMemoryFileAbstraction;
+    use file_abstraction::FileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn mounted(read_only: bool) -> MountFileAbstraction {
+        let team = Mount::new(String::from("team"),
+                              PathBuf::from("/team-store"),
+                              Box::new(InMemoryFileAbstraction::new()),
+                              read_only).unwrap();
+
+        MountFileAbstraction::new(Box::new(InMemoryFileAbstraction::new()),
+                                  PathBuf::from("/"),
+                                  vec![team])
+    }
+
+    fn write(backend: &FileAbstraction, id: &str) -> Result<(), SE> {
+        let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(id)).unwrap();
+        let entry = Entry::new(id.clone());
+        backend.new_instance(id.into_pathbuf().unwrap()).write_file_content(&entry)
+    }
+
+    #[test]
+    fn test_invalid_prefixes_are_rejected() {
+        for prefix in vec!["", ".team", "team/sub", "/team", ".."] {
+            let backend = Box::new(InMemoryFileAbstraction::new());
+            assert!(Mount::new(String::from(prefix), PathBuf::from("/x"), backend, false).is_err(),
+                    "Prefix '{}' was accepted", prefix);
+        }
+    }
+
+    #[test]
+    fn test_entries_on_mounts_live_in_the_mounted_store() {
+        let backend = mounted(false);
+        write(&backend, "team/meeting").unwrap();
+        write(&backend, "notes/note").unwrap();
+
+        let team = &backend.mounts[0].backend;
+        assert!(team.exists(&PathBuf::from("/team-store/meeting")).unwrap());
+        assert!(!backend.inner.exists(&PathBuf::from("/team/meeting")).unwrap());
+        assert!(backend.inner.exists(&PathBuf::from("/notes/note")).unwrap());
+
+        let mut pathes = backend.pathes_recursively(PathBuf::from("/")).unwrap().collect::<Vec<_>>();
+        pathes.sort();
+        assert_eq!(vec![PathBuf::from("/notes/note"), PathBuf::from("/team/meeting")], pathes);
+    }
+
+    #[test]
+    fn test_rename_between_stores() {
+        let backend = mounted(false);
+        write(&backend, "notes/note").unwrap();
+
+        backend.rename(&PathBuf::from("/notes/note"), &PathBuf::from("/team/note")).unwrap();
+
+        assert!(!backend.exists(&PathBuf::from("/notes/note")).unwrap());
+        assert!(backend.exists(&PathBuf::from("/team/note")).unwrap());
+        assert!(backend.mounts[0].backend.exists(&PathBuf::from("/team-store/note")).unwrap());
+    }
+
+    #[test]
+    fn test_read_only_mounts_are_not_written() {
+        let backend = mounted(true);
+        let _ = backend.mounts[0].backend
+            .new_instance(PathBuf::from("/team-store/meeting"))
+            .write_file_content(&Entry::new(StoreId::new_baseless(PathBuf::from("meeting")).unwrap()))
+            .unwrap();
+
+        let meeting = PathBuf::from("/team/meeting");
+        assert!(backend.is_read_only(&meeting).unwrap());
+        assert!(backend.exists(&meeting).unwrap());
+
+        assert!(write(&backend, "team/meeting").is_err());
+        assert!(write(&backend, "team/new").is_err());
+        assert!(backend.remove_file(&meeting).is_err());
+        assert!(backend.rename(&meeting, &PathBuf::from("/notes/meeting")).is_err());
+        assert!(backend.copy(&PathBuf::from("/notes/x"), &PathBuf::from("/team/x")).is_err());
+
+        // Copying from a read-only mount is fine
+        backend.copy(&meeting, &PathBuf::from("/notes/meeting")).unwrap();
+        assert!(backend.inner.exists(&PathBuf::from("/notes/meeting")).unwrap());
+    }
+
+}
+
@@ -0,0 +1,437 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A FileAbstraction which stores all entries in a single SQLite database
+//!
+//! Each entry is one row in the `entries` table, keyed by its path relative to the store. The
+//! header is stored as JSON, so it can be queried with the JSON1 functions of SQLite (for example
+//! `json_extract(header, '$.imag.version')`), and the content is stored as text.
+//!
+//! The database is opened (and the table is created) lazily when it is accessed the first time.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use rusqlite::Connection;
+use serde_json;
+use toml;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use store::Entry;
+use storeid::StoreId;
+use file_abstraction::iter::PathIterator;
+
+/// Handle on the database file
+#[derive(Debug)]
+struct Database {
+    /// The location of the store, the paths in the database are relative to this
+    location: PathBuf,
+    path: PathBuf,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl Database {
+
+    fn new(location: PathBuf, path: PathBuf) -> Database {
+        Database {
+            location: location,
+            path: path,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Execute `f` with the connection to the database, opening the database if necessary
+    fn with_connection<F, T>(&self, f: F) -> Result<T, SE>
+        where F: FnOnce(&mut Connection) -> Result<T, SE>
+    {
+        let mut guard = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        if guard.is_none() {
+            debug!("Opening database at {:?}", self.path);
+            let conn = Connection::open(&self.path).chain_err(|| SEK::SqliteBackendError)?;
+            let _ = conn
+                .execute("CREATE TABLE IF NOT EXISTS entries (
+                            path    TEXT PRIMARY KEY NOT NULL,
+                            header  TEXT NOT NULL,
+                            content TEXT NOT NULL
+                          )", &[])
+                .chain_err(|| SEK::SqliteBackendError)?;
+            *guard = Some(conn);
+        }
+
+        f(guard.as_mut().unwrap()) // safe by above
+    }
+
+    /// Get the key in the database for the full path `path`
+    fn key(&self, path: &Path) -> Result<String, SE> {
+        path.strip_prefix(&self.location)
+            .chain_err(|| SEK::StoreIdBuildFromFullPathError)
+            .and_then(|p| p.to_str().map(String::from).ok_or(SE::from_kind(SEK::EncodingError)))
+    }
+
+    /// Get the full path for the key `key` from the database
+    fn full_path(&self, key: &str) -> PathBuf {
+        self.location.join(key)
+    }
+
+}
+
+/// Build an Entry from the JSON header and the content as stored in the database
+fn row_to_entry(id: StoreId, header: String, content: String) -> Result<Entry, SE> {
+    let header : toml::Value = serde_json::from_str(&header).chain_err(|| SEK::SqliteBackendError)?;
+
+    toml::to_string(&header)
+        .chain_err(|| SEK::SqliteBackendError)
+        .and_then(|hdr| {
+            let s = format!("---\n{header}---\n{content}", header = hdr, content = content);
+            Entry::from_str(id, &s)
+        })
+}
+
+fn write_entry(conn: &Connection, key: String, entry: &Entry) -> Result<(), SE> {
+    let header  = serde_json::to_string(entry.get_header()).chain_err(|| SEK::SqliteBackendError)?;
+    let content = entry.get_content();
+
+    conn.execute("INSERT OR REPLACE INTO entries (path, header, content) VALUES (?1, ?2, ?3)",
+                 &[&key, &header, content])
+        .chain_err(|| SEK::SqliteBackendError)
+        .map(|_| ())
+}
+
+fn expect_changed_rows(n: i32) -> Result<(), SE> {
+    if n == 0 {
+        Err(SE::from_kind(SEK::FileNotFound))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteFileAbstractionInstance {
+    db: Arc<Database>,
+    path: PathBuf,
+}
+
+impl FileAbstractionInstance for SqliteFileAbstractionInstance {
+
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
+        let key = self.db.key(&self.path)?;
+        let row = self.db.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT header, content FROM entries WHERE path = ?1")
+                .chain_err(|| SEK::SqliteBackendError)?;
+
+            let mut rows = stmt
+                .query_map(&[&key], |row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+                .chain_err(|| SEK::SqliteBackendError)?;
+
+            match rows.next() {
+                Some(row) => row.map(Some).chain_err(|| SEK::SqliteBackendError),
+                None      => Ok(None),
+            }
+        })?;
+
+        match row {
+            Some((header, content)) => row_to_entry(id, header, content),
+            None                    => Err(SE::from_kind(SEK::FileNotFound)),
+        }
+    }
+
+    fn write_file_content(&mut self, buf: &Entry) -> Result<(), SE> {
+        let key = self.db.key(&self.path)?;
+        self.db.with_connection(|conn| write_entry(conn, key, buf))
+    }
+}
+
+/// A FileAbstraction which stores all entries in a SQLite database
+#[derive(Debug)]
+pub struct SqliteFileAbstraction {
+    db: Arc<Database>,
+}
+
+impl SqliteFileAbstraction {
+
+    /// Create a new SqliteFileAbstraction for the store at `location`, which uses the database
+    /// file at `database`
+    ///
+    /// The database file is created if it does not exist.
+    pub fn new(location: PathBuf, database: PathBuf) -> SqliteFileAbstraction {
+        SqliteFileAbstraction {
+            db: Arc::new(Database::new(location, database)),
+        }
+    }
+
+}
+
+impl FileAbstraction for SqliteFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        let key = self.db.key(path)?;
+        self.db
+            .with_connection(|conn| {
+                conn.execute("DELETE FROM entries WHERE path = ?1", &[&key])
+                    .chain_err(|| SEK::SqliteBackendError)
+            })
+            .and_then(expect_changed_rows)
+            .chain_err(|| SEK::FileNotRemoved)
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let from = self.db.key(from)?;
+        let to   = self.db.key(to)?;
+        self.db
+            .with_connection(|conn| {
+                conn.execute("INSERT OR REPLACE INTO entries (path, header, content)
+                              SELECT ?2, header, content FROM entries WHERE path = ?1",
+                             &[&from, &to])
+                    .chain_err(|| SEK::SqliteBackendError)
+            })
+            .and_then(expect_changed_rows)
+            .chain_err(|| SEK::FileNotCopied)
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let from = self.db.key(from)?;
+        let to   = self.db.key(to)?;
+        self.db
+            .with_connection(|conn| {
+                conn.execute("UPDATE entries SET path = ?2 WHERE path = ?1", &[&from, &to])
+                    .chain_err(|| SEK::SqliteBackendError)
+            })
+            .and_then(expect_changed_rows)
+            .chain_err(|| SEK::FileNotRenamed)
+    }
+
+    /// There are no directories in the database, so there is nothing to create
+    fn create_dir_all(&self, _: &PathBuf) -> Result<(), SE> {
+        Ok(())
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE> {
+        let key = self.db.key(path)?;
+        self.db.with_connection(|conn| {
+            conn.query_row("SELECT count(*) FROM entries WHERE path = ?1",
+                           &[&key],
+                           |row| row.get::<_, i64>(0))
+                .chain_err(|| SEK::SqliteBackendError)
+                .map(|n| n > 0)
+        })
+    }
+
+    /// As only entries are stored in the database, every path which exists is a file
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.exists(path)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(SqliteFileAbstractionInstance {
+            db: self.db.clone(),
+            path: p,
+        })
+    }
+
+    fn drain(&self) -> Result<Drain, SE> {
+        let db = self.db.clone();
+        self.db.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT path, header, content FROM entries")
+                .chain_err(|| SEK::SqliteBackendError)?;
+
+            let rows = stmt
+                .query_map(&[], |row| {
+                    (row.get::<_, String>(0), row.get::<_, String>(1), row.get::<_, String>(2))
+                })
+                .chain_err(|| SEK::SqliteBackendError)?;
+
+            let mut hm = HashMap::new();
+            for row in rows {
+                let (key, header, content) = row.chain_err(|| SEK::SqliteBackendError)?;
+                let id    = StoreId::new_baseless(PathBuf::from(&key))?;
+                let entry = row_to_entry(id, header, content)?;
+                hm.insert(db.full_path(&key), entry);
+            }
+
+            Ok(Drain::new(hm))
+        })
+    }
+
+    /// Write all entries from the Drain into the database, in one transaction
+    fn fill(&mut self, mut d: Drain) -> Result<(), SE> {
+        let db = self.db.clone();
+        self.db.with_connection(|conn| {
+            let tx = conn.transaction().chain_err(|| SEK::SqliteBackendError)?;
+
+            for (path, element) in d.iter() {
+                debug!("Drain into database: {:?}", path);
+                let _ = write_entry(&tx, db.key(&path)?, &element)?;
+            }
+
+            tx.commit().chain_err(|| SEK::SqliteBackendError)
+        })
+    }
+
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE> {
+        let db   = self.db.clone();
+        let base = self.db.key(&basepath)?;
+        self.db.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT path FROM entries WHERE substr(path, 1, length(?1)) = ?1")
+                .chain_err(|| SEK::SqliteBackendError)?;
+
+            let rows = stmt
+                .query_map(&[&base], |row| row.get::<_, String>(0))
+                .chain_err(|| SEK::SqliteBackendError)?;
+
+            let mut pathes = vec![];
+            for row in rows {
+                pathes.push(db.full_path(&row.chain_err(|| SEK::SqliteBackendError)?));
+            }
+
+            Ok(PathIterator::new(Box::new(pathes.into_iter())))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use super::SqliteFileAbstraction;
+    use file_abstraction::InMemoryFileAbstraction;
+    use store::Store;
+
+    fn sqlite_backend(dir: &TempDir) -> Box<SqliteFileAbstraction> {
+        let location = dir.path().to_path_buf();
+        let database = location.join("store.sqlite");
+        Box::new(SqliteFileAbstraction::new(location, database))
+    }
+
+    #[test]
+    fn test_create_get_delete() {
+        let dir   = TempDir::new("imag-sqlite-backend").unwrap();
+        let store = Store::new_with_backend(dir.path().to_path_buf(), &None, sqlite_backend(&dir))
+            .unwrap();
+
+        {
+            let mut entry = store.create(PathBuf::from("test/entry")).unwrap();
+            entry.get_content_mut().push_str("content");
+        }
+
+        {
+            let entry = store.get(PathBuf::from("test/entry")).unwrap();
+            assert!(entry.is_some());
+            assert_eq!("content", *entry.unwrap().get_content());
+        }
+
+        let ids = store.retrieve_for_module("test").unwrap().collect::<Vec<_>>();
+        assert_eq!(1, ids.len());
+
+        assert!(store.delete(PathBuf::from("test/entry")).is_ok());
+        assert!(store.get(PathBuf::from("test/entry")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrate_to_sqlite_and_back() {
+        let dir       = TempDir::new("imag-sqlite-backend").unwrap();
+        let location  = dir.path().to_path_buf();
+        let backend   = Box::new(InMemoryFileAbstraction::new());
+        let mut store = Store::new_with_backend(location.clone(), &None, backend).unwrap();
+
+        for n in 1..10 {
+            let mut entry = store.create(PathBuf::from(format!("test-{}", n))).unwrap();
+            entry.get_content_mut().push_str(&format!("content {}", n));
+        }
+
+        assert!(store.migrate_backend(sqlite_backend(&dir)).is_ok());
+        assert_eq!(9, store.entries().unwrap().count());
+
+        assert!(store.migrate_backend(Box::new(InMemoryFileAbstraction::new())).is_ok());
+        assert_eq!(9, store.entries().unwrap().count());
+
+        for n in 1..10 {
+            let entry = store.get(PathBuf::from(format!("test-{}", n))).unwrap().unwrap();
+            assert_eq!(format!("content {}", n), *entry.get_content());
+        }
+    }
+
+    #[test]
+    fn test_migrate_hidden_pathes_from_fs_to_sqlite_and_back() {
+        use std::fs;
+
+        use file_abstraction::FSFileAbstraction;
+        use store::Entry;
+        use storeid::StoreId;
+
+        let dir       = TempDir::new("imag-sqlite-backend").unwrap();
+        let db_dir    = TempDir::new("imag-sqlite-backend-db").unwrap();
+        let location  = dir.path().to_path_buf();
+        let database  = db_dir.path().join("store.sqlite");
+        let backend   = Box::new(FSFileAbstraction::new());
+        let mut store = Store::new_with_backend(location.clone(), &None, backend).unwrap();
+
+        let hidden = vec![".history/test/1", ".trash/test~1", ".index/test.uuid", ".blobs/ab/cdef"];
+
+        {
+            let mut entry = store.create(PathBuf::from("test")).unwrap();
+            entry.get_content_mut().push_str("entry");
+        }
+        for h in hidden.iter() {
+            let mut entry = Entry::new(StoreId::new_baseless(PathBuf::from(h)).unwrap());
+            entry.get_content_mut().push_str(h);
+            store.write_hidden(&entry).unwrap();
+        }
+
+        let sqlite = Box::new(SqliteFileAbstraction::new(location.clone(), database));
+        assert!(store.migrate_backend(sqlite).is_ok());
+
+        // Nothing is left on the filesystem which could be read instead of the database
+        for name in vec!["test", ".history", ".trash", ".index", ".blobs"] {
+            let path = location.join(name);
+            if path.is_dir() {
+                fs::remove_dir_all(path).unwrap();
+            } else {
+                fs::remove_file(path).unwrap();
+            }
+        }
+
+        assert!(store.migrate_backend(Box::new(FSFileAbstraction::new())).is_ok());
+
+        assert!(location.join("test").is_file());
+        assert_eq!(1, store.entries().unwrap().count());
+        assert_eq!("entry", *store.get(PathBuf::from("test")).unwrap().unwrap().get_content());
+
+        for h in hidden.iter() {
+            assert!(location.join(h).is_file(), "{} was not migrated", h);
+            let id    = StoreId::new_baseless(PathBuf::from(h)).unwrap();
+            let entry = store.get_hidden(id).unwrap().unwrap();
+            assert_eq!(*h, *entry.get_content());
+        }
+    }
+
+}
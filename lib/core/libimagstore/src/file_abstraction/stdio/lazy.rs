@@ -0,0 +1,324 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A StdIoFileAbstraction for newline-delimited JSON which parses entries only if they are used
+//!
+//! The lines of the entries are kept as they were read. An entry is parsed when it is retrieved
+//! for the first time, entries which are not used are written to the output as they were read.
+//!
+//! Only the parsing is lazy: the whole input is read into memory when the backend is created, and
+//! the store is written to the output when the backend is dropped.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Error as FmtError;
+use std::fmt::Formatter;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use libimagerror::trace::*;
+
+use error::StoreErrorKind as SEK;
+use error::StoreError as SE;
+use error::ResultExt;
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use super::mapper::ndjson;
+use store::Entry;
+use storeid::StoreId;
+use file_abstraction::iter::PathIterator;
+
+#[derive(Debug, Clone)]
+enum LazyEntry {
+    /// The line the entry was read from, not parsed yet
+    Line(String),
+    Parsed(Entry),
+}
+
+impl LazyEntry {
+
+    /// Parse the entry if it is not parsed yet, as entry `id` or as the entry at the path in its
+    /// line if the id is not known
+    fn parse(&mut self, id: Option<StoreId>) -> Result<&Entry, SE> {
+        let parsed = match *self {
+            LazyEntry::Line(ref line) => match id {
+                Some(id) => ndjson::entry_of_line_with_id(line, id)?,
+                None     => ndjson::entry_of_line(line).map(|(_, entry)| entry)?,
+            },
+            LazyEntry::Parsed(ref entry) => return Ok(entry),
+        };
+
+        *self = LazyEntry::Parsed(parsed);
+        match *self {
+            LazyEntry::Parsed(ref entry) => Ok(entry),
+            LazyEntry::Line(_) => unreachable!(),
+        }
+    }
+
+}
+
+type Backend = Arc<Mutex<BTreeMap<PathBuf, LazyEntry>>>;
+
+#[derive(Debug)]
+pub struct LazyStdIoFileAbstractionInstance {
+    entries: Backend,
+    path: PathBuf,
+}
+
+impl FileAbstractionInstance for LazyStdIoFileAbstractionInstance {
+
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
+        let mut entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        entries
+            .get_mut(&self.path)
+            .ok_or_else(|| SE::from_kind(SEK::FileNotFound))
+            .and_then(|entry| entry.parse(Some(id)).map(Clone::clone))
+    }
+
+    fn write_file_content(&mut self, buf: &Entry) -> Result<(), SE> {
+        let mut entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        let _ = entries.insert(self.path.clone(), LazyEntry::Parsed(buf.clone()));
+        Ok(())
+    }
+
+}
+
+/// A backend which reads the store as newline-delimited JSON when it is created and writes it to
+/// the output when it is dropped
+///
+/// See the `ndjson` mapper for the format.
+pub struct LazyStdIoFileAbstraction<W: Write> {
+    entries: Backend,
+    out: Rc<RefCell<W>>,
+}
+
+impl<W: Write> LazyStdIoFileAbstraction<W> {
+
+    pub fn new<R: Read>(in_stream: &mut R, out_stream: Rc<RefCell<W>>)
+        -> Result<LazyStdIoFileAbstraction<W>, SE>
+    {
+        let mut entries = BTreeMap::new();
+        let _ = ndjson::read_lines(in_stream, |line| {
+            ndjson::path_of_line(&line).map(|path| {
+                let _ = entries.insert(path, LazyEntry::Line(line));
+            })
+        })?;
+
+        Ok(LazyStdIoFileAbstraction {
+            entries: Arc::new(Mutex::new(entries)),
+            out:     out_stream,
+        })
+    }
+
+    /// Get the number of entries which are parsed
+    pub fn parsed_entries(&self) -> Result<usize, SE> {
+        self.entries
+            .lock()
+            .map_err(|_| SE::from_kind(SEK::LockError))
+            .map(|entries| {
+                entries
+                    .values()
+                    .filter(|e| match **e { LazyEntry::Parsed(_) => true, _ => false })
+                    .count()
+            })
+    }
+
+    fn write_out(&self) -> Result<(), SE> {
+        let entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        let mut out = self.out.borrow_mut();
+
+        let _ = ndjson::write_version_line(&mut *out)?;
+        for (path, entry) in entries.iter() {
+            let _ = match *entry {
+                LazyEntry::Line(ref line) => writeln!(out, "{}", line).chain_err(|| SEK::IoError),
+                LazyEntry::Parsed(ref entry) => ndjson::write_entry_line(&mut *out, path, entry),
+            }?;
+        }
+
+        out.flush().chain_err(|| SEK::IoError)
+    }
+
+}
+
+impl<W: Write> Debug for LazyStdIoFileAbstraction<W> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "LazyStdIoFileAbstraction({:?})", self.entries)
+    }
+}
+
+impl<W: Write> Drop for LazyStdIoFileAbstraction<W> {
+    fn drop(&mut self) {
+        // We can do nothing but end this here with a trace, see StdoutFileAbstraction
+        let _ = self.write_out().map_err_trace();
+    }
+}
+
+impl<W: Write> FileAbstraction for LazyStdIoFileAbstraction<W> {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        self.entries
+            .lock()
+            .map_err(|_| SE::from_kind(SEK::LockError))?
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| SE::from_kind(SEK::FileNotFound))
+    }
+
+    // The line of an entry contains its path, so copied and renamed entries are parsed
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let mut entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        let entry = entries
+            .get_mut(from)
+            .ok_or_else(|| SE::from_kind(SEK::FileNotFound))
+            .and_then(|entry| entry.parse(None).map(Clone::clone))?;
+
+        let _ = entries.insert(to.clone(), LazyEntry::Parsed(entry));
+        Ok(())
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        let mut entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        let mut entry   = entries.remove(from).ok_or_else(|| SE::from_kind(SEK::FileNotFound))?;
+        let _           = entry.parse(None)?;
+
+        let _ = entries.insert(to.clone(), entry);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, _: &PathBuf) -> Result<(), SE> {
+        Ok(())
+    }
+
+    fn exists(&self, pb: &PathBuf) -> Result<bool, SE> {
+        self.entries
+            .lock()
+            .map_err(|_| SE::from_kind(SEK::LockError))
+            .map(|entries| entries.contains_key(pb))
+    }
+
+    fn is_file(&self, pb: &PathBuf) -> Result<bool, SE> {
+        // Like with the InMemoryFileAbstraction, each path is a path to an entry
+        self.exists(pb)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(LazyStdIoFileAbstractionInstance {
+            entries: self.entries.clone(),
+            path:    p,
+        })
+    }
+
+    /// Drain all entries, which parses them
+    fn drain(&self) -> Result<Drain, SE> {
+        let mut entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        let mut drained = HashMap::new();
+
+        for (path, entry) in entries.iter_mut() {
+            let entry = entry.parse(None)?.clone();
+            let _     = drained.insert(path.clone(), entry);
+        }
+
+        Ok(Drain::new(drained))
+    }
+
+    fn fill(&mut self, mut d: Drain) -> Result<(), SE> {
+        let mut entries = self.entries.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        for (path, element) in d.iter() {
+            debug!("Drain into {:?}: {:?}", self.entries, path);
+            let _ = entries.insert(path, LazyEntry::Parsed(element));
+        }
+
+        Ok(())
+    }
+
+    fn pathes_recursively(&self, _basepath: PathBuf) -> Result<PathIterator, SE> {
+        let pathes : Vec<PathBuf> = self.entries
+            .lock()
+            .map_err(|_| SE::from_kind(SEK::LockError))?
+            .keys()
+            .cloned()
+            .collect();
+
+        Ok(PathIterator::new(Box::new(pathes.into_iter())))
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use super::LazyStdIoFileAbstraction;
+    use file_abstraction::FileAbstraction;
+    use store::Store;
+    use storeid::StoreId;
+
+    const INPUT : &'static str = r#"{"version":"0.6.0"}
+{"path":"/a","header":{"imag":{"version":"0.6.0"}},"content":"A"}
+{"path":"/b","header":{"imag":{"version":"0.6.0"}},"content":"B"}
+{"path":"/c","header":{"imag":{"version":"0.6.0"}},   "content":"C"}
+"#;
+
+    #[test]
+    fn test_only_used_entries_are_parsed() {
+        let mut input = Cursor::new(INPUT);
+        let output    = Rc::new(RefCell::new(vec![]));
+        let backend   = LazyStdIoFileAbstraction::new(&mut input, output).unwrap();
+
+        let mut instance = backend.new_instance(PathBuf::from("/b"));
+        let id           = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("b")).unwrap();
+        assert_eq!(0, backend.parsed_entries().unwrap());
+        assert_eq!("B", instance.get_file_content(id).unwrap().get_content());
+        assert_eq!(1, backend.parsed_entries().unwrap());
+    }
+
+    #[test]
+    fn test_unused_entries_are_written_as_read() {
+        let mut input = Cursor::new(INPUT);
+        let output    = Rc::new(RefCell::new(vec![]));
+
+        {
+            let backend = LazyStdIoFileAbstraction::new(&mut input, output.clone()).unwrap();
+            let store   = Store::new_with_backend(PathBuf::from("/"), &None, Box::new(backend))
+                .unwrap();
+
+            let mut entry = store.retrieve(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("changed");
+        }
+
+        let out   = String::from_utf8(Rc::try_unwrap(output).unwrap().into_inner()).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(4, lines.len());
+        assert_eq!(r#"{"version":"0.6.0"}"#, lines[0]);
+        assert!(lines[1].contains(r#""content":"changed""#));
+        assert_eq!(r#"{"path":"/b","header":{"imag":{"version":"0.6.0"}},"content":"B"}"#, lines[2]);
+        assert_eq!(r#"{"path":"/c","header":{"imag":{"version":"0.6.0"}},   "content":"C"}"#, lines[3]);
+    }
+}
@@ -0,0 +1,63 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A mapper which reads and writes the store as CBOR document
+//!
+//! Only available with the "cbor" feature.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use serde_cbor;
+
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use super::Mapper;
+use super::document::Document;
+use store::Result;
+use store::Entry;
+
+pub struct CborMapper;
+
+impl CborMapper {
+
+    pub fn new() -> CborMapper {
+        CborMapper
+    }
+
+}
+
+impl Mapper for CborMapper {
+
+    fn read_to_fs<R: Read>(&self, r: &mut R, hm: &mut HashMap<PathBuf, Entry>) -> Result<()> {
+        debug!("Parsing CBOR document");
+        serde_cbor::from_reader::<Document, _>(r)
+            .chain_err(|| SEK::IoError)
+            .and_then(|doc| doc.into_entries(hm))
+    }
+
+    fn fs_to_write<W: Write>(&self, hm: &mut HashMap<PathBuf, Entry>, out: &mut W) -> Result<()> {
+        serde_cbor::to_writer(&mut *out, &Document::from_entries(hm))
+            .chain_err(|| SEK::IoError)
+            .and_then(|_| out.flush().chain_err(|| SEK::IoError))
+    }
+
+}
@@ -0,0 +1,114 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The document the serde based mappers read and write
+//!
+//! The document has the same structure in each format: the version of the store which wrote it,
+//! and the entries of the store by their path, each with its header and content.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use toml::Value;
+
+use error::StoreErrorKind as SEK;
+use error::StoreError as SE;
+use error::ResultExt;
+use store::Result;
+use store::Entry;
+use storeid::StoreId;
+
+// The content comes first, as TOML requires values before tables
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DocumentEntry {
+    content: String,
+    header: Value,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Document {
+    version: String,
+    store: HashMap<PathBuf, DocumentEntry>,
+}
+
+impl Document {
+
+    /// Build the document from the entries, draining them
+    pub fn from_entries(hm: &mut HashMap<PathBuf, Entry>) -> Document {
+        let store = hm
+            .drain()
+            .map(|(path, entry)| {
+                let doc_entry = DocumentEntry {
+                    content: entry.get_content().clone(),
+                    header:  entry.get_header().clone(),
+                };
+                (path, doc_entry)
+            })
+            .collect();
+
+        Document {
+            version: String::from(version!()),
+            store:   store,
+        }
+    }
+
+    /// Put the entries of the document into `hm`
+    ///
+    /// Fails with a VersionError if the document was written by a newer version of the store.
+    pub fn into_entries(self, hm: &mut HashMap<PathBuf, Entry>) -> Result<()> {
+        let _ = check_version(&self.version)?;
+
+        for (path, doc_entry) in self.store {
+            let entry = entry_from_parts(&path, &doc_entry.header, &doc_entry.content)?;
+            hm.insert(path, entry);
+        }
+
+        Ok(())
+    }
+
+}
+
+/// Check whether a document written by the store in version `version` can be read
+///
+/// Fails with a VersionError if the document was written by a newer version of the store.
+pub fn check_version(version: &str) -> Result<()> {
+    let doc_version = ::semver::Version::parse(version).chain_err(|| SEK::VersionError)?;
+
+    // safe because cargo does not compile if crate version is not valid
+    let crate_version = ::semver::Version::parse(version!()).unwrap();
+    if doc_version > crate_version {
+        return Err(SE::from_kind(SEK::VersionError));
+    }
+
+    Ok(())
+}
+
+/// Build the entry at `path` from its header and content, as read by a mapper
+pub fn entry_from_parts(path: &PathBuf, header: &Value, content: &str) -> Result<Entry> {
+    // The paths are written as they are in the backend, which might be absolute
+    let local = path.strip_prefix("/").map(PathBuf::from).unwrap_or_else(|_| path.clone());
+    StoreId::new_baseless(local).and_then(|id| entry_with_id(id, header, content))
+}
+
+/// Build the entry `id` from its header and content
+pub fn entry_with_id(id: StoreId, header: &Value, content: &str) -> Result<Entry> {
+    let header = ::toml::to_string(header).chain_err(|| SEK::IoError)?;
+    Entry::from_str(id, &format!("---\n{}---\n{}", header, content))
+}
@@ -20,6 +20,10 @@
 use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use error::StoreErrorKind as SEK;
+use error::StoreError as SE;
 use store::Result;
 use store::Entry;
 
@@ -28,5 +32,121 @@ pub trait Mapper {
     fn fs_to_write<W: Write>(&self, &mut HashMap<PathBuf, Entry>, &mut W) -> Result<()>;
 }
 
+mod document;
 pub mod json;
+pub mod ndjson;
+pub mod toml;
+#[cfg(feature = "yaml")] pub mod yaml;
+#[cfg(feature = "cbor")] pub mod cbor;
+
+/// A mapper which is selected at runtime, for example from a commandline flag
+///
+/// Use `"json"`, `"ndjson"`, `"toml"`, `"yaml"` or `"cbor"` with `Format::from_str()`. YAML and CBOR are only
+/// available with the features of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    NdJson,
+    Toml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl FromStr for Format {
+    type Err = SE;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "json"   => Ok(Format::Json),
+            "ndjson" => Ok(Format::NdJson),
+            "toml"   => Ok(Format::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml"   => Ok(Format::Yaml),
+            #[cfg(feature = "cbor")]
+            "cbor"   => Ok(Format::Cbor),
+            other    => Err(SE::from_kind(SEK::UnknownMapper(String::from(other)))),
+        }
+    }
+}
+
+impl Mapper for Format {
+
+    fn read_to_fs<R: Read>(&self, r: &mut R, hm: &mut HashMap<PathBuf, Entry>) -> Result<()> {
+        match *self {
+            Format::Json   => json::JsonMapper::new().read_to_fs(r, hm),
+            Format::NdJson => ndjson::NdJsonMapper::new().read_to_fs(r, hm),
+            Format::Toml   => toml::TomlMapper::new().read_to_fs(r, hm),
+            #[cfg(feature = "yaml")]
+            Format::Yaml   => yaml::YamlMapper::new().read_to_fs(r, hm),
+            #[cfg(feature = "cbor")]
+            Format::Cbor   => cbor::CborMapper::new().read_to_fs(r, hm),
+        }
+    }
+
+    fn fs_to_write<W: Write>(&self, hm: &mut HashMap<PathBuf, Entry>, out: &mut W) -> Result<()> {
+        match *self {
+            Format::Json   => json::JsonMapper::new().fs_to_write(hm, out),
+            Format::NdJson => ndjson::NdJsonMapper::new().fs_to_write(hm, out),
+            Format::Toml   => toml::TomlMapper::new().fs_to_write(hm, out),
+            #[cfg(feature = "yaml")]
+            Format::Yaml   => yaml::YamlMapper::new().fs_to_write(hm, out),
+            #[cfg(feature = "cbor")]
+            Format::Cbor   => cbor::CborMapper::new().fs_to_write(hm, out),
+        }
+    }
+
+}
 
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    use super::Format;
+    use file_abstraction::stdio::StdIoFileAbstraction;
+    use store::Store;
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(Format::Json, Format::from_str("json").unwrap());
+        assert_eq!(Format::Toml, Format::from_str("toml").unwrap());
+        assert!(Format::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn test_toml_in_json_out() {
+        let mut input = Cursor::new(r#"
+            version = "0.6.0"
+
+            [store."/example"]
+            content = "hi there!"
+
+            [store."/example".header.imag]
+            version = "0.6.0"
+        "#);
+        let output = Rc::new(RefCell::new(vec![]));
+
+        {
+            let backend = StdIoFileAbstraction::with_input_mapper(&mut input,
+                                                                  &Format::Toml,
+                                                                  output.clone(),
+                                                                  Format::Json)
+                .unwrap();
+            let store   = Store::new_with_backend(PathBuf::from("/"), &None, Box::new(backend))
+                .unwrap();
+
+            assert_eq!("hi there!", store.get_copy(PathBuf::from("example")).unwrap().get_content());
+        }
+
+        let json = String::from_utf8(Rc::try_unwrap(output).unwrap().into_inner()).unwrap();
+        let json : ::serde_json::Value = ::serde_json::from_str(&json).unwrap();
+        assert_eq!("hi there!", json["store"]["/example"]["content"]);
+    }
+
+}
@@ -0,0 +1,240 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A mapper which reads and writes the store as newline-delimited JSON
+//!
+//! In contrast to the other mappers, the store is not one document: the first line holds the
+//! version of the store which wrote it, each following line holds one entry:
+//!
+//! ```json
+//! {"version":"0.6.0"}
+//! {"path":"example","header":{"imag":{"version":"0.6.0"}},"content":"hi there!"}
+//! ```
+//!
+//! So each entry can be parsed and written on its own, without the rest of the document. The
+//! `LazyStdIoFileAbstraction` uses the functions of this module to keep the lines of the entries
+//! as they are and parse them only if they are used.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+
+use serde_json;
+use toml::Value;
+
+use error::StoreErrorKind as SEK;
+use error::StoreError as SE;
+use error::ResultExt;
+use super::Mapper;
+use super::document::check_version;
+use super::document::entry_from_parts;
+use super::document::entry_with_id;
+use store::Result;
+use store::Entry;
+use storeid::StoreId;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct VersionLine {
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathOfLine {
+    path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryLine {
+    path: PathBuf,
+    header: Value,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryLineRef<'a> {
+    path: &'a PathBuf,
+    header: &'a Value,
+    content: &'a str,
+}
+
+/// Read the lines of the entries from `r`, after checking the version line
+///
+/// Empty lines are skipped, an empty input is an empty store.
+pub fn read_lines<R, F>(r: &mut R, mut f: F) -> Result<()>
+    where R: Read,
+          F: FnMut(String) -> Result<()>
+{
+    let mut version_checked = false;
+
+    for line in BufReader::new(r).lines() {
+        let line = line.chain_err(|| SEK::IoError)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if version_checked {
+            let _ = f(line)?;
+        } else {
+            let version = serde_json::from_str::<VersionLine>(&line).chain_err(|| SEK::VersionError)?;
+            let _       = check_version(&version.version)?;
+            version_checked = true;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the path of the entry in `line`, without parsing the entry
+pub fn path_of_line(line: &str) -> Result<PathBuf> {
+    serde_json::from_str::<PathOfLine>(line)
+        .map(|l| l.path)
+        .chain_err(|| SEK::IoError)
+}
+
+/// Parse the entry in `line`
+pub fn entry_of_line(line: &str) -> Result<(PathBuf, Entry)> {
+    let line  = serde_json::from_str::<EntryLine>(line).chain_err(|| SEK::IoError)?;
+    let entry = entry_from_parts(&line.path, &line.header, &line.content)?;
+    Ok((line.path, entry))
+}
+
+/// Parse the entry in `line` as the entry `id`
+pub fn entry_of_line_with_id(line: &str, id: StoreId) -> Result<Entry> {
+    let line = serde_json::from_str::<EntryLine>(line).chain_err(|| SEK::IoError)?;
+    entry_with_id(id, &line.header, &line.content)
+}
+
+/// Write the version line to `out`
+pub fn write_version_line<W: Write>(out: &mut W) -> Result<()> {
+    let line = VersionLine { version: String::from(version!()) };
+    serde_json::to_writer(&mut *out, &line).chain_err(|| SEK::IoError)?;
+    out.write_all(b"\n").chain_err(|| SEK::IoError)
+}
+
+/// Write the line of `entry` at `path` to `out`
+pub fn write_entry_line<W: Write>(out: &mut W, path: &PathBuf, entry: &Entry) -> Result<()> {
+    let line = EntryLineRef {
+        path:    path,
+        header:  entry.get_header(),
+        content: entry.get_content(),
+    };
+    serde_json::to_writer(&mut *out, &line).chain_err(|| SEK::IoError)?;
+    out.write_all(b"\n").chain_err(|| SEK::IoError)
+}
+
+pub struct NdJsonMapper;
+
+impl NdJsonMapper {
+
+    pub fn new() -> NdJsonMapper {
+        NdJsonMapper
+    }
+
+}
+
+impl Mapper for NdJsonMapper {
+
+    fn read_to_fs<R: Read>(&self, r: &mut R, hm: &mut HashMap<PathBuf, Entry>) -> Result<()> {
+        read_lines(r, |line| {
+            entry_of_line(&line).map(|(path, entry)| {
+                debug!("Read entry line for {:?}", path);
+                hm.insert(path, entry);
+            })
+        })
+    }
+
+    /// Write the entries ordered by their path, removing each from `hm` once it is written
+    fn fs_to_write<W: Write>(&self, hm: &mut HashMap<PathBuf, Entry>, out: &mut W) -> Result<()> {
+        let mut pathes : Vec<PathBuf> = hm.keys().cloned().collect();
+        pathes.sort();
+
+        let _ = write_version_line(out)?;
+        for path in pathes {
+            let entry = hm.remove(&path).ok_or_else(|| SE::from_kind(SEK::FileNotFound))?;
+            let _     = write_entry_line(out, &path, &entry)?;
+        }
+
+        out.flush().chain_err(|| SEK::IoError)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_empty_ndjson_to_fs() {
+        let mut input = Cursor::new("");
+        let mut hm    = HashMap::new();
+
+        assert!(NdJsonMapper::new().read_to_fs(&mut input, &mut hm).is_ok());
+        assert!(hm.is_empty());
+    }
+
+    #[test]
+    fn test_ndjson_to_fs() {
+        let mut input = Cursor::new(r#"{"version":"0.6.0"}
+{"path":"a","header":{"imag":{"version":"0.6.0"}},"content":"A"}
+
+{"path":"b","header":{"imag":{"version":"0.6.0"}},"content":"B"}
+"#);
+        let mut hm = HashMap::new();
+
+        assert!(NdJsonMapper::new().read_to_fs(&mut input, &mut hm).is_ok());
+        assert_eq!(2, hm.len());
+        assert_eq!("A", hm.get(&PathBuf::from("a")).unwrap().get_content());
+        assert_eq!("B", hm.get(&PathBuf::from("b")).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_newer_version_fails() {
+        let mut input = Cursor::new("{\"version\":\"100.0.0\"}\n");
+        let mut hm    = HashMap::new();
+
+        assert!(NdJsonMapper::new().read_to_fs(&mut input, &mut hm).is_err());
+    }
+
+    #[test]
+    fn test_fs_to_ndjson() {
+        let mut hm = HashMap::new();
+        for name in &["b", "a"] {
+            let id    = PathBuf::from(name);
+            let text  = format!("---\n[imag]\nversion = \"0.6.0\"\n---\n{}", name);
+            let entry = Entry::from_str(id.clone(), &text).unwrap();
+            hm.insert(id, entry);
+        }
+
+        let mut out = Cursor::new(vec![]);
+        assert!(NdJsonMapper::new().fs_to_write(&mut hm, &mut out).is_ok());
+        assert!(hm.is_empty());
+
+        let out = String::from_utf8(out.into_inner()).unwrap();
+        let exp = r#"{"version":"0.6.0"}
+{"path":"a","header":{"imag":{"version":"0.6.0"}},"content":"a"}
+{"path":"b","header":{"imag":{"version":"0.6.0"}},"content":"b"}
+"#;
+        assert_eq!(exp, out);
+    }
+}
@@ -0,0 +1,118 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! A mapper which reads and writes the store as TOML document
+//!
+//! The entries are tables below the `store` table, keyed by their path:
+//!
+//! ```toml
+//! version = "0.6.0"
+//!
+//! [store.example]
+//! content = "hi there!"
+//!
+//! [store.example.header.imag]
+//! version = "0.6.0"
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use super::Mapper;
+use super::document::Document;
+use store::Result;
+use store::Entry;
+
+pub struct TomlMapper;
+
+impl TomlMapper {
+
+    pub fn new() -> TomlMapper {
+        TomlMapper
+    }
+
+}
+
+impl Mapper for TomlMapper {
+
+    fn read_to_fs<R: Read>(&self, r: &mut R, hm: &mut HashMap<PathBuf, Entry>) -> Result<()> {
+        let mut s = String::new();
+        let _ = r.read_to_string(&mut s).chain_err(|| SEK::IoError)?;
+
+        debug!("Parsing TOML document");
+        ::toml::from_str::<Document>(&s)
+            .chain_err(|| SEK::IoError)
+            .and_then(|doc| doc.into_entries(hm))
+    }
+
+    fn fs_to_write<W: Write>(&self, hm: &mut HashMap<PathBuf, Entry>, out: &mut W) -> Result<()> {
+        ::toml::to_string(&Document::from_entries(hm))
+            .chain_err(|| SEK::IoError)
+            .and_then(|toml| out.write_all(toml.as_bytes()).chain_err(|| SEK::IoError))
+            .and_then(|_| out.flush().chain_err(|| SEK::IoError))
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use super::TomlMapper;
+    use file_abstraction::stdio::mapper::Mapper;
+    use store::Entry;
+
+    #[test]
+    fn test_empty_toml_to_fs() {
+        let mut toml = Cursor::new(String::from("version = \"0.6.0\"\n[store]\n").into_bytes());
+        let mut hm   = HashMap::new();
+
+        assert!(TomlMapper::new().read_to_fs(&mut toml, &mut hm).is_ok());
+        assert!(hm.is_empty());
+    }
+
+    #[test]
+    fn test_toml_roundtrip() {
+        let mapper = TomlMapper::new();
+
+        let mut hm = HashMap::new();
+        let id     = PathBuf::from("example");
+        let entry  = Entry::from_str(id.clone(), "---\n[imag]\nversion = \"0.6.0\"\n---\nhi there!")
+            .unwrap();
+        hm.insert(id.clone(), entry);
+
+        let mut out : Cursor<Vec<u8>> = Cursor::new(vec![]);
+        assert!(mapper.fs_to_write(&mut hm, &mut out).is_ok());
+
+        let text = String::from_utf8(out.into_inner()).unwrap();
+        assert!(text.contains("[store.example]"));
+
+        let mut read = HashMap::new();
+        assert!(mapper.read_to_fs(&mut Cursor::new(text.into_bytes()), &mut read).is_ok());
+        assert_eq!(1, read.len());
+        assert_eq!("hi there!", read.get(&id).unwrap().get_content());
+    }
+
+}
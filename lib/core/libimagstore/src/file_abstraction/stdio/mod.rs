@@ -38,6 +38,7 @@ use super::InMemoryFileAbstraction;
 use store::Entry;
 use file_abstraction::iter::PathIterator;
 
+pub mod lazy;
 pub mod mapper;
 pub mod out;
 use self::mapper::Mapper;
@@ -65,6 +66,27 @@ impl<W, M> StdIoFileAbstraction<W, M>
             })
     }
 
+    /// Like `StdIoFileAbstraction::new()`, but read the input with another mapper than the one
+    /// the output is written with
+    pub fn with_input_mapper<R, I>(in_stream: &mut R,
+                                   in_mapper: &I,
+                                   out_stream: Rc<RefCell<W>>,
+                                   mapper: M)
+        -> Result<StdIoFileAbstraction<W, M>, SE>
+        where R: Read,
+              I: Mapper
+    {
+        StdoutFileAbstraction::new(out_stream, mapper)
+            .and_then(|out| {
+                let _ = out.backend()
+                     .lock()
+                     .map_err(|_| SE::from_kind(SEK::LockError))
+                     .and_then(|mut mtx| in_mapper.read_to_fs(in_stream, mtx.get_mut()))?;
+
+                Ok(StdIoFileAbstraction(out))
+            })
+    }
+
     pub fn backend(&self) -> &Backend {
         self.0.backend()
     }
@@ -0,0 +1,339 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The revision history of entries
+//!
+//! If `store.history.revisions` is configured, the store keeps the previous revisions of each
+//! entry. Before an entry is overwritten or deleted, its current state is saved as a revision in
+//! the hidden `.history` namespace of the store: the revisions of `foo/bar` are kept as
+//! `.history/foo/bar~<n>-<time>`, where `n` counts up for each revision of the entry and `time` is
+//! the time the revision was saved. Only the configured number of revisions is kept, older ones
+//! are removed.
+//!
+//! A revision is a copy of the file of the entry, made through the backend. The revision number
+//! and the time are only kept in the name of the copy, so the copy can be read back like any other
+//! entry file (and stays encrypted if the backend encrypts it).
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use file_abstraction::FileAbstraction;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The directory the revisions live in, relative to the store path
+const HISTORY_DIR : &'static str = ".history";
+
+/// A previous state of an entry
+#[derive(Debug, Clone)]
+pub struct Revision {
+    number: u64,
+    timestamp: u64,
+    entry: Entry,
+}
+
+impl Revision {
+
+    /// The number of the revision, counting up for each revision of an entry
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// The time the revision was saved, in seconds since the unix epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// The entry as it was in this revision
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> Entry {
+        self.entry
+    }
+
+}
+
+/// A revision file in the history: the revision number, the time and the path of the copy
+struct RevisionFile {
+    number: u64,
+    timestamp: u64,
+    path: PathBuf,
+}
+
+/// The directory the revisions of `id` live in and the prefix of their file names
+fn revision_location(id: &StoreId, store_location: &PathBuf) -> Result<(PathBuf, String)> {
+    let local = id.local();
+    let name  = local
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| format!("{}~", n))
+        .ok_or_else(|| SE::from_kind(SEK::StoreIdHandlingError))?;
+
+    let mut dir = store_location.join(HISTORY_DIR);
+    if let Some(parent) = local.parent() {
+        dir.push(parent);
+    }
+
+    Ok((dir, name))
+}
+
+fn revision_path(id: &StoreId, store_location: &PathBuf, number: u64, timestamp: u64)
+    -> Result<PathBuf>
+{
+    let (dir, prefix) = revision_location(id, store_location)?;
+    Ok(dir.join(format!("{}{}-{}", prefix, number, timestamp)))
+}
+
+/// Parse the `<n>-<time>` part of the name of a revision file
+fn parse_revision_suffix(suffix: &str) -> Option<(u64, u64)> {
+    let mut parts = suffix.splitn(2, '-');
+    let number    = parts.next().and_then(|n| n.parse::<u64>().ok());
+    let timestamp = parts.next().and_then(|t| t.parse::<u64>().ok());
+
+    match (number, timestamp) {
+        (Some(n), Some(t)) => Some((n, t)),
+        _ => None,
+    }
+}
+
+/// All revision files of `id`, oldest first
+fn revision_files(backend: &FileAbstraction, store_location: &PathBuf, id: &StoreId)
+    -> Result<Vec<RevisionFile>>
+{
+    let (dir, prefix) = revision_location(id, store_location)?;
+
+    let mut files = backend
+        .pathes_recursively(dir.clone())?
+        .filter(|path| path.parent() == Some(&dir))
+        .filter_map(|path| {
+            let parsed = path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| if n.starts_with(&prefix) { Some(n[prefix.len()..].to_owned()) } else { None })
+                .and_then(|n| parse_revision_suffix(&n));
+
+            parsed.map(|(number, timestamp)| RevisionFile {
+                number: number,
+                timestamp: timestamp,
+                path: path,
+            })
+        })
+        .collect::<Vec<RevisionFile>>();
+
+    files.sort_by_key(|f| f.number);
+    Ok(files)
+}
+
+/// The numbers of all revisions of `id`, oldest first
+pub fn list(backend: &FileAbstraction, store_location: &PathBuf, id: &StoreId) -> Result<Vec<u64>> {
+    revision_files(backend, store_location, id)
+        .map(|files| files.into_iter().map(|f| f.number).collect())
+}
+
+/// Load revision `number` of `id`, if it exists
+pub fn load(backend: &FileAbstraction, store_location: &PathBuf, id: &StoreId, number: u64)
+    -> Result<Option<Revision>>
+{
+    let file = match revision_files(backend, store_location, id)?
+        .into_iter()
+        .find(|f| f.number == number)
+    {
+        Some(file) => file,
+        None       => return Ok(None),
+    };
+
+    let entry_id = id.clone().with_base(store_location.clone());
+    let stored   = backend
+        .new_instance(file.path)
+        .get_file_content(entry_id.clone())
+        .chain_err(|| SEK::HistoryError)?;
+
+    // The revision might have been saved under another id before the entry was moved
+    let mut entry = Entry::new(entry_id);
+    *entry.get_header_mut()  = stored.get_header().clone();
+    *entry.get_content_mut() = stored.get_content().clone();
+
+    Ok(Some(Revision { number: number, timestamp: file.timestamp, entry: entry }))
+}
+
+/// Load the revisions of all entries, in no particular order
+pub fn all(backend: &FileAbstraction, store_location: &PathBuf) -> Result<Vec<Entry>> {
+    let dir = store_location.join(HISTORY_DIR);
+
+    let mut entries = vec![];
+    for path in backend.pathes_recursively(dir.clone())? {
+        if !path.starts_with(&dir) || !backend.is_file(&path)? {
+            continue;
+        }
+
+        let id    = StoreId::from_full_path(store_location, path.clone())?;
+        let entry = backend
+            .new_instance(path)
+            .get_file_content(id)
+            .chain_err(|| SEK::HistoryError)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Save the current file of `id` as its newest revision, keeping at most `keep` revisions
+pub fn record(backend: &FileAbstraction, store_location: &PathBuf, id: &StoreId, keep: usize)
+    -> Result<()>
+{
+    let existing = revision_files(backend, store_location, id)?;
+    let number   = existing.last().map(|f| f.number + 1).unwrap_or(1);
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let source = id.clone().with_base(store_location.clone()).into_pathbuf()?;
+    let target = revision_path(id, store_location, number, timestamp)?;
+
+    debug!("Saving revision {} of {}", number, id);
+    if let Some(parent) = target.parent() {
+        let _ = backend.create_dir_all(&parent.to_path_buf()).chain_err(|| SEK::HistoryError)?;
+    }
+    let _ = backend.copy(&source, &target).chain_err(|| SEK::HistoryError)?;
+
+    // `existing` does not contain the revision we just wrote
+    let obsolete = (existing.len() + 1).saturating_sub(keep);
+    for old in existing.into_iter().take(obsolete) {
+        debug!("Removing revision {} of {}", old.number, id);
+        let _ = backend.remove_file(&old.path).chain_err(|| SEK::HistoryError)?;
+    }
+
+    Ok(())
+}
+
+/// Move all revisions of `old` to `new`
+///
+/// If `new` has revisions already (because an entry with that id existed before), the moved
+/// revisions are numbered after the newest of them.
+pub fn rename(backend: &FileAbstraction, store_location: &PathBuf, old: &StoreId, new: &StoreId)
+    -> Result<()>
+{
+    let mut number = revision_files(backend, store_location, new)?
+        .last()
+        .map(|f| f.number)
+        .unwrap_or(0);
+
+    for file in revision_files(backend, store_location, old)? {
+        number += 1;
+        let to = revision_path(new, store_location, number, file.timestamp)?;
+        let _  = backend.rename(&file.path, &to).chain_err(|| SEK::HistoryError)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use file_abstraction::FileAbstraction;
+    use file_abstraction::InMemoryFileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn write(backend: &FileAbstraction, id: &str, content: &str) -> StoreId {
+        let id        = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from(content);
+        backend.new_instance(id.clone().into_pathbuf().unwrap()).write_file_content(&entry).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_record_and_load() {
+        let backend  = InMemoryFileAbstraction::new();
+        let location = PathBuf::from("/");
+
+        let id = write(&backend, "foo/bar", "first");
+        record(&backend, &location, &id, 3).unwrap();
+        let _  = write(&backend, "foo/bar", "second");
+        record(&backend, &location, &id, 3).unwrap();
+
+        // revisions of other entries in the same collection are not listed
+        let other = write(&backend, "foo/baz", "other");
+        record(&backend, &location, &other, 3).unwrap();
+
+        assert_eq!(list(&backend, &location, &id).unwrap(), vec![1, 2]);
+
+        let rev = load(&backend, &location, &id, 1).unwrap().unwrap();
+        assert_eq!(rev.number(), 1);
+        assert_eq!(rev.entry().get_content(), "first");
+        assert_eq!(rev.entry().get_location(), &id);
+
+        assert!(load(&backend, &location, &id, 3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_record_keeps_only_configured_number_of_revisions() {
+        let backend  = InMemoryFileAbstraction::new();
+        let location = PathBuf::from("/");
+
+        for i in 0..5 {
+            let id = write(&backend, "foo", &format!("{}", i));
+            record(&backend, &location, &id, 2).unwrap();
+        }
+
+        let id = StoreId::new(Some(location.clone()), PathBuf::from("foo")).unwrap();
+        assert_eq!(list(&backend, &location, &id).unwrap(), vec![4, 5]);
+
+        let rev = load(&backend, &location, &id, 5).unwrap().unwrap();
+        assert_eq!(rev.entry().get_content(), "4");
+    }
+
+    #[test]
+    fn test_rename_numbers_after_existing_revisions() {
+        let backend  = InMemoryFileAbstraction::new();
+        let location = PathBuf::from("/");
+
+        let old = write(&backend, "old", "old");
+        record(&backend, &location, &old, 5).unwrap();
+        record(&backend, &location, &old, 5).unwrap();
+
+        let new = write(&backend, "new", "new");
+        record(&backend, &location, &new, 5).unwrap();
+
+        rename(&backend, &location, &old, &new).unwrap();
+        assert!(list(&backend, &location, &old).unwrap().is_empty());
+        assert_eq!(list(&backend, &location, &new).unwrap(), vec![1, 2, 3]);
+
+        let rev = load(&backend, &location, &new, 3).unwrap().unwrap();
+        assert_eq!(rev.entry().get_content(), "old");
+        assert_eq!(rev.entry().get_location(), &new);
+    }
+
+    #[test]
+    fn test_parse_revision_suffix() {
+        assert_eq!(Some((3, 1514761200)), parse_revision_suffix("3-1514761200"));
+        assert_eq!(None, parse_revision_suffix("3"));
+        assert_eq!(None, parse_revision_suffix("a-1"));
+    }
+
+}
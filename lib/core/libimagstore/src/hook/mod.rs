@@ -0,0 +1,107 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Hooks for the store
+//!
+//! A hook is a piece of code which gets executed before (pre-hooks) or after (post-hooks) the
+//! store executes an action on an entry. Hooks get registered at the `Store` via
+//! `Store::register_hook()` for a `HookPosition`.
+//!
+//! Pre-hooks can abort the action by returning an error, if the hook is configured to be
+//! "aborting" (which is the default). Whether a hook is enabled and aborting is configured in the
+//! `store.hooks.<hookname>` section of the configuration file.
+
+use std::fmt::Debug;
+
+use toml::Value;
+
+use store::Entry;
+use store::Result;
+use store::Store;
+use storeid::StoreId;
+
+pub mod position;
+pub mod registry;
+
+pub use self::position::HookPosition;
+
+/// The data which is passed to a hook when it gets executed
+#[derive(Debug)]
+pub enum HookData<'a> {
+    /// Only the id of the entry is known, passed to the pre-create, pre-retrieve, pre-delete and
+    /// post-delete hooks
+    Id(&'a StoreId),
+
+    /// The entry itself, passed to the post-create, post-retrieve, pre-update and post-update hooks
+    Entry(&'a Entry),
+
+    /// The old and the new id of the entry, passed to the pre-move and post-move hooks
+    Move(&'a StoreId, &'a StoreId),
+}
+
+impl<'a> HookData<'a> {
+
+    /// Get the id of the entry the action is executed on
+    ///
+    /// For a move, this is the id the entry is moved away from.
+    pub fn id(&self) -> &StoreId {
+        match *self {
+            HookData::Id(id)         => id,
+            HookData::Entry(entry)   => entry.get_location(),
+            HookData::Move(from, _)  => from,
+        }
+    }
+
+}
+
+/// The trait each hook has to implement
+pub trait Hook : Debug {
+
+    /// The name of the hook
+    ///
+    /// The name is used to find the configuration of the hook in `store.hooks.<name>`.
+    fn name(&self) -> &'static str;
+
+    /// Set the configuration of the hook
+    ///
+    /// This gets called when the hook is registered and there is a `store.hooks.<name>` section in
+    /// the configuration. The default implementation ignores the configuration.
+    fn set_config(&mut self, _config: &Value) {
+        // default: nothing
+    }
+
+    /// Execute the hook
+    ///
+    /// Returning an error from a pre-hook aborts the action the store is about to execute, if the
+    /// hook is configured to be aborting.
+    fn execute(&self, position: HookPosition, data: &HookData) -> Result<()>;
+
+    /// Execute the hook with access to the store the action is executed on
+    ///
+    /// This is what the store calls. Hooks which keep data in the store (like an index) implement
+    /// this to write it through the backend of the store, see `Store::write_hidden()`. The default
+    /// implementation calls `Hook::execute()`.
+    fn execute_in_store(&self, _store: &Store, position: HookPosition, data: &HookData)
+        -> Result<()>
+    {
+        self.execute(position, data)
+    }
+
+}
@@ -0,0 +1,78 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::fmt::{Display, Formatter};
+use std::fmt::Error as FmtError;
+
+/// The position a hook is executed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookPosition {
+    PreCreate,
+    PostCreate,
+    PreRetrieve,
+    PostRetrieve,
+    PreUpdate,
+    PostUpdate,
+    PreDelete,
+    PostDelete,
+    PreMoveById,
+    PostMoveById,
+}
+
+impl HookPosition {
+
+    /// Whether the position is before the action is executed
+    pub fn is_pre(&self) -> bool {
+        match *self {
+            HookPosition::PreCreate   |
+            HookPosition::PreRetrieve |
+            HookPosition::PreUpdate   |
+            HookPosition::PreDelete   |
+            HookPosition::PreMoveById => true,
+            _                         => false,
+        }
+    }
+
+    /// Whether the position is after the action was executed
+    pub fn is_post(&self) -> bool {
+        !self.is_pre()
+    }
+
+}
+
+impl Display for HookPosition {
+
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        let s = match *self {
+            HookPosition::PreCreate    => "pre-create",
+            HookPosition::PostCreate   => "post-create",
+            HookPosition::PreRetrieve  => "pre-retrieve",
+            HookPosition::PostRetrieve => "post-retrieve",
+            HookPosition::PreUpdate    => "pre-update",
+            HookPosition::PostUpdate   => "post-update",
+            HookPosition::PreDelete    => "pre-delete",
+            HookPosition::PostDelete   => "post-delete",
+            HookPosition::PreMoveById  => "pre-move",
+            HookPosition::PostMoveById => "post-move",
+        };
+        write!(fmt, "{}", s)
+    }
+
+}
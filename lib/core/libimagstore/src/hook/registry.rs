@@ -0,0 +1,262 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::collections::HashMap;
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+use libimagerror::trace::trace_error;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Result;
+use store::Store;
+use super::Hook;
+use super::HookData;
+use super::HookPosition;
+
+#[derive(Debug)]
+struct RegisteredHook {
+    hook: Box<Hook>,
+    enabled: bool,
+    aborting: bool,
+}
+
+/// The registry of all hooks of a `Store`
+///
+/// Holds the `store.hooks` section of the configuration, which is used to configure each hook
+/// when it is registered.
+#[derive(Debug)]
+pub struct HookRegistry {
+    config: Option<Value>,
+    hooks: HashMap<HookPosition, Vec<RegisteredHook>>,
+}
+
+impl HookRegistry {
+
+    /// Create a new, empty HookRegistry
+    ///
+    /// The store configuration is searched for a `store.hooks` table, which is then used to
+    /// configure the hooks which get registered.
+    pub fn new(store_config: &Option<Value>) -> Result<HookRegistry> {
+        let config = match *store_config {
+            Some(ref cfg) => match cfg.read("store.hooks")? {
+                Some(&Value::Table(ref t)) => Some(Value::Table(t.clone())),
+                Some(_) => return Err(SE::from_kind(SEK::ConfigTypeError("store.hooks", "Table"))),
+                None    => None,
+            },
+            None => None,
+        };
+
+        Ok(HookRegistry {
+            config: config,
+            hooks: HashMap::new(),
+        })
+    }
+
+    /// Register a hook for a position
+    ///
+    /// If there is a `store.hooks.<name>` section in the configuration, it is passed to the hook
+    /// via `Hook::set_config()`. The keys `enabled` and `aborting` of that section are read by the
+    /// registry and default to `true`.
+    pub fn register(&mut self, position: HookPosition, mut hook: Box<Hook>) -> Result<()> {
+        let name = hook.name();
+        debug!("Registering hook '{}' for {}", name, position);
+
+        let (enabled, aborting) = match self.hook_config(name) {
+            Some(cfg) => {
+                hook.set_config(cfg);
+
+                let enabled  = get_bool_or_true(cfg, "enabled");
+                let aborting = get_bool_or_true(cfg, "aborting");
+
+                (enabled.chain_err(|| SEK::HookRegisterError(String::from(name)))?,
+                 aborting.chain_err(|| SEK::HookRegisterError(String::from(name)))?)
+            },
+            None => (true, true),
+        };
+
+        self.hooks
+            .entry(position)
+            .or_insert_with(Vec::new)
+            .push(RegisteredHook {
+                hook: hook,
+                enabled: enabled,
+                aborting: aborting,
+            });
+
+        Ok(())
+    }
+
+    /// Execute all enabled hooks for a position
+    ///
+    /// If a hook fails and is configured to be aborting, the error is returned and no further hooks
+    /// are executed. Errors from non-aborting hooks are only traced.
+    pub fn execute(&self, store: &Store, position: HookPosition, data: &HookData) -> Result<()> {
+        let hooks = match self.hooks.get(&position) {
+            Some(hooks) => hooks,
+            None        => return Ok(()),
+        };
+
+        for registered in hooks.iter().filter(|rh| rh.enabled) {
+            let name = registered.hook.name();
+            debug!("Executing {} hook '{}' for {}", position, name, data.id());
+
+            if let Err(e) = registered.hook.execute_in_store(store, position, data) {
+                let res = if position.is_pre() {
+                    Err(e).chain_err(|| SEK::PreHookExecuteError(String::from(name)))
+                } else {
+                    Err(e).chain_err(|| SEK::PostHookExecuteError(String::from(name)))
+                };
+
+                if registered.aborting {
+                    return res;
+                } else {
+                    let _ = res.map_err(|e| trace_error(&e));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn hook_config(&self, name: &str) -> Option<&Value> {
+        self.config
+            .as_ref()
+            .and_then(Value::as_table)
+            .and_then(|t| t.get(name))
+    }
+
+}
+
+fn get_bool_or_true(cfg: &Value, key: &'static str) -> Result<bool> {
+    match cfg.as_table().and_then(|t| t.get(key)) {
+        Some(&Value::Boolean(b)) => Ok(b),
+        Some(_)                  => Err(SE::from_kind(SEK::ConfigTypeError(key, "boolean"))),
+        None                     => Ok(true),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::de::from_str as toml_from_str;
+
+    use super::*;
+    use storeid::StoreId;
+    use error::StoreError as SE;
+    use error::StoreErrorKind as SEK;
+
+    #[derive(Debug)]
+    struct TestHook(bool);
+
+    impl Hook for TestHook {
+        fn name(&self) -> &'static str {
+            "testhook"
+        }
+
+        fn execute(&self, _: HookPosition, _: &HookData) -> Result<()> {
+            if self.0 {
+                Err(SE::from_kind(SEK::IdLocked))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn id() -> StoreId {
+        StoreId::new_baseless(PathBuf::from("test")).unwrap()
+    }
+
+    fn store() -> Store {
+        use file_abstraction::InMemoryFileAbstraction;
+
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    #[test]
+    fn test_no_hooks_execute_ok() {
+        let registry = HookRegistry::new(&None).unwrap();
+        let id       = id();
+        assert!(registry.execute(&store(), HookPosition::PreCreate, &HookData::Id(&id)).is_ok());
+    }
+
+    #[test]
+    fn test_failing_pre_hook_aborts() {
+        let mut registry = HookRegistry::new(&None).unwrap();
+        let hook         = Box::new(TestHook(true));
+        registry.register(HookPosition::PreCreate, hook).unwrap();
+
+        let id  = id();
+        let res = registry.execute(&store(), HookPosition::PreCreate, &HookData::Id(&id));
+        assert!(res.is_err());
+        match *res.unwrap_err().kind() {
+            SEK::PreHookExecuteError(ref name) => assert_eq!("testhook", name),
+            ref other => panic!("Unexpected error kind: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_failing_non_aborting_hook() {
+        let config = toml_from_str(r#"
+        [store.hooks.testhook]
+            aborting = false
+        "#).unwrap();
+
+        let mut registry = HookRegistry::new(&Some(config)).unwrap();
+        let hook         = Box::new(TestHook(true));
+        registry.register(HookPosition::PreDelete, hook).unwrap();
+
+        let id = id();
+        assert!(registry.execute(&store(), HookPosition::PreDelete, &HookData::Id(&id)).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_hook() {
+        let config = toml_from_str(r#"
+        [store.hooks.testhook]
+            enabled = false
+        "#).unwrap();
+
+        let mut registry = HookRegistry::new(&Some(config)).unwrap();
+        let hook         = Box::new(TestHook(true));
+        registry.register(HookPosition::PreUpdate, hook).unwrap();
+
+        let id = id();
+        assert!(registry.execute(&store(), HookPosition::PreUpdate, &HookData::Id(&id)).is_ok());
+    }
+
+    #[test]
+    fn test_hook_config_type_error() {
+        let config = toml_from_str(r#"
+        [store.hooks.testhook]
+            enabled = "yes"
+        "#).unwrap();
+
+        let mut registry = HookRegistry::new(&Some(config)).unwrap();
+        let hook         = Box::new(TestHook(false));
+        assert!(registry.register(HookPosition::PreUpdate, hook).is_err());
+    }
+
+}
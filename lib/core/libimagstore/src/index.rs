@@ -0,0 +1,385 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Persistent secondary indexes over header fields
+//!
+//! For each header path configured in `store.index`, the store keeps an index which maps the
+//! values found at this path to the ids of the entries which contain them. The indexes are kept
+//! up to date on each write and are persisted in the store (at `<store>/.index/<header path>`).
+//!
+//! Other processes might change an index at any time, so the store reloads an index (while
+//! holding its lock) before it uses or changes it.
+//!
+//! Booleans, integers, strings and datetimes are indexed. Arrays are indexed by each of their
+//! elements. Other values (floats and tables) are not indexed.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::path::PathBuf;
+
+use serde_json;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use file_abstraction::FileAbstraction;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The directory the indexes live in, relative to the store path
+const INDEX_DIR : &'static str = ".index";
+
+/// A value in the index
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum IndexKey {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+impl IndexKey {
+
+    /// Get the keys a header value is indexed with
+    pub fn from_value(value: &Value) -> Vec<IndexKey> {
+        match *value {
+            Value::Boolean(b)      => vec![IndexKey::Boolean(b)],
+            Value::Integer(i)      => vec![IndexKey::Integer(i)],
+            Value::String(ref s)   => vec![IndexKey::String(s.clone())],
+            Value::Datetime(ref d) => vec![IndexKey::String(d.to_string())],
+            Value::Array(ref a)    => a.iter().flat_map(IndexKey::from_value).collect(),
+            Value::Float(_)        => vec![],
+            Value::Table(_)        => vec![],
+        }
+    }
+
+    /// Get the key to look up `value` with
+    ///
+    /// Fails if `value` is not a single indexable value.
+    pub fn for_lookup(value: &Value) -> Result<IndexKey> {
+        let mut keys = IndexKey::from_value(value);
+        if keys.len() == 1 && !is_match!(*value, Value::Array(_)) {
+            Ok(keys.remove(0))
+        } else {
+            Err(SE::from_kind(SEK::HeaderIndexError))
+        }
+    }
+
+}
+
+/// The on-disk format of an index
+#[derive(Debug, Serialize, Deserialize)]
+struct SerializedIndex {
+    header_path: String,
+    keys: Vec<(IndexKey, Vec<PathBuf>)>,
+}
+
+/// The index over one header path
+///
+/// Ids are stored without the store path.
+#[derive(Debug)]
+pub struct HeaderIndex {
+    header_path: String,
+    keys: BTreeMap<IndexKey, BTreeSet<PathBuf>>,
+    ids: HashMap<PathBuf, Vec<IndexKey>>,
+}
+
+impl HeaderIndex {
+
+    pub fn new(header_path: String) -> HeaderIndex {
+        HeaderIndex {
+            header_path: header_path,
+            keys: BTreeMap::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Update the index for the entry `id` with the header `header`
+    ///
+    /// Returns whether the index changed.
+    pub fn update(&mut self, id: &PathBuf, header: &Value) -> Result<bool> {
+        let mut keys = match header.read(&self.header_path)? {
+            Some(value) => IndexKey::from_value(value),
+            None        => vec![],
+        };
+        keys.sort();
+        keys.dedup();
+
+        if self.ids.get(id).map(|old| *old == keys).unwrap_or(keys.is_empty()) {
+            return Ok(false);
+        }
+
+        let _ = self.remove(id);
+        for key in keys.iter() {
+            self.keys.entry(key.clone()).or_insert_with(BTreeSet::new).insert(id.clone());
+        }
+        if !keys.is_empty() {
+            self.ids.insert(id.clone(), keys);
+        }
+
+        Ok(true)
+    }
+
+    /// Remove the entry `id` from the index
+    ///
+    /// Returns whether the index changed.
+    pub fn remove(&mut self, id: &PathBuf) -> bool {
+        match self.ids.remove(id) {
+            None       => false,
+            Some(keys) => {
+                for key in keys {
+                    let now_empty = self.keys
+                        .get_mut(&key)
+                        .map(|ids| { ids.remove(id); ids.is_empty() })
+                        .unwrap_or(false);
+
+                    if now_empty {
+                        self.keys.remove(&key);
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// Move the entry `old` to `new` in the index
+    ///
+    /// Returns whether the index changed.
+    pub fn rename(&mut self, old: &PathBuf, new: &PathBuf) -> bool {
+        match self.ids.remove(old) {
+            None       => false,
+            Some(keys) => {
+                for key in keys.iter() {
+                    if let Some(ids) = self.keys.get_mut(key) {
+                        ids.remove(old);
+                        ids.insert(new.clone());
+                    }
+                }
+                self.ids.insert(new.clone(), keys);
+                true
+            }
+        }
+    }
+
+    /// Get the ids of all entries which have `key` in the header
+    pub fn find(&self, key: &IndexKey) -> Vec<PathBuf> {
+        self.keys
+            .get(key)
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Get the ids of all entries which have a value in the range `from`..`to` in the header
+    ///
+    /// Keys of different types are ordered booleans first, then integers, then strings.
+    pub fn range(&self, from: Bound<IndexKey>, to: Bound<IndexKey>) -> Vec<PathBuf> {
+        // BTreeMap::range() panics on empty ranges
+        let is_empty = match (&from, &to) {
+            (&Bound::Included(ref a), &Bound::Included(ref b)) => a > b,
+            (&Bound::Included(ref a), &Bound::Excluded(ref b)) |
+            (&Bound::Excluded(ref a), &Bound::Included(ref b)) |
+            (&Bound::Excluded(ref a), &Bound::Excluded(ref b)) => a >= b,
+            _ => false,
+        };
+
+        if is_empty {
+            return vec![];
+        }
+
+        let ids = self.keys
+            .range((from, to))
+            .flat_map(|(_, ids)| ids.iter().cloned())
+            .collect::<BTreeSet<PathBuf>>();
+
+        ids.into_iter().collect()
+    }
+
+    /// Get the id the index for `header_path` is kept at in the store at `store_location`
+    pub fn storeid(store_location: &PathBuf, header_path: &str) -> Result<StoreId> {
+        let mut id = PathBuf::from(INDEX_DIR);
+        id.push(header_path);
+        StoreId::new(Some(store_location.clone()), id)
+    }
+
+    /// Load the index for `header_path` from the store at `store_location`, if there is one
+    pub fn load(backend: &FileAbstraction, store_location: &PathBuf, header_path: &str)
+        -> Result<Option<HeaderIndex>>
+    {
+        let id = HeaderIndex::storeid(store_location, header_path)?;
+        let pb = id.clone().into_pathbuf()?;
+
+        if !backend.exists(&pb)? {
+            return Ok(None);
+        }
+
+        let entry = backend
+            .new_instance(pb)
+            .get_file_content(id)
+            .chain_err(|| SEK::HeaderIndexError)?;
+
+        let serialized : SerializedIndex = serde_json::from_str(entry.get_content())
+            .chain_err(|| SEK::HeaderIndexError)?;
+
+        let mut index = HeaderIndex::new(serialized.header_path);
+        for (key, ids) in serialized.keys {
+            for id in ids.iter() {
+                index.ids.entry(id.clone()).or_insert_with(Vec::new).push(key.clone());
+            }
+            index.keys.insert(key, ids.into_iter().collect());
+        }
+
+        Ok(Some(index))
+    }
+
+    /// Replace the index with the one in the store at `store_location`, if there is one
+    ///
+    /// If the index was removed from the store, it is kept as it is.
+    pub fn reload(&mut self, backend: &FileAbstraction, store_location: &PathBuf) -> Result<()> {
+        if let Some(loaded) = HeaderIndex::load(backend, store_location, &self.header_path)? {
+            *self = loaded;
+        }
+        Ok(())
+    }
+
+    /// Write the index into the store at `store_location`
+    pub fn save(&self, backend: &FileAbstraction, store_location: &PathBuf) -> Result<()> {
+        let id = HeaderIndex::storeid(store_location, &self.header_path)?;
+
+        let serialized = SerializedIndex {
+            header_path: self.header_path.clone(),
+            keys: self.keys
+                .iter()
+                .map(|(key, ids)| (key.clone(), ids.iter().cloned().collect()))
+                .collect(),
+        };
+
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = serde_json::to_string(&serialized)
+            .chain_err(|| SEK::HeaderIndexError)?;
+
+        debug!("Writing index for '{}'", self.header_path);
+        backend
+            .new_instance(id.into_pathbuf()?)
+            .write_file_content(&entry)
+            .chain_err(|| SEK::HeaderIndexError)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+    use std::path::PathBuf;
+
+    use toml::de::from_str as toml_from_str;
+
+    use super::HeaderIndex;
+    use super::IndexKey;
+    use file_abstraction::InMemoryFileAbstraction;
+
+    fn index() -> HeaderIndex {
+        let mut index = HeaderIndex::new(String::from("test.value"));
+
+        for (id, header) in vec![
+            ("a", "[test]\nvalue = 1"),
+            ("b", "[test]\nvalue = 2"),
+            ("c", "[test]\nvalue = 3"),
+            ("d", "[test]\nvalue = [3, 4]"),
+            ("e", "[other]\nvalue = 1"),
+        ] {
+            let header = toml_from_str(header).unwrap();
+            let _ = index.update(&PathBuf::from(id), &header).unwrap();
+        }
+
+        index
+    }
+
+    #[test]
+    fn test_find() {
+        let index = index();
+
+        assert_eq!(vec![PathBuf::from("a")], index.find(&IndexKey::Integer(1)));
+        assert_eq!(vec![PathBuf::from("c"), PathBuf::from("d")], index.find(&IndexKey::Integer(3)));
+        assert!(index.find(&IndexKey::Integer(5)).is_empty());
+        assert!(index.find(&IndexKey::String(String::from("1"))).is_empty());
+    }
+
+    #[test]
+    fn test_range() {
+        let index = index();
+
+        let ids = index.range(Bound::Included(IndexKey::Integer(2)), Bound::Excluded(IndexKey::Integer(4)));
+        assert_eq!(vec![PathBuf::from("b"), PathBuf::from("c"), PathBuf::from("d")], ids);
+
+        let ids = index.range(Bound::Excluded(IndexKey::Integer(3)), Bound::Unbounded);
+        assert_eq!(vec![PathBuf::from("d")], ids);
+
+        let ids = index.range(Bound::Included(IndexKey::Integer(4)), Bound::Included(IndexKey::Integer(1)));
+        assert!(ids.is_empty());
+    }
+
+    #[test]
+    fn test_update_remove_rename() {
+        let mut index = index();
+
+        let header = toml_from_str("[test]\nvalue = 5").unwrap();
+        assert!(index.update(&PathBuf::from("a"), &header).unwrap());
+        assert!(!index.update(&PathBuf::from("a"), &header).unwrap());
+        assert!(index.find(&IndexKey::Integer(1)).is_empty());
+        assert_eq!(vec![PathBuf::from("a")], index.find(&IndexKey::Integer(5)));
+
+        assert!(index.rename(&PathBuf::from("a"), &PathBuf::from("f")));
+        assert_eq!(vec![PathBuf::from("f")], index.find(&IndexKey::Integer(5)));
+
+        assert!(index.remove(&PathBuf::from("f")));
+        assert!(!index.remove(&PathBuf::from("f")));
+        assert!(index.find(&IndexKey::Integer(5)).is_empty());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let backend = InMemoryFileAbstraction::new();
+        let base    = PathBuf::from("/");
+
+        assert!(HeaderIndex::load(&backend, &base, "test.value").unwrap().is_none());
+        index().save(&backend, &base).unwrap();
+
+        let loaded = HeaderIndex::load(&backend, &base, "test.value").unwrap().unwrap();
+        assert_eq!(vec![PathBuf::from("c"), PathBuf::from("d")], loaded.find(&IndexKey::Integer(3)));
+        assert_eq!(loaded.ids.len(), 4);
+    }
+
+    #[test]
+    fn test_reload() {
+        let backend = InMemoryFileAbstraction::new();
+        let base    = PathBuf::from("/");
+
+        let mut stale = HeaderIndex::new(String::from("test.value"));
+        stale.reload(&backend, &base).unwrap();
+        assert!(stale.find(&IndexKey::Integer(3)).is_empty());
+
+        index().save(&backend, &base).unwrap();
+        stale.reload(&backend, &base).unwrap();
+        assert_eq!(vec![PathBuf::from("c"), PathBuf::from("d")], stale.find(&IndexKey::Integer(3)));
+    }
+
+}
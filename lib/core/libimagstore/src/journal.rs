@@ -0,0 +1,292 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The write-ahead journal used by `Store::transaction()`
+//!
+//! Before the entries of a transaction are written, all of them are written into the journal,
+//! which itself lives in the store (at `<store>/.journal`) and is written through the store
+//! backend. The journal is removed once all entries were written.
+//!
+//! If the journal is found when opening the store, the last transaction did not finish. If the
+//! journal can be read completely, it is replayed (entries which cannot be written are reported
+//! and skipped). Otherwise, writing the journal itself was interrupted, no entry was touched yet
+//! and the journal is simply removed (the transaction is rolled back).
+
+use std::path::PathBuf;
+use std::collections::BTreeMap;
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use toml_query::insert::TomlValueInsertExt;
+
+use libimagerror::trace::trace_error;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use file_abstraction::FileAbstraction;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The id of the journal, relative to the store path
+const JOURNAL_ID : &'static str = ".journal";
+
+#[derive(Debug)]
+pub struct Journal(Vec<Entry>);
+
+impl Journal {
+
+    pub fn new(entries: Vec<Entry>) -> Journal {
+        Journal(entries)
+    }
+
+    fn id(store_location: &PathBuf) -> Result<StoreId> {
+        StoreId::new(Some(store_location.clone()), PathBuf::from(JOURNAL_ID))
+    }
+
+    /// Write the journal into the store at `store_location`
+    pub fn write(&self, backend: &FileAbstraction, store_location: &PathBuf) -> Result<()> {
+        let id        = Journal::id(store_location)?;
+        let mut entry = Entry::new(id.clone());
+
+        let entries = self.0
+            .iter()
+            .map(|e| {
+                let mut tbl = BTreeMap::new();
+                let local   = e.get_location().clone().without_base().to_str()?;
+                tbl.insert(String::from("id"), Value::String(local));
+                tbl.insert(String::from("entry"), Value::String(e.to_str()));
+                Ok(Value::Table(tbl))
+            })
+            .collect::<Result<Vec<Value>>>()?;
+
+        {
+            let header = entry.get_header_mut();
+            let _ = header.insert("journal.count", Value::Integer(self.0.len() as i64))?;
+            let _ = header.insert("journal.entries", Value::Array(entries))?;
+        }
+
+        debug!("Writing journal with {} entries", self.0.len());
+        backend
+            .new_instance(id.into_pathbuf()?)
+            .write_file_content(&entry)
+            .chain_err(|| SEK::JournalError)
+    }
+
+    /// Read the journal from the store at `store_location`, if there is one
+    ///
+    /// Returns `Ok(None)` if there is no journal or if it could not be read completely.
+    pub fn read(backend: &FileAbstraction, store_location: &PathBuf) -> Result<Option<Journal>> {
+        let id = Journal::id(store_location)?;
+        let pb = id.clone().into_pathbuf()?;
+
+        if !backend.exists(&pb)? {
+            return Ok(None);
+        }
+
+        let entry = match backend.new_instance(pb).get_file_content(id) {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!("Journal cannot be parsed, assuming it was not completely written: {:?}", e);
+                return Ok(None);
+            },
+        };
+
+        let count = match entry.get_header().read("journal.count")? {
+            Some(&Value::Integer(i)) => i as usize,
+            _ => return Ok(None),
+        };
+
+        let entries = match entry.get_header().read("journal.entries")? {
+            Some(&Value::Array(ref entries)) => entries.clone(),
+            _ => return Ok(None),
+        };
+
+        if entries.len() != count {
+            debug!("Journal contains {} of {} entries", entries.len(), count);
+            return Ok(None);
+        }
+
+        entries
+            .into_iter()
+            .map(|value| {
+                let id = match value.read("id")? {
+                    Some(&Value::String(ref s)) => s.clone(),
+                    _ => return Err(SE::from_kind(SEK::JournalError)),
+                };
+                let text = match value.read("entry")? {
+                    Some(&Value::String(ref s)) => s.clone(),
+                    _ => return Err(SE::from_kind(SEK::JournalError)),
+                };
+                let id = StoreId::new(Some(store_location.clone()), PathBuf::from(id))?;
+
+                Entry::from_str(id, &text)
+            })
+            .collect::<Result<Vec<Entry>>>()
+            .map(|entries| Some(Journal(entries)))
+            .chain_err(|| SEK::JournalError)
+    }
+
+    /// Write all entries of the journal through the backend
+    ///
+    /// Entries which cannot be written (for example because they lie on a read-only mount) are
+    /// reported and skipped, so they do not make the store unopenable. Returns the ids of the
+    /// skipped entries.
+    pub fn replay(&self, backend: &FileAbstraction) -> Result<Vec<StoreId>> {
+        let mut skipped = vec![];
+        for entry in self.0.iter() {
+            debug!("Replaying journal: {}", entry.get_location());
+            let pb  = entry.get_location().clone().into_pathbuf()?;
+            let res = backend
+                .new_instance(pb)
+                .write_file_content(entry)
+                .chain_err(|| SEK::JournalError);
+
+            if let Err(e) = res {
+                error!("Could not replay {} from the journal, skipping it", entry.get_location());
+                trace_error(&e);
+                skipped.push(entry.get_location().clone());
+            }
+        }
+        Ok(skipped)
+    }
+
+    /// Remove the journal from the store at `store_location`
+    pub fn remove(backend: &FileAbstraction, store_location: &PathBuf) -> Result<()> {
+        let pb = Journal::id(store_location)?.into_pathbuf()?;
+
+        if backend.exists(&pb)? {
+            backend.remove_file(&pb).chain_err(|| SEK::JournalError)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Replay or roll back an unfinished transaction in the store at `store_location`
+    ///
+    /// Returns whether a transaction was replayed.
+    pub fn recover(backend: &FileAbstraction, store_location: &PathBuf) -> Result<bool> {
+        let replayed = match Journal::read(backend, store_location)? {
+            Some(journal) => {
+                info!("Replaying unfinished transaction ({} entries)", journal.0.len());
+                let _ = journal.replay(backend)?;
+                true
+            },
+            None => {
+                debug!("No complete journal found, nothing to replay");
+                false
+            },
+        };
+
+        Journal::remove(backend, store_location).map(|_| replayed)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::Journal;
+    use file_abstraction::FileAbstraction;
+    use file_abstraction::InMemoryFileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn entry(id: &str, content: &str) -> Entry {
+        let id        = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id);
+        *entry.get_content_mut() = String::from(content);
+        entry
+    }
+
+    #[test]
+    fn test_journal_write_read_replay() {
+        let backend = InMemoryFileAbstraction::new();
+        let base    = PathBuf::from("/");
+
+        Journal::new(vec![entry("a", "A"), entry("b", "B")]).write(&backend, &base).unwrap();
+
+        let journal = Journal::read(&backend, &base).unwrap();
+        assert!(journal.is_some());
+        let journal = journal.unwrap();
+        assert_eq!(2, journal.0.len());
+
+        assert!(journal.replay(&backend).unwrap().is_empty());
+        Journal::remove(&backend, &base).unwrap();
+
+        assert!(!backend.exists(&PathBuf::from("/.journal")).unwrap());
+        assert!(backend.exists(&PathBuf::from("/a")).unwrap());
+        assert!(backend.exists(&PathBuf::from("/b")).unwrap());
+
+        let a = backend
+            .new_instance(PathBuf::from("/a"))
+            .get_file_content(StoreId::new_baseless(PathBuf::from("a")).unwrap())
+            .unwrap();
+        assert_eq!("A", *a.get_content());
+    }
+
+    #[test]
+    fn test_journal_replay_skips_unwritable_entries() {
+        use file_abstraction::Mount;
+        use file_abstraction::MountFileAbstraction;
+
+        let team    = Mount::new(String::from("team"),
+                                 PathBuf::from("/team-store"),
+                                 Box::new(InMemoryFileAbstraction::new()),
+                                 true).unwrap();
+        let backend = MountFileAbstraction::new(Box::new(InMemoryFileAbstraction::new()),
+                                                PathBuf::from("/"),
+                                                vec![team]);
+        let base    = PathBuf::from("/");
+
+        Journal::new(vec![entry("team/a", "A"), entry("b", "B")]).write(&backend, &base).unwrap();
+        assert!(Journal::recover(&backend, &base).unwrap());
+
+        assert!(!backend.exists(&PathBuf::from("/.journal")).unwrap());
+        assert!(!backend.exists(&PathBuf::from("/team/a")).unwrap());
+        assert!(backend.exists(&PathBuf::from("/b")).unwrap());
+    }
+
+    #[test]
+    fn test_journal_incomplete_is_rolled_back() {
+        let backend = InMemoryFileAbstraction::new();
+        let base    = PathBuf::from("/");
+
+        let mut journal = entry(".journal", "");
+        {
+            use toml::Value;
+            use toml_query::insert::TomlValueInsertExt;
+
+            let header = journal.get_header_mut();
+            let _ = header.insert("journal.count", Value::Integer(2)).unwrap();
+            let _ = header.insert("journal.entries", Value::Array(vec![])).unwrap();
+        }
+        backend
+            .new_instance(PathBuf::from("/.journal"))
+            .write_file_content(&journal)
+            .unwrap();
+
+        assert!(!Journal::recover(&backend, &base).unwrap());
+
+        assert!(!backend.exists(&PathBuf::from("/.journal")).unwrap());
+        assert!(!backend.exists(&PathBuf::from("/a")).unwrap());
+    }
+
+}
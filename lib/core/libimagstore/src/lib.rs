@@ -17,7 +17,7 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-#![recursion_limit="256"]
+#![recursion_limit="512"]
 
 #![deny(
     dead_code,
@@ -37,6 +37,7 @@
 
 #[macro_use] extern crate log;
 #[macro_use] extern crate version;
+extern crate chrono;
 extern crate glob;
 #[macro_use] extern crate lazy_static;
 extern crate regex;
@@ -45,10 +46,20 @@ extern crate toml;
 extern crate semver;
 extern crate walkdir;
 #[macro_use] extern crate is_match;
+extern crate serde;
 extern crate serde_json;
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate error_chain;
 extern crate toml_query;
+#[cfg(feature = "git")] extern crate git2;
+#[cfg(feature = "sqlite")] extern crate rusqlite;
+#[cfg(feature = "fs-locking")] extern crate libc;
+#[cfg(feature = "encryption")] extern crate chacha20poly1305;
+#[cfg(feature = "encryption")] extern crate scrypt;
+#[cfg(feature = "encryption")] extern crate getrandom;
+#[cfg(feature = "encryption")] extern crate base64;
+#[cfg(feature = "yaml")] extern crate serde_yaml;
+#[cfg(feature = "cbor")] extern crate serde_cbor;
 
 extern crate libimagerror;
 extern crate libimagutil;
@@ -59,6 +70,14 @@ pub mod storeid;
 pub mod error;
 pub mod iter;
 pub mod store;
-mod configuration;
+pub mod hook;
+pub mod migration;
+pub mod schema;
+pub mod watch;
+pub mod configuration;
+mod journal;
+mod history;
+mod trash;
+mod index;
 pub mod file_abstraction;
 
@@ -0,0 +1,373 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Migrations of entries between versions of imag
+//!
+//! Each entry carries the version of imag it was written with in `imag.version`. If a module
+//! changes the layout of the headers it writes, it registers a `Migration` which upgrades entries
+//! written with an older version of imag to the new layout.
+//!
+//! A migration is applicable to an entry if the version of the entry is in the range
+//! `from..to` of the migration. After all applicable migrations were run, `imag.version` of the
+//! entry is set to the highest `to` version of these migrations, and the process is repeated
+//! until no migration is applicable anymore.
+//!
+//! Migrations are applied to _all_ entries, not only to the entries in the collection of the
+//! module which registered them: a module might write header sections into entries of other
+//! modules (like the links do). The module of a migration is only used to report it. Migrations
+//! of several modules for the same versions are applied together, so modules have to register
+//! their migrations for the version ranges in which their header layout changed.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Error as FMTError;
+use std::result::Result as RResult;
+
+use semver::Version;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use toml_query::set::TomlValueSetExt;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use store::Entry;
+use store::Result;
+use store::Store;
+use storeid::StoreId;
+
+/// The function which upgrades an entry
+pub type MigrationFn = fn(&mut Entry) -> Result<()>;
+
+/// A migration step of a module
+pub struct Migration {
+    module: &'static str,
+    from: Version,
+    to: Version,
+    migrate: MigrationFn,
+}
+
+impl Migration {
+
+    /// The name of the module which registered this migration
+    ///
+    /// This is only used to report the migration, it does not restrict the entries the migration
+    /// is applied to.
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    /// The oldest version this migration can upgrade
+    pub fn from(&self) -> &Version {
+        &self.from
+    }
+
+    /// The version this migration upgrades to
+    pub fn to(&self) -> &Version {
+        &self.to
+    }
+
+    fn is_applicable(&self, version: &Version) -> bool {
+        self.from <= *version && *version < self.to
+    }
+
+}
+
+impl Debug for Migration {
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
+        write!(fmt, "Migration({}: {} -> {})", self.module, self.from, self.to)
+    }
+}
+
+/// The migrations which were (or would be) applied to an entry
+#[derive(Debug)]
+pub struct MigrationReport<'a> {
+    pub id: StoreId,
+    pub migrations: Vec<&'a Migration>,
+}
+
+/// A registry of all known migrations
+#[derive(Debug)]
+pub struct MigrationRegistry {
+    migrations: Vec<Migration>,
+}
+
+impl MigrationRegistry {
+
+    pub fn new() -> MigrationRegistry {
+        MigrationRegistry { migrations: vec![] }
+    }
+
+    /// Register a migration of `module` from version `from` to version `to`
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - MigrationRegisterError(VersionError()) if one of the versions cannot be parsed
+    ///  - MigrationRegisterError() if `from` is not older than `to`
+    ///
+    pub fn register(&mut self, module: &'static str, from: &str, to: &str, migrate: MigrationFn)
+        -> Result<()>
+    {
+        let from = Version::parse(from)
+            .chain_err(|| SEK::VersionError)
+            .chain_err(|| SEK::MigrationRegisterError(String::from(module)))?;
+        let to   = Version::parse(to)
+            .chain_err(|| SEK::VersionError)
+            .chain_err(|| SEK::MigrationRegisterError(String::from(module)))?;
+
+        if from >= to {
+            return Err(SE::from_kind(SEK::MigrationRegisterError(String::from(module))));
+        }
+
+        debug!("Registering migration for {}: {} -> {}", module, from, to);
+        self.migrations.push(Migration {
+            module: module,
+            from: from,
+            to: to,
+            migrate: migrate,
+        });
+        Ok(())
+    }
+
+    /// Get the migrations which have to be applied to `entry`, in the order they would be applied
+    pub fn pending(&self, entry: &Entry) -> Result<Vec<&Migration>> {
+        let mut version = entry_version(entry)?;
+        let mut pending = vec![];
+
+        while let Some((step, next_version)) = self.next_step(&version) {
+            pending.extend(step);
+            version = next_version;
+        }
+
+        Ok(pending)
+    }
+
+    /// Check whether `entry` has to be migrated
+    pub fn needs_migration(&self, entry: &Entry) -> Result<bool> {
+        entry_version(entry).map(|v| self.migrations.iter().any(|m| m.is_applicable(&v)))
+    }
+
+    /// Apply all pending migrations to `entry`
+    ///
+    /// Returns the migrations which were applied.
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - MigrationError(_) if a migration failed. The entry might be partially migrated then.
+    ///
+    pub fn migrate(&self, entry: &mut Entry) -> Result<Vec<&Migration>> {
+        let mut version = entry_version(entry)?;
+        let mut applied = vec![];
+
+        while let Some((step, next_version)) = self.next_step(&version) {
+            for migration in step {
+                debug!("Applying {:?} to {}", migration, entry.get_location());
+                (migration.migrate)(entry)
+                    .chain_err(|| SEK::MigrationError(String::from(migration.module)))?;
+                applied.push(migration);
+            }
+
+            let _ = entry
+                .get_header_mut()
+                .set("imag.version", Value::String(next_version.to_string()))?;
+            version = next_version;
+        }
+
+        Ok(applied)
+    }
+
+    /// Migrate all entries in `store`
+    ///
+    /// If `dry_run` is true, the entries are not altered, but the migrations which would be
+    /// applied are reported. Entries which do not need to be migrated are not reported.
+    ///
+    /// Only the entries which have to be migrated are retrieved (and thereby locked), all other
+    /// entries are only read.
+    pub fn migrate_store<'a>(&'a self, store: &Store, dry_run: bool)
+        -> Result<Vec<MigrationReport<'a>>>
+    {
+        let mut reports = vec![];
+
+        for id in store.entries()? {
+            let copy = store.get_copy(id.clone())?;
+            if !self.needs_migration(&copy)? {
+                continue;
+            }
+
+            let migrations = if dry_run {
+                self.pending(&copy)?
+            } else {
+                let mut entry = store.retrieve(id.clone())?;
+                let applied   = self.migrate(&mut entry)?;
+                let _       = store.update(&mut entry)?;
+                applied
+            };
+
+            if !migrations.is_empty() {
+                reports.push(MigrationReport { id: id, migrations: migrations });
+            }
+        }
+
+        Ok(reports)
+    }
+
+    /// Find all migrations applicable to `version` and the version they migrate to
+    fn next_step(&self, version: &Version) -> Option<(Vec<&Migration>, Version)> {
+        let mut step = self.migrations
+            .iter()
+            .filter(|m| m.is_applicable(version))
+            .collect::<Vec<&Migration>>();
+
+        step.sort_by(|a, b| a.to.cmp(&b.to));
+
+        step.last()
+            .map(|m| m.to.clone())
+            .map(|next_version| (step, next_version))
+    }
+
+}
+
+fn entry_version(entry: &Entry) -> Result<Version> {
+    match entry.get_header().read("imag.version")? {
+        Some(&Value::String(ref s)) => Version::parse(s).chain_err(|| SEK::VersionError),
+        Some(_) => Err(SE::from_kind(SEK::ConfigTypeError("imag.version", "String"))),
+        None    => Err(SE::from_kind(SEK::ConfigKeyMissingError("imag.version"))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::read::TomlValueReadExt;
+    use toml_query::insert::TomlValueInsertExt;
+
+    use super::MigrationRegistry;
+    use store::Entry;
+    use store::Result;
+    use storeid::StoreId;
+
+    fn entry_with_version(version: &str) -> Entry {
+        let id = StoreId::new_baseless(PathBuf::from("test/entry")).unwrap();
+        Entry::from_str(id, &format!("---\n[imag]\nversion = \"{}\"\n---\n", version)).unwrap()
+    }
+
+    fn add_a(e: &mut Entry) -> Result<()> {
+        e.get_header_mut().insert("test.a", Value::Boolean(true)).map(|_| ()).map_err(From::from)
+    }
+
+    fn add_b(e: &mut Entry) -> Result<()> {
+        e.get_header_mut().insert("test.b", Value::Boolean(true)).map(|_| ()).map_err(From::from)
+    }
+
+    fn registry() -> MigrationRegistry {
+        let mut registry = MigrationRegistry::new();
+        registry.register("test", "0.1.0", "0.2.0", add_a).unwrap();
+        registry.register("test", "0.2.0", "0.3.0", add_b).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_register_invalid_range() {
+        let mut registry = MigrationRegistry::new();
+        assert!(registry.register("test", "0.2.0", "0.1.0", add_a).is_err());
+        assert!(registry.register("test", "foo", "0.1.0", add_a).is_err());
+    }
+
+    #[test]
+    fn test_pending_chains_migrations() {
+        let registry = registry();
+
+        assert_eq!(2, registry.pending(&entry_with_version("0.1.5")).unwrap().len());
+        assert_eq!(1, registry.pending(&entry_with_version("0.2.0")).unwrap().len());
+        assert_eq!(0, registry.pending(&entry_with_version("0.3.0")).unwrap().len());
+        assert_eq!(0, registry.pending(&entry_with_version("0.0.1")).unwrap().len());
+    }
+
+    #[test]
+    fn test_migrate_sets_version() {
+        let registry  = registry();
+        let mut entry = entry_with_version("0.1.0");
+
+        assert!(registry.needs_migration(&entry).unwrap());
+        assert_eq!(2, registry.migrate(&mut entry).unwrap().len());
+        assert!(!registry.needs_migration(&entry).unwrap());
+
+        let header = entry.get_header();
+        assert_eq!(Some(&Value::Boolean(true)), header.read("test.a").unwrap());
+        assert_eq!(Some(&Value::Boolean(true)), header.read("test.b").unwrap());
+        assert_eq!(Some(&Value::String(String::from("0.3.0"))), header.read("imag.version").unwrap());
+    }
+
+    #[test]
+    fn test_migrations_of_several_modules() {
+        fn add_c(e: &mut Entry) -> Result<()> {
+            e.get_header_mut().insert("other.c", Value::Boolean(true)).map(|_| ()).map_err(From::from)
+        }
+
+        let mut registry = registry();
+        registry.register("other", "0.2.0", "0.3.0", add_c).unwrap();
+
+        let mut entry = entry_with_version("0.1.0");
+        let modules   = registry
+            .pending(&entry)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.module())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["test", "test", "other"], modules);
+
+        // The migration of "other" is applied to an entry which is not in its collection
+        assert_eq!(3, registry.migrate(&mut entry).unwrap().len());
+
+        let header = entry.get_header();
+        assert_eq!(Some(&Value::Boolean(true)), header.read("test.a").unwrap());
+        assert_eq!(Some(&Value::Boolean(true)), header.read("test.b").unwrap());
+        assert_eq!(Some(&Value::Boolean(true)), header.read("other.c").unwrap());
+        assert_eq!(Some(&Value::String(String::from("0.3.0"))), header.read("imag.version").unwrap());
+    }
+
+    #[test]
+    fn test_migrate_store_retrieves_only_outdated_entries() {
+        use store::Store;
+        use store::Entry as StoreEntry;
+        use file_abstraction::InMemoryFileAbstraction;
+
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        let store   = Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap();
+
+        {
+            let mut old = store.create(PathBuf::from("test/old")).unwrap();
+            *old.get_header_mut() = entry_with_version("0.1.0").get_header().clone();
+        }
+        let _current = store.create(PathBuf::from("test/current")).unwrap();
+
+        // "test/current" is borrowed, retrieving it would fail
+        let registry = registry();
+        let reports  = registry.migrate_store(&store, false).unwrap();
+        assert_eq!(1, reports.len());
+        assert_eq!(PathBuf::from("test/old"), *reports[0].id.local());
+
+        let old : StoreEntry = store.get_copy(PathBuf::from("test/old")).unwrap();
+        assert!(!registry.needs_migration(&old).unwrap());
+    }
+
+}
@@ -0,0 +1,593 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Schemas for the header sections of modules
+//!
+//! A module can register a `Schema` for the section it writes to the header of entries (for
+//! example `todo` or `habit`): the types of the fields, which fields are required, which values a
+//! field can have. `Entry::schema_violations()` checks the header of an entry against all
+//! registered schemas, so entries with typos in their header (for example because they were edited
+//! by hand) can be found. Violations are only reported, they do not keep an entry from being
+//! written.
+//!
+//! A schema only applies to entries which have its section, all other entries are not checked.
+//! The schemas are registered for the whole process with `register()`, like the migrations of a
+//! module, each module provides a function which registers its schemas.
+//!
+//! Schemas are not bound to headers: the crates also describe their sections of the configuration
+//! file with them, which `imag-config validate` checks the configuration against.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error as FMTError;
+use std::result::Result as RResult;
+use std::sync::RwLock;
+
+use regex::Regex;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use store::Result;
+
+lazy_static! {
+    static ref SCHEMAS: RwLock<BTreeMap<String, Schema>> = RwLock::new(BTreeMap::new());
+
+    static ref UUID_REGEX: Regex =
+        Regex::new("^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$")
+            .unwrap();
+
+    static ref DATE_REGEX: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+
+    static ref DATETIME_REGEX: Regex =
+        Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}(:\d{2}(\.\d+)?)?$").unwrap();
+}
+
+/// The type of a field in a header section
+#[derive(Debug, Clone)]
+pub enum FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Table,
+
+    /// An array, each element of which has the type
+    Array(Box<FieldType>),
+
+    /// A string which is one of the values
+    OneOf(Vec<&'static str>),
+
+    /// A string matching the regex
+    Matches(Regex),
+
+    /// A string which is a UUID (`550e8400-e29b-11d4-a716-446655440000`)
+    Uuid,
+
+    /// A string which is a date (`2018-01-31`)
+    Date,
+
+    /// A string which is a date and time (`2018-01-31T12:00:00`), as written by chrono
+    DateTime,
+
+    /// A value which has one of the types
+    AnyOf(Vec<FieldType>),
+}
+
+impl FieldType {
+
+    /// Check whether `value` has this type, returns what is wrong with it if not
+    fn check(&self, value: &Value) -> Option<ViolationKind> {
+        let wrong_type = || Some(ViolationKind::WrongType(self.to_string(), type_of(value)));
+
+        match (self, value) {
+            (&FieldType::String,  &Value::String(_))  => None,
+            (&FieldType::Integer, &Value::Integer(_)) => None,
+            (&FieldType::Float,   &Value::Float(_))   => None,
+            (&FieldType::Boolean, &Value::Boolean(_)) => None,
+            (&FieldType::Table,   &Value::Table(_))   => None,
+
+            (&FieldType::Array(ref inner), &Value::Array(ref elems)) => {
+                elems.iter().filter_map(|e| inner.check(e)).next()
+            },
+
+            (&FieldType::OneOf(ref values), &Value::String(ref s)) => if values.contains(&s.as_str()) {
+                None
+            } else {
+                Some(ViolationKind::NotOneOf(s.clone(), values.clone()))
+            },
+
+            (&FieldType::Matches(ref r), &Value::String(ref s)) => matches(r, s),
+            (&FieldType::Uuid,           &Value::String(ref s)) => matches(&UUID_REGEX, s),
+            (&FieldType::Date,           &Value::String(ref s)) => matches(&DATE_REGEX, s),
+            (&FieldType::DateTime,       &Value::String(ref s)) => matches(&DATETIME_REGEX, s),
+
+            (&FieldType::AnyOf(ref types), _) => if types.iter().any(|t| t.check(value).is_none()) {
+                None
+            } else {
+                wrong_type()
+            },
+
+            _ => wrong_type(),
+        }
+    }
+
+}
+
+impl Display for FieldType {
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
+        match *self {
+            FieldType::String        => write!(fmt, "String"),
+            FieldType::Integer       => write!(fmt, "Integer"),
+            FieldType::Float         => write!(fmt, "Float"),
+            FieldType::Boolean       => write!(fmt, "Boolean"),
+            FieldType::Table         => write!(fmt, "Table"),
+            FieldType::Array(ref t)  => write!(fmt, "Array of {}", t),
+            FieldType::OneOf(_)      => write!(fmt, "String"),
+            FieldType::Matches(_)    => write!(fmt, "String"),
+            FieldType::Uuid          => write!(fmt, "UUID"),
+            FieldType::Date          => write!(fmt, "Date"),
+            FieldType::DateTime      => write!(fmt, "DateTime"),
+            FieldType::AnyOf(ref types) => {
+                let types = types.iter().map(FieldType::to_string).collect::<Vec<_>>();
+                write!(fmt, "{}", types.join(" or "))
+            },
+        }
+    }
+}
+
+fn matches(r: &Regex, s: &str) -> Option<ViolationKind> {
+    if r.is_match(s) {
+        None
+    } else {
+        Some(ViolationKind::NoMatch(String::from(s), String::from(r.as_str())))
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match *value {
+        Value::String(_)   => "String",
+        Value::Integer(_)  => "Integer",
+        Value::Float(_)    => "Float",
+        Value::Boolean(_)  => "Boolean",
+        Value::Datetime(_) => "Datetime",
+        Value::Array(_)    => "Array",
+        Value::Table(_)    => "Table",
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    path: String,
+    ty: FieldType,
+    required: bool,
+}
+
+/// The schema of a header section
+///
+/// ```ignore
+/// Schema::new("todo")
+///     .required("uuid", FieldType::Uuid)
+///     .optional("status", FieldType::OneOf(vec!["pending", "done"]))
+/// ```
+///
+/// A `*` in the path of a field stands for each key of the table at that point, so
+/// `.optional("diaries.*.timed", FieldType::String)` applies to the `timed` field of each table
+/// in `diaries`. A required field is required in each of these tables.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    section: String,
+    fields: Vec<Field>,
+    deny_unknown: bool,
+}
+
+impl Schema {
+
+    pub fn new<S: Into<String>>(section: S) -> Schema {
+        Schema {
+            section: section.into(),
+            fields: vec![],
+            deny_unknown: false,
+        }
+    }
+
+    /// Fields in the section which are not in the schema are violations
+    ///
+    /// Values below a field of type `Table` are not checked.
+    pub fn deny_unknown_fields(mut self) -> Schema {
+        self.deny_unknown = true;
+        self
+    }
+
+    /// The field at `path` (relative to the section) must be present and have the type `ty`
+    pub fn required<S: Into<String>>(mut self, path: S, ty: FieldType) -> Schema {
+        self.fields.push(Field { path: path.into(), ty: ty, required: true });
+        self
+    }
+
+    /// The field at `path` (relative to the section) must have the type `ty`, if it is present
+    pub fn optional<S: Into<String>>(mut self, path: S, ty: FieldType) -> Schema {
+        self.fields.push(Field { path: path.into(), ty: ty, required: false });
+        self
+    }
+
+    /// The header section this schema is for
+    pub fn section(&self) -> &str {
+        &self.section
+    }
+
+    /// Check `header` against this schema
+    ///
+    /// Returns nothing if `header` does not have the section of this schema.
+    pub fn verify(&self, header: &Value) -> Vec<SchemaViolation> {
+        let section = match header.read(&self.section) {
+            Ok(Some(section)) => section,
+            Ok(None)          => return vec![],
+            Err(_)            => return vec![self.violation(None, ViolationKind::NotATable)],
+        };
+
+        if !is_match!(*section, Value::Table(_)) {
+            return vec![self.violation(None, ViolationKind::NotATable)];
+        }
+
+        let mut violations = self.fields
+            .iter()
+            .flat_map(|field| self.verify_field(section, field))
+            .collect::<Vec<_>>();
+
+        if self.deny_unknown {
+            self.find_unknown_fields(section, &mut vec![], &mut violations);
+        }
+
+        violations
+    }
+
+    fn verify_field(&self, section: &Value, field: &Field) -> Vec<SchemaViolation> {
+        // "a.*.b.c" is split into "a.*", which is expanded to the tables, and "b.c"
+        let (wildcard, rest) = match field.path.rfind('*') {
+            Some(i) if i + 1 == field.path.len() => (&field.path[..], ""),
+            Some(i) => (&field.path[..i + 1], &field.path[i + 2..]),
+            None    => ("", &field.path[..]),
+        };
+
+        expand(section, wildcard)
+            .into_iter()
+            .filter_map(|(prefix, value)| {
+                let path  = join_path(&prefix, rest);
+                let path  = &path[..];
+                let found = if rest.is_empty() { Ok(Some(value)) } else { value.read(rest) };
+
+                match found {
+                    Ok(Some(value)) => field.ty.check(value).map(|k| self.violation(Some(path), k)),
+                    Ok(None)        => if field.required {
+                        Some(self.violation(Some(path), ViolationKind::Missing(field.ty.to_string())))
+                    } else {
+                        None
+                    },
+                    Err(_) => Some(self.violation(Some(path), ViolationKind::NotATable)),
+                }
+            })
+            .collect()
+    }
+
+    fn find_unknown_fields(&self, value: &Value, path: &mut Vec<String>, out: &mut Vec<SchemaViolation>) {
+        let table = match *value {
+            Value::Table(ref table) => table,
+            _ => return,
+        };
+
+        for (key, value) in table {
+            path.push(key.clone());
+
+            let is_field = self.fields.iter().any(|f| path_matches(&f.path, path, false));
+            let is_above = self.fields.iter().any(|f| path_matches(&f.path, path, true));
+
+            if is_field {
+                // checked by verify_field()
+            } else if is_above && is_match!(*value, Value::Table(_)) {
+                self.find_unknown_fields(value, path, out);
+            } else {
+                out.push(self.violation(Some(&path.join(".")[..]), ViolationKind::Unknown));
+            }
+
+            let _ = path.pop();
+        }
+    }
+
+    fn violation(&self, path: Option<&str>, kind: ViolationKind) -> SchemaViolation {
+        let path = match path {
+            Some(path) => format!("{}.{}", self.section, path),
+            None       => self.section.clone(),
+        };
+
+        SchemaViolation { path: path, kind: kind }
+    }
+
+}
+
+/// Get the values at `path` in `value`, where a `*` stands for each key of the table at that point,
+/// together with their paths
+fn expand<'a>(value: &'a Value, path: &str) -> Vec<(String, &'a Value)> {
+    if path.is_empty() {
+        return vec![(String::new(), value)];
+    }
+
+    let (key, rest) = match path.find('.') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None    => (path, ""),
+    };
+
+    let table = match *value {
+        Value::Table(ref table) => table,
+        _ => return vec![],
+    };
+
+    table
+        .iter()
+        .filter(|&(k, _)| key == "*" || k == key)
+        .flat_map(|(k, v)| {
+            expand(v, rest)
+                .into_iter()
+                .map(move |(p, v)| (join_path(k, &p), v))
+        })
+        .collect()
+}
+
+/// Check whether the field `pattern` is at `path` (or below it, if `below`)
+fn path_matches(pattern: &str, path: &[String], below: bool) -> bool {
+    let pattern = pattern.split('.').collect::<Vec<_>>();
+    let length_ok = if below { pattern.len() > path.len() } else { pattern.len() == path.len() };
+
+    length_ok && pattern.iter().zip(path.iter()).all(|(p, k)| *p == "*" || p == k)
+}
+
+fn join_path(a: &str, b: &str) -> String {
+    match (a.is_empty(), b.is_empty()) {
+        (true, _)      => String::from(b),
+        (_, true)      => String::from(a),
+        (false, false) => format!("{}.{}", a, b),
+    }
+}
+
+/// What is wrong with a field of a header section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The field is missing, it should have the type
+    Missing(String),
+
+    /// The field (or a table on the way to it) is not a table
+    NotATable,
+
+    /// The field should have the first type, but has the second one
+    WrongType(String, &'static str),
+
+    /// The value is not one of the allowed values
+    NotOneOf(String, Vec<&'static str>),
+
+    /// The value does not match the pattern
+    NoMatch(String, String),
+
+    /// The field is not in the schema, which denies unknown fields
+    Unknown,
+}
+
+/// A field of a header section which does not conform to the schema of the section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    path: String,
+    kind: ViolationKind,
+}
+
+impl SchemaViolation {
+
+    /// The path of the field in the header
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
+        match self.kind {
+            ViolationKind::Missing(ref ty) => {
+                write!(fmt, "'{}' is missing, should be {}", self.path, ty)
+            },
+            ViolationKind::NotATable => write!(fmt, "'{}' is not a table", self.path),
+            ViolationKind::WrongType(ref expected, found) => {
+                write!(fmt, "'{}' should be {}, is {}", self.path, expected, found)
+            },
+            ViolationKind::NotOneOf(ref value, ref values) => {
+                write!(fmt, "'{}' is '{}', should be one of '{}'", self.path, value, values.join("', '"))
+            },
+            ViolationKind::NoMatch(ref value, ref pattern) => {
+                write!(fmt, "'{}' is '{}', which does not match '{}'", self.path, value, pattern)
+            },
+            ViolationKind::Unknown => write!(fmt, "'{}' is not a known field", self.path),
+        }
+    }
+}
+
+/// Register `schema` for its section, replacing the schema which was registered for the section
+/// before
+pub fn register(schema: Schema) -> Result<()> {
+    debug!("Registering schema for header section '{}'", schema.section());
+    SCHEMAS
+        .write()
+        .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+        .map(|mut schemas| {
+            let _ = schemas.insert(schema.section().to_owned(), schema);
+        })
+}
+
+/// Check `header` against all registered schemas
+pub fn verify_header(header: &Value) -> Result<Vec<SchemaViolation>> {
+    SCHEMAS
+        .read()
+        .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+        .map(|schemas| schemas.values().flat_map(|s| s.verify(header)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use toml::Value;
+
+    use super::*;
+
+    fn header(s: &str) -> Value {
+        ::toml::de::from_str(s).unwrap()
+    }
+
+    fn schema() -> Schema {
+        Schema::new("test")
+            .required("uuid", FieldType::Uuid)
+            .required("status", FieldType::OneOf(vec!["pending", "done"]))
+            .optional("due", FieldType::Date)
+            .optional("tags", FieldType::Array(Box::new(FieldType::String)))
+            .optional("nested.count", FieldType::Integer)
+    }
+
+    #[test]
+    fn test_valid_section() {
+        let h = header(r#"
+            [test]
+            uuid = "550e8400-e29b-11d4-a716-446655440000"
+            status = "done"
+            due = "2018-01-31"
+            tags = ["a", "b"]
+            nested = { count = 3 }
+        "#);
+
+        assert!(schema().verify(&h).is_empty());
+    }
+
+    #[test]
+    fn test_other_sections_are_not_checked() {
+        let h = header(r#"
+            [other]
+            uuid = 1
+        "#);
+
+        assert!(schema().verify(&h).is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let h = header(r#"
+            [test]
+            uuid = "not-a-uuid"
+            statsu = "done"
+            due = 20180131
+            tags = [1, 2]
+        "#);
+
+        let violations = schema().verify(&h);
+        let paths      = violations.iter().map(SchemaViolation::path).collect::<Vec<_>>();
+        assert_eq!(vec!["test.uuid", "test.status", "test.due", "test.tags"], paths);
+
+        assert!(is_match!(*violations[0].kind(), ViolationKind::NoMatch(_, _)));
+        assert_eq!(ViolationKind::Missing(String::from("String")), *violations[1].kind());
+        assert_eq!(ViolationKind::WrongType(String::from("Date"), "Integer"), *violations[2].kind());
+        assert_eq!(ViolationKind::WrongType(String::from("String"), "Integer"), *violations[3].kind());
+    }
+
+    #[test]
+    fn test_enum_violation() {
+        let h = header(r#"
+            [test]
+            uuid = "550e8400-e29b-11d4-a716-446655440000"
+            status = "dnoe"
+        "#);
+
+        let violations = schema().verify(&h);
+        assert_eq!(1, violations.len());
+        assert_eq!("'test.status' is 'dnoe', should be one of 'pending', 'done'",
+                   violations[0].to_string());
+    }
+
+    #[test]
+    fn test_section_not_a_table() {
+        let h = header(r#"
+            test = 1
+        "#);
+
+        assert_eq!(ViolationKind::NotATable, *schema().verify(&h)[0].kind());
+    }
+
+    #[test]
+    fn test_wildcard_fields() {
+        let schema = Schema::new("test")
+            .optional("diaries.*.timed", FieldType::OneOf(vec!["hourly", "minutely"]))
+            .required("diaries.*.name", FieldType::String)
+            .optional("aliases.*", FieldType::AnyOf(vec![
+                FieldType::String,
+                FieldType::Array(Box::new(FieldType::String)),
+            ]));
+
+        let h = header(r#"
+            [test.diaries.a]
+            timed = "hourly"
+            name = "a"
+
+            [test.diaries.b]
+            timed = "daily"
+
+            [test.aliases]
+            store = ["s", "st"]
+            tag = "t"
+            view = 1
+        "#);
+
+        let violations = schema.verify(&h);
+        let paths      = violations.iter().map(SchemaViolation::path).collect::<Vec<_>>();
+        assert_eq!(vec!["test.diaries.b.timed", "test.diaries.b.name", "test.aliases.view"], paths);
+        assert_eq!("'test.aliases.view' should be String or Array of String, is Integer",
+                   violations[2].to_string());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let schema = Schema::new("test")
+            .optional("level", FieldType::String)
+            .optional("modules.*.enabled", FieldType::Boolean)
+            .optional("hooks", FieldType::Table)
+            .deny_unknown_fields();
+
+        let h = header(r#"
+            [test]
+            levle = "debug"
+            hooks = { anything = { goes = 1 } }
+
+            [test.modules.libimagstore]
+            enabled = true
+            enabeld = false
+        "#);
+
+        let violations = schema.verify(&h);
+        let paths      = violations.iter().map(SchemaViolation::path).collect::<Vec<_>>();
+        assert_eq!(vec!["test.levle", "test.modules.libimagstore.enabeld"], paths);
+        assert!(violations.iter().all(|v| *v.kind() == ViolationKind::Unknown));
+    }
+}
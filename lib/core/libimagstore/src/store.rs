@@ -18,12 +18,19 @@
 //
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::ops::Drop;
+use std::path::Path;
 use std::path::PathBuf;
 use std::result::Result as RResult;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::sync::RwLock;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::io::Read;
 use std::ops::Deref;
 use std::ops::DerefMut;
@@ -31,27 +38,53 @@ use std::fmt::Formatter;
 use std::fmt::Debug;
 use std::fmt::Error as FMTError;
 
+use chrono::Local;
+use chrono::NaiveDateTime;
 use toml::Value;
-use glob::glob;
 use walkdir::WalkDir;
 use walkdir::Iter as WalkDirIter;
 use toml_query::read::TomlValueReadExt;
+use toml_query::insert::TomlValueInsertExt;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 
 use error::{StoreError as SE, StoreErrorKind as SEK};
 use error::ResultExt;
 use storeid::{IntoStoreId, StoreId, StoreIdIterator};
 use file_abstraction::FileAbstractionInstance;
+use file_abstraction::Drain;
+use file_abstraction::EntryLock;
+use file_abstraction::register_mounts;
+use hook::Hook;
+use hook::HookData;
+use hook::HookPosition;
+use hook::registry::HookRegistry;
+use journal::Journal;
+use configuration::MountConfig;
+use history;
+use trash;
+use schema;
+use schema::SchemaViolation;
+use index::HeaderIndex;
+use index::IndexKey;
+use watch::StoreEvent;
+use watch::StoreEventKind;
+use watch::Watcher;
 
 // We re-export the following things so tests can use them
 pub use file_abstraction::FileAbstraction;
 pub use file_abstraction::FSFileAbstraction;
 pub use file_abstraction::InMemoryFileAbstraction;
+pub use history::Revision;
+pub use trash::TrashedEntry;
+#[cfg(feature = "git")]
+pub use file_abstraction::GitFileAbstraction;
+#[cfg(feature = "sqlite")]
+pub use file_abstraction::SqliteFileAbstraction;
 
 use libimagerror::trace::trace_error;
 use libimagutil::debug_result::*;
 
-use self::glob_store_iter::*;
-
 /// The Result Type returned by any interaction with the store that could fail
 pub type Result<T> = RResult<T, SE>;
 
@@ -64,11 +97,19 @@ enum StoreEntryStatus {
 
 /// A store entry, depending on the option type it is either borrowed currently
 /// or not.
+///
+/// While borrowed, the entry holds the lock of the backend (if the backend locks entries), so
+/// other processes cannot borrow it as well.
 #[derive(Debug)]
 struct StoreEntry {
     id: StoreId,
     file: Box<FileAbstractionInstance>,
     status: StoreEntryStatus,
+    lock: Option<Box<EntryLock>>,
+
+    /// The fingerprint of the entry as it was in the backend when it was loaded or written by this
+    /// store, `None` if it did not exist
+    loaded: Option<u64>,
 }
 
 pub enum StoreObject {
@@ -109,6 +150,12 @@ impl Iterator for Walk {
         while let Some(something) = self.dirwalker.next() {
             debug!("[Walk] Processing next item: {:?}", something);
             match something {
+                // Store-internal data (like the entry locks or the transaction journal) lives in
+                // hidden pathes, these are neither entries nor collections
+                Ok(ref next) if is_hidden_path(&self.store_path, next.path()) => {
+                    debug!("Skipping hidden path...");
+                    continue;
+                },
                 Ok(next) => if next.file_type().is_dir() {
                     debug!("Found directory...");
                     return Some(StoreObject::Collection(next.path().to_path_buf()))
@@ -137,25 +184,47 @@ impl Iterator for Walk {
     }
 }
 
+/// Check whether `path` has a hidden component (one starting with a dot) below `base`
+pub(crate) fn is_hidden_path(base: &Path, path: &Path) -> bool {
+    path.strip_prefix(base)
+        .map(|p| {
+            p.components()
+                .filter_map(|c| c.as_os_str().to_str())
+                .any(|c| c.starts_with("."))
+        })
+        .unwrap_or(false)
+}
+
 impl StoreEntry {
 
     fn new(id: StoreId, backend: &Box<FileAbstraction>) -> Result<StoreEntry> {
         let pb = id.clone().into_pathbuf()?;
 
-        #[cfg(feature = "fs-lock")]
-        {
-            open_file(pb.clone())
-                .and_then(|f| f.lock_exclusive())
-                .chain_err(|| SEK::IoError)?;
-        }
-
         Ok(StoreEntry {
             id: id,
             file: backend.new_instance(pb),
             status: StoreEntryStatus::Present,
+            lock: None,
+            loaded: None,
         })
     }
 
+    /// Keep the lock of the entry in the backend, before it is borrowed
+    ///
+    /// The lock is taken with `Store::lock_in_backend()`. If the entry holds a lock already, the
+    /// new one is released right away.
+    fn attach_lock(&mut self, lock: Option<Box<EntryLock>>) {
+        if self.lock.is_none() {
+            self.lock = lock;
+        }
+    }
+
+    /// Mark the entry as not borrowed anymore and release the lock
+    fn release(&mut self) {
+        self.status = StoreEntryStatus::Present;
+        self.lock   = None;
+    }
+
     /// The entry is currently borrowed, meaning that some thread is currently
     /// mutating it
     fn is_borrowed(&self) -> bool {
@@ -164,39 +233,94 @@ impl StoreEntry {
 
     fn get_entry(&mut self) -> Result<Entry> {
         if !self.is_borrowed() {
-            self.file
-                .get_file_content(self.id.clone())
-                .or_else(|err| if is_match!(err.kind(), &SEK::FileNotFound) {
-                    Ok(Entry::new(self.id.clone()))
-                } else {
-                    Err(err)
-                })
+            let entry = self.read_backend()?;
+            self.loaded = entry.as_ref().map(fingerprint);
+            Ok(entry.unwrap_or_else(|| Entry::new(self.id.clone())))
         } else {
             Err(SE::from_kind(SEK::EntryAlreadyBorrowed(self.id.clone())))
         }
     }
 
+    /// Read the entry from the backend, `None` if it does not exist there
+    fn read_backend(&mut self) -> Result<Option<Entry>> {
+        match self.file.get_file_content(self.id.clone()) {
+            Ok(entry) => Ok(Some(entry)),
+            Err(ref err) if is_match!(err.kind(), &SEK::FileNotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Get the version in the backend if it was changed since this store loaded or wrote it
+    fn changed_in_backend(&mut self) -> Result<Option<Entry>> {
+        let current = self.read_backend()?;
+        if current.as_ref().map(fingerprint) == self.loaded {
+            Ok(None)
+        } else {
+            Ok(current)
+        }
+    }
+
+    /// Whether `entry` differs from the entry as it was loaded or written last
+    fn is_changed_by(&self, entry: &Entry) -> bool {
+        self.loaded != Some(fingerprint(entry))
+    }
+
     fn write_entry(&mut self, entry: &Entry) -> Result<()> {
         if self.is_borrowed() {
             assert_eq!(self.id, entry.location);
             self.file
                 .write_file_content(entry)
-                .map(|_| ())
+                .map(|_| self.loaded = Some(fingerprint(entry)))
         } else {
             Ok(())
         }
     }
 }
 
-#[cfg(feature = "fs-lock")]
-impl Drop for StoreEntry {
+/// The format of the timestamps in `imag.created` and `imag.modified`
+pub const TIMESTAMP_FORMAT : &'static str = "%Y-%m-%dT%H:%M:%S";
+
+/// The hidden directory where the changes which could not be written on drop because of a
+/// conflict are saved, see `FileLockEntry`
+pub const CONFLICTS_DIR : &'static str = ".conflicts";
+
+/// Set `imag.modified` of `entry` to now, and `imag.created` as well if the entry is new and does
+/// not have it yet
+fn stamp(entry: &mut Entry, is_new: bool) -> Result<()> {
+    let now = Value::String(Local::now().naive_local().format(TIMESTAMP_FORMAT).to_string());
+
+    if is_new && entry.get_header().read("imag.created")?.is_none() {
+        let _ = entry.get_header_mut().insert("imag.created", now.clone())?;
+    }
+
+    entry.get_header_mut()
+        .insert("imag.modified", now)
+        .map(|_| ())
+        .map_err(SE::from)
+}
+
+/// Verify `entry` before it is written
+///
+/// Only a broken `imag` section keeps the entry from being written. Schema violations are logged,
+/// as an entry which violates a schema has to be writable still (to fix it, for example).
+fn verify_before_write(entry: &Entry) -> Result<()> {
+    let _ = entry.get_header().verify()?;
 
-    fn drop(self) {
-        self.get_entry()
-            .and_then(|entry| open_file(entry.get_location().clone()))
-            .and_then(|f| f.unlock())
+    for violation in entry.schema_violations()? {
+        warn!("{}: {}", entry.get_location(), violation);
     }
 
+    Ok(())
+}
+
+/// A fingerprint of the entry, to notice whether it was changed in the backend
+fn fingerprint(entry: &Entry) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    entry.to_str().hash(&mut hasher);
+    hasher.finish()
 }
 
 
@@ -217,6 +341,27 @@ pub struct Store {
     ///
     /// This provides the filesystem-operation functions (or pretends to)
     backend: Box<FileAbstraction>,
+
+    /// The hooks which get executed before and after actions on entries
+    hooks: HookRegistry,
+
+    /// The indexes over header fields, by header path
+    indexes: RwLock<BTreeMap<String, HeaderIndex>>,
+
+    /// The channels of the `Watcher`s of this store
+    watchers: Mutex<Vec<Sender<StoreEvent>>>,
+
+    /// The number of previous revisions kept of each entry, none if zero
+    history_revisions: usize,
+
+    /// How long to wait for an entry which is locked by another process
+    lock_timeout: Option<Duration>,
+
+    /// Whether deleted entries are moved to the trash
+    trash_enabled: bool,
+
+    /// The collections which are encrypted by the backend
+    encrypted_collections: Vec<String>,
 }
 
 impl Store {
@@ -236,11 +381,101 @@ impl Store {
     ///
     /// - On success: Store object
     ///
+    /// The backend is selected via the `store.backend` configuration key, which defaults to the
+    /// filesystem backend. The SQLite backend uses a database file next to `location`, named like
+    /// the store directory with a ".sqlite" extension.
+    ///
+    /// If collections are listed in `store.encryption.collections`, the backend is wrapped in a
+    /// `CryptFileAbstraction` which encrypts these collections with the passphrase from the
+    /// `IMAG_STORE_PASSPHRASE` environment variable (requires the "encryption" feature).
+    /// Unencrypted entries in these collections are only read if
+    /// `store.encryption.allow-plaintext` is not false.
+    ///
+    /// If stores are configured in `store.mounts`, they are mounted into the store with a
+    /// `MountFileAbstraction`. Mounted stores always use the filesystem backend.
+    ///
     pub fn new(location: PathBuf, store_config: &Option<Value>) -> Result<Store> {
-        let backend = Box::new(FSFileAbstraction::new());
+        use configuration::*;
+
+        let backend : Box<FileAbstraction> = match config_store_backend(store_config)? {
+            StoreBackend::Filesystem => Box::new(FSFileAbstraction::new()),
+            #[cfg(feature = "git")]
+            StoreBackend::Git        => Box::new(GitFileAbstraction::new(location.clone())),
+            #[cfg(feature = "sqlite")]
+            StoreBackend::Sqlite     => {
+                let database = location.with_extension("sqlite");
+                Box::new(SqliteFileAbstraction::new(location.clone(), database))
+            },
+        };
+
+        let collections = config_encrypted_collections(store_config)?;
+        let backend     = if collections.is_empty() {
+            backend
+        } else {
+            let plaintext = config_encryption_allows_plaintext(store_config)?;
+            Store::encrypting_backend(&location, backend, collections, plaintext)?
+        };
+
+        let mounts  = config_mounts(store_config)?;
+        let backend = if mounts.is_empty() {
+            backend
+        } else {
+            Store::mounting_backend(&location, backend, mounts)?
+        };
+
         Store::new_with_backend(location, store_config, backend)
     }
 
+    /// Wrap `backend` in a `MountFileAbstraction` mounting the configured stores
+    ///
+    /// Relative mount pathes are relative to the store.
+    fn mounting_backend(location: &PathBuf, backend: Box<FileAbstraction>, mounts: Vec<MountConfig>)
+        -> Result<Box<FileAbstraction>>
+    {
+        use file_abstraction::Mount;
+        use file_abstraction::MountFileAbstraction;
+
+        let mounts = mounts
+            .into_iter()
+            .map(|mount| {
+                let path = location.join(&mount.path);
+                if !path.is_dir() {
+                    return Err(SE::from_kind(SEK::MountPathMissing(mount.prefix, path)));
+                }
+
+                debug!("Mounting {:?} at '{}'", path, mount.prefix);
+                Mount::new(mount.prefix, path, Box::new(FSFileAbstraction::new()), mount.read_only)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Box::new(MountFileAbstraction::new(backend, location.clone(), mounts)))
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encrypting_backend(location: &PathBuf,
+                          backend: Box<FileAbstraction>,
+                          collections: Vec<String>,
+                          allow_plaintext: bool)
+        -> Result<Box<FileAbstraction>>
+    {
+        use std::env;
+        use file_abstraction::CryptFileAbstraction;
+        use file_abstraction::PASSPHRASE_ENV_VAR;
+
+        let passphrase = env::var(PASSPHRASE_ENV_VAR)
+            .map_err(|_| SE::from_kind(SEK::MissingPassphrase(PASSPHRASE_ENV_VAR)))?;
+
+        CryptFileAbstraction::new(backend, location.clone(), &passphrase, collections, allow_plaintext)
+            .map(|crypt| Box::new(crypt) as Box<FileAbstraction>)
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn encrypting_backend(_: &PathBuf, _: Box<FileAbstraction>, _: Vec<String>, _: bool)
+        -> Result<Box<FileAbstraction>>
+    {
+        Err(SE::from_kind(SEK::UnknownBackend(String::from("encryption"))))
+    }
+
     /// Create a Store object as descripbed in `Store::new()` documentation, but with an alternative
     /// backend implementation.
     ///
@@ -267,12 +502,32 @@ impl Store {
             return Err(SE::from_kind(SEK::StorePathExists(location)));
         }
 
+        let hooks       = HookRegistry::new(store_config)?;
+        let index_paths = config_header_indexes(store_config)?;
+        let revisions   = config_history_revisions(store_config)?;
+        let lock_wait   = config_lock_timeout(store_config)?;
+        let trash       = config_trash_enabled(store_config)?;
+        let encrypted   = config_encrypted_collections(store_config)?;
+        let replayed    = Journal::recover(&*backend, &location)?;
+
+        let _ = register_mounts(&location, backend.mounts())?;
+
         let store = Store {
             location: location.clone(),
             entries: Arc::new(RwLock::new(HashMap::new())),
             backend: backend,
+            hooks: hooks,
+            indexes: RwLock::new(BTreeMap::new()),
+            watchers: Mutex::new(Vec::new()),
+            history_revisions: revisions,
+            lock_timeout: lock_wait,
+            trash_enabled: trash,
+            encrypted_collections: encrypted,
         };
 
+        // If a transaction was replayed, the indexes might be outdated
+        store.load_indexes(index_paths, replayed)?;
+
         debug!("Store building succeeded");
         debug!("------------------------");
         debug!("{:?}", store);
@@ -301,6 +556,10 @@ impl Store {
     /// to stdout, we need to be able to replace the in-memory backend with the real filesystem
     /// backend.
     ///
+    /// Only what the current backend yields in its `Drain` is moved, which is nothing for the
+    /// filesystem backend. To move the entries of such a store into another backend (or to
+    /// encrypt them with a `CryptFileAbstraction`), use `Store::migrate_backend()`.
+    ///
     pub fn reset_backend(&mut self, mut backend: Box<FileAbstraction>) -> Result<()> {
         self.backend
             .drain()
@@ -308,6 +567,64 @@ impl Store {
             .map(|_| self.backend = backend)
     }
 
+    /// Migrate the store to another backend
+    ///
+    /// In contrast to `Store::reset_backend()`, which only moves what the current backend yields
+    /// in its `Drain` (which is nothing for the filesystem backend), this function reads _all_
+    /// entries of the store and writes them to the new backend. This can be used to move a store
+    /// from the filesystem into a database backend and back out again.
+    ///
+    /// The files in the hidden directories of the store (the history, the trash, the header
+    /// indexes, the attachment blobs, ...) are migrated as well. Only the lock files and the
+    /// journal are not, as they only make sense for the backend they were written with.
+    ///
+    /// # Warning
+    ///
+    /// The same warnings as for `Store::reset_backend()` apply.
+    ///
+    pub fn migrate_backend(&mut self, mut backend: Box<FileAbstraction>) -> Result<()> {
+        let mut hm = HashMap::new();
+        for id in self.entries()? {
+            let entry = self.get_copy(id.clone())?;
+            let path  = id.with_base(self.path().clone()).into_pathbuf()?;
+            hm.insert(path, entry);
+        }
+
+        for id in self.migratable_hidden_ids()? {
+            let path = id.clone().with_base(self.path().clone()).into_pathbuf()?;
+            if let Some(entry) = self.get_hidden(id)? {
+                hm.insert(path, entry);
+            }
+        }
+
+        let _ = backend.fill(Drain::new(hm))?;
+
+        {
+            // The cached entries still point to the old backend
+            let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            for (id, se) in hsmap.iter_mut() {
+                se.file = backend.new_instance(id.clone().into_pathbuf()?);
+            }
+        }
+
+        self.backend = backend;
+        Ok(())
+    }
+
+    /// Register a hook which gets executed at `position`
+    ///
+    /// The hook gets configured from the `store.hooks.<name>` section of the configuration, if
+    /// present. See the documentation of the `hook` module.
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - HookRegisterError() if the configuration of the hook is invalid
+    ///
+    pub fn register_hook(&mut self, position: HookPosition, hook: Box<Hook>) -> Result<()> {
+        self.hooks.register(position, hook)
+    }
+
     /// Creates the Entry at the given location (inside the entry)
     ///
     /// # Return value
@@ -318,12 +635,41 @@ impl Store {
     ///  - Errors StoreId::into_storeid() might return
     ///  - CreateCallError(LockPoisoned()) if the internal lock is poisened.
     ///  - CreateCallError(EntryAlreadyExists()) if the entry exists already.
+    ///  - CreateCallError(EntryLocked()) if the entry is locked by another process.
+    ///  - CreateCallError(ReadOnlyMount()) if the entry would lie on a read-only mount.
+    ///  - CreateCallError(PreHookExecuteError()) if a pre-create hook aborted the action.
+    ///  - CreateCallError(PostHookExecuteError()) if a post-create hook failed.
     ///
     pub fn create<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
         let id = id.into_storeid()?.with_base(self.path().clone());
 
         debug!("Creating id: '{}'", id);
 
+        self.ensure_writable(&id).chain_err(|| SEK::CreateCallError)?;
+
+        self.hooks
+            .execute(self, HookPosition::PreCreate, &HookData::Id(&id))
+            .chain_err(|| SEK::CreateCallError)?;
+
+        // Waiting for another process must not block the internal cache, so the lock is taken
+        // before the cache is locked
+        let cached = self
+            .entries
+            .read()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+            .map(|hsmap| hsmap.contains_key(&id))
+            .chain_err(|| SEK::CreateCallError)?;
+        if cached {
+            debug!("Cannot create, internal cache already contains: '{}'", id);
+            return Err(SE::from_kind(SEK::EntryAlreadyExists(id.clone())))
+                       .chain_err(|| SEK::CreateCallError);
+        }
+        let lock = self.lock_in_backend(&id).chain_err(|| SEK::CreateCallError)?;
+
+        // Stamped before the entry is put into the cache, which must not keep the id if this fails
+        let mut entry = Entry::new(id.clone());
+        let _         = stamp(&mut entry, true).chain_err(|| SEK::CreateCallError)?;
+
         {
             let mut hsmap = self
                 .entries
@@ -339,6 +685,7 @@ impl Store {
             hsmap.insert(id.clone(), {
                 debug!("Creating: '{}'", id);
                 let mut se = StoreEntry::new(id.clone(), &self.backend)?;
+                se.attach_lock(lock);
                 se.status = StoreEntryStatus::Borrowed;
                 se
             });
@@ -346,7 +693,14 @@ impl Store {
 
         debug!("Constructing FileLockEntry: '{}'", id);
 
-        Ok(FileLockEntry::new(self, Entry::new(id)))
+        let fle = FileLockEntry::new(self, entry);
+        if let Err(e) = self.hooks.execute(self, HookPosition::PostCreate, &HookData::Entry(&fle)) {
+            // The entry was never handed out, so it must not be written
+            let _ = self.discard(fle, true)?;
+            return Err(e).chain_err(|| SEK::CreateCallError);
+        }
+
+        Ok(fle)
     }
 
     /// Borrow a given Entry. When the `FileLockEntry` is either `update`d or
@@ -362,25 +716,84 @@ impl Store {
     /// On error:
     ///  - Errors StoreId::into_storeid() might return
     ///  - RetrieveCallError(LockPoisoned()) if the internal lock is poisened.
+    ///  - RetrieveCallError(EntryLocked()) if the entry is locked by another process.
+    ///  - RetrieveCallError(PreHookExecuteError()) if a pre-retrieve hook aborted the action.
+    ///  - RetrieveCallError(PostHookExecuteError()) if a post-retrieve hook failed.
     ///
     pub fn retrieve<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
         let id = id.into_storeid()?.with_base(self.path().clone());
         debug!("Retrieving id: '{}'", id);
-        let entry = self
+
+        self.hooks
+            .execute(self, HookPosition::PreRetrieve, &HookData::Id(&id))
+            .chain_err(|| SEK::RetrieveCallError)?;
+
+        // Entries borrowed in this process are locked already. Waiting for another process must
+        // not block the internal cache, so the lock is taken before the cache is locked.
+        let borrowed = self
+            .entries
+            .read()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+            .map(|es| es.get(&id).map(|se| se.is_borrowed()).unwrap_or(false))
+            .chain_err(|| SEK::RetrieveCallError)?;
+        let lock = if borrowed {
+            None
+        } else {
+            self.lock_in_backend(&id).chain_err(|| SEK::RetrieveCallError)?
+        };
+
+        let (entry, was_borrowed) = self
             .entries
             .write()
             .map_err(|_| SE::from_kind(SEK::LockPoisoned))
             .and_then(|mut es| {
                 let new_se = StoreEntry::new(id.clone(), &self.backend)?;
                 let se = es.entry(id.clone()).or_insert(new_se);
+                let was_borrowed = se.is_borrowed();
+                if !was_borrowed {
+                    se.attach_lock(lock);
+                }
                 let entry = se.get_entry();
                 se.status = StoreEntryStatus::Borrowed;
-                entry
+                entry.map(|e| (e, was_borrowed))
             })
             .chain_err(|| SEK::RetrieveCallError)?;
 
         debug!("Constructing FileLockEntry: '{}'", id);
-        Ok(FileLockEntry::new(self, entry))
+        let fle = FileLockEntry::new(self, entry);
+        if let Err(e) = self.hooks.execute(self, HookPosition::PostRetrieve, &HookData::Entry(&fle)) {
+            // If the entry is borrowed elsewhere, the other borrow still needs the cache entry
+            let _ = self.discard(fle, !was_borrowed)?;
+            return Err(e).chain_err(|| SEK::RetrieveCallError);
+        }
+
+        Ok(fle)
+    }
+
+    /// Lock the entry `id` in the backend, waiting for other processes up to the lock timeout
+    ///
+    /// This must not be called while holding the lock on the internal cache.
+    fn lock_in_backend(&self, id: &StoreId) -> Result<Option<Box<EntryLock>>> {
+        self.backend.lock(&self.location, id, self.lock_timeout)
+    }
+
+    /// Give up a `FileLockEntry` which could not be handed out, without writing it
+    ///
+    /// If `forget` is true, the entry is removed from the internal cache as well, which releases
+    /// its lock.
+    fn discard<'a>(&'a self, mut fle: FileLockEntry<'a>, forget: bool) -> Result<()> {
+        debug!("Discarding: '{}'", fle.get_location());
+        fle.discarded = true;
+
+        if forget {
+            let _ = self
+                .entries
+                .write()
+                .map_err(|_| SE::from_kind(SEK::LockPoisoned))?
+                .remove(fle.get_location());
+        }
+
+        Ok(())
     }
 
     /// Get an entry from the store if it exists.
@@ -398,7 +811,7 @@ impl Store {
 
         debug!("Getting id: '{}'", id);
 
-        let exists = id.exists()? || self.entries
+        let exists = self.backend.exists(&id.clone().into_pathbuf()?)? || self.entries
             .read()
             .map(|map| map.contains_key(&id))
             .map_err(|_| SE::from_kind(SEK::LockPoisoned))
@@ -419,10 +832,7 @@ impl Store {
     /// On success: An iterator over all entries in the module
     ///
     /// On failure:
-    ///  - (if the glob or one of the intermediate fail)
-    ///  - RetrieveForModuleCallError(GlobError(EncodingError())) if the path string cannot be
-    ///    encoded
-    ///  - GRetrieveForModuleCallError(GlobError(lobError())) if the glob() failed.
+    ///  - RetrieveForModuleCallError(...) if the backend failed to list the entries
     ///
     pub fn retrieve_for_module(&self, mod_name: &str) -> Result<StoreIdIterator> {
         let mut path = self.path().clone();
@@ -430,25 +840,15 @@ impl Store {
 
         debug!("Retrieving for module: '{}'", mod_name);
 
-        path.to_str()
-            .ok_or(SE::from_kind(SEK::EncodingError))
-            .and_then(|path| {
-                let path = [ path, "/**/*" ].join("");
-                debug!("glob()ing with '{}'", path);
-                glob(&path[..]).map_err(From::from)
-            })
-            .and_then(|paths| {
-                GlobStoreIdIterator::new(paths, self.path().clone())
-                    .collect::<Result<Vec<_>>>()
-            })
-            .map(|v| StoreIdIterator::new(Box::new(v.into_iter())))
-            .chain_err(|| SEK::RetrieveForModuleCallError)
+        self.ids_below(path).chain_err(|| SEK::RetrieveForModuleCallError)
     }
 
     /// Walk the store tree for the module
     ///
     /// The difference between a `Walk` and a `StoreIdIterator` is that with a `Walk`, one can find
     /// "collections" (folders).
+    ///
+    /// Hidden pathes, which hold store-internal data, are skipped.
     pub fn walk<'a>(&'a self, mod_name: &str) -> Walk {
         debug!("Creating Walk object for {}", mod_name);
         Walk::new(self.path().clone(), mod_name)
@@ -458,9 +858,38 @@ impl Store {
     ///
     /// See `Store::_update()`.
     ///
+    /// If the entry was changed in the backend since it was loaded (for example by another
+    /// process), the entry is not written and an `EntryConflict` error is returned, which contains
+    /// both versions of the entry. This error is not wrapped in an `UpdateCallError`, so callers
+    /// can match on it and use `Store::update_resolved()` to write a merged version.
+    ///
     pub fn update<'a>(&'a self, entry: &mut FileLockEntry<'a>) -> Result<()> {
         debug!("Updating FileLockEntry at '{}'", entry.get_location());
-        self._update(entry, false).chain_err(|| SEK::UpdateCallError)
+        self._update(entry, false).map_err(|e| if is_match!(e.kind(), &SEK::EntryConflict(..)) {
+            e
+        } else {
+            SE::with_chain(e, SEK::UpdateCallError)
+        })
+    }
+
+    /// Write the `FileLockEntry` after resolving an `EntryConflict`
+    ///
+    /// `theirs` is the version of the entry from the `EntryConflict` error, which the caller merged
+    /// into `entry`. The entry is written if the backend still contains `theirs`, otherwise the
+    /// entry was changed again and another `EntryConflict` is returned.
+    ///
+    pub fn update_resolved<'a>(&'a self, entry: &mut FileLockEntry<'a>, theirs: &Entry) -> Result<()> {
+        debug!("Updating FileLockEntry at '{}' after conflict", entry.get_location());
+        {
+            let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
+                SE::from_kind(SEK::IdNotFound(entry.location.clone()))
+            })?;
+
+            se.loaded = Some(fingerprint(theirs));
+        }
+
+        self.update(entry)
     }
 
     /// Internal method to write to the filesystem store.
@@ -479,27 +908,96 @@ impl Store {
     ///  - IdNotFound() if the entry was not found in the stor
     ///  - Errors Entry::verify() might return
     ///  - Errors StoreEntry::write_entry() might return
+    ///  - EntryConflict() if the entry was changed in the backend since it was loaded
+    ///  - ReadOnlyMount() if the entry was changed, but lies on a read-only mount
+    ///  - PreHookExecuteError() if a pre-update hook aborted the action
+    ///  - PostHookExecuteError() if a post-update hook failed
+    ///
+    /// If the entry belongs to a running transaction, it is only staged in the transaction and
+    /// written when the transaction commits.
     ///
     fn _update<'a>(&'a self, entry: &mut FileLockEntry<'a>, modify_presence: bool) -> Result<()> {
-        let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+        if let Some(ref staging) = entry.staging {
+            let mut staging = staging.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            if staging.open {
+                debug!("Staging {} in transaction", entry.get_location());
+                let presence = staging
+                    .entries
+                    .get(&entry.location)
+                    .map(|&(_, p)| p)
+                    .unwrap_or(false) || modify_presence;
+                staging.entries.insert(entry.location.clone(), (entry.entry.clone(), presence));
+                return Ok(());
+            }
+        }
 
-        let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
-            SE::from_kind(SEK::IdNotFound(entry.location.clone()))
-        })?;
+        self.hooks.execute(self, HookPosition::PreUpdate, &HookData::Entry(&entry.entry))?;
 
-        assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
+        let written = {
+            let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
 
-        debug!("Verifying Entry");
-        entry.entry.verify()?;
+            let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
+                SE::from_kind(SEK::IdNotFound(entry.location.clone()))
+            })?;
 
-        debug!("Writing Entry");
-        se.write_entry(&entry.entry)?;
-        if modify_presence {
-            debug!("Modifying ppresence of {} -> Present", entry.get_location());
-            se.status = StoreEntryStatus::Present;
-        }
+            assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
 
-        Ok(())
+            debug!("Verifying Entry");
+            if let Err(e) = verify_before_write(&entry.entry) {
+                if modify_presence {
+                    se.release();
+                }
+                return Err(e);
+            }
+
+            // Unchanged entries are released without writing them, so an entry which was only
+            // read neither overwrites nor conflicts with changes someone else made meanwhile
+            let changed = se.is_changed_by(&entry.entry);
+
+            if changed {
+                if let Some(theirs) = se.changed_in_backend()? {
+                    debug!("Entry {} was changed in the backend", entry.get_location());
+                    if modify_presence {
+                        se.release();
+                    }
+
+                    let ours = Box::new(entry.entry.clone());
+                    return Err(SE::from_kind(SEK::EntryConflict(entry.location.clone(), ours, Box::new(theirs))));
+                }
+
+                if self.backend.is_read_only(&entry.location.clone().into_pathbuf()?)? {
+                    if modify_presence {
+                        se.release();
+                    }
+
+                    let path = entry.location.clone().into_pathbuf()?;
+                    return Err(SE::from_kind(SEK::ReadOnlyMount(path)));
+                }
+
+                let _ = stamp(&mut entry.entry, se.loaded.is_none())?;
+
+                let kind = self.update_event_kind(&entry.location)?;
+                self.record_revision(&entry.location, Some(&entry.entry))?;
+
+                debug!("Writing Entry");
+                se.write_entry(&entry.entry)?;
+                self.notify_watchers(kind, &entry.location);
+            } else {
+                debug!("Not writing unchanged Entry: {}", entry.get_location());
+            }
+
+            if modify_presence {
+                debug!("Modifying ppresence of {} -> Present", entry.get_location());
+                se.release();
+            }
+
+            changed
+        };
+
+        if written {
+            self.update_indexes(&entry.location, Some(entry.get_header()))?;
+        }
+        self.hooks.execute(self, HookPosition::PostUpdate, &HookData::Entry(&entry.entry))
     }
 
     /// Get a copy of a given entry, this cannot be used to mutate the one on disk
@@ -530,6 +1028,9 @@ impl Store {
 
     /// Delete an entry
     ///
+    /// Unless the trash is disabled in the configuration, the entry is moved to the trash and can be
+    /// restored with `Store::restore_deleted()`.
+    ///
     /// # Return value
     ///
     /// On success: ()
@@ -537,13 +1038,37 @@ impl Store {
     /// On error:
     ///  - DeleteCallError(LockPoisoned()) if the internal write lock cannot be aquierd.
     ///  - DeleteCallError(FileNotFound()) if the StoreId refers to a non-existing entry.
+    ///  - DeleteCallError(IdLocked()) if the entry is borrowed in this process.
+    ///  - DeleteCallError(EntryLocked()) if the entry is locked by another process.
     ///  - DeleteCallError(FileError()) if the internals failed to remove the file.
+    ///  - DeleteCallError(ReadOnlyMount()) if the entry lies on a read-only mount.
+    ///  - DeleteCallError(PreHookExecuteError()) if a pre-delete hook aborted the action.
+    ///  - DeleteCallError(PostHookExecuteError()) if a post-delete hook failed.
     ///
     pub fn delete<S: IntoStoreId>(&self, id: S) -> Result<()> {
         let id = id.into_storeid()?.with_base(self.path().clone());
 
         debug!("Deleting id: '{}'", id);
 
+        self.ensure_writable(&id).chain_err(|| SEK::DeleteCallError)?;
+
+        self.hooks
+            .execute(self, HookPosition::PreDelete, &HookData::Id(&id))
+            .chain_err(|| SEK::DeleteCallError)?;
+
+        // Like in `Store::retrieve()`, the lock is taken before the cache is locked. It is held
+        // until the file is removed, so no other process can use the entry meanwhile.
+        let borrowed = self
+            .entries
+            .read()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+            .map(|es| es.get(&id).map(|se| se.is_borrowed()).unwrap_or(false))
+            .chain_err(|| SEK::DeleteCallError)?;
+        if borrowed {
+            return Err(SE::from_kind(SEK::IdLocked)).chain_err(|| SEK::DeleteCallError);
+        }
+        let _lock = self.lock_in_backend(&id).chain_err(|| SEK::DeleteCallError)?;
+
         {
             let mut entries = self
                 .entries
@@ -561,12 +1086,19 @@ impl Store {
                     // StoreId::exists(), a PathBuf object gets allocated. So we simply get a
                     // PathBuf here, check whether it is there and if it is, we can re-use it to
                     // delete the filesystem file.
-                    let pb = id.into_pathbuf()?;
+                    let pb = id.clone().into_pathbuf()?;
 
-                    if pb.exists() {
+                    if self.backend.exists(&pb)? {
                         // looks like we're deleting a not-loaded file from the store.
                         debug!("Seems like {:?} is on the FS", pb);
-                        return self.backend.remove_file(&pb)
+                        let _ = self.record_revision(&id, None).chain_err(|| SEK::DeleteCallError)?;
+                        let _ = self.move_to_trash(&id).chain_err(|| SEK::DeleteCallError)?;
+                        let _ = self.backend.remove_file(&pb)?;
+                        self.notify_watchers(StoreEventKind::Removed, &id);
+                        let _ = self.update_indexes(&id, None).chain_err(|| SEK::DeleteCallError)?;
+                        return self.hooks
+                            .execute(self, HookPosition::PostDelete, &HookData::Id(&id))
+                            .chain_err(|| SEK::DeleteCallError)
                     } else {
                         debug!("Seems like {:?} is not even on the FS", pb);
                         return Err(SE::from_kind(SEK::FileNotFound))
@@ -578,6 +1110,9 @@ impl Store {
                 }
             }
 
+            let _ = self.record_revision(&id, None).chain_err(|| SEK::DeleteCallError)?;
+            let _ = self.move_to_trash(&id).chain_err(|| SEK::DeleteCallError)?;
+
             // remove the entry first, then the file
             entries.remove(&id);
             let pb = id.clone().with_base(self.path().clone()).into_pathbuf()?;
@@ -588,8 +1123,13 @@ impl Store {
                 .chain_err(|| SEK::DeleteCallError)?;
         }
 
+        self.notify_watchers(StoreEventKind::Removed, &id);
+        let _ = self.update_indexes(&id, None).chain_err(|| SEK::DeleteCallError)?;
+
         debug!("Deleted");
-        Ok(())
+        self.hooks
+            .execute(self, HookPosition::PostDelete, &HookData::Id(&id))
+            .chain_err(|| SEK::DeleteCallError)
     }
 
     /// Save a copy of the Entry in another place
@@ -622,6 +1162,11 @@ impl Store {
 
         let old_id = entry.get_location().clone();
 
+        self.ensure_writable(&new_id).chain_err(|| SEK::MoveCallError)?;
+        if remove_old {
+            self.ensure_writable(&old_id).chain_err(|| SEK::MoveCallError)?;
+        }
+
         let old_id_as_path = old_id.clone().with_base(self.path().clone()).into_pathbuf()?;
         let new_id_as_path = new_id.clone().with_base(self.path().clone()).into_pathbuf()?;
         self.backend
@@ -633,6 +1178,21 @@ impl Store {
                 Ok(())
             })
             .chain_err(|| SEK::FileError)
+            .chain_err(|| SEK::MoveCallError)?;
+
+        self.notify_watchers(StoreEventKind::Created, &new_id);
+        if remove_old {
+            self.notify_watchers(StoreEventKind::Removed, &old_id);
+            let _ = history::rename(&*self.backend, &self.location, &old_id, &new_id)
+                .chain_err(|| SEK::MoveCallError)?;
+        }
+
+        self.update_indexes(&new_id, Some(entry.get_header()))
+            .and_then(|_| if remove_old {
+                self.update_indexes(&old_id, None)
+            } else {
+                Ok(())
+            })
             .chain_err(|| SEK::MoveCallError)
     }
 
@@ -652,6 +1212,8 @@ impl Store {
     /// * If the new path already exists
     /// * If the about-to-be-moved entry does not exist
     /// * If the FS-operation failed
+    /// * If the old or the new path lies on a read-only mount
+    /// * If a pre-move hook aborted the action or a post-move hook failed
     ///
     /// # Warnings
     ///
@@ -675,6 +1237,13 @@ impl Store {
 
         debug!("Moving '{}' to '{}'", old_id, new_id);
 
+        self.ensure_writable(&old_id).chain_err(|| SEK::MoveByIdCallError)?;
+        self.ensure_writable(&new_id).chain_err(|| SEK::MoveByIdCallError)?;
+
+        self.hooks
+            .execute(self, HookPosition::PreMoveById, &HookData::Move(&old_id, &new_id))
+            .chain_err(|| SEK::MoveByIdCallError)?;
+
         {
             let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
 
@@ -703,7 +1272,7 @@ impl Store {
             let _ = self
                 .backend
                 .rename(&old_id_pb, &new_id_pb)
-                .chain_err(|| SEK::EntryRenameError(old_id_pb, new_id_pb))?;
+                .chain_err(|| SEK::EntryRenameError(old_id_pb, new_id_pb.clone()))?;
 
             debug!("Rename worked on filesystem");
 
@@ -712,46 +1281,872 @@ impl Store {
             assert!(hsmap
                     .remove(&old_id)
                     .and_then(|mut entry| {
-                        entry.id = new_id.clone();
+                        entry.id   = new_id.clone();
+                        entry.file = self.backend.new_instance(new_id_pb);
                         hsmap.insert(new_id.clone(), entry)
                     }).is_none())
         }
 
+        self.notify_watchers(StoreEventKind::Removed, &old_id);
+        self.notify_watchers(StoreEventKind::Created, &new_id);
+        self.rename_in_indexes(&old_id, &new_id).chain_err(|| SEK::MoveByIdCallError)?;
+        history::rename(&*self.backend, &self.location, &old_id, &new_id)
+            .chain_err(|| SEK::MoveByIdCallError)?;
+
         debug!("Moved");
-        Ok(())
+        self.hooks
+            .execute(self, HookPosition::PostMoveById, &HookData::Move(&old_id, &new_id))
+            .chain_err(|| SEK::MoveByIdCallError)
     }
 
-    /// Get _all_ entries in the store (by id as iterator)
-    pub fn entries(&self) -> Result<StoreIdIterator> {
-        self.backend
-            .pathes_recursively(self.path().clone())
-            .and_then(|iter| {
-                let mut elems = vec![];
-                for element in iter {
-                    let is_file = {
-                        let mut base = self.path().clone();
-                        base.push(element.clone());
-                        self.backend.is_file(&base)?
-                    };
+    /// Find all entries which have `value` at `header_path` in their header
+    ///
+    /// If the value in the header of an entry is an array, the entry is found by each of the
+    /// elements of the array.
+    ///
+    /// # Return value
+    ///
+    /// On success: An iterator over the ids of the entries
+    ///
+    /// On error:
+    ///  - HeaderIndexMissing() if `header_path` is not indexed (see `store.index` in the
+    ///    configuration)
+    ///  - HeaderIndexError() if `value` cannot be indexed
+    ///
+    pub fn find_by_header(&self, header_path: &str, value: &Value) -> Result<StoreIdIterator> {
+        let key = IndexKey::for_lookup(value)?;
+        self.with_index(header_path, |index| index.find(&key))
+    }
 
-                    if is_file {
-                        let sid = StoreId::from_full_path(self.path(), element)?;
-                        elems.push(sid);
-                    }
-                }
-                Ok(StoreIdIterator::new(Box::new(elems.into_iter())))
-            })
+    /// Find all entries which have a value in the range `from`..`to` at `header_path`
+    ///
+    /// Values of different types are ordered booleans first, then integers, then strings (which
+    /// includes datetimes).
+    ///
+    /// # Return value
+    ///
+    /// See `Store::find_by_header()`.
+    ///
+    pub fn find_by_header_range(&self, header_path: &str, from: Bound<&Value>, to: Bound<&Value>)
+        -> Result<StoreIdIterator>
+    {
+        fn to_key_bound(b: Bound<&Value>) -> Result<Bound<IndexKey>> {
+            match b {
+                Bound::Included(v) => IndexKey::for_lookup(v).map(Bound::Included),
+                Bound::Excluded(v) => IndexKey::for_lookup(v).map(Bound::Excluded),
+                Bound::Unbounded   => Ok(Bound::Unbounded),
+            }
+        }
 
+        let from = to_key_bound(from)?;
+        let to   = to_key_bound(to)?;
+        self.with_index(header_path, |index| index.range(from, to))
     }
 
-    /// Gets the path where this store is on the disk
-    pub fn path(&self) -> &PathBuf {
-        &self.location
-    }
+    fn with_index<F>(&self, header_path: &str, f: F) -> Result<StoreIdIterator>
+        where F: FnOnce(&HeaderIndex) -> Vec<PathBuf>
+    {
+        let ids = {
+            let mut indexes = self.indexes.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            let index       = indexes
+                .get_mut(header_path)
+                .ok_or_else(|| SE::from_kind(SEK::HeaderIndexMissing(String::from(header_path))))?;
+
+            // Another process might have changed the index since it was loaded
+            let _lock = self.lock_index(header_path)?;
+            index.reload(&*self.backend, &self.location)?;
+            f(index)
+        };
 
-}
+        ids.into_iter()
+            .map(|id| StoreId::new(Some(self.location.clone()), id))
+            .collect::<Result<Vec<StoreId>>>()
+            .map(|ids| StoreIdIterator::new(Box::new(ids.into_iter())))
+    }
 
-impl Debug for Store {
+    /// Load the indexes for `header_paths` from the backend, or build them if they do not exist
+    /// yet or `rebuild` is true
+    fn load_indexes(&self, header_paths: Vec<String>, rebuild: bool) -> Result<()> {
+        let mut loaded = BTreeMap::new();
+
+        for header_path in header_paths {
+            let _lock    = self.lock_index(&header_path)?;
+            let existing = if rebuild {
+                None
+            } else {
+                HeaderIndex::load(&*self.backend, &self.location, &header_path)?
+            };
+
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    info!("Building index for '{}'", header_path);
+                    let mut index = HeaderIndex::new(header_path.clone());
+                    for id in self.entries()? {
+                        let entry = self.get_copy(id)?;
+                        let local = entry.get_location().local().clone();
+                        let _     = index.update(&local, entry.get_header())?;
+                    }
+                    index.save(&*self.backend, &self.location)?;
+                    index
+                },
+            };
+
+            loaded.insert(header_path, index);
+        }
+
+        *self.indexes.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))? = loaded;
+        Ok(())
+    }
+
+    /// Update the indexes for the entry `id`, which has the header `header` now or was removed if
+    /// `header` is None
+    fn update_indexes(&self, id: &StoreId, header: Option<&Value>) -> Result<()> {
+        self.change_indexes(|index| match header {
+            Some(header) => index.update(id.local(), header),
+            None         => Ok(index.remove(id.local())),
+        })
+    }
+
+    /// Move the entry `old` to `new` in the indexes
+    fn rename_in_indexes(&self, old: &StoreId, new: &StoreId) -> Result<()> {
+        self.change_indexes(|index| Ok(index.rename(old.local(), new.local())))
+    }
+
+    /// Apply `f` to each index and write the indexes it changed
+    ///
+    /// Each index is reloaded from the backend while its lock is held, so changes other processes
+    /// made to the index since it was loaded are not overwritten.
+    fn change_indexes<F>(&self, mut f: F) -> Result<()>
+        where F: FnMut(&mut HeaderIndex) -> Result<bool>
+    {
+        let mut indexes = self.indexes.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        for (header_path, index) in indexes.iter_mut() {
+            let _lock = self.lock_index(header_path)?;
+            index.reload(&*self.backend, &self.location)?;
+
+            if f(index)? {
+                index.save(&*self.backend, &self.location)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lock the index for `header_path` in the backend, like an entry
+    fn lock_index(&self, header_path: &str) -> Result<Option<Box<EntryLock>>> {
+        HeaderIndex::storeid(&self.location, header_path)
+            .and_then(|id| self.lock_in_backend(&id))
+            .chain_err(|| SEK::HeaderIndexError)
+    }
+
+    /// Get _all_ entries in the store (by id as iterator)
+    pub fn entries(&self) -> Result<StoreIdIterator> {
+        let path = self.path().clone();
+        self.ids_below(path)
+    }
+
+    /// Get the ids of all entries which live below `basepath` in the backend
+    fn ids_below(&self, basepath: PathBuf) -> Result<StoreIdIterator> {
+        self.backend
+            .pathes_recursively(basepath.clone())
+            .and_then(|iter| {
+                let mut elems = vec![];
+                for element in iter {
+                    // Not all backends limit the pathes to the basepath
+                    if !element.starts_with(&basepath) {
+                        continue;
+                    }
+
+                    let is_file = {
+                        let mut base = self.path().clone();
+                        base.push(element.clone());
+                        self.backend.is_file(&base)?
+                    };
+
+                    if is_file {
+                        let sid = StoreId::from_full_path(self.path(), element)?;
+
+                        // Store-internal data (like the transaction journal or the header
+                        // indexes) lives in hidden pathes, these are not entries
+                        if !is_hidden_path(Path::new(""), sid.local()) {
+                            elems.push(sid);
+                        }
+                    }
+                }
+                Ok(StoreIdIterator::new(Box::new(elems.into_iter())))
+            })
+    }
+
+    /// Run `f` as a transaction on the store
+    ///
+    /// All `FileLockEntry` objects which are fetched through the `Transaction` object are not
+    /// written when they are updated or dropped, but staged in the transaction. If `f` returns
+    /// successfully, all staged entries are written at once: first into a write-ahead journal,
+    /// then to their real locations. If `f` fails, all staged changes are discarded.
+    ///
+    /// If the process dies while writing the entries, the journal is replayed (or, if it was not
+    /// written completely, discarded) the next time the store is opened with `Store::new()`.
+    ///
+    /// `FileLockEntry` objects from the transaction must not escape `f`, they are written like
+    /// normal entries otherwise.
+    ///
+    /// `f` may fail with any error a `StoreError` converts into, so libraries can run their own
+    /// functions in a transaction.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// store.transaction(|tx| -> Result<(), LinkError> {
+    ///     let mut a = tx.retrieve(a_id)?;
+    ///     let mut b = tx.retrieve(b_id)?;
+    ///     a.add_internal_link(&mut b)
+    /// })
+    /// ```
+    ///
+    /// # Return value
+    ///
+    /// On success: The value returned by `f`
+    ///
+    /// On error:
+    ///  - The error returned by `f`
+    ///  - TransactionCallError(JournalError()) if the journal could not be written or removed
+    ///  - TransactionCallError(ReadOnlyMount()) if a changed entry lies on a read-only mount
+    ///  - TransactionCallError(EntryConflict()) if an entry was changed in the backend since it
+    ///    was loaded
+    ///  - TransactionCallError(PreHookExecuteError()) if a pre-update hook aborted the commit
+    ///  - TransactionCallError(PostHookExecuteError()) if a post-update hook failed
+    ///
+    pub fn transaction<'a, F, T, E>(&'a self, f: F) -> RResult<T, E>
+        where F: FnOnce(&mut Transaction<'a>) -> RResult<T, E>,
+              E: From<SE>
+    {
+        debug!("Starting transaction");
+        let mut tx = Transaction {
+            store: self,
+            staging: Arc::new(Mutex::new(Staging {
+                open: true,
+                entries: HashMap::new(),
+            })),
+        };
+
+        let result = f(&mut tx);
+
+        let staged = tx.staging
+            .lock()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+            .map(|mut staging| {
+                staging.open = false;
+                staging.entries.drain().collect::<Vec<_>>()
+            })
+            .chain_err(|| SEK::TransactionCallError)?;
+
+        match result {
+            Ok(t) => self
+                .commit_transaction(staged)
+                .map(|_| t)
+                .chain_err(|| SEK::TransactionCallError)
+                .map_err(E::from),
+            Err(e) => {
+                debug!("Transaction failed, rolling back");
+                self.rollback_transaction(staged)
+                    .chain_err(|| SEK::TransactionCallError)
+                    .map_err(E::from)
+                    .and(Err(e))
+            },
+        }
+    }
+
+    /// Commit the staged entries of a transaction
+    ///
+    /// If the commit fails, the staged entries are released. The journal is removed as well if no
+    /// entry was written yet, otherwise it is kept so the transaction is completed when the store
+    /// is opened the next time.
+    fn commit_transaction(&self, mut staged: Vec<(StoreId, (Entry, bool))>) -> Result<()> {
+        if staged.is_empty() {
+            debug!("Nothing to commit");
+            return Ok(());
+        }
+
+        let mut writing = false;
+        match self.write_transaction(&mut staged, &mut writing) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                debug!("Committing the transaction failed, releasing its entries");
+                // The error of the commit is the one to report, so errors while cleaning up are
+                // only logged
+                if !writing {
+                    if let Err(re) = Journal::remove(&*self.backend, &self.location) {
+                        warn!("Could not remove the journal of the failed transaction, it is \
+                               replayed when the store is opened the next time: {}", re);
+                    }
+                }
+                if let Err(re) = self.rollback_transaction(staged) {
+                    warn!("Could not release the entries of the failed transaction: {}", re);
+                }
+                Err(e)
+            },
+        }
+    }
+
+    /// Write the staged entries of a transaction, see `Store::commit_transaction()`
+    ///
+    /// `writing` is set as soon as the first entry is written.
+    fn write_transaction(&self, staged: &mut Vec<(StoreId, (Entry, bool))>, writing: &mut bool)
+        -> Result<()>
+    {
+        // Like in `Store::_update()`, unchanged entries are released without writing them. Changed
+        // ones on read-only mounts are rejected before the journal is written, it could not be
+        // replayed otherwise.
+        let mut unwritten = HashSet::new();
+        {
+            let hsmap = self.entries.read().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            for &(ref id, (ref entry, _)) in staged.iter() {
+                if !hsmap.get(id).map(|se| se.is_changed_by(entry)).unwrap_or(true) {
+                    let _ = unwritten.insert(id.clone());
+                    continue;
+                }
+
+                let path = id.clone().into_pathbuf()?;
+                if self.backend.is_read_only(&path)? {
+                    return Err(SE::from_kind(SEK::ReadOnlyMount(path)));
+                }
+            }
+        }
+
+        // Like in `Store::_update()`, entries which were changed in the backend since they were
+        // loaded are not overwritten
+        {
+            let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            for &(ref id, (ref entry, _)) in staged.iter() {
+                if unwritten.contains(id) {
+                    continue;
+                }
+
+                let se = hsmap
+                    .get_mut(id)
+                    .ok_or_else(|| SE::from_kind(SEK::IdNotFound(id.clone())))?;
+
+                if let Some(theirs) = se.changed_in_backend()? {
+                    debug!("Entry {} was changed in the backend", id);
+                    let ours = Box::new(entry.clone());
+                    return Err(SE::from_kind(SEK::EntryConflict(id.clone(), ours, Box::new(theirs))));
+                }
+            }
+        }
+
+        {
+            let hsmap = self.entries.read().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            for &mut (ref id, (ref mut entry, _)) in staged.iter_mut() {
+                match hsmap.get(id) {
+                    Some(se) => if se.is_changed_by(entry) {
+                        let _ = stamp(entry, se.loaded.is_none())?;
+                    },
+                    None => {
+                        let _ = stamp(entry, true)?;
+                    },
+                }
+            }
+        }
+
+        for &(_, (ref entry, _)) in staged.iter() {
+            verify_before_write(entry)?;
+            self.hooks.execute(self, HookPosition::PreUpdate, &HookData::Entry(entry))?;
+        }
+
+        debug!("Writing journal");
+        Journal::new(staged
+                     .iter()
+                     .filter(|&&(ref id, _)| !unwritten.contains(id))
+                     .map(|&(_, (ref e, _))| e.clone())
+                     .collect())
+            .write(&*self.backend, &self.location)?;
+
+        {
+            let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+            for &(ref id, (ref entry, presence)) in staged.iter() {
+                let se = hsmap
+                    .get_mut(id)
+                    .ok_or_else(|| SE::from_kind(SEK::IdNotFound(id.clone())))?;
+
+                if unwritten.contains(id) {
+                    debug!("Not writing unchanged Entry from transaction: {}", id);
+                    if presence {
+                        se.release();
+                    }
+                    continue;
+                }
+
+                debug!("Writing Entry from transaction: {}", id);
+                let kind = self.update_event_kind(id)?;
+                self.record_revision(id, Some(entry))?;
+                *writing = true;
+                se.write_entry(entry)?;
+                if presence {
+                    se.release();
+                }
+                self.notify_watchers(kind, id);
+            }
+        }
+
+        debug!("Removing journal");
+        Journal::remove(&*self.backend, &self.location)?;
+
+        for &(ref id, (ref entry, _)) in staged.iter() {
+            self.update_indexes(id, Some(entry.get_header()))?;
+        }
+
+        for &(_, (ref entry, _)) in staged.iter() {
+            self.hooks.execute(self, HookPosition::PostUpdate, &HookData::Entry(entry))?;
+        }
+
+        Ok(())
+    }
+
+    /// Discard the staged entries of a transaction and release the ones which were returned to the
+    /// store already
+    fn rollback_transaction(&self, staged: Vec<(StoreId, (Entry, bool))>) -> Result<()> {
+        let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        for (id, (_, presence)) in staged {
+            // Entries which are not returned to the store yet are still borrowed, we must not
+            // remove them from the cache
+            if presence {
+                debug!("Discarding Entry from transaction: {}", id);
+                let _ = hsmap.remove(&id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the path where this store is on the disk
+    pub fn path(&self) -> &PathBuf {
+        &self.location
+    }
+
+    /// Check whether the entry `id` is in one of the collections configured in
+    /// `store.encryption.collections`
+    ///
+    /// Libraries which keep data derived from entries outside of the store (like the full-text
+    /// index) must not do so for encrypted entries.
+    pub fn is_encrypted(&self, id: &StoreId) -> bool {
+        self.encrypted_collections
+            .iter()
+            .any(|collection| id.is_in_collection(&[collection.as_str()]))
+    }
+
+    /// The collections configured in `store.encryption.collections`
+    pub fn encrypted_collections(&self) -> &[String] {
+        &self.encrypted_collections
+    }
+
+    /// Watch the store for changes
+    ///
+    /// The returned `Watcher` yields an event for each entry which is created, modified or
+    /// removed, see the `watch` module. Changes made by other processes are only noticed if the
+    /// backend knows when entries were modified, which is the case for the filesystem backend.
+    pub fn watch(&self) -> Result<Watcher> {
+        let (sender, receiver) = channel();
+        self.watchers
+            .lock()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))?
+            .push(sender);
+        Watcher::new(self, receiver)
+    }
+
+    /// Get the time the entry was modified last in the backend, if the backend knows it
+    pub fn modification_time(&self, id: &StoreId) -> Result<Option<SystemTime>> {
+        let pb = id.clone().with_base(self.path().clone()).into_pathbuf()?;
+        self.backend.modified(&pb)
+    }
+
+    /// Apply a change made by another process to this store object
+    ///
+    /// This updates the indexes and runs the post-update (for created and modified entries) or
+    /// post-delete (for removed entries) hooks for an external event from a `Watcher`, as if the
+    /// change was made through this store object. Used by long-running processes which keep the
+    /// indexes up to date.
+    pub fn apply_external_event(&self, event: &StoreEvent) -> Result<()> {
+        let id = event.id().clone().with_base(self.path().clone());
+        match event.kind() {
+            StoreEventKind::Created | StoreEventKind::Modified => {
+                let entry = self.get_copy(id.clone())?;
+                self.update_indexes(&id, Some(entry.get_header()))?;
+                self.hooks.execute(self, HookPosition::PostUpdate, &HookData::Entry(&entry))
+            },
+            StoreEventKind::Removed => {
+                self.update_indexes(&id, None)?;
+                self.hooks.execute(self, HookPosition::PostDelete, &HookData::Id(&id))
+            },
+        }
+    }
+
+    /// Get the previous revisions of an entry, oldest first
+    ///
+    /// Revisions are only kept if `store.history.revisions` is configured. The revisions of a
+    /// deleted entry are kept as well, so it can be restored.
+    ///
+    /// # Return value
+    ///
+    /// On success: The revisions, which might be empty
+    ///
+    /// On error:
+    ///  - HistoryError() if a revision cannot be read
+    ///
+    pub fn revisions<S: IntoStoreId>(&self, id: S) -> Result<Vec<Revision>> {
+        let id = id.into_storeid()?.with_base(self.path().clone());
+
+        let mut revisions = vec![];
+        for number in history::list(&*self.backend, &self.location, &id)? {
+            if let Some(rev) = history::load(&*self.backend, &self.location, &id, number)? {
+                revisions.push(rev);
+            }
+        }
+        Ok(revisions)
+    }
+
+    /// Restore an entry to a previous revision
+    ///
+    /// The entry is overwritten with the header and content it had in the revision (or created
+    /// again, if it was deleted). As this is a normal update of the entry, the state before the
+    /// restore is saved as a new revision.
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - RevisionNotFound() if there is no such revision of the entry
+    ///  - Errors Store::retrieve() and Store::update() might return
+    ///
+    pub fn restore<S: IntoStoreId>(&self, id: S, revision: u64) -> Result<()> {
+        let id  = id.into_storeid()?.with_base(self.path().clone());
+        let rev = history::load(&*self.backend, &self.location, &id, revision)?
+            .ok_or_else(|| SE::from_kind(SEK::RevisionNotFound(id.clone(), revision)))?
+            .into_entry();
+
+        debug!("Restoring revision {} of {}", revision, id);
+        let mut entry = self.retrieve(id)?;
+        *entry.get_header_mut()  = rev.header;
+        *entry.get_content_mut() = rev.content;
+        self.update(&mut entry)
+    }
+
+    /// Get the deleted entries in the trash, the most recently deleted last
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - TrashError() if the trash cannot be read
+    ///
+    pub fn deleted_entries(&self) -> Result<Vec<TrashedEntry>> {
+        trash::list(&*self.backend, &self.location)
+    }
+
+    /// Restore a deleted entry from the trash
+    ///
+    /// If the entry was deleted more than once, the most recently deleted version is restored.
+    ///
+    /// # Return value
+    ///
+    /// On success: The restored entry, which is not in the trash anymore
+    ///
+    /// On error:
+    ///  - NotInTrash() if the entry is not in the trash
+    ///  - EntryAlreadyExists() if an entry with the id exists in the store
+    ///  - Errors Store::create() and Store::update() might return
+    ///
+    pub fn restore_deleted<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
+        let id      = id.into_storeid()?.with_base(self.path().clone());
+        let trashed = trash::list(&*self.backend, &self.location)?
+            .into_iter()
+            .filter(|t| *t.id() == id)
+            .last()
+            .ok_or_else(|| SE::from_kind(SEK::NotInTrash(id.clone())))?;
+
+        if self.backend.exists(&id.clone().into_pathbuf()?)? {
+            return Err(SE::from_kind(SEK::EntryAlreadyExists(id)));
+        }
+
+        debug!("Restoring {} from the trash", id);
+        let deleted   = trash::load(&*self.backend, &trashed)?;
+        let mut entry = self.create(id)?;
+        *entry.get_header_mut()  = deleted.header;
+        *entry.get_content_mut() = deleted.content;
+        let _ = self.update(&mut entry)?;
+
+        trash::remove(&*self.backend, &trashed).map(|_| entry)
+    }
+
+    /// Remove the entries from the trash which were deleted more than `older_than` ago
+    ///
+    /// # Return value
+    ///
+    /// On success: The number of entries removed from the trash
+    ///
+    /// On error:
+    ///  - TrashError() if the trash cannot be read or an entry cannot be removed
+    ///
+    pub fn empty_trash(&self, older_than: Duration) -> Result<usize> {
+        let limit = trash::now().saturating_sub(older_than.as_secs());
+
+        let mut removed = 0;
+        for trashed in trash::list(&*self.backend, &self.location)? {
+            if trashed.deleted() <= limit {
+                let _ = trash::remove(&*self.backend, &trashed)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Get the revisions of all entries in the history and all deleted entries in the trash
+    ///
+    /// These are not entries of the store, but they might be restored. Libraries which keep data
+    /// referenced by entries outside of the entries themselves (like attachments) must keep the
+    /// data these reference as well.
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - HistoryError() if a revision cannot be read
+    ///  - TrashError() if the trash cannot be read
+    ///
+    pub fn archived_entries(&self) -> Result<Vec<Entry>> {
+        let mut entries = history::all(&*self.backend, &self.location)?;
+        for trashed in trash::list(&*self.backend, &self.location)? {
+            entries.push(trash::load(&*self.backend, &trashed)?);
+        }
+        Ok(entries)
+    }
+
+    /// Read the file `id` from a hidden directory of the store, if it exists
+    ///
+    /// Hidden directories (like `.blobs`) are not collections of entries: Their files are never
+    /// listed as entries, are not locked, hooked, indexed or kept in the history. Libraries can
+    /// use them to store data which belongs to the store but is not an entry, through the backend
+    /// of the store.
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - NotHidden() if `id` is not in a hidden directory
+    ///  - Errors the backend might return
+    ///
+    pub fn get_hidden(&self, id: StoreId) -> Result<Option<Entry>> {
+        let path = self.hidden_path(&id)?;
+        if !self.backend.exists(&path)? {
+            return Ok(None);
+        }
+
+        let id = id.with_base(self.path().clone());
+        self.backend.new_instance(path).get_file_content(id).map(Some)
+    }
+
+    /// Write `entry` to a hidden directory of the store, see `Store::get_hidden()`
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - NotHidden() if the entry is not in a hidden directory
+    ///  - Errors the backend might return
+    ///
+    pub fn write_hidden(&self, entry: &Entry) -> Result<()> {
+        let path = self.hidden_path(entry.get_location())?;
+        if let Some(parent) = path.parent() {
+            let _ = self.backend.create_dir_all(&parent.to_path_buf())?;
+        }
+        self.backend.new_instance(path).write_file_content(entry)
+    }
+
+    /// Remove the file `id` from a hidden directory of the store, see `Store::get_hidden()`
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - NotHidden() if `id` is not in a hidden directory
+    ///  - Errors the backend might return
+    ///
+    pub fn delete_hidden(&self, id: StoreId) -> Result<()> {
+        let path = self.hidden_path(&id)?;
+        self.backend.remove_file(&path)
+    }
+
+    /// Get the ids of all files below the hidden directory `dir`, see `Store::get_hidden()`
+    ///
+    /// # Return value
+    ///
+    /// On error:
+    ///  - NotHidden() if `dir` is not a hidden directory
+    ///  - Errors the backend might return
+    ///
+    pub fn hidden_ids(&self, dir: StoreId) -> Result<Vec<StoreId>> {
+        let base = self.hidden_path(&dir)?;
+
+        let mut ids = vec![];
+        for path in self.backend.pathes_recursively(base.clone())? {
+            // Not all backends limit the pathes to the basepath
+            if path.starts_with(&base) && self.backend.is_file(&path)? {
+                ids.push(StoreId::from_full_path(self.path(), path)?);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Get the ids of all files in hidden directories which are moved by `Store::migrate_backend()`
+    fn migratable_hidden_ids(&self) -> Result<Vec<StoreId>> {
+        let base = self.path().clone();
+
+        let mut ids = vec![];
+        for path in self.backend.pathes_recursively(base.clone())? {
+            // Not all backends limit the pathes to the basepath
+            if !path.starts_with(&base) || !self.backend.is_file(&path)? {
+                continue;
+            }
+
+            let id = StoreId::from_full_path(&base, path)?;
+            let is_migratable = {
+                let first = id
+                    .local()
+                    .components()
+                    .next()
+                    .and_then(|c| c.as_os_str().to_str());
+
+                match first {
+                    Some(".lock") | Some(".journal") | Some(".git") => false,
+                    _ => is_hidden_path(Path::new(""), id.local()),
+                }
+            };
+
+            if is_migratable {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// The path of `id` in the store, if `id` lies in a hidden directory
+    fn hidden_path(&self, id: &StoreId) -> Result<PathBuf> {
+        let is_hidden = id
+            .local()
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .map(|c| c.starts_with("."))
+            .unwrap_or(false);
+
+        if !is_hidden {
+            return Err(SE::from_kind(SEK::NotHidden(id.clone())));
+        }
+
+        id.clone().with_base(self.path().clone()).into_pathbuf()
+    }
+
+    /// Move the current state of the entry `id` into the trash before it is deleted, if the trash
+    /// is enabled
+    fn move_to_trash(&self, id: &StoreId) -> Result<()> {
+        if !self.trash_enabled {
+            return Ok(());
+        }
+
+        let pb = id.clone().with_base(self.path().clone()).into_pathbuf()?;
+        if !self.backend.exists(&pb)? {
+            return Ok(());
+        }
+
+        trash::put(&*self.backend, &self.location, id)
+    }
+
+    /// Fail with `ReadOnlyMount` if the entry `id` lies on a read-only mount
+    fn ensure_writable(&self, id: &StoreId) -> Result<()> {
+        let path = id.clone().with_base(self.path().clone()).into_pathbuf()?;
+        if self.backend.is_read_only(&path)? {
+            return Err(SE::from_kind(SEK::ReadOnlyMount(path)));
+        }
+        Ok(())
+    }
+
+    /// Save the current state of the entry `id` as a revision before it is overwritten with `new`
+    /// or deleted, if the store keeps revisions
+    fn record_revision(&self, id: &StoreId, new: Option<&Entry>) -> Result<()> {
+        if self.history_revisions == 0 {
+            return Ok(());
+        }
+
+        let pb = id.clone().with_base(self.path().clone()).into_pathbuf()?;
+        if !self.backend.exists(&pb)? {
+            return Ok(());
+        }
+
+        if let Some(new) = new {
+            let current = self.backend.new_instance(pb).get_file_content(id.clone())?;
+            if new.to_str() == current.to_str() {
+                debug!("{} did not change, not saving a revision", id);
+                return Ok(());
+            }
+        }
+
+        history::record(&*self.backend, &self.location, id, self.history_revisions)
+    }
+
+    /// Report an error from writing a dropped `FileLockEntry`
+    ///
+    /// If the entry was changed by someone else, our version is saved as
+    /// `.conflicts/<id>~<time>`, as the caller cannot handle the conflict anymore.
+    fn report_drop_error(&self, e: &SE) {
+        if let SEK::EntryConflict(ref id, ref ours, _) = *e.kind() {
+            match self.save_conflict(ours) {
+                Ok(saved) => error!("{} was changed by someone else, your changes were not \
+                                     written but saved as {}", id, saved),
+                Err(_)    => error!("{} was changed by someone else, your changes were lost", id),
+            }
+        }
+    }
+
+    /// Save `ours` as `.conflicts/<id>~<time>`, see `Store::report_drop_error()`
+    fn save_conflict(&self, ours: &Entry) -> Result<StoreId> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let name = format!("{}~{}", ours.get_location().local().display(), timestamp);
+        let id   = StoreId::new_baseless(PathBuf::from(CONFLICTS_DIR).join(name))?;
+
+        let mut saved = ours.clone();
+        saved.location = id.clone();
+        self.write_hidden(&saved).map(|_| id)
+    }
+
+    /// Whether writing the entry creates it or modifies it, for the events sent to the watchers
+    fn update_event_kind(&self, id: &StoreId) -> Result<StoreEventKind> {
+        if self.watchers.lock().map(|w| w.is_empty()).unwrap_or(true) {
+            // Nobody is interested, do not ask the backend
+            return Ok(StoreEventKind::Modified);
+        }
+
+        let pb = id.clone().with_base(self.path().clone()).into_pathbuf()?;
+        if self.backend.exists(&pb)? {
+            Ok(StoreEventKind::Modified)
+        } else {
+            Ok(StoreEventKind::Created)
+        }
+    }
+
+    /// Send an event to all watchers, forgetting the ones which were dropped
+    fn notify_watchers(&self, kind: StoreEventKind, id: &StoreId) {
+        match self.watchers.lock() {
+            Ok(mut watchers) => watchers.retain(|sender| {
+                sender.send(StoreEvent::new(kind, id.clone(), false)).is_ok()
+            }),
+            Err(_) => warn!("Cannot notify watchers of {}: lock poisoned", id),
+        }
+    }
+
+}
+
+impl Debug for Store {
 
     /// TODO: Make pretty.
     fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
@@ -780,10 +2175,77 @@ impl Drop for Store {
 
 }
 
+/// The changes staged in a transaction
+///
+/// Maps the ids of the staged entries to their new state and whether they were returned to the
+/// store (dropped) already.
+#[derive(Debug)]
+struct Staging {
+    open: bool,
+    entries: HashMap<StoreId, (Entry, bool)>,
+}
+
+/// A transaction on the store, see `Store::transaction()`
+pub struct Transaction<'a> {
+    store: &'a Store,
+    staging: Arc<Mutex<Staging>>,
+}
+
+impl<'a> Transaction<'a> {
+
+    /// Like `Store::create()`, but the entry is part of the transaction
+    pub fn create<S: IntoStoreId>(&self, id: S) -> Result<FileLockEntry<'a>> {
+        self.store.create(id).map(|fle| self.attach(fle))
+    }
+
+    /// Like `Store::retrieve()`, but the entry is part of the transaction
+    pub fn retrieve<S: IntoStoreId>(&self, id: S) -> Result<FileLockEntry<'a>> {
+        self.store.retrieve(id).map(|fle| self.attach(fle))
+    }
+
+    /// Like `Store::get()`, but the entry is part of the transaction
+    pub fn get<S: IntoStoreId + Clone>(&self, id: S) -> Result<Option<FileLockEntry<'a>>> {
+        self.store.get(id).map(|o| o.map(|fle| self.attach(fle)))
+    }
+
+    /// Make `fle`, which was fetched from the store directly, part of the transaction
+    ///
+    /// This is for entries which a library fetches from `Transaction::store()` itself. Changes
+    /// which were made to the entry already are staged with it.
+    pub fn attach(&self, mut fle: FileLockEntry<'a>) -> FileLockEntry<'a> {
+        fle.staging = Some(self.staging.clone());
+        fle
+    }
+
+    /// Get the store the transaction runs on
+    pub fn store(&self) -> &'a Store {
+        self.store
+    }
+
+}
+
+impl<'a> Debug for Transaction<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
+        write!(fmt, "Transaction(Store = {})", self.store.location.to_str()
+               .unwrap_or("Unknown Path"))
+    }
+}
+
 /// A struct that allows you to borrow an Entry
+///
+/// The entry is written when the `FileLockEntry` is dropped. Errors are not returned then: if the
+/// entry was changed by someone else meanwhile, the changes are not written but saved to
+/// `.conflicts/<id>~<time>` and an error is logged. Callers which want to handle conflicts use
+/// `Store::update()`.
 pub struct FileLockEntry<'a> {
     store: &'a Store,
     entry: Entry,
+
+    /// The staging area of the transaction this entry belongs to, if any
+    staging: Option<Arc<Mutex<Staging>>>,
+
+    /// Whether the entry is dropped without writing it, see `Store::discard()`
+    discarded: bool,
 }
 
 impl<'a> FileLockEntry<'a, > {
@@ -795,6 +2257,8 @@ impl<'a> FileLockEntry<'a, > {
         FileLockEntry {
             store: store,
             entry: entry,
+            staging: None,
+            discarded: false,
         }
     }
 }
@@ -830,8 +2294,12 @@ impl<'a> Drop for FileLockEntry<'a> {
     fn drop(&mut self) {
         use libimagerror::trace::trace_error_dbg;
         trace!("Dropping: {:?} - from FileLockEntry::drop()", self.get_location());
+        if self.discarded {
+            return;
+        }
         match self.store._update(self, true) {
             Err(e) => {
+                self.store.report_drop_error(&e);
                 trace_error_dbg(&e);
                 if_cfg_panic!("ERROR WHILE DROPPING: {:?}", e);
             },
@@ -846,7 +2314,13 @@ impl<'a> Drop for FileLockEntry<'a> {
     /// This will not silently ignore errors but prints the result of the _update() call for testing
     fn drop(&mut self) {
         trace!("Dropping: {:?} - from FileLockEntry::drop() (test impl)", self.get_location());
-        let _ = self.store._update(self, true).map_err(|e| trace_error(&e));
+        if self.discarded {
+            return;
+        }
+        let _ = self.store._update(self, true).map_err(|e| {
+            self.store.report_drop_error(&e);
+            trace_error(&e)
+        });
     }
 
 }
@@ -957,86 +2431,132 @@ impl Entry {
         &mut self.content
     }
 
-    /// Verify the entry.
+    /// Read the header section at `path` (for example `"todo"`) into a `T`
     ///
-    /// Currently, this only verifies the header. This might change in the future.
-    pub fn verify(&self) -> Result<()> {
-        self.header.verify()
-    }
+    /// This way, a module can define a plain struct for its section instead of reading each value
+    /// from the header by hand.
+    ///
+    /// # Return value
+    ///
+    /// `None` if there is no section at `path`. Fails with `HeaderSectionTypeError`, which names
+    /// the path and `T`, if the section cannot be read as `T`.
+    pub fn read_section<T>(&self, path: &str) -> Result<Option<T>>
+        where T: DeserializeOwned + HeaderSection
+    {
+        let type_error = || SEK::HeaderSectionTypeError(String::from(path), T::type_name());
 
-}
+        match self.header.read(path).chain_err(&type_error)? {
+            None          => Ok(None),
+            Some(section) => section.clone().try_into().map(Some).chain_err(&type_error),
+        }
+    }
 
-impl PartialEq for Entry {
+    /// Write `section` to the header at `path`, replacing what is there
+    ///
+    /// Fails with `HeaderSectionWriteError`, which names the path and `T`, if `section` cannot be
+    /// represented in the header (TOML has no null value, for example).
+    pub fn write_section<T>(&mut self, path: &str, section: &T) -> Result<()>
+        where T: Serialize + HeaderSection
+    {
+        let write_error = || SEK::HeaderSectionWriteError(String::from(path), T::type_name());
 
-    fn eq(&self, other: &Entry) -> bool {
-        self.location == other.location && // As the location only compares from the store root
-            self.header == other.header && // and the other Entry could be from another store (not
-            self.content == other.content  // implemented by now, but we think ahead here)
+        Value::try_from(section)
+            .chain_err(&write_error)
+            .and_then(|v| self.header.insert(path, v).chain_err(&write_error))
+            .map(|_| ())
     }
 
-}
-
-mod glob_store_iter {
-    use std::fmt::{Debug, Formatter};
-    use std::fmt::Error as FmtError;
-    use std::path::PathBuf;
-    use std::result::Result as RResult;
-    use glob::Paths;
-    use storeid::StoreId;
-    use error::Result;
+    /// Verify the entry.
+    ///
+    /// This verifies the `imag` section of the header and checks the header against the schemas
+    /// registered by the modules. Fails with `SchemaViolations`, which lists the violating fields,
+    /// if the header violates a schema.
+    ///
+    /// The store only refuses to write an entry with a broken `imag` section. An entry which
+    /// violates a schema is still written (so it can be fixed), the violations are logged.
+    pub fn verify(&self) -> Result<()> {
+        let _ = self.header.verify()?;
 
-    use error::StoreErrorKind as SEK;
-    use error::ResultExt;
+        let violations = self.schema_violations()?;
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(SE::from_kind(SEK::SchemaViolations(self.location.clone(), violations)))
+        }
+    }
 
-    /// An iterator which is constructed from a `glob()` and returns valid `StoreId` objects or
-    /// errors
-    pub struct GlobStoreIdIterator {
-        store_path: PathBuf,
-        paths: Paths,
+    /// Get the time the entry was created, from `imag.created`
+    ///
+    /// `None` for entries which were created before imag kept track of it.
+    pub fn created(&self) -> Result<Option<NaiveDateTime>> {
+        self.read_timestamp("imag.created")
     }
 
-    impl Debug for GlobStoreIdIterator {
+    /// Get the time the entry was changed last, from `imag.modified`
+    ///
+    /// `None` for entries which were not changed since imag keeps track of it.
+    pub fn modified(&self) -> Result<Option<NaiveDateTime>> {
+        self.read_timestamp("imag.modified")
+    }
 
-        fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FmtError> {
-            write!(fmt, "GlobStoreIdIterator")
+    fn read_timestamp(&self, path: &'static str) -> Result<Option<NaiveDateTime>> {
+        match self.read_section::<String>(path)? {
+            None    => Ok(None),
+            Some(s) => NaiveDateTime::parse_from_str(&s, TIMESTAMP_FORMAT)
+                .map(Some)
+                .chain_err(|| {
+                    SEK::HeaderSectionTypeError(String::from(path), String::from("NaiveDateTime"))
+                }),
         }
-
     }
 
-    impl GlobStoreIdIterator {
+    /// Get the fields of the header which violate the registered schemas
+    ///
+    /// Violations do not keep the entry from being written, they are only reported (by
+    /// `Entry::verify()`, `imag-store verify` and `imag-diagnostics`).
+    pub fn schema_violations(&self) -> Result<Vec<SchemaViolation>> {
+        schema::verify_header(&self.header)
+    }
 
-        pub fn new(paths: Paths, store_path: PathBuf) -> GlobStoreIdIterator {
-            debug!("Create a GlobStoreIdIterator(store_path = {:?}, /* ... */)", store_path);
+}
 
-            GlobStoreIdIterator {
-                store_path: store_path,
-                paths: paths,
-            }
-        }
+impl PartialEq for Entry {
 
+    fn eq(&self, other: &Entry) -> bool {
+        self.location == other.location && // As the location only compares from the store root
+            self.header == other.header && // and the other Entry could be from another store (not
+            self.content == other.content  // implemented by now, but we think ahead here)
     }
 
-    impl Iterator for GlobStoreIdIterator {
-        type Item = Result<StoreId>;
+}
+
+/// A type a header section can be read into and written from
+///
+/// See `Entry::read_section()` and `Entry::write_section()`. A module implements this for the
+/// struct of its section.
+pub trait HeaderSection {
+    /// The name of the type, used in the error if a section does not match it
+    fn type_name() -> String;
+}
 
-        fn next(&mut self) -> Option<Self::Item> {
-            while let Some(o) = self.paths.next() {
-                debug!("GlobStoreIdIterator::next() => {:?}", o);
-                match o.chain_err(|| SEK::StoreIdHandlingError) {
-                    Err(e)   => return Some(Err(e)),
-                    Ok(path) => if path.exists() && path.is_file() {
-                        return Some(StoreId::from_full_path(&self.store_path, path));
-                    /* } else { */
-                        /* continue */
-                    }
+macro_rules! impl_header_section {
+    ($($t:ty),*) => {
+        $(
+            impl HeaderSection for $t {
+                fn type_name() -> String {
+                    String::from(stringify!($t))
                 }
             }
+        )*
+    }
+}
 
-            None
-        }
+impl_header_section!(String, bool, i64, f64, Value);
 
+impl<T: HeaderSection> HeaderSection for Vec<T> {
+    fn type_name() -> String {
+        format!("Vec<{}>", T::type_name())
     }
-
 }
 
 /// Extension trait for top-level toml::Value::Table, will only yield correct results on the
@@ -1242,6 +2762,85 @@ Hai";
         assert_eq!(TEST_ENTRY, string);
     }
 
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestSection {
+        name: String,
+        count: u64,
+    }
+
+    impl super::HeaderSection for TestSection {
+        fn type_name() -> String {
+            String::from("TestSection")
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_section() {
+        use super::Entry;
+        use std::path::PathBuf;
+
+        let mut entry = Entry::new(StoreId::new_baseless(PathBuf::from("test")).unwrap());
+        let section   = TestSection { name: String::from("foo"), count: 3 };
+
+        assert!(entry.write_section("test.section", &section).is_ok());
+        assert_eq!(Some(section), entry.read_section::<TestSection>("test.section").unwrap());
+        assert!(entry.verify().is_ok());
+    }
+
+    #[test]
+    fn test_read_missing_section() {
+        use super::Entry;
+        use std::path::PathBuf;
+
+        let entry = Entry::new(StoreId::new_baseless(PathBuf::from("test")).unwrap());
+        assert_eq!(None, entry.read_section::<TestSection>("test").unwrap());
+    }
+
+    #[test]
+    fn test_read_section_type_error() {
+        use super::Entry;
+        use std::path::PathBuf;
+        use error::StoreErrorKind as SEK;
+
+        let entry = Entry::new(StoreId::new_baseless(PathBuf::from("test")).unwrap());
+        let err   = entry.read_section::<TestSection>("imag").unwrap_err();
+        match *err.kind() {
+            SEK::HeaderSectionTypeError(ref path, ref expected) => {
+                assert_eq!("imag", path);
+                assert_eq!("TestSection", expected);
+            },
+            ref other => panic!("Expected HeaderSectionTypeError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_verify_reports_schema_violations() {
+        use super::Entry;
+        use std::path::PathBuf;
+        use error::StoreErrorKind as SEK;
+        use schema::{self, Schema, FieldType};
+        use toml_query::insert::TomlValueInsertExt;
+
+        // The section is only used by this test, as the schemas are registered for the process
+        schema::register(Schema::new("verify_schema_test").required("uuid", FieldType::Uuid))
+            .unwrap();
+
+        let mut entry = Entry::new(StoreId::new_baseless(PathBuf::from("test")).unwrap());
+        assert!(entry.verify().is_ok());
+
+        entry.get_header_mut().insert("verify_schema_test.uuid", Value::String(String::from("x")))
+            .unwrap();
+
+        let err = entry.verify().unwrap_err();
+        match *err.kind() {
+            SEK::SchemaViolations(ref id, ref violations) => {
+                assert_eq!(entry.get_location(), id);
+                assert_eq!(1, violations.len());
+            },
+            ref other => panic!("Expected SchemaViolations, got {:?}", other),
+        }
+    }
+
 }
 
 #[cfg(test)]
@@ -1482,54 +3081,49 @@ mod store_tests {
         }
     }
 
-    // Disabled because we cannot test this by now, as we rely on glob() in
-    // Store::retieve_for_module(), which accesses the filesystem and tests run in-memory, so there
-    // are no files on the filesystem in this test after Store::create().
-    //
-    // #[test]
-    // fn test_retrieve_for_module() {
-    //     let pathes = vec![
-    //         "foo/1", "foo/2", "foo/3", "foo/4", "foo/5",
-    //         "bar/1", "bar/2", "bar/3", "bar/4", "bar/5",
-    //         "bla/1", "bla/2", "bla/3", "bla/4", "bla/5",
-    //         "boo/1", "boo/2", "boo/3", "boo/4", "boo/5",
-    //         "glu/1", "glu/2", "glu/3", "glu/4", "glu/5",
-    //     ];
-
-    //     fn test(store: &Store, modulename: &str) {
-    //         use std::path::Component;
-    //         use storeid::StoreId;
-
-    //         let retrieved = store.retrieve_for_module(modulename);
-    //         assert!(retrieved.is_ok());
-    //         let v : Vec<StoreId> = retrieved.unwrap().collect();
-    //         println!("v = {:?}", v);
-    //         assert!(v.len() == 5);
-
-    //         let retrieved = store.retrieve_for_module(modulename);
-    //         assert!(retrieved.is_ok());
-
-    //         assert!(retrieved.unwrap().all(|e| {
-    //             let first = e.components().next();
-    //             assert!(first.is_some());
-    //             match first.unwrap() {
-    //                 Component::Normal(s) => s == modulename,
-    //                 _                    => false,
-    //             }
-    //         }))
-    //     }
-
-    //     let store = get_store();
-    //     for path in pathes {
-    //         assert!(store.create(PathBuf::from(path)).is_ok());
-    //     }
-
-    //     test(&store, "foo");
-    //     test(&store, "bar");
-    //     test(&store, "bla");
-    //     test(&store, "boo");
-    //     test(&store, "glu");
-    // }
+    #[test]
+    fn test_retrieve_for_module() {
+        let pathes = vec![
+            "foo/1", "foo/2", "foo/3", "foo/4", "foo/5",
+            "bar/1", "bar/2", "bar/3", "bar/4", "bar/5",
+            "bla/1", "bla/2", "bla/3", "bla/4", "bla/5",
+            "boo/1", "boo/2", "boo/3", "boo/4", "boo/5",
+            "glu/1", "glu/2", "glu/3", "glu/4", "glu/5",
+        ];
+
+        fn test(store: &Store, modulename: &str) {
+            use std::path::Component;
+            use storeid::StoreId;
+
+            let retrieved = store.retrieve_for_module(modulename);
+            assert!(retrieved.is_ok());
+            let v : Vec<StoreId> = retrieved.unwrap().collect();
+            assert!(v.len() == 5);
+
+            let retrieved = store.retrieve_for_module(modulename);
+            assert!(retrieved.is_ok());
+
+            assert!(retrieved.unwrap().all(|e| {
+                let first = e.components().next();
+                assert!(first.is_some());
+                match first.unwrap() {
+                    Component::Normal(s) => s == modulename,
+                    _                    => false,
+                }
+            }))
+        }
+
+        let store = get_store();
+        for path in pathes {
+            assert!(store.create(PathBuf::from(path)).is_ok());
+        }
+
+        test(&store, "foo");
+        test(&store, "bar");
+        test(&store, "bla");
+        test(&store, "boo");
+        test(&store, "glu");
+    }
 
     #[test]
     fn test_store_move_moves_in_hm() {
@@ -1546,29 +3140,533 @@ mod store_tests {
                     assert!(store.entries.read().unwrap().get(&id).is_none());
                 }
 
-                {
-                    assert!(store.create(id.clone()).is_ok());
-                }
+                {
+                    assert!(store.create(id.clone()).is_ok());
+                }
+
+                {
+                    let id_with_base = id.clone().with_base(store.path().clone());
+                    assert!(store.entries.read().unwrap().get(&id_with_base).is_some());
+                }
+
+                let r = store.move_by_id(id.clone(), id_mv.clone());
+                assert!(r.map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+
+                {
+                    let id_mv_with_base = id_mv.clone().with_base(store.path().clone());
+                    assert!(store.entries.read().unwrap().get(&id_mv_with_base).is_some());
+                }
+
+                assert!(match store.get(id.clone()) { Ok(None) => true, _ => false },
+                        "Moved id ({:?}) is still there", id);
+                assert!(match store.get(id_mv.clone()) { Ok(Some(_)) => true, _ => false },
+                        "New id ({:?}) is not in store...", id_mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_store_pre_create_hook_aborts() {
+        use hook::{Hook, HookData, HookPosition};
+        use error::StoreError as SE;
+        use error::StoreErrorKind as SEK;
+        use error::Result;
+
+        #[derive(Debug)]
+        struct DenyHook;
+
+        impl Hook for DenyHook {
+            fn name(&self) -> &'static str {
+                "deny"
+            }
+
+            fn execute(&self, _: HookPosition, _: &HookData) -> Result<()> {
+                Err(SE::from_kind(SEK::IdLocked))
+            }
+        }
+
+        let mut store = get_store();
+        assert!(store.register_hook(HookPosition::PreCreate, Box::new(DenyHook)).is_ok());
+
+        for n in 1..100 {
+            let res = store.create(PathBuf::from(format!("test-{}", n)));
+            assert!(match res { Err(ref e) => is_match!(e.kind(), &SEK::CreateCallError), _ => false });
+            assert!(store.entries.read().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_store_failing_post_hooks_do_not_write() {
+        use hook::{Hook, HookData, HookPosition};
+        use error::StoreError as SE;
+        use error::StoreErrorKind as SEK;
+        use error::Result;
+
+        #[derive(Debug)]
+        struct DenyHook;
+
+        impl Hook for DenyHook {
+            fn name(&self) -> &'static str {
+                "deny"
+            }
+
+            fn execute(&self, _: HookPosition, _: &HookData) -> Result<()> {
+                Err(SE::from_kind(SEK::IdLocked))
+            }
+        }
+
+        let mut store = get_store();
+        assert!(store.register_hook(HookPosition::PostCreate, Box::new(DenyHook)).is_ok());
+        assert!(store.register_hook(HookPosition::PostRetrieve, Box::new(DenyHook)).is_ok());
+
+        let res = store.create(PathBuf::from("test"));
+        assert!(match res { Err(ref e) => is_match!(e.kind(), &SEK::CreateCallError), _ => false });
+        assert!(store.entries.read().unwrap().is_empty());
+        assert!(!store.backend.exists(&PathBuf::from("/test")).unwrap());
+
+        let res = store.retrieve(PathBuf::from("test"));
+        assert!(match res { Err(ref e) => is_match!(e.kind(), &SEK::RetrieveCallError), _ => false });
+        assert!(store.entries.read().unwrap().is_empty());
+        assert!(!store.backend.exists(&PathBuf::from("/test")).unwrap());
+    }
+
+    #[test]
+    fn test_store_hooks_get_executed() {
+        use std::rc::Rc;
+        use std::cell::RefCell;
+        use hook::{Hook, HookData, HookPosition};
+        use error::Result;
+
+        #[derive(Debug)]
+        struct RecordingHook(Rc<RefCell<Vec<HookPosition>>>);
+
+        impl Hook for RecordingHook {
+            fn name(&self) -> &'static str {
+                "recording"
+            }
+
+            fn execute(&self, position: HookPosition, _: &HookData) -> Result<()> {
+                self.0.borrow_mut().push(position);
+                Ok(())
+            }
+        }
+
+        let record    = Rc::new(RefCell::new(vec![]));
+        let mut store = get_store();
+        let positions = vec![
+            HookPosition::PreCreate,
+            HookPosition::PostCreate,
+            HookPosition::PreUpdate,
+            HookPosition::PostUpdate,
+            HookPosition::PreDelete,
+            HookPosition::PostDelete,
+        ];
+
+        for pos in positions.iter() {
+            let hook = Box::new(RecordingHook(record.clone()));
+            assert!(store.register_hook(*pos, hook).is_ok());
+        }
+
+        {
+            let mut entry = store.create(PathBuf::from("test")).unwrap();
+            assert!(store.update(&mut entry).is_ok());
+        }
+        assert!(store.delete(PathBuf::from("test")).is_ok());
+
+        let expected = vec![
+            HookPosition::PreCreate,
+            HookPosition::PostCreate,
+            HookPosition::PreUpdate,  // Store::update()
+            HookPosition::PostUpdate,
+            HookPosition::PreUpdate,  // FileLockEntry::drop()
+            HookPosition::PostUpdate,
+            HookPosition::PreDelete,
+            HookPosition::PostDelete,
+        ];
+        assert_eq!(expected, *record.borrow());
+    }
+
+    #[test]
+    fn test_transaction_commit() {
+        use error::Result;
+
+        let store = get_store();
+
+        let res : Result<()> = store.transaction(|tx| {
+            let mut a = tx.create(PathBuf::from("a"))?;
+            let mut b = tx.create(PathBuf::from("b"))?;
+            *a.get_content_mut() = String::from("A");
+            *b.get_content_mut() = String::from("B");
+            Ok(())
+        });
+        assert!(res.is_ok());
+
+        assert_eq!("A", *store.get_copy(PathBuf::from("a")).unwrap().get_content());
+        assert_eq!("B", *store.get_copy(PathBuf::from("b")).unwrap().get_content());
+        assert!(!store.backend.exists(&PathBuf::from("/.journal")).unwrap());
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        use error::Result;
+        use error::StoreError as SE;
+        use error::StoreErrorKind as SEK;
+
+        let store = get_store();
+
+        {
+            let mut b = store.create(PathBuf::from("b")).unwrap();
+            *b.get_content_mut() = String::from("old");
+        }
+
+        let res : Result<()> = store.transaction(|tx| {
+            let mut a = tx.create(PathBuf::from("a"))?;
+            let mut b = tx.retrieve(PathBuf::from("b"))?;
+            *a.get_content_mut() = String::from("A");
+            *b.get_content_mut() = String::from("new");
+            Err(SE::from_kind(SEK::IdLocked))
+        });
+        assert!(res.is_err());
+
+        assert!(store.get(PathBuf::from("a")).unwrap().is_none());
+        assert_eq!("old", *store.get_copy(PathBuf::from("b")).unwrap().get_content());
+        assert!(!store.backend.exists(&PathBuf::from("/.journal")).unwrap());
+    }
+
+    #[test]
+    fn test_transaction_failed_commit_releases_entries() {
+        use hook::{Hook, HookData, HookPosition};
+        use error::StoreError as SE;
+        use error::StoreErrorKind as SEK;
+        use error::Result;
+
+        #[derive(Debug)]
+        struct DenyHook;
+
+        impl Hook for DenyHook {
+            fn name(&self) -> &'static str {
+                "deny"
+            }
+
+            fn execute(&self, _: HookPosition, _: &HookData) -> Result<()> {
+                Err(SE::from_kind(SEK::IdLocked))
+            }
+        }
+
+        let mut store = get_store();
+        {
+            let mut b = store.create(PathBuf::from("b")).unwrap();
+            *b.get_content_mut() = String::from("old");
+        }
+        assert!(store.register_hook(HookPosition::PreUpdate, Box::new(DenyHook)).is_ok());
+
+        let res : Result<()> = store.transaction(|tx| {
+            let mut a = tx.create(PathBuf::from("a"))?;
+            let mut b = tx.retrieve(PathBuf::from("b"))?;
+            *a.get_content_mut() = String::from("A");
+            *b.get_content_mut() = String::from("new");
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert!(!store.backend.exists(&PathBuf::from("/.journal")).unwrap());
+        assert!(!store.backend.exists(&PathBuf::from("/a")).unwrap());
+
+        assert!(store.retrieve(PathBuf::from("a")).is_ok());
+        let b = store.retrieve(PathBuf::from("b"));
+        assert!(b.is_ok());
+        assert_eq!("old", *b.unwrap().get_content());
+    }
+
+    #[test]
+    fn test_transaction_on_read_only_mount() {
+        use error::Result;
+        use error::StoreError as SE;
+        use error::StoreErrorKind as SEK;
+        use file_abstraction::FileAbstraction;
+        use file_abstraction::Mount;
+        use file_abstraction::MountFileAbstraction;
+        use storeid::StoreId;
+        use super::Entry;
+
+        let team = InMemoryFileAbstraction::new();
+        let id   = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("team/meeting")).unwrap();
+        let _    = team
+            .new_instance(PathBuf::from("/team-store/meeting"))
+            .write_file_content(&Entry::new(id))
+            .unwrap();
+
+        let team    = Mount::new(String::from("team"), PathBuf::from("/team-store"), Box::new(team), true)
+            .unwrap();
+        let backend = MountFileAbstraction::new(Box::new(InMemoryFileAbstraction::new()),
+                                                PathBuf::from("/"),
+                                                vec![team]);
+        let store   = Store::new_with_backend(PathBuf::from("/"), &None, Box::new(backend)).unwrap();
+
+        // Unchanged entries on read-only mounts are not written
+        let res : Result<()> = store.transaction(|tx| {
+            let _     = tx.retrieve(PathBuf::from("team/meeting"))?;
+            let mut a = tx.create(PathBuf::from("a"))?;
+            *a.get_content_mut() = String::from("A");
+            Ok(())
+        });
+        assert!(res.is_ok());
+        assert_eq!("A", *store.get_copy(PathBuf::from("a")).unwrap().get_content());
+
+        let res : Result<()> = store.transaction(|tx| {
+            let mut meeting = tx.retrieve(PathBuf::from("team/meeting"))?;
+            let mut b       = tx.create(PathBuf::from("b"))?;
+            *meeting.get_content_mut() = String::from("changed");
+            *b.get_content_mut()       = String::from("B");
+            Ok(())
+        });
+        let err = res.unwrap_err();
+        let msg = format!("{}", SE::from_kind(SEK::ReadOnlyMount(PathBuf::from("/team/meeting"))));
+        assert!(err.iter().any(|e| format!("{}", e) == msg), "Unexpected error: {:?}", err);
+        assert!(!store.backend.exists(&PathBuf::from("/.journal")).unwrap());
+        assert!(!store.backend.exists(&PathBuf::from("/b")).unwrap());
+        assert_eq!("", *store.retrieve(PathBuf::from("team/meeting")).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_journal_replayed_on_open() {
+        use journal::Journal;
+        use storeid::StoreId;
+        use super::Entry;
+
+        let backend   = InMemoryFileAbstraction::new();
+        let base      = PathBuf::from("/");
+        let id        = StoreId::new(Some(base.clone()), PathBuf::from("a")).unwrap();
+        let mut entry = Entry::new(id);
+        *entry.get_content_mut() = String::from("A");
+
+        Journal::new(vec![entry]).write(&backend, &base).unwrap();
+
+        let store = Store::new_with_backend(base, &None, Box::new(backend)).unwrap();
+        assert_eq!("A", *store.get_copy(PathBuf::from("a")).unwrap().get_content());
+        assert!(!store.backend.exists(&PathBuf::from("/.journal")).unwrap());
+    }
+
+    #[test]
+    fn test_find_by_header() {
+        use std::ops::Bound;
+        use toml::Value;
+        use toml::de::from_str as toml_from_str;
+        use toml_query::insert::TomlValueInsertExt;
+        use storeid::StoreId;
+
+        let config  = toml_from_str("[store]\nindex = [\"test.value\"]").unwrap();
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        let store   = Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap();
+
+        for n in 1..10 {
+            let mut entry = store.create(PathBuf::from(format!("test-{}", n))).unwrap();
+            let _ = entry.get_header_mut().insert("test.value", Value::Integer(n)).unwrap();
+        }
+
+        let ids = |iter: ::storeid::StoreIdIterator| -> Vec<String> {
+            iter.map(|id| id.local().to_str().unwrap().to_owned()).collect()
+        };
+
+        let found = store.find_by_header("test.value", &Value::Integer(3)).unwrap();
+        assert_eq!(vec!["test-3"], ids(found));
+
+        let from  = Value::Integer(7);
+        let found = store.find_by_header_range("test.value", Bound::Included(&from), Bound::Unbounded);
+        assert_eq!(vec!["test-7", "test-8", "test-9"], ids(found.unwrap()));
+
+        assert!(store.find_by_header("other.value", &Value::Integer(3)).is_err());
+
+        store.delete(PathBuf::from("test-3")).unwrap();
+        let found = store.find_by_header("test.value", &Value::Integer(3)).unwrap();
+        assert!(ids(found).is_empty());
+
+        let old = StoreId::new_baseless(PathBuf::from("test-4")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("moved")).unwrap();
+        store.move_by_id(old, new).unwrap();
+        let found = store.find_by_header("test.value", &Value::Integer(4)).unwrap();
+        assert_eq!(vec!["moved"], ids(found));
+
+        // The index is not an entry
+        assert!(store.backend.exists(&PathBuf::from("/.index/test.value")).unwrap());
+        assert_eq!(8, store.entries().unwrap().count());
+    }
+
+    #[test]
+    fn test_index_is_built_on_open() {
+        use toml::Value;
+        use toml::de::from_str as toml_from_str;
+        use toml_query::insert::TomlValueInsertExt;
+        use storeid::StoreId;
+        use super::Entry;
+        use file_abstraction::FileAbstraction;
+
+        let backend   = InMemoryFileAbstraction::new();
+        let id        = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("test")).unwrap();
+        let mut entry = Entry::new(id);
+        let _ = entry.get_header_mut().insert("test.value", Value::Boolean(true)).unwrap();
+        backend.new_instance(PathBuf::from("/test")).write_file_content(&entry).unwrap();
+
+        let config = toml_from_str("[store]\nindex = [\"test.value\"]").unwrap();
+        let store  = Store::new_with_backend(PathBuf::from("/"), &Some(config), Box::new(backend))
+            .unwrap();
+
+        let found = store.find_by_header("test.value", &Value::Boolean(true)).unwrap();
+        assert_eq!(1, found.count());
+    }
+
+    #[test]
+    fn test_index_is_shared_with_other_store() {
+        use tempdir::TempDir;
+        use toml::Value;
+        use toml::de::from_str as toml_from_str;
+        use toml_query::insert::TomlValueInsertExt;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-index").unwrap();
+        let new_store = || {
+            let config  = toml_from_str("[store]\nindex = [\"test.value\"]").unwrap();
+            let backend = Box::new(FSFileAbstraction::new());
+            Store::new_with_backend(dir.path().to_path_buf(), &Some(config), backend).unwrap()
+        };
+
+        let store = new_store();
+        let other = new_store();
+
+        for (s, name) in vec![(&store, "test-a"), (&other, "test-b"), (&store, "test-c")] {
+            let mut entry = s.create(PathBuf::from(name)).unwrap();
+            let _ = entry.get_header_mut().insert("test.value", Value::Integer(1)).unwrap();
+        }
+
+        // Neither store overwrote the changes of the other one
+        for s in vec![&store, &other, &new_store()] {
+            assert_eq!(3, s.find_by_header("test.value", &Value::Integer(1)).unwrap().count());
+        }
+    }
+
+    fn get_store_with_history(revisions: usize) -> Store {
+        use toml::de::from_str as toml_from_str;
+
+        let config  = toml_from_str(&format!("[store.history]\nrevisions = {}", revisions)).unwrap();
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap()
+    }
+
+    fn write_content(store: &Store, id: &str, content: &str) {
+        let mut entry = store.retrieve(PathBuf::from(id)).unwrap();
+        *entry.get_content_mut() = String::from(content);
+    }
+
+    #[test]
+    fn test_history_keeps_revisions() {
+        let store = get_store_with_history(2);
+
+        for content in &["one", "two", "three", "four"] {
+            write_content(&store, "test", content);
+        }
+
+        // Writing an unchanged entry does not add a revision
+        let _ = store.retrieve(PathBuf::from("test")).unwrap();
+
+        let revisions = store.revisions(PathBuf::from("test")).unwrap();
+        let contents  = revisions.iter().map(|r| r.entry().get_content().clone()).collect::<Vec<_>>();
+        assert_eq!(vec!["two", "three"], contents);
+        assert!(revisions[0].number() < revisions[1].number());
+
+        // Revisions are not entries
+        assert_eq!(1, store.entries().unwrap().count());
+    }
+
+    #[test]
+    fn test_history_disabled_by_default() {
+        let store = get_store();
+        write_content(&store, "test", "one");
+        write_content(&store, "test", "two");
+        assert!(store.revisions(PathBuf::from("test")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore_revision() {
+        let store = get_store_with_history(5);
+        write_content(&store, "test", "one");
+        write_content(&store, "test", "two");
+
+        let first = store.revisions(PathBuf::from("test")).unwrap()[0].number();
+        store.restore(PathBuf::from("test"), first).unwrap();
+        assert_eq!("one", store.get_copy(PathBuf::from("test")).unwrap().get_content());
+
+        // The state before restoring is a revision now
+        let revisions = store.revisions(PathBuf::from("test")).unwrap();
+        assert_eq!("two", revisions.last().unwrap().entry().get_content());
+
+        assert!(store.restore(PathBuf::from("test"), 1000).is_err());
+    }
+
+    #[test]
+    fn test_restore_deleted_and_moved_entry() {
+        use storeid::StoreId;
+
+        let store = get_store_with_history(5);
+        write_content(&store, "test", "one");
+        write_content(&store, "test", "two");
+
+        let old = StoreId::new_baseless(PathBuf::from("test")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("moved")).unwrap();
+        store.move_by_id(old, new).unwrap();
+        assert!(store.revisions(PathBuf::from("test")).unwrap().is_empty());
+
+        store.delete(PathBuf::from("moved")).unwrap();
+
+        let revisions = store.revisions(PathBuf::from("moved")).unwrap();
+        assert_eq!(2, revisions.len());
+        assert_eq!("two", revisions[1].entry().get_content());
+
+        store.restore(PathBuf::from("moved"), revisions[1].number()).unwrap();
+        assert_eq!("two", store.get_copy(PathBuf::from("moved")).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_moved_revisions_follow_existing_revisions() {
+        use storeid::StoreId;
+
+        let store = get_store_with_history(5);
+        write_content(&store, "target", "old one");
+        write_content(&store, "target", "old two");
+        store.delete(PathBuf::from("target")).unwrap();
+
+        write_content(&store, "test", "one");
+        write_content(&store, "test", "two");
+
+        let old = StoreId::new_baseless(PathBuf::from("test")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("target")).unwrap();
+        store.move_by_id(old, new).unwrap();
+
+        let revisions = store.revisions(PathBuf::from("target")).unwrap();
+        let contents  = revisions.iter().map(|r| r.entry().get_content().clone()).collect::<Vec<_>>();
+        assert_eq!(vec!["old one", "old two", "one"], contents);
+        assert_eq!(vec![1, 2, 3], revisions.iter().map(|r| r.number()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_history_on_filesystem() {
+        use tempdir::TempDir;
+        use toml::de::from_str as toml_from_str;
+        use file_abstraction::FSFileAbstraction;
 
-                {
-                    let id_with_base = id.clone().with_base(store.path().clone());
-                    assert!(store.entries.read().unwrap().get(&id_with_base).is_some());
-                }
+        let dir     = TempDir::new("imag-store-history").unwrap();
+        let config  = toml_from_str("[store.history]\nrevisions = 5").unwrap();
+        let backend = Box::new(FSFileAbstraction::new());
+        let store   = Store::new_with_backend(dir.path().to_path_buf(), &Some(config), backend).unwrap();
 
-                let r = store.move_by_id(id.clone(), id_mv.clone());
-                assert!(r.map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+        write_content(&store, "coll/test", "one\n\nwith lines\n");
+        write_content(&store, "coll/test", "two");
 
-                {
-                    let id_mv_with_base = id_mv.clone().with_base(store.path().clone());
-                    assert!(store.entries.read().unwrap().get(&id_mv_with_base).is_some());
-                }
+        let revisions = store.revisions(PathBuf::from("coll/test")).unwrap();
+        let contents  = revisions.iter().map(|r| r.entry().get_content().clone()).collect::<Vec<_>>();
+        assert_eq!(vec!["one\n\nwith lines\n"], contents);
+        assert_eq!(1, store.entries().unwrap().count());
 
-                assert!(match store.get(id.clone()) { Ok(None) => true, _ => false },
-                        "Moved id ({:?}) is still there", id);
-                assert!(match store.get(id_mv.clone()) { Ok(Some(_)) => true, _ => false },
-                        "New id ({:?}) is not in store...", id_mv);
-            }
-        }
+        store.restore(PathBuf::from("coll/test"), revisions[0].number()).unwrap();
+        assert_eq!("one\n\nwith lines\n", store.get_copy(PathBuf::from("coll/test")).unwrap().get_content());
     }
 
     #[test]
@@ -1715,5 +3813,656 @@ mod store_tests {
         }
 
     }
-}
 
+    #[cfg(feature = "fs-locking")]
+    #[test]
+    fn test_entry_locked_by_other_store() {
+        use tempdir::TempDir;
+        use toml::Value;
+        use toml::de::from_str as toml_from_str;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-lock").unwrap();
+        let new_store = |config: Option<Value>| {
+            let backend = Box::new(FSFileAbstraction::new());
+            Store::new_with_backend(dir.path().to_path_buf(), &config, backend).unwrap()
+        };
+
+        let store = new_store(None);
+        let other = new_store(Some(toml_from_str("[store]\nlock-timeout = 100").unwrap()));
+
+        {
+            let _entry = store.create(PathBuf::from("test-locked")).unwrap();
+
+            let err = other.retrieve(PathBuf::from("test-locked")).unwrap_err();
+            assert!(err.iter().any(|e| e.to_string().contains("locked by another process")));
+        }
+
+        // The lock is released when the FileLockEntry is dropped
+        let _entry = other.retrieve(PathBuf::from("test-locked")).unwrap();
+        assert!(store.get(PathBuf::from("test-locked")).is_err());
+    }
+
+    #[cfg(feature = "fs-locking")]
+    #[test]
+    fn test_delete_entry_locked_by_other_store() {
+        use tempdir::TempDir;
+        use toml::Value;
+        use toml::de::from_str as toml_from_str;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-lock").unwrap();
+        let new_store = |config: Option<Value>| {
+            let backend = Box::new(FSFileAbstraction::new());
+            Store::new_with_backend(dir.path().to_path_buf(), &config, backend).unwrap()
+        };
+
+        let store = new_store(None);
+        let other = new_store(Some(toml_from_str("[store]\nlock-timeout = 100").unwrap()));
+
+        {
+            let _ = store.create(PathBuf::from("test-locked-delete")).unwrap();
+        }
+
+        {
+            let _entry = store.retrieve(PathBuf::from("test-locked-delete")).unwrap();
+
+            // `other` has not loaded the entry, nor does it have it cached
+            let err = other.delete(PathBuf::from("test-locked-delete")).unwrap_err();
+            assert!(err.iter().any(|e| e.to_string().contains("locked by another process")));
+            assert!(dir.path().join("test-locked-delete").is_file());
+        }
+
+        // The lock is released when the FileLockEntry is dropped
+        other.delete(PathBuf::from("test-locked-delete")).unwrap();
+        assert!(!dir.path().join("test-locked-delete").exists());
+    }
+
+
+    /// Write an entry through the backend, like another process would
+    fn write_externally(store: &Store, id: &str, content: &str) -> super::Entry {
+        use storeid::StoreId;
+        use super::Entry;
+
+        let id        = StoreId::new(Some(store.path().clone()), PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from(content);
+        store.backend.new_instance(id.into_pathbuf().unwrap()).write_file_content(&entry).unwrap();
+        entry
+    }
+
+    #[test]
+    fn test_create_sets_timestamps() {
+        let store = get_store();
+        let entry = store.create(PathBuf::from("test-created")).unwrap();
+
+        assert!(entry.created().unwrap().is_some());
+        assert_eq!(entry.created().unwrap(), entry.modified().unwrap());
+    }
+
+    #[test]
+    fn test_update_sets_modified_only_on_change() {
+        let store = get_store();
+        let _     = write_externally(&store, "test-modified", "old");
+
+        {
+            let entry = store.retrieve(PathBuf::from("test-modified")).unwrap();
+            assert!(entry.modified().unwrap().is_none());
+        }
+
+        {
+            let mut entry = store.retrieve(PathBuf::from("test-modified")).unwrap();
+            assert!(entry.modified().unwrap().is_none());
+            *entry.get_content_mut() = String::from("new");
+        }
+
+        let entry = store.get(PathBuf::from("test-modified")).unwrap().unwrap();
+        assert!(entry.modified().unwrap().is_some());
+        assert!(entry.created().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_entry_violating_schema_is_written() {
+        use schema::{self, Schema, FieldType};
+        use toml::Value;
+        use toml_query::insert::TomlValueInsertExt;
+        use toml_query::read::TomlValueReadExt;
+
+        // The section is only used by this test, as the schemas are registered for the process
+        schema::register(Schema::new("written_schema_test").required("count", FieldType::Integer))
+            .unwrap();
+
+        let store = get_store();
+        {
+            let mut entry = store.create(PathBuf::from("test-schema")).unwrap();
+            entry.get_header_mut()
+                .insert("written_schema_test.count", Value::String(String::from("one")))
+                .unwrap();
+            assert!(store.update(&mut entry).is_ok());
+        }
+
+        let entry = store.get_copy(PathBuf::from("test-schema")).unwrap();
+        assert!(entry.get_header().read("written_schema_test.count").unwrap().is_some());
+        assert_eq!(1, entry.schema_violations().unwrap().len());
+    }
+
+    /// A store with a store mounted at "team", which contains the entry "meeting"
+    fn get_mounting_store(read_only: bool) -> Store {
+        use storeid::StoreId;
+        use super::Entry;
+        use file_abstraction::{FileAbstraction, Mount, MountFileAbstraction};
+
+        // The in-memory backend keeps the id of the entry, which is the id in the mounting store
+        let team = InMemoryFileAbstraction::new();
+        let id   = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("team/meeting")).unwrap();
+        team.new_instance(PathBuf::from("/team-store/meeting"))
+            .write_file_content(&Entry::new(id))
+            .unwrap();
+
+        let mount   = Mount::new(String::from("team"), PathBuf::from("/team-store"), Box::new(team), read_only);
+        let backend = MountFileAbstraction::new(Box::new(InMemoryFileAbstraction::new()),
+                                                PathBuf::from("/"),
+                                                vec![mount.unwrap()]);
+        Store::new_with_backend(PathBuf::from("/"), &None, Box::new(backend)).unwrap()
+    }
+
+    #[test]
+    fn test_mounted_entries_are_entries() {
+        use storeid::StoreId;
+
+        let store = get_mounting_store(false);
+        let _     = store.create(PathBuf::from("notes/note")).unwrap();
+        let _     = store.create(PathBuf::from("team/minutes")).unwrap();
+
+        let mut ids = store.entries().unwrap().map(|id| id.local().clone()).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(vec![
+            PathBuf::from("notes/note"),
+            PathBuf::from("team/meeting"),
+            PathBuf::from("team/minutes"),
+        ], ids);
+
+        store.move_by_id(StoreId::new_baseless(PathBuf::from("notes/note")).unwrap(),
+                         StoreId::new_baseless(PathBuf::from("team/note")).unwrap())
+            .unwrap();
+        assert!(store.get(PathBuf::from("notes/note")).unwrap().is_none());
+        assert!(store.get(PathBuf::from("team/note")).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_only_mount_is_enforced() {
+        use storeid::StoreId;
+
+        let store = get_mounting_store(true);
+
+        {
+            let mut entry = store.get(PathBuf::from("team/meeting")).unwrap().unwrap();
+            store.update(&mut entry).unwrap(); // nothing changed, nothing written
+            *entry.get_content_mut() = String::from("changed");
+            assert!(store.update(&mut entry).is_err());
+        }
+
+        let meeting = || StoreId::new_baseless(PathBuf::from("team/meeting")).unwrap();
+        assert!(store.create(PathBuf::from("team/new")).is_err());
+        assert!(store.delete(meeting()).is_err());
+        assert!(store.move_by_id(meeting(), StoreId::new_baseless(PathBuf::from("meeting")).unwrap()).is_err());
+        assert_eq!("", store.get_copy(meeting()).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_walk_skips_hidden_pathes() {
+        use std::fs;
+        use std::io::Write;
+        use tempdir::TempDir;
+        use storeid::StoreId;
+        use super::StoreObject;
+
+        let dir   = TempDir::new("imag-store-walk").unwrap();
+        let store = Store::new(dir.path().to_path_buf(), &None).unwrap();
+        let _     = store.create(PathBuf::from("test/a")).unwrap();
+
+        // Like the entry locks, the journal or the history
+        fs::create_dir_all(dir.path().join(".history").join("test")).unwrap();
+        fs::File::create(dir.path().join(".history").join("test").join("a"))
+            .and_then(|mut f| f.write_all(b"not an entry"))
+            .unwrap();
+
+        let mut ids         = vec![];
+        let mut collections = vec![];
+        for object in store.walk("") {
+            match object {
+                StoreObject::Id(id)         => ids.push(id.without_base()),
+                StoreObject::Collection(pb) => collections.push(pb),
+            }
+        }
+
+        assert_eq!(vec![StoreId::new_baseless(PathBuf::from("test/a")).unwrap()], ids);
+        assert!(collections.iter().all(|pb| !pb.to_str().unwrap().contains("/.")),
+                "Hidden collections found: {:?}", collections);
+    }
+
+    #[test]
+    fn test_migrate_backend_moves_hidden_pathes() {
+        use std::fs;
+        use tempdir::TempDir;
+        use storeid::StoreId;
+        use super::Entry;
+        use file_abstraction::FSFileAbstraction;
+        use file_abstraction::InMemoryFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-migrate").unwrap();
+        let location  = dir.path().to_path_buf();
+        let mut store = Store::new(location.clone(), &None).unwrap();
+
+        let hidden = vec![".history/test/1", ".trash/test~1", ".index/test.uuid", ".blobs/ab/cdef"];
+
+        {
+            let mut entry = store.create(PathBuf::from("test")).unwrap();
+            entry.get_content_mut().push_str("entry");
+        }
+        for h in hidden.iter() {
+            let mut entry = Entry::new(StoreId::new_baseless(PathBuf::from(h)).unwrap());
+            entry.get_content_mut().push_str(h);
+            store.write_hidden(&entry).unwrap();
+        }
+
+        assert!(store.migrate_backend(Box::new(InMemoryFileAbstraction::new())).is_ok());
+        fs::remove_dir_all(&location).unwrap();
+        assert!(store.migrate_backend(Box::new(FSFileAbstraction::new())).is_ok());
+
+        // The lock files only make sense for the backend they were written with
+        assert!(!location.join(".lock").exists());
+
+        assert_eq!(1, store.entries().unwrap().count());
+        assert_eq!("entry", *store.get(PathBuf::from("test")).unwrap().unwrap().get_content());
+
+        for h in hidden.iter() {
+            assert!(location.join(h).is_file(), "{} was not migrated", h);
+            let id    = StoreId::new_baseless(PathBuf::from(h)).unwrap();
+            let entry = store.get_hidden(id).unwrap().unwrap();
+            assert_eq!(*h, *entry.get_content());
+        }
+    }
+
+    #[test]
+    fn test_mounts_on_real_directories() {
+        use std::fs;
+        use tempdir::TempDir;
+        use toml::de::from_str as toml_from_str;
+        use file_abstraction::Origin;
+        use storeid::StoreId;
+
+        let dir      = TempDir::new("imag-store-mounts").unwrap();
+        let personal = dir.path().join("personal");
+        let team     = dir.path().join("team");
+        fs::create_dir(&personal).unwrap();
+        fs::create_dir(&team).unwrap();
+
+        // Relative mount pathes are relative to the store
+        let open = |read_only: bool| {
+            let config = format!("[store.mounts.team]\npath = \"../team\"\nread-only = {}", read_only);
+            Store::new(personal.clone(), &Some(toml_from_str(&config).unwrap())).unwrap()
+        };
+
+        {
+            let store = open(false);
+            let _     = store.create(PathBuf::from("team/meeting")).unwrap();
+            let _     = store.create(PathBuf::from("notes/note")).unwrap();
+        }
+
+        assert!(team.join("meeting").is_file());
+        assert!(!personal.join("team").exists());
+        assert!(personal.join("notes").join("note").is_file());
+
+        let store  = open(true);
+        let id     = StoreId::new(Some(personal.clone()), PathBuf::from("team/meeting")).unwrap();
+        let origin = Origin::of(&id).unwrap();
+        assert_eq!(fs::canonicalize(&team).unwrap(), origin.store);
+        assert_eq!(PathBuf::from("meeting"), origin.id);
+        assert!(origin.read_only);
+        assert_eq!(Some(id), origin.id_in(&personal).unwrap());
+
+        let note = Origin::of(&StoreId::new(Some(personal.clone()), PathBuf::from("notes/note")).unwrap());
+        assert_eq!(fs::canonicalize(&personal).unwrap(), note.unwrap().store);
+
+        assert!(store.get(PathBuf::from("team/meeting")).unwrap().is_some());
+        assert!(store.create(PathBuf::from("team/minutes")).is_err());
+        assert!(!team.join("minutes").exists());
+    }
+
+    #[test]
+    fn test_update_conflict() {
+        use error::StoreErrorKind as SEK;
+
+        let store     = get_store();
+        let mut entry = store.retrieve(PathBuf::from("test-conflict")).unwrap();
+        *entry.get_content_mut() = String::from("ours");
+        store.update(&mut entry).unwrap();
+        store.update(&mut entry).unwrap(); // no conflict with our own write
+
+        let _ = write_externally(&store, "test-conflict", "theirs");
+
+        *entry.get_content_mut() = String::from("ours again");
+        let theirs = match store.update(&mut entry) {
+            Err(e) => match *e.kind() {
+                SEK::EntryConflict(_, ref ours, ref theirs) => {
+                    assert_eq!("ours again", ours.get_content());
+                    assert_eq!("theirs", theirs.get_content());
+                    theirs.clone()
+                },
+                ref other => panic!("Unexpected error: {:?}", other),
+            },
+            Ok(_) => panic!("Conflict was not detected"),
+        };
+
+        *entry.get_content_mut() = String::from("merged");
+        store.update_resolved(&mut entry, &theirs).unwrap();
+        drop(entry);
+
+        assert_eq!("merged", store.get_copy(PathBuf::from("test-conflict")).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_transaction_conflict() {
+        use error::Result;
+        use error::StoreError as SE;
+        use error::StoreErrorKind as SEK;
+        use storeid::StoreId;
+
+        let store = get_store();
+        {
+            let mut b = store.create(PathBuf::from("test-conflict-b")).unwrap();
+            *b.get_content_mut() = String::from("ours");
+        }
+
+        let res : Result<()> = store.transaction(|tx| {
+            let mut a = tx.create(PathBuf::from("test-conflict-a"))?;
+            let mut b = tx.retrieve(PathBuf::from("test-conflict-b"))?;
+            *a.get_content_mut() = String::from("A");
+            *b.get_content_mut() = String::from("ours again");
+
+            let _ = write_externally(tx.store(), "test-conflict-b", "theirs");
+            Ok(())
+        });
+
+        let err  = res.unwrap_err();
+        let id   = StoreId::new(Some(store.path().clone()), PathBuf::from("test-conflict-b")).unwrap();
+        let ours = Box::new(super::Entry::new(id.clone()));
+        let msg  = format!("{}", SE::from_kind(SEK::EntryConflict(id, ours.clone(), ours)));
+        assert!(err.iter().any(|e| format!("{}", e) == msg), "Unexpected error: {:?}", err);
+
+        assert!(!store.backend.exists(&PathBuf::from("/.journal")).unwrap());
+        assert!(!store.backend.exists(&PathBuf::from("/test-conflict-a")).unwrap());
+        assert_eq!("theirs", store.retrieve(PathBuf::from("test-conflict-b")).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_no_conflict_with_own_writes_on_filesystem() {
+        use tempdir::TempDir;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir     = TempDir::new("imag-store-conflict").unwrap();
+        let backend = Box::new(FSFileAbstraction::new());
+        let store   = Store::new_with_backend(dir.path().to_path_buf(), &None, backend).unwrap();
+
+        for content in &["first", "second\n\nwith lines\n", ""] {
+            let mut entry = store.retrieve(PathBuf::from("test-fs")).unwrap();
+            *entry.get_content_mut() = String::from(*content);
+            store.update(&mut entry).unwrap();
+            store.update(&mut entry).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_drop_does_not_overwrite_changes_in_backend() {
+        use storeid::StoreId;
+        use super::CONFLICTS_DIR;
+
+        let store = get_store();
+        {
+            let mut entry = store.retrieve(PathBuf::from("test-conflict-drop")).unwrap();
+            *entry.get_content_mut() = String::from("ours");
+            let _ = write_externally(&store, "test-conflict-drop", "theirs");
+        }
+
+        // Their version is kept and the entry is not borrowed anymore
+        let entry = store.retrieve(PathBuf::from("test-conflict-drop")).unwrap();
+        assert_eq!("theirs", entry.get_content());
+
+        // Our version is saved, so it can be recovered
+        let dir       = StoreId::new_baseless(PathBuf::from(CONFLICTS_DIR)).unwrap();
+        let conflicts = store.hidden_ids(dir).unwrap();
+        assert_eq!(1, conflicts.len());
+        assert!(conflicts[0].local().to_str().unwrap().starts_with(".conflicts/test-conflict-drop~"));
+        assert_eq!("ours", store.get_hidden(conflicts[0].clone()).unwrap().unwrap().get_content());
+    }
+
+    #[test]
+    fn test_unchanged_entries_do_not_conflict() {
+        use error::StoreErrorKind as SEK;
+
+        let store = get_store();
+        {
+            let mut entry = store.retrieve(PathBuf::from("test-conflict-unchanged")).unwrap();
+            *entry.get_content_mut() = String::from("ours");
+        }
+
+        {
+            let mut entry = store.retrieve(PathBuf::from("test-conflict-unchanged")).unwrap();
+            let _ = write_externally(&store, "test-conflict-unchanged", "theirs");
+            store.update(&mut entry).unwrap();
+
+            *entry.get_content_mut() = String::from("ours again");
+            match store.update(&mut entry) {
+                Err(e) => assert!(is_match!(e.kind(), &SEK::EntryConflict(..))),
+                Ok(_)  => panic!("Conflict was not detected"),
+            }
+            store.discard(entry, true).unwrap();
+        }
+
+        let entry = store.retrieve(PathBuf::from("test-conflict-unchanged")).unwrap();
+        assert_eq!("theirs", entry.get_content());
+    }
+
+
+    #[test]
+    fn test_delete_moves_to_trash() {
+        use std::time::Duration;
+
+        let store = get_store();
+        write_content(&store, "test-trash", "deleted content");
+        store.delete(PathBuf::from("test-trash")).unwrap();
+
+        assert!(store.get(PathBuf::from("test-trash")).unwrap().is_none());
+        assert_eq!(0, store.entries().unwrap().count());
+
+        let trashed = store.deleted_entries().unwrap();
+        assert_eq!(1, trashed.len());
+        assert_eq!(PathBuf::from("test-trash"), *trashed[0].id().local());
+
+        {
+            let entry = store.restore_deleted(PathBuf::from("test-trash")).unwrap();
+            assert_eq!("deleted content", entry.get_content());
+        }
+        assert!(store.deleted_entries().unwrap().is_empty());
+        assert!(store.restore_deleted(PathBuf::from("test-trash")).is_err());
+
+        store.delete(PathBuf::from("test-trash")).unwrap();
+        assert_eq!(0, store.empty_trash(Duration::from_secs(3600)).unwrap());
+        assert_eq!(1, store.empty_trash(Duration::from_secs(0)).unwrap());
+        assert!(store.deleted_entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore_deleted_does_not_overwrite() {
+        let store = get_store();
+        write_content(&store, "test-trash", "old");
+        store.delete(PathBuf::from("test-trash")).unwrap();
+        write_content(&store, "test-trash", "new");
+
+        assert!(store.restore_deleted(PathBuf::from("test-trash")).is_err());
+        assert_eq!("new", store.get_copy(PathBuf::from("test-trash")).unwrap().get_content());
+        assert_eq!(1, store.deleted_entries().unwrap().len());
+    }
+
+    #[test]
+    fn test_trash_disabled() {
+        use toml::de::from_str as toml_from_str;
+
+        let config  = toml_from_str("[store.trash]\nenabled = false").unwrap();
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        let store   = Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap();
+
+        write_content(&store, "test-trash", "gone");
+        store.delete(PathBuf::from("test-trash")).unwrap();
+        assert!(store.deleted_entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_encrypted() {
+        use toml::de::from_str as toml_from_str;
+        use storeid::StoreId;
+
+        let config  = toml_from_str("[store.encryption]\ncollections = [\"diary\"]").unwrap();
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        let store   = Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap();
+
+        let id = |s: &str| StoreId::new_baseless(PathBuf::from(s)).unwrap();
+        assert!(store.is_encrypted(&id("diary/today")));
+        assert!(!store.is_encrypted(&id("notes/diary")));
+        assert!(!store.is_encrypted(&id("diaryx/today")));
+        assert!(!get_store().is_encrypted(&id("diary/today")));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypt_existing_store() {
+        use std::fs::File;
+        use std::io::Read;
+        use tempdir::TempDir;
+        use file_abstraction::CryptFileAbstraction;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-encrypt").unwrap();
+        let location  = dir.path().to_path_buf();
+        let mut store = Store::new(location.clone(), &None).unwrap();
+        {
+            let mut entry = store.create(PathBuf::from("diary/today")).unwrap();
+            *entry.get_content_mut() = String::from("dear diary");
+        }
+
+        let read_raw = || {
+            let mut s = String::new();
+            let _     = File::open(location.join("diary").join("today"))
+                .and_then(|mut f| f.read_to_string(&mut s))
+                .unwrap();
+            s
+        };
+        assert!(read_raw().contains("dear diary"));
+
+        // Resetting the backend would move nothing, the filesystem backend does not drain
+        let crypt = CryptFileAbstraction::new(Box::new(FSFileAbstraction::new()),
+                                              location.clone(),
+                                              "secret",
+                                              vec![String::from("diary")],
+                                              false).unwrap();
+        assert!(store.migrate_backend(Box::new(crypt)).is_ok());
+
+        assert!(!read_raw().contains("dear diary"));
+        assert_eq!("dear diary", store.get_copy(PathBuf::from("diary/today")).unwrap().get_content());
+    }
+
+    #[test]
+    fn test_archived_entries() {
+        let store = get_store_with_history(2);
+        write_content(&store, "test-archived", "old");
+        write_content(&store, "test-archived", "new");
+        write_content(&store, "test-deleted", "deleted");
+        store.delete(PathBuf::from("test-deleted")).unwrap();
+
+        let contents = store.archived_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.get_content().clone())
+            .collect::<Vec<_>>();
+
+        assert!(contents.contains(&String::from("old")));
+        assert!(contents.contains(&String::from("deleted")));
+        assert!(!contents.contains(&String::from("new")));
+    }
+
+    #[test]
+    fn test_hidden_entries() {
+        use storeid::StoreId;
+        use store::Entry;
+
+        let store = get_store();
+        let id    = |s: &str| StoreId::new_baseless(PathBuf::from(s)).unwrap();
+
+        let mut entry = Entry::new(id(".test-hidden/a/b").with_base(store.path().clone()));
+        *entry.get_content_mut() = String::from("hidden");
+        store.write_hidden(&entry).unwrap();
+
+        let read = store.get_hidden(id(".test-hidden/a/b")).unwrap().unwrap();
+        assert_eq!("hidden", read.get_content());
+        assert!(store.get_hidden(id(".test-hidden/a/c")).unwrap().is_none());
+        assert!(store.entries().unwrap().next().is_none());
+
+        let ids = store.hidden_ids(id(".test-hidden")).unwrap();
+        assert_eq!(vec![id(".test-hidden/a/b").with_base(store.path().clone())], ids);
+
+        assert!(store.get_hidden(id("test-hidden/a/b")).is_err());
+        assert!(store.write_hidden(&Entry::new(id("test-hidden/a/b"))).is_err());
+        assert!(store.retrieve(PathBuf::from("test-visible")).is_ok());
+        assert!(store.delete_hidden(id("test-visible")).is_err());
+
+        store.delete_hidden(id(".test-hidden/a/b")).unwrap();
+        assert!(store.get_hidden(id(".test-hidden/a/b")).unwrap().is_none());
+        assert!(store.hidden_ids(id(".test-hidden")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trash_on_filesystem() {
+        use std::time::Duration;
+        use tempdir::TempDir;
+        use toml::Value;
+        use toml_query::read::TomlValueReadExt;
+        use toml_query::insert::TomlValueInsertExt;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir     = TempDir::new("imag-store-trash").unwrap();
+        let backend = Box::new(FSFileAbstraction::new());
+        let store   = Store::new_with_backend(dir.path().to_path_buf(), &None, backend).unwrap();
+
+        {
+            let mut entry = store.retrieve(PathBuf::from("coll/test-trash")).unwrap();
+            let _ = entry.get_header_mut().insert("test.value", Value::Integer(1)).unwrap();
+            *entry.get_content_mut() = String::from("first\n\nwith lines\n");
+        }
+        store.delete(PathBuf::from("coll/test-trash")).unwrap();
+        {
+            let entry = store.restore_deleted(PathBuf::from("coll/test-trash")).unwrap();
+            assert_eq!("first\n\nwith lines\n", entry.get_content());
+            assert_eq!(Some(&Value::Integer(1)), entry.get_header().read("test.value").unwrap());
+        }
+
+        store.delete(PathBuf::from("coll/test-trash")).unwrap();
+        write_content(&store, "coll/test-trash", "second");
+        store.delete(PathBuf::from("coll/test-trash")).unwrap();
+
+        let trashed = store.deleted_entries().unwrap();
+        assert_eq!(2, trashed.len());
+        assert!(trashed.iter().all(|t| *t.id().local() == PathBuf::from("coll/test-trash")));
+
+        {
+            let entry = store.restore_deleted(PathBuf::from("coll/test-trash")).unwrap();
+            assert_eq!("second", entry.get_content());
+        }
+        store.delete(PathBuf::from("coll/test-trash")).unwrap();
+        assert_eq!(2, store.deleted_entries().unwrap().len());
+
+        assert_eq!(2, store.empty_trash(Duration::from_secs(0)).unwrap());
+        assert!(store.deleted_entries().unwrap().is_empty());
+    }
+
+}
@@ -119,6 +119,11 @@ impl StoreId {
         self.id.components()
     }
 
+    /// Get the base of the StoreId object, the path of the store, if it is set
+    pub fn base(&self) -> Option<&PathBuf> {
+        self.base.as_ref()
+    }
+
     /// Get the _local_ part of a StoreId object, as in "the part from the store root to the entry".
     pub fn local(&self) -> &PathBuf {
         &self.id
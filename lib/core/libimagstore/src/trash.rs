@@ -0,0 +1,254 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The trash of the store
+//!
+//! Unless `store.trash.enabled` is set to false, `Store::delete()` does not remove an entry right
+//! away, but moves it into the hidden `.trash` namespace of the store: a deleted `foo/bar` is kept
+//! as `.trash/foo/bar~<time>`, where `time` is the time of the deletion. If the same entry is
+//! deleted more than once in the same second, the later copies are kept as
+//! `.trash/foo/bar~<time>-<k>`.
+//!
+//! The file of the deleted entry is copied into the trash as it is, through the backend. The
+//! original id and the time of the deletion are only kept in the name of the copy, so the copy can
+//! be read back like any other entry file (and stays encrypted if the backend encrypts it).
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+use file_abstraction::FileAbstraction;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The directory the trashed entries live in, relative to the store path
+const TRASH_DIR : &'static str = ".trash";
+
+/// A deleted entry in the trash
+#[derive(Debug, Clone)]
+pub struct TrashedEntry {
+    id: StoreId,
+    deleted: u64,
+    sequence: u64,
+    path: PathBuf,
+}
+
+impl TrashedEntry {
+
+    /// The id the entry had before it was deleted
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// The time the entry was deleted, in seconds since the unix epoch
+    pub fn deleted(&self) -> u64 {
+        self.deleted
+    }
+
+}
+
+/// The seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The path the `k`th copy of `id` deleted at `deleted` is kept at
+fn trash_path(id: &StoreId, store_location: &PathBuf, deleted: u64, k: u64) -> Result<PathBuf> {
+    let local = id.local();
+    let name  = local
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| SE::from_kind(SEK::StoreIdHandlingError))?;
+
+    let mut path = store_location.join(TRASH_DIR);
+    if let Some(parent) = local.parent() {
+        path.push(parent);
+    }
+
+    if k == 0 {
+        path.push(format!("{}~{}", name, deleted));
+    } else {
+        path.push(format!("{}~{}-{}", name, deleted, k));
+    }
+
+    Ok(path)
+}
+
+/// Parse the path of a copy in the trash back into the original id, the time of the deletion and
+/// the number of the copy. Returns None if `path` is not named like a copy in the trash.
+fn parse_trash_path(store_location: &PathBuf, path: &PathBuf) -> Option<(StoreId, u64, u64)> {
+    let local = match path.strip_prefix(&store_location.join(TRASH_DIR)) {
+        Ok(local) => local,
+        Err(_)    => return None,
+    };
+
+    let name      = match local.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None       => return None,
+    };
+    let separator = match name.rfind('~') {
+        Some(pos) => pos,
+        None      => return None,
+    };
+
+    let mut suffix = name[separator + 1..].splitn(2, '-');
+    let deleted    = match suffix.next().and_then(|d| d.parse::<u64>().ok()) {
+        Some(deleted) => deleted,
+        None          => return None,
+    };
+    let k = match suffix.next() {
+        Some(k) => match k.parse::<u64>() {
+            Ok(k)  => k,
+            Err(_) => return None,
+        },
+        None => 0,
+    };
+
+    let original = local.with_file_name(&name[..separator]);
+    StoreId::new(Some(store_location.clone()), original)
+        .ok()
+        .map(|id| (id, deleted, k))
+}
+
+/// Copy the file of the entry `id`, which is about to be deleted, into the trash
+pub fn put(backend: &FileAbstraction, store_location: &PathBuf, id: &StoreId) -> Result<()> {
+    let deleted = now();
+    let source  = id.clone().with_base(store_location.clone()).into_pathbuf()?;
+
+    let mut k      = 0;
+    let mut target = trash_path(id, store_location, deleted, k)?;
+    while backend.exists(&target)? {
+        k += 1;
+        target = trash_path(id, store_location, deleted, k)?;
+    }
+
+    debug!("Moving {} to the trash: {:?}", id, target);
+    if let Some(parent) = target.parent() {
+        let _ = backend.create_dir_all(&parent.to_path_buf()).chain_err(|| SEK::TrashError)?;
+    }
+    backend.copy(&source, &target).chain_err(|| SEK::TrashError)
+}
+
+/// All entries in the trash, the most recently deleted last
+pub fn list(backend: &FileAbstraction, store_location: &PathBuf) -> Result<Vec<TrashedEntry>> {
+    let dir = store_location.join(TRASH_DIR);
+
+    let mut trashed = vec![];
+    for path in backend.pathes_recursively(dir.clone())? {
+        if !path.starts_with(&dir) || !backend.is_file(&path)? {
+            continue;
+        }
+
+        match parse_trash_path(store_location, &path) {
+            Some((id, deleted, k)) => trashed.push(TrashedEntry {
+                id: id,
+                deleted: deleted,
+                sequence: k,
+                path: path,
+            }),
+            None => debug!("Ignoring {:?} in the trash", path),
+        }
+    }
+
+    trashed.sort_by(|a, b| (a.deleted, a.sequence, &a.path).cmp(&(b.deleted, b.sequence, &b.path)));
+    Ok(trashed)
+}
+
+/// Load the deleted entry of `trashed`
+pub fn load(backend: &FileAbstraction, trashed: &TrashedEntry) -> Result<Entry> {
+    backend
+        .new_instance(trashed.path.clone())
+        .get_file_content(trashed.id.clone())
+        .chain_err(|| SEK::TrashError)
+}
+
+/// Remove `trashed` from the trash for good
+pub fn remove(backend: &FileAbstraction, trashed: &TrashedEntry) -> Result<()> {
+    debug!("Removing {} from the trash", trashed.id);
+    backend
+        .remove_file(&trashed.path)
+        .chain_err(|| SEK::TrashError)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use file_abstraction::FileAbstraction;
+    use file_abstraction::InMemoryFileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn write(backend: &FileAbstraction, id: &str, content: &str) -> StoreId {
+        let id        = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from(content);
+        backend.new_instance(id.clone().into_pathbuf().unwrap()).write_file_content(&entry).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_put_list_load_remove() {
+        let backend  = InMemoryFileAbstraction::new();
+        let location = PathBuf::from("/");
+
+        let id = write(&backend, "foo/bar", "first");
+        put(&backend, &location, &id).unwrap();
+        let _  = write(&backend, "foo/bar", "second");
+        put(&backend, &location, &id).unwrap();
+
+        let trashed = list(&backend, &location).unwrap();
+        assert_eq!(2, trashed.len());
+        assert!(trashed.iter().all(|t| *t.id() == id));
+
+        // Deleted twice in the same second, the later deletion is listed last
+        let newest = trashed.last().unwrap();
+        let loaded = load(&backend, newest).unwrap();
+        assert_eq!("second", loaded.get_content());
+        assert_eq!(&id, loaded.get_location());
+
+        remove(&backend, newest).unwrap();
+        assert_eq!(1, list(&backend, &location).unwrap().len());
+    }
+
+    #[test]
+    fn test_parse_trash_path() {
+        let location = PathBuf::from("/store");
+        let id       = StoreId::new(Some(location.clone()), PathBuf::from("foo/bar~baz")).unwrap();
+
+        let first = trash_path(&id, &location, 1514761200, 0).unwrap();
+        assert_eq!(PathBuf::from("/store/.trash/foo/bar~baz~1514761200"), first);
+        assert_eq!(Some((id.clone(), 1514761200, 0)), parse_trash_path(&location, &first));
+
+        let third = trash_path(&id, &location, 1514761200, 2).unwrap();
+        assert_eq!(Some((id.clone(), 1514761200, 2)), parse_trash_path(&location, &third));
+
+        assert!(parse_trash_path(&location, &PathBuf::from("/store/.trash/foo/bar")).is_none());
+        assert!(parse_trash_path(&location, &PathBuf::from("/store/foo/bar~1")).is_none());
+    }
+
+}
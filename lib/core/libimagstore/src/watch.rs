@@ -0,0 +1,367 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Watching the store for changes
+//!
+//! `Store::watch()` returns a `Watcher`, which yields a `StoreEvent` for each entry which is
+//! created, modified or removed. Changes made through the same `Store` object are reported as
+//! they happen. If the backend knows the modification times of the entries (the filesystem
+//! backend does), changes made by other processes are detected by comparing the modification
+//! times of all entries periodically. These events are marked as external.
+//!
+//! Such a check lists all entries of the store and reads the modification time of each of them
+//! (one `stat()` per entry with the filesystem backend), so its cost grows with the size of the
+//! store. That is why the store is only checked every `DEFAULT_INTERVAL_MS` by default.
+//!
+//! A move is reported as a removal of the old and a creation of the new entry.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use error::Result;
+use store::Store;
+use storeid::StoreId;
+
+/// What happened to an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StoreEventKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// A change of an entry in the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreEvent {
+    kind: StoreEventKind,
+    id: StoreId,
+    external: bool,
+}
+
+impl StoreEvent {
+
+    pub fn new(kind: StoreEventKind, id: StoreId, external: bool) -> StoreEvent {
+        StoreEvent {
+            kind: kind,
+            id: id,
+            external: external,
+        }
+    }
+
+    pub fn kind(&self) -> StoreEventKind {
+        self.kind
+    }
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// Whether the change was made outside of the `Store` object which is watched
+    pub fn is_external(&self) -> bool {
+        self.external
+    }
+
+}
+
+/// The default interval in which the store is checked for changes by other processes
+///
+/// Each check walks the whole store, see the module documentation.
+pub const DEFAULT_INTERVAL_MS : u64 = 30 * 1000;
+
+type Snapshot = HashMap<StoreId, SystemTime>;
+
+/// A stream of `StoreEvent`s
+///
+/// Iterating over the watcher blocks until the next event happens. `Watcher::poll()` returns the
+/// events which happened since the last call without blocking.
+#[derive(Debug)]
+pub struct Watcher<'a> {
+    store: &'a Store,
+    receiver: Receiver<StoreEvent>,
+    pending: VecDeque<StoreEvent>,
+
+    /// The modification times of all entries when the store was checked last, if the backend
+    /// knows them
+    snapshot: Option<Snapshot>,
+
+    interval: Duration,
+    last_scan: Instant,
+}
+
+impl<'a> Watcher<'a> {
+
+    /// Create a watcher which receives the events of `store` via `receiver`
+    ///
+    /// Use `Store::watch()` instead of calling this directly.
+    pub fn new(store: &'a Store, receiver: Receiver<StoreEvent>) -> Result<Watcher<'a>> {
+        Ok(Watcher {
+            store: store,
+            receiver: receiver,
+            pending: VecDeque::new(),
+            snapshot: scan(store)?,
+            interval: Duration::from_millis(DEFAULT_INTERVAL_MS),
+            last_scan: Instant::now(),
+        })
+    }
+
+    /// Set the interval in which the store is checked for changes by other processes
+    pub fn with_interval(mut self, interval: Duration) -> Watcher<'a> {
+        self.interval = interval;
+        self
+    }
+
+    /// Get all events which happened since the last call, without blocking
+    ///
+    /// This checks the store for changes by other processes on each call, regardless of the
+    /// interval.
+    pub fn poll(&mut self) -> Result<Vec<StoreEvent>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event)                       => self.internal_event(event)?,
+                Err(TryRecvError::Empty)        |
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        self.external_events()?;
+        Ok(self.pending.drain(..).collect())
+    }
+
+    fn internal_event(&mut self, event: StoreEvent) -> Result<()> {
+        // Remember the modification time, so the change is not reported again as external change
+        if let Some(ref mut snapshot) = self.snapshot {
+            match event.kind() {
+                StoreEventKind::Removed => {
+                    let _ = snapshot.remove(event.id());
+                },
+                _ => if let Some(time) = self.store.modification_time(event.id())? {
+                    let _ = snapshot.insert(event.id().clone(), time);
+                },
+            }
+        }
+
+        self.pending.push_back(event);
+        Ok(())
+    }
+
+    fn external_events(&mut self) -> Result<()> {
+        let old = match self.snapshot {
+            Some(ref snapshot) => snapshot,
+            None               => return Ok(()), // backend cannot be changed by others
+        };
+
+        let new = match scan(self.store)? {
+            Some(new) => new,
+            None      => return Ok(()),
+        };
+
+        for (id, time) in new.iter() {
+            let kind = match old.get(id) {
+                None                 => Some(StoreEventKind::Created),
+                Some(t) if t != time => Some(StoreEventKind::Modified),
+                Some(_)              => None,
+            };
+
+            if let Some(kind) = kind {
+                self.pending.push_back(StoreEvent::new(kind, id.clone(), true));
+            }
+        }
+
+        for id in old.keys().filter(|id| !new.contains_key(id)) {
+            self.pending.push_back(StoreEvent::new(StoreEventKind::Removed, id.clone(), true));
+        }
+
+        self.snapshot = Some(new);
+        self.last_scan = Instant::now();
+        Ok(())
+    }
+
+    /// The time until the store has to be checked for changes by other processes again
+    fn until_next_scan(&self) -> Duration {
+        let elapsed = self.last_scan.elapsed();
+        if elapsed >= self.interval {
+            Duration::from_millis(0)
+        } else {
+            self.interval - elapsed
+        }
+    }
+
+}
+
+impl<'a> Iterator for Watcher<'a> {
+    type Item = Result<StoreEvent>;
+
+    /// Wait for the next event
+    ///
+    /// This only returns `None` if the store stopped sending events.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            match self.receiver.recv_timeout(self.until_next_scan()) {
+                Ok(event) => if let Err(e) = self.internal_event(event) {
+                    return Some(Err(e));
+                },
+                Err(RecvTimeoutError::Timeout)      => {},
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+
+            // Only walk the store once per interval, not after each change of this process
+            if self.until_next_scan() == Duration::from_millis(0) {
+                if let Err(e) = self.external_events() {
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+
+}
+
+/// Get the modification times of all entries, or `None` if the backend does not know them
+fn scan(store: &Store) -> Result<Option<Snapshot>> {
+    let mut snapshot = HashMap::new();
+    for id in store.entries()? {
+        match store.modification_time(&id)? {
+            Some(time) => { let _ = snapshot.insert(id, time); },
+            None       => return Ok(None),
+        }
+    }
+    Ok(Some(snapshot))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use store::Store;
+    use storeid::StoreId;
+    use file_abstraction::FSFileAbstraction;
+    use file_abstraction::InMemoryFileAbstraction;
+    use super::*;
+
+    fn events(watcher: &mut Watcher) -> Vec<(StoreEventKind, String, bool)> {
+        watcher
+            .poll()
+            .unwrap()
+            .into_iter()
+            .map(|e| {
+                let id = e.id().clone().without_base().to_str().unwrap();
+                (e.kind(), id, e.is_external())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_watch_reports_changes_of_the_store() {
+        let backend     = Box::new(InMemoryFileAbstraction::new());
+        let store       = Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap();
+        let mut watcher = store.watch().unwrap();
+
+        {
+            let _ = store.create(PathBuf::from("a")).unwrap();
+        }
+        {
+            let mut entry = store.retrieve(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("changed");
+        }
+
+        let old = StoreId::new_baseless(PathBuf::from("a")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("b")).unwrap();
+        store.move_by_id(old, new).unwrap();
+        store.delete(PathBuf::from("b")).unwrap();
+
+        assert_eq!(events(&mut watcher), vec![
+            (StoreEventKind::Created,  String::from("a"), false),
+            (StoreEventKind::Modified, String::from("a"), false),
+            (StoreEventKind::Removed,  String::from("a"), false),
+            (StoreEventKind::Created,  String::from("b"), false),
+            (StoreEventKind::Removed,  String::from("b"), false),
+        ]);
+        assert!(events(&mut watcher).is_empty());
+    }
+
+    #[test]
+    fn test_watch_reports_changes_of_other_processes() {
+        let dir = TempDir::new("imag-watch").unwrap();
+        let new_store = || {
+            let backend = Box::new(FSFileAbstraction::new());
+            Store::new_with_backend(dir.path().to_path_buf(), &None, backend).unwrap()
+        };
+
+        let watched     = new_store();
+        let other       = new_store();
+        let mut watcher = watched.watch().unwrap();
+
+        {
+            let _ = other.create(PathBuf::from("a")).unwrap();
+        }
+        assert_eq!(events(&mut watcher), vec![(StoreEventKind::Created, String::from("a"), true)]);
+
+        // Changes of the watched store are not reported twice
+        {
+            let _ = watched.create(PathBuf::from("b")).unwrap();
+        }
+        assert_eq!(events(&mut watcher), vec![(StoreEventKind::Created, String::from("b"), false)]);
+
+        other.delete(PathBuf::from("a")).unwrap();
+        assert_eq!(events(&mut watcher), vec![(StoreEventKind::Removed, String::from("a"), true)]);
+    }
+
+    #[test]
+    fn test_watch_iterator_checks_for_external_changes_once_per_interval() {
+        let dir = TempDir::new("imag-watch").unwrap();
+        let new_store = || {
+            let backend = Box::new(FSFileAbstraction::new());
+            Store::new_with_backend(dir.path().to_path_buf(), &None, backend).unwrap()
+        };
+
+        let watched     = new_store();
+        let other       = new_store();
+        let mut watcher = watched.watch().unwrap().with_interval(Duration::from_millis(50));
+
+        {
+            let _ = other.create(PathBuf::from("a")).unwrap();
+        }
+        {
+            let _ = watched.create(PathBuf::from("b")).unwrap();
+        }
+
+        // The change of the watched store does not wait for the interval, the store is not
+        // walked for it either
+        let event = watcher.next().unwrap().unwrap();
+        assert_eq!((StoreEventKind::Created, false), (event.kind(), event.is_external()));
+        assert_eq!(PathBuf::from("b"), *event.id().local());
+
+        let event = watcher.next().unwrap().unwrap();
+        assert_eq!((StoreEventKind::Created, true), (event.kind(), event.is_external()));
+        assert_eq!(PathBuf::from("a"), *event.id().local());
+    }
+
+}
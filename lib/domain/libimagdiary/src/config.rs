@@ -20,6 +20,7 @@
 use toml::Value;
 
 use libimagrt::runtime::Runtime;
+use libimagstore::schema::{Schema, FieldType};
 
 use toml_query::read::TomlValueReadExt;
 
@@ -39,3 +40,11 @@ pub fn get_diary_config_section<'a>(rt: &'a Runtime) -> Option<&'a Value> {
         Err(_) => None,
     })
 }
+
+/// The schema of the "diary" section of the configuration
+pub fn config_schema() -> Schema {
+    Schema::new("diary")
+        .optional("default_diary", FieldType::String)
+        .optional("diaries.*.timed", FieldType::OneOf(vec!["h", "hourly", "m", "minutely"]))
+        .deny_unknown_fields()
+}
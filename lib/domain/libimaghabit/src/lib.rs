@@ -37,6 +37,7 @@ pub mod habit;
 pub mod instance;
 pub mod iter;
 pub mod result;
+pub mod schema;
 pub mod store;
 pub mod util;
 
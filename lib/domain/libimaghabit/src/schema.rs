@@ -0,0 +1,44 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The schemas of the header sections written by libimaghabit
+//!
+//! Templates and instances have sections of their own, so each is checked only on the entries
+//! which are of its kind.
+
+use libimagstore::schema::{self, Schema, FieldType};
+use libimagstore::store::Result;
+
+/// Register the schemas of the `habit.template` and `habit.instance` sections
+pub fn register_schemas() -> Result<()> {
+    let _ = schema::register(Schema::new("habit.template")
+        .required("name", FieldType::String)
+        .required("basedate", FieldType::Date)
+        .required("recurspec", FieldType::String)
+        .required("comment", FieldType::String)
+        .optional("until", FieldType::Date)
+        .optional("is_habit_template", FieldType::Boolean))?;
+
+    schema::register(Schema::new("habit.instance")
+        .required("name", FieldType::String)
+        .required("date", FieldType::Date)
+        .required("comment", FieldType::String)
+        .optional("is_habit_instance", FieldType::Boolean))
+}
@@ -49,6 +49,7 @@ extern crate task_hookrs;
 module_entry_path_mod!("todo");
 
 pub mod error;
+pub mod schema;
 pub mod task;
 pub mod taskstore;
 
@@ -28,6 +28,8 @@ use task_hookrs::task::Task as TTask;
 use task_hookrs::import::{import_task, import_tasks};
 
 use libimagstore::store::{FileLockEntry, Store};
+use libimagstore::error::StoreError;
+use libimagstore::error::StoreErrorKind as SEK;
 use libimagstore::storeid::{IntoStoreId, StoreIdIterator};
 use module_path::ModuleEntryPath;
 
@@ -95,12 +97,28 @@ impl<'a> TaskStore<'a> for Store {
 
     /// Get a task from an UUID.
     ///
+    /// If "todo.uuid" is indexed by the store (see `store.index` in the configuration), the index
+    /// is used to find the task. Otherwise, the task is expected at the path derived from the UUID.
+    ///
     /// If there is no task with this UUID, this returns `Ok(None)`.
     fn get_task_from_uuid(&'a self, uuid: Uuid) -> Result<Option<FileLockEntry<'a>>> {
-        ModuleEntryPath::new(format!("taskwarrior/{}", uuid))
-            .into_storeid()
-            .and_then(|store_id| self.get(store_id))
-            .chain_err(|| TEK::StoreError)
+        let uuid_value = Value::String(format!("{}", uuid));
+
+        match self.find_by_header("todo.uuid", &uuid_value) {
+            Ok(mut ids) => match ids.next() {
+                Some(id) => self.get(id).chain_err(|| TEK::StoreError),
+                None     => Ok(None),
+            },
+
+            Err(ref e) if is_index_missing(e) => {
+                ModuleEntryPath::new(format!("taskwarrior/{}", uuid))
+                    .into_storeid()
+                    .and_then(|store_id| self.get(store_id))
+                    .chain_err(|| TEK::StoreError)
+            },
+
+            Err(e) => Err(e).chain_err(|| TEK::StoreError),
+        }
     }
 
     /// Same as Task::get_from_import() but uses Store::retrieve() rather than Store::get(), to
@@ -205,3 +223,9 @@ impl<'a> TaskStore<'a> for Store {
 
 }
 
+fn is_index_missing(e: &StoreError) -> bool {
+    match *e.kind() {
+        SEK::HeaderIndexMissing(_) => true,
+        _                          => false,
+    }
+}
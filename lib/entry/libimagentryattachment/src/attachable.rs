@@ -0,0 +1,301 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+
+use attachment::Attachment;
+use blob;
+use error::AttachmentErrorKind as AEK;
+use error::AttachmentError as AE;
+use error::ResultExt;
+use error::Result;
+use mime;
+
+const ATTACHMENTS_HEADER_PATH : &'static str = "attachments.files";
+
+/// Attaching files to entries
+///
+/// The attachments of an entry are listed in the header, as `attachments.files`:
+///
+/// ```toml
+/// [[attachments.files]]
+/// name = "receipt.pdf"
+/// hash = "<SHA-256 of the content>"
+/// mime = "application/pdf"
+/// size = 20480
+/// ```
+///
+/// The content itself is stored as blob in the store, see the `blob` module. The names of the
+/// attachments of an entry are unique: attaching a file under a name which is in use replaces
+/// the attachment.
+pub trait Attachable {
+
+    fn attachments(&self) -> Result<Vec<Attachment>>;
+    fn attachment(&self, name: &str) -> Result<Option<Attachment>>;
+
+    /// Attach the file at `path`, named after the file name
+    ///
+    /// If `mime` is not passed, the MIME type is guessed from the file extension.
+    fn attach(&mut self, store: &Store, path: &Path, mime: Option<String>) -> Result<Attachment>;
+
+    /// Attach everything `content` yields as `name`
+    fn attach_from<R: Read>(&mut self, store: &Store, name: String, mime: String, content: R)
+        -> Result<Attachment>;
+
+    /// Remove the attachment `name` from the entry
+    ///
+    /// The blob stays in the store until the next garbage collection, as other entries may still
+    /// reference it.
+    fn detach(&mut self, name: &str) -> Result<Option<Attachment>>;
+
+}
+
+impl Attachable for Entry {
+
+    fn attachments(&self) -> Result<Vec<Attachment>> {
+        self.read_section::<Vec<Attachment>>(ATTACHMENTS_HEADER_PATH)
+            .chain_err(|| AEK::HeaderReadError)
+            .map(|attachments| attachments.unwrap_or_else(Vec::new))
+    }
+
+    fn attachment(&self, name: &str) -> Result<Option<Attachment>> {
+        self.attachments().map(|atts| atts.into_iter().find(|a| a.name() == name))
+    }
+
+    fn attach(&mut self, store: &Store, path: &Path, mime: Option<String>) -> Result<Attachment> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| AE::from_kind(AEK::NoFileName(path.to_path_buf())))?;
+        let mime = mime.unwrap_or_else(|| String::from(mime::guess(path)));
+        let file = File::open(path)?;
+
+        self.attach_from(store, name, mime, file)
+    }
+
+    fn attach_from<R: Read>(&mut self, store: &Store, name: String, mime: String, content: R)
+        -> Result<Attachment>
+    {
+        let (hash, size) = blob::store_blob(store, content)?;
+        let attachment   = Attachment::new(name, hash, mime, size);
+        debug!("Attaching {:?} to {}", attachment, self.get_location());
+
+        let mut attachments = self.attachments()?;
+        attachments.retain(|a| a.name() != attachment.name());
+        attachments.push(attachment.clone());
+
+        self.write_section(ATTACHMENTS_HEADER_PATH, &attachments)
+            .chain_err(|| AEK::HeaderWriteError)
+            .map(|_| attachment)
+    }
+
+    fn detach(&mut self, name: &str) -> Result<Option<Attachment>> {
+        let mut attachments = self.attachments()?;
+        let position = attachments.iter().position(|a| a.name() == name);
+
+        match position {
+            None      => Ok(None),
+            Some(pos) => {
+                let removed = attachments.remove(pos);
+                self.write_section(ATTACHMENTS_HEADER_PATH, &attachments)
+                    .chain_err(|| AEK::HeaderWriteError)
+                    .map(|_| Some(removed))
+            },
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+    use toml::de::from_str as toml_from_str;
+
+    use libimagstore::store::Store;
+    use libimagstore::file_abstraction::FSFileAbstraction;
+    use libimagstore::file_abstraction::InMemoryFileAbstraction;
+
+    use super::*;
+    use blob;
+
+    fn setup_logging() {
+        use env_logger;
+        let _ = env_logger::init().unwrap_or(());
+    }
+
+    fn get_store() -> Store {
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn read_attachment(store: &Store, attachment: &Attachment) -> String {
+        let mut s = String::new();
+        attachment.open(store).unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn test_attach_and_read_back() {
+        setup_logging();
+        let store = get_store();
+        let mut entry = store.create(PathBuf::from("test_attach")).unwrap();
+
+        assert!(entry.attachments().unwrap().is_empty());
+
+        let a = entry.attach_from(&store,
+                                  String::from("receipt.txt"),
+                                  String::from("text/plain"),
+                                  "42 EUR".as_bytes())
+            .unwrap();
+
+        assert_eq!(6, a.size());
+        assert!(blob::is_hash(a.hash()));
+        assert_eq!(vec![a.clone()], entry.attachments().unwrap());
+        assert_eq!("42 EUR", read_attachment(&store, &a));
+    }
+
+    #[test]
+    fn test_same_content_is_stored_once() {
+        setup_logging();
+        let store = get_store();
+        let mut one = store.create(PathBuf::from("test_one")).unwrap();
+        let mut two = store.create(PathBuf::from("test_two")).unwrap();
+
+        let a = one.attach_from(&store, String::from("a"), String::from("text/plain"), "x".as_bytes());
+        let b = two.attach_from(&store, String::from("b"), String::from("text/plain"), "x".as_bytes());
+
+        assert_eq!(a.unwrap().hash(), b.unwrap().hash());
+        assert_eq!(1, blob::blobs(&store).unwrap().len());
+    }
+
+    #[test]
+    fn test_attaching_a_name_again_replaces() {
+        setup_logging();
+        let store = get_store();
+        let mut entry = store.create(PathBuf::from("test_replace")).unwrap();
+
+        let mime = String::from("text/plain");
+        entry.attach_from(&store, String::from("a"), mime.clone(), "old".as_bytes()).unwrap();
+        let new = entry.attach_from(&store, String::from("a"), mime, "new".as_bytes()).unwrap();
+
+        assert_eq!(vec![new], entry.attachments().unwrap());
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        setup_logging();
+        let store = get_store();
+
+        let (kept, dropped) = {
+            let mut entry = store.create(PathBuf::from("test_gc")).unwrap();
+            let mime = String::from("text/plain");
+            let kept = entry.attach_from(&store, String::from("kept"), mime.clone(), "1".as_bytes());
+            let drop = entry.attach_from(&store, String::from("dropped"), mime, "2".as_bytes());
+            assert!(entry.detach("dropped").unwrap().is_some());
+            assert!(entry.detach("dropped").unwrap().is_none());
+            (kept.unwrap(), drop.unwrap())
+        };
+
+        assert_eq!(vec![dropped.hash().clone()], blob::collect_garbage(&store, Duration::from_secs(0), true).unwrap());
+        assert_eq!(2, blob::blobs(&store).unwrap().len());
+
+        assert_eq!(vec![dropped.hash().clone()], blob::collect_garbage(&store, Duration::from_secs(0), false).unwrap());
+        assert_eq!(vec![kept.hash().clone()], blob::blobs(&store).unwrap());
+        assert_eq!("1", read_attachment(&store, &kept));
+        assert!(dropped.open(&store).is_err());
+    }
+
+    #[test]
+    fn test_garbage_collection_keeps_attachments_of_revisions() {
+        setup_logging();
+        let config  = toml_from_str("[store.history]\nrevisions = 2").unwrap();
+        let backend = Box::new(InMemoryFileAbstraction::new());
+        let store   = Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap();
+
+        let attachment = {
+            let mut entry = store.create(PathBuf::from("test_gc_history")).unwrap();
+            let mime = String::from("text/plain");
+            let a = entry.attach_from(&store, String::from("a"), mime, "1".as_bytes()).unwrap();
+            store.update(&mut entry).unwrap();
+            let _ = entry.detach("a").unwrap();
+            store.update(&mut entry).unwrap();
+            a
+        };
+
+        assert!(blob::collect_garbage(&store, Duration::from_secs(0), false).unwrap().is_empty());
+        assert_eq!("1", read_attachment(&store, &attachment));
+    }
+
+    #[test]
+    fn test_garbage_collection_keeps_attachments_in_trash() {
+        setup_logging();
+        let store = get_store();
+
+        let attachment = {
+            let mut entry = store.create(PathBuf::from("test_gc_trash")).unwrap();
+            let mime = String::from("text/plain");
+            entry.attach_from(&store, String::from("a"), mime, "1".as_bytes()).unwrap()
+        };
+        store.delete(PathBuf::from("test_gc_trash")).unwrap();
+
+        assert!(blob::collect_garbage(&store, Duration::from_secs(0), false).unwrap().is_empty());
+        assert_eq!("1", read_attachment(&store, &attachment));
+
+        assert_eq!(1, store.empty_trash(Duration::from_secs(0)).unwrap());
+        let removed = blob::collect_garbage(&store, Duration::from_secs(0), false).unwrap();
+        assert_eq!(vec![attachment.hash().clone()], removed);
+    }
+
+    #[test]
+    fn test_garbage_collection_keeps_recent_blobs_on_filesystem() {
+        setup_logging();
+        let dir     = TempDir::new("imag-attach").unwrap();
+        let backend = Box::new(FSFileAbstraction::new());
+        let store   = Store::new_with_backend(dir.path().to_path_buf(), &None, backend).unwrap();
+
+        let content  = (0..200).map(|i| (i % 256) as u8).collect::<Vec<u8>>();
+        let (hash, size) = blob::store_blob(&store, &content[..]).unwrap();
+        assert_eq!(200, size);
+        assert!(dir.path().join(".blobs").join(&hash[..2]).join(&hash[2..]).is_file());
+
+        let mut read = vec![];
+        blob::open_blob(&store, &hash).unwrap().read_to_end(&mut read).unwrap();
+        assert_eq!(content, read);
+
+        // Not attached to any entry, but written just now
+        let grace = Duration::from_secs(blob::DEFAULT_GRACE_PERIOD_SECS);
+        assert!(blob::collect_garbage(&store, grace, false).unwrap().is_empty());
+        assert_eq!(vec![hash.clone()], blob::blobs(&store).unwrap());
+
+        assert_eq!(vec![hash.clone()], blob::collect_garbage(&store, Duration::from_secs(0), false).unwrap());
+        assert!(blob::blobs(&store).unwrap().is_empty());
+        assert!(blob::open_blob(&store, &hash).is_err());
+    }
+
+}
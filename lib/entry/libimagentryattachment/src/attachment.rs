@@ -0,0 +1,82 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::io::Cursor;
+
+use libimagstore::store::Store;
+use libimagstore::store::HeaderSection;
+
+use blob;
+use error::Result;
+
+/// An attachment of an entry, as listed in the header of the entry
+///
+/// The content of the attachment lives in the blob named after `hash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    name: String,
+    hash: String,
+    mime: String,
+    size: u64,
+}
+
+impl Attachment {
+
+    pub fn new(name: String, hash: String, mime: String, size: u64) -> Attachment {
+        Attachment {
+            name: name,
+            hash: hash,
+            mime: mime,
+            size: size,
+        }
+    }
+
+    /// The name of the attachment, unique within the entry
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// The SHA-256 hash of the content, which is also the name of the blob
+    pub fn hash(&self) -> &String {
+        &self.hash
+    }
+
+    pub fn mime(&self) -> &String {
+        &self.mime
+    }
+
+    /// The size of the content in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Open the blob holding the content of the attachment for reading
+    pub fn open(&self, store: &Store) -> Result<Cursor<Vec<u8>>> {
+        blob::open_blob(store, &self.hash)
+    }
+
+}
+
+
+impl HeaderSection for Attachment {
+    fn type_name() -> String {
+        String::from("Attachment")
+    }
+}
@@ -0,0 +1,231 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The content-addressed blob storage
+//!
+//! Blobs live in the hidden `.blobs` directory of the store, which is never seen as a collection
+//! of entries. The blob with the SHA-256 hash `h` is stored as `.blobs/<h[..2]>/<h[2..]>`, so that
+//! no single directory grows too large.
+//!
+//! Blobs are written through the backend of the store (see `Store::write_hidden()`), so they end
+//! up wherever the entries of the store do and are encrypted if the store is. A blob is stored
+//! like an entry, with the base64 encoded content as its content. As the content has to be hashed
+//! before the blob can be named, it is read into memory completely. When a blob is read, its hash
+//! is checked, so a blob which was not written completely is never handed out.
+
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use base64;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use attachable::Attachable;
+use error::AttachmentErrorKind as AEK;
+use error::AttachmentError as AE;
+use error::ResultExt;
+use error::Result;
+
+const BLOB_DIR : &'static str = ".blobs";
+
+/// The length of the lines of the base64 encoded content
+const LINE_LENGTH : usize = 76;
+
+/// The grace period `imag-attach gc` uses, see `collect_garbage()`
+pub const DEFAULT_GRACE_PERIOD_SECS : u64 = 60 * 60;
+
+/// Check whether `s` looks like the name of a blob (a hex SHA-256 hash)
+pub fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_digit(16) && !c.is_uppercase())
+}
+
+/// The path of the blob `hash`, relative to the store
+pub fn blob_path(hash: &str) -> PathBuf {
+    let dir = PathBuf::from(BLOB_DIR);
+    if hash.len() > 2 {
+        let (prefix, rest) = hash.split_at(2);
+        dir.join(prefix).join(rest)
+    } else {
+        dir.join(hash)
+    }
+}
+
+fn blob_id(store: &Store, hash: &str) -> Result<StoreId> {
+    StoreId::new(Some(store.path().clone()), blob_path(hash)).map_err(From::from)
+}
+
+fn hash_of(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(content);
+    hasher.result_str()
+}
+
+fn encode(content: &[u8]) -> String {
+    let encoded     = base64::encode(content);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / LINE_LENGTH + 1);
+
+    for (i, c) in encoded.chars().enumerate() {
+        if i > 0 && i % LINE_LENGTH == 0 {
+            wrapped.push('\n');
+        }
+        wrapped.push(c);
+    }
+    wrapped.push('\n');
+    wrapped
+}
+
+fn decode(content: &str) -> Option<Vec<u8>> {
+    base64::decode(&content.lines().collect::<String>()).ok()
+}
+
+/// Store everything `content` yields as blob in `store`
+///
+/// If the blob is in the store already, it is written again all the same, so the garbage
+/// collection sees it as recently written.
+///
+/// # Return value
+///
+/// The hash and the size of the blob.
+pub fn store_blob<R: Read>(store: &Store, mut content: R) -> Result<(String, u64)> {
+    let mut buffer = vec![];
+    let size = content.read_to_end(&mut buffer).chain_err(|| AEK::BlobWriteError)? as u64;
+    let hash = hash_of(&buffer);
+
+    let mut blob = Entry::new(blob_id(store, &hash)?);
+    *blob.get_content_mut() = encode(&buffer);
+
+    debug!("Writing blob {}", hash);
+    store.write_hidden(&blob)
+        .chain_err(|| AEK::BlobWriteError)
+        .map(|_| (hash, size))
+}
+
+/// Open the blob `hash` in `store` for reading
+pub fn open_blob(store: &Store, hash: &str) -> Result<Cursor<Vec<u8>>> {
+    if !is_hash(hash) {
+        return Err(AE::from_kind(AEK::BlobNotFound(String::from(hash))));
+    }
+
+    let blob = store.get_hidden(blob_id(store, hash)?)
+        .chain_err(|| AEK::BlobReadError(String::from(hash)))?
+        .ok_or_else(|| AE::from_kind(AEK::BlobNotFound(String::from(hash))))?;
+
+    match decode(blob.get_content()) {
+        Some(content) => if hash_of(&content) == hash {
+            Ok(Cursor::new(content))
+        } else {
+            Err(AE::from_kind(AEK::BlobReadError(String::from(hash))))
+        },
+        None => Err(AE::from_kind(AEK::BlobReadError(String::from(hash)))),
+    }
+}
+
+/// Get the hashes of all blobs in `store`
+pub fn blobs(store: &Store) -> Result<Vec<String>> {
+    let dir = StoreId::new(Some(store.path().clone()), PathBuf::from(BLOB_DIR))?;
+
+    let mut hashes = store
+        .hidden_ids(dir)?
+        .into_iter()
+        .filter_map(|id| {
+            id.local()
+                .strip_prefix(BLOB_DIR)
+                .ok()
+                .map(|rest| rest.components()
+                     .filter_map(|c| c.as_os_str().to_str())
+                     .collect::<String>())
+        })
+        .filter(|hash| is_hash(hash))
+        .collect::<Vec<_>>();
+
+    hashes.sort();
+    Ok(hashes)
+}
+
+/// Remove all blobs from the store which are not attached to any entry
+///
+/// All entries of the store are read for this, as well as the revisions in the history and the
+/// entries in the trash (see `Store::archived_entries()`), as they might be restored. If one of
+/// them cannot be read (for example because it is borrowed at the moment), nothing is removed,
+/// as the blobs it references are not known.
+///
+/// Blobs which were written less than `grace_period` ago are kept as well: they might belong to
+/// an attachment which is not written to its entry yet. If the backend does not know when a blob
+/// was written, it is removed.
+///
+/// # Return value
+///
+/// The hashes of the removed blobs. With `dry_run`, the blobs which would be removed are returned,
+/// but left in the store.
+pub fn collect_garbage(store: &Store, grace_period: Duration, dry_run: bool)
+    -> Result<Vec<String>>
+{
+    let mut referenced = HashSet::new();
+    {
+        let mut reference = |entry: Entry| -> Result<()> {
+            for attachment in entry.attachments()? {
+                referenced.insert(attachment.hash().clone());
+            }
+            Ok(())
+        };
+
+        for id in store.entries()? {
+            let _ = reference(store.get_copy(id)?)?;
+        }
+        for entry in store.archived_entries()? {
+            let _ = reference(entry)?;
+        }
+    }
+
+    let now         = SystemTime::now();
+    let mut garbage = vec![];
+    for hash in blobs(store)? {
+        if referenced.contains(&hash) {
+            continue;
+        }
+
+        let recent = store
+            .modification_time(&blob_id(store, &hash)?)?
+            .map(|written| now.duration_since(written).map(|age| age < grace_period).unwrap_or(true))
+            .unwrap_or(false);
+
+        if recent {
+            debug!("Keeping recently written blob {}", hash);
+        } else {
+            garbage.push(hash);
+        }
+    }
+
+    if !dry_run {
+        for hash in garbage.iter() {
+            debug!("Removing unreferenced blob {}", hash);
+            let _ = store.delete_hidden(blob_id(store, hash)?)?;
+        }
+    }
+
+    Ok(garbage)
+}
+
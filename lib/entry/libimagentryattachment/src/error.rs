@@ -0,0 +1,69 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use std::path::PathBuf;
+
+error_chain! {
+    types {
+        AttachmentError, AttachmentErrorKind, ResultExt, Result;
+    }
+
+    links {
+        StoreError(::libimagstore::error::StoreError, ::libimagstore::error::StoreErrorKind);
+    }
+
+    foreign_links {
+        IoError(::std::io::Error);
+    }
+
+    errors {
+        BlobWriteError {
+            description("Could not write blob")
+            display("Could not write blob")
+        }
+
+        BlobReadError(hash: String) {
+            description("Could not read blob")
+            display("Could not read blob '{}'", hash)
+        }
+
+        BlobNotFound(hash: String) {
+            description("Blob not found")
+            display("Blob '{}' not found in the store", hash)
+        }
+
+        NoFileName(path: PathBuf) {
+            description("Path has no file name")
+            display("Path '{}' has no file name", path.display())
+        }
+
+        HeaderReadError {
+            description("Couldn't read attachments from the header of the entry")
+            display("Couldn't read attachments from the header of the entry")
+        }
+
+        HeaderWriteError {
+            description("Couldn't write attachments to the header of the entry")
+            display("Couldn't write attachments to the header of the entry")
+        }
+
+    }
+}
+
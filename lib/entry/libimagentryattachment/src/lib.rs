@@ -0,0 +1,70 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Binary attachments for store entries
+//!
+//! The content of an entry is text, so files like images, PDFs or receipts cannot be put into
+//! it. This library stores such files as _blobs_ next to the entries, in the hidden `.blobs`
+//! directory of the store. Blobs are content-addressed: a blob is named after the SHA-256 hash
+//! of its content, so the same file attached to several entries is stored only once.
+//!
+//! The attachments of an entry are listed in its header, with the name, MIME type and size of
+//! each file (see `attachable::Attachable`). Blobs which are not referenced by any entry, revision
+//! or trashed entry anymore are removed by `blob::collect_garbage()`.
+
+#![recursion_limit="256"]
+
+#![deny(
+    dead_code,
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+extern crate toml;
+extern crate crypto;
+extern crate base64;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+#[macro_use] extern crate error_chain;
+
+extern crate libimagstore;
+extern crate libimagerror;
+
+#[cfg(test)] extern crate tempdir;
+#[cfg(test)] extern crate env_logger;
+
+pub mod attachable;
+pub mod attachment;
+pub mod blob;
+pub mod error;
+pub mod mime;
+pub mod schema;
+
@@ -0,0 +1,87 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Guessing the MIME type of a file from its extension
+
+use std::path::Path;
+
+/// The MIME type used if nothing better is known
+pub const DEFAULT_MIME : &'static str = "application/octet-stream";
+
+const MIME_TYPES : &'static [(&'static str, &'static str)] = &[
+    ("bmp"  , "image/bmp"),
+    ("csv"  , "text/csv"),
+    ("doc"  , "application/msword"),
+    ("docx" , "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("gif"  , "image/gif"),
+    ("gz"   , "application/gzip"),
+    ("htm"  , "text/html"),
+    ("html" , "text/html"),
+    ("ics"  , "text/calendar"),
+    ("jpeg" , "image/jpeg"),
+    ("jpg"  , "image/jpeg"),
+    ("json" , "application/json"),
+    ("md"   , "text/markdown"),
+    ("mp3"  , "audio/mpeg"),
+    ("mp4"  , "video/mp4"),
+    ("odt"  , "application/vnd.oasis.opendocument.text"),
+    ("ods"  , "application/vnd.oasis.opendocument.spreadsheet"),
+    ("ogg"  , "audio/ogg"),
+    ("pdf"  , "application/pdf"),
+    ("png"  , "image/png"),
+    ("svg"  , "image/svg+xml"),
+    ("tar"  , "application/x-tar"),
+    ("tif"  , "image/tiff"),
+    ("tiff" , "image/tiff"),
+    ("txt"  , "text/plain"),
+    ("vcf"  , "text/vcard"),
+    ("webp" , "image/webp"),
+    ("xls"  , "application/vnd.ms-excel"),
+    ("xlsx" , "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("xml"  , "application/xml"),
+    ("zip"  , "application/zip"),
+];
+
+/// Guess the MIME type of the file at `path` from its extension
+///
+/// Falls back to `DEFAULT_MIME` if the extension is unknown.
+pub fn guess(path: &Path) -> &'static str {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .and_then(|ext| MIME_TYPES.iter().find(|&&(e, _)| e == ext).map(|&(_, mime)| mime))
+        .unwrap_or(DEFAULT_MIME)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_guess() {
+        assert_eq!("application/pdf", guess(Path::new("/tmp/receipt.pdf")));
+        assert_eq!("image/jpeg", guess(Path::new("holiday.JPG")));
+        assert_eq!(DEFAULT_MIME, guess(Path::new("archive.unknown")));
+        assert_eq!(DEFAULT_MIME, guess(Path::new("Makefile")));
+    }
+}
+
@@ -26,4 +26,5 @@ pub mod field_istype;
 pub mod field_lt;
 pub mod field_path;
 pub mod field_predicate;
+pub mod timestamp;
 pub mod version;
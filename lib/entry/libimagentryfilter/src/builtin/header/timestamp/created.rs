@@ -0,0 +1,71 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use chrono::NaiveDateTime;
+
+use libimagstore::store::Entry;
+
+use filters::filter::Filter;
+
+/// Filter entries which were created before a point in time
+pub struct CreatedBefore {
+    time: NaiveDateTime,
+}
+
+impl CreatedBefore {
+
+    pub fn new(time: NaiveDateTime) -> CreatedBefore {
+        CreatedBefore { time: time }
+    }
+
+}
+
+impl Filter<Entry> for CreatedBefore {
+
+    fn filter(&self, e: &Entry) -> bool {
+        e.created()
+            .map(|t| t.map_or(false, |t| t < self.time))
+            .unwrap_or(false)
+    }
+
+}
+
+/// Filter entries which were created after a point in time
+pub struct CreatedAfter {
+    time: NaiveDateTime,
+}
+
+impl CreatedAfter {
+
+    pub fn new(time: NaiveDateTime) -> CreatedAfter {
+        CreatedAfter { time: time }
+    }
+
+}
+
+impl Filter<Entry> for CreatedAfter {
+
+    fn filter(&self, e: &Entry) -> bool {
+        e.created()
+            .map(|t| t.map_or(false, |t| t > self.time))
+            .unwrap_or(false)
+    }
+
+}
@@ -0,0 +1,71 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+use chrono::NaiveDateTime;
+
+use libimagstore::store::Entry;
+
+use filters::filter::Filter;
+
+/// Filter entries which were modified before a point in time
+pub struct ModifiedBefore {
+    time: NaiveDateTime,
+}
+
+impl ModifiedBefore {
+
+    pub fn new(time: NaiveDateTime) -> ModifiedBefore {
+        ModifiedBefore { time: time }
+    }
+
+}
+
+impl Filter<Entry> for ModifiedBefore {
+
+    fn filter(&self, e: &Entry) -> bool {
+        e.modified()
+            .map(|t| t.map_or(false, |t| t < self.time))
+            .unwrap_or(false)
+    }
+
+}
+
+/// Filter entries which were modified after a point in time
+pub struct ModifiedAfter {
+    time: NaiveDateTime,
+}
+
+impl ModifiedAfter {
+
+    pub fn new(time: NaiveDateTime) -> ModifiedAfter {
+        ModifiedAfter { time: time }
+    }
+
+}
+
+impl Filter<Entry> for ModifiedAfter {
+
+    fn filter(&self, e: &Entry) -> bool {
+        e.modified()
+            .map(|t| t.map_or(false, |t| t > self.time))
+            .unwrap_or(false)
+    }
+
+}
@@ -33,6 +33,7 @@
     while_true,
 )]
 
+extern crate chrono;
 extern crate filters;
 extern crate regex;
 extern crate semver;
@@ -18,13 +18,13 @@
 //
 
 use std::collections::BTreeMap;
-#[cfg(test)]
 use std::path::PathBuf;
 
 use libimagstore::storeid::StoreId;
 use libimagstore::storeid::IntoStoreId;
 use libimagstore::store::Entry;
 use libimagstore::store::Result as StoreResult;
+use libimagstore::file_abstraction::Origin;
 
 use toml_query::read::TomlValueReadExt;
 use toml_query::insert::TomlValueInsertExt;
@@ -34,8 +34,8 @@ use error::LinkError as LE;
 use error::ResultExt;
 use error::Result;
 use self::iter::LinkIter;
-use self::iter::IntoValues;
 
+use itertools::Itertools;
 use toml::Value;
 
 #[derive(Eq, PartialOrd, Ord, Hash, Debug, Clone)]
@@ -97,22 +97,29 @@ impl Link {
         }
     }
 
-    fn to_value(&self) -> Result<Value> {
-        match self {
-            &Link::Id { link: ref s } =>
-                s.to_str().map(Value::String).chain_err(|| LEK::InternalConversionError),
-            &Link::Annotated { ref link, annotation: ref anno } => {
-                link.to_str()
-                    .map(Value::String)
-                    .chain_err(|| LEK::InternalConversionError)
-                    .map(|link| {
-                        let mut tab = BTreeMap::new();
-
-                        tab.insert("link".to_owned(),       link);
-                        tab.insert("annotation".to_owned(), Value::String(anno.clone()));
-                        Value::Table(tab)
-                    })
-            }
+    /// Get the value the link to `target` is written as in an entry kept at `owner`, see
+    /// `links_to_values()`
+    fn to_value(&self, owner: &Origin, target: &Origin) -> Result<Value> {
+        let to_string = |p: &PathBuf| {
+            p.to_str()
+                .map(String::from)
+                .ok_or_else(|| LE::from_kind(LEK::InternalConversionError))
+        };
+
+        let mut tab = BTreeMap::new();
+        if target.store != owner.store {
+            tab.insert("store".to_owned(), Value::String(to_string(&target.store)?));
+        }
+        if let Link::Annotated { ref annotation, .. } = *self {
+            tab.insert("annotation".to_owned(), Value::String(annotation.clone()));
+        }
+
+        let link = Value::String(to_string(&target.id)?);
+        if tab.is_empty() {
+            Ok(link)
+        } else {
+            tab.insert("link".to_owned(), link);
+            Ok(Value::Table(tab))
         }
     }
 
@@ -186,13 +193,8 @@ pub mod iter {
     use std::vec::IntoIter;
     use super::Link;
 
-    use error::LinkErrorKind as LEK;
-    use error::ResultExt;
     use error::Result;
 
-    use toml::Value;
-    use itertools::Itertools;
-
     use libimagstore::store::Store;
     use libimagstore::store::FileLockEntry;
 
@@ -218,21 +220,6 @@ pub mod iter {
         }
     }
 
-    pub trait IntoValues {
-        fn into_values(self) -> Vec<Result<Value>>;
-    }
-
-    impl<I: Iterator<Item = Link>> IntoValues for I {
-        fn into_values(self) -> Vec<Result<Value>> {
-            self.map(|s| s.without_base())
-                .unique()
-                .sorted()
-                .into_iter() // Cannot sort toml::Value, hence uglyness here
-                .map(|link| link.to_value().chain_err(|| LEK::InternalConversionError))
-                .collect()
-        }
-    }
-
     /// An Iterator that `Store::get()`s the Entries from the store while consumed
     pub struct GetIter<'a>(IntoIter<Link>, &'a Store);
 
@@ -389,13 +376,11 @@ impl InternalLinker for Entry {
             .read("links.internal")
             .chain_err(|| LEK::EntryHeaderReadError)
             .map(|r| r.cloned());
-        process_rw_result(res)
+        process_rw_result(res, self.get_location())
     }
 
     /// Set the links in a header and return the old links, if any.
     fn set_internal_links(&mut self, links: Vec<&mut Entry>) -> Result<LinkIter> {
-        use internal::iter::IntoValues;
-
         let self_location = self.get_location().clone();
         let mut new_links = vec![];
 
@@ -406,23 +391,7 @@ impl InternalLinker for Entry {
             new_links.push(link.get_location().clone().into());
         }
 
-        let new_links = LinkIter::new(new_links)
-                             .into_values()
-                             .into_iter()
-                             .fold(Ok(vec![]), |acc, elem| {
-                                acc.and_then(move |mut v| {
-                                    elem.chain_err(|| LEK::InternalConversionError)
-                                        .map(|e| {
-                                            v.push(e);
-                                            v
-                                        })
-                                })
-                            })?;
-        let res = self
-            .get_header_mut()
-            .insert("links.internal", Value::Array(new_links))
-            .chain_err(|| LEK::EntryHeaderReadError);
-        process_rw_result(res)
+        rewrite_links(self, new_links.into_iter())
     }
 
     fn add_internal_link(&mut self, link: &mut Entry) -> Result<()> {
@@ -436,20 +405,16 @@ impl InternalLinker for Entry {
 
         debug!("Removing internal link from {:?} to {:?}", own_loc, other_loc);
 
-        link.get_internal_links()
-            .and_then(|links| {
-                debug!("Rewriting own links for {:?}, without {:?}", other_loc, own_loc);
-                let links = links.filter(|l| !l.eq_store_id(&own_loc));
-                rewrite_links(link.get_header_mut(), links)
-            })
-            .and_then(|_| {
-                self.get_internal_links()
-                    .and_then(|links| {
-                        debug!("Rewriting own links for {:?}, without {:?}", own_loc, other_loc);
-                        let links = links.filter(|l| !l.eq_store_id(&other_loc));
-                        rewrite_links(self.get_header_mut(), links)
-                    })
-            })
+        // Entries on read-only mounts were not linked back
+        if !is_read_only(link)? {
+            debug!("Rewriting own links for {:?}, without {:?}", other_loc, own_loc);
+            let links = link.get_internal_links()?.filter(|l| !l.eq_store_id(&own_loc));
+            let _     = rewrite_links(link, links)?;
+        }
+
+        debug!("Rewriting own links for {:?}, without {:?}", own_loc, other_loc);
+        let links = self.get_internal_links()?.filter(|l| !l.eq_store_id(&other_loc));
+        rewrite_links(self, links).map(|_| ())
     }
 
     fn add_internal_annotated_link(&mut self, link: &mut Entry, annotation: String) -> Result<()> {
@@ -471,64 +436,157 @@ fn add_internal_link_with_instance(this: &mut Entry, link: &mut Entry, instance:
             this.get_internal_links()
                 .and_then(|links| {
                     let links = links.chain(LinkIter::new(vec![instance]));
-                    rewrite_links(this.get_header_mut(), links)
+                    rewrite_links(this, links).map(|_| ())
                 })
         })
 }
 
-fn rewrite_links<I: Iterator<Item = Link>>(header: &mut Value, links: I) -> Result<()> {
-    let links = links.into_values()
-                     .into_iter()
-                     .fold(Ok(vec![]), |acc, elem| {
-                        acc.and_then(move |mut v| {
-                            elem.chain_err(|| LEK::InternalConversionError)
-                                .map(|e| {
-                                    v.push(e);
-                                    v
-                                })
-                        })
-                     })?;
-
-    debug!("Setting new link array: {:?}", links);
-    let process = header
-        .insert("links.internal", Value::Array(links))
+/// Replace the links in the header of `entry` with `links` and return the old links
+///
+/// Links into stores which are not mounted cannot be read, so they are kept as they are.
+fn rewrite_links<I: Iterator<Item = Link>>(entry: &mut Entry, links: I) -> Result<LinkIter> {
+    let owner      = entry.get_location().clone();
+    let mut values = links_to_values(&owner, links)?;
+    values.extend(unresolvable_links(entry)?);
+
+    debug!("Setting links in {:?}: {:?}", owner, values);
+    let res = entry
+        .get_header_mut()
+        .insert("links.internal", Value::Array(values))
         .chain_err(|| LEK::EntryHeaderReadError);
-    process_rw_result(process).map(|_| ())
+    process_rw_result(res, &owner)
 }
 
 /// When Linking A -> B, the specification wants us to link back B -> A.
 /// This is a helper function which does this.
+///
+/// Entries on read-only mounts cannot be changed, so they are not linked back.
 fn add_foreign_link(target: &mut Entry, from: StoreId) -> Result<()> {
+    if is_read_only(target)? {
+        debug!("Not linking back from {:?}, it is on a read-only mount", target.get_location());
+        return Ok(());
+    }
+
     debug!("Linking back from {:?} to {:?}", target.get_location(), from);
     target.get_internal_links()
         .and_then(|links| {
-            let links = links
-                             .chain(LinkIter::new(vec![from.into()]))
-                             .into_values()
-                             .into_iter()
-                             .fold(Ok(vec![]), |acc, elem| {
-                                acc.and_then(move |mut v| {
-                                    elem.chain_err(|| LEK::InternalConversionError)
-                                        .map(|e| {
-                                            v.push(e);
-                                            v
-                                        })
-                                })
-                             })?;
-            debug!("Setting links in {:?}: {:?}", target.get_location(), links);
-
-            let res = target
-                .get_header_mut()
-                .insert("links.internal", Value::Array(links))
-                .chain_err(|| LEK::EntryHeaderReadError);
-
-            process_rw_result(res).map(|_| ())
+            let links = links.chain(LinkIter::new(vec![from.into()]));
+            rewrite_links(target, links).map(|_| ())
         })
 }
 
-fn process_rw_result(links: Result<Option<Value>>) -> Result<LinkIter> {
+fn is_read_only(entry: &Entry) -> Result<bool> {
+    Origin::of(entry.get_location())
+        .map(|origin| origin.read_only)
+        .map_err(From::from)
+}
+
+/// Get the values the links of the entry `owner` are written as
+///
+/// Links to entries in the store `owner` is kept in are written as the id of the entry in this
+/// store. Links to entries in other stores (mounted into the store or the store a mounted store is
+/// mounted into) are written as a table, with the location of the other store in `store` (see
+/// `Origin`). So links do not depend on where the stores are mounted.
+fn links_to_values<I: Iterator<Item = Link>>(owner: &StoreId, links: I) -> Result<Vec<Value>> {
+    let origin = Origin::of(owner)?;
+
+    links.map(|link| link.without_base())
+        .unique()
+        .sorted()
+        .into_iter() // Cannot sort toml::Value, hence uglyness here
+        .map(|link| {
+            let target = match owner.base() {
+                Some(base) => link.get_store_id().clone().with_base(base.clone()),
+                None       => link.get_store_id().clone(),
+            };
+
+            Origin::of(&target)
+                .map_err(From::from)
+                .and_then(|target| link.to_value(&origin, &target))
+                .chain_err(|| LEK::InternalConversionError)
+        })
+        .collect()
+}
+
+/// Get the links in the header of `entry` which point into stores which are not mounted
+fn unresolvable_links(entry: &Entry) -> Result<Vec<Value>> {
+    let links = entry.get_header()
+        .read("links.internal")
+        .chain_err(|| LEK::EntryHeaderReadError)?;
+
+    let links = match links {
+        Some(&Value::Array(ref links)) => links.clone(),
+        _                              => return Ok(vec![]),
+    };
+
+    let mut unresolvable = vec![];
+    for link in links {
+        if resolve(entry.get_location(), link.clone())?.is_none() {
+            unresolvable.push(link);
+        }
+    }
+
+    Ok(unresolvable)
+}
+
+/// Read the link `value` from the header of the entry `owner`, see `links_to_values()`
+///
+/// `None` if the link points into a store which is not mounted.
+fn resolve(owner: &StoreId, value: Value) -> Result<Option<Link>> {
     use std::path::PathBuf;
 
+    let (id, annotation, store) = match value {
+        Value::String(s) => (s, None, None),
+        Value::Table(mut tab) => {
+            debug!("Destructuring table");
+            let id = match tab.remove("link") {
+                Some(Value::String(s)) => s,
+                Some(_)                => return Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
+                None                   => return Err(LE::from_kind(LEK::LinkParserFieldMissingError)),
+            };
+
+            let annotation = match tab.remove("annotation") {
+                Some(Value::String(s)) => Some(s),
+                Some(_)                => return Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
+                None                   => None,
+            };
+
+            let store = match tab.remove("store") {
+                Some(Value::String(s)) => Some(PathBuf::from(s)),
+                Some(_)                => return Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
+                None                   => None,
+            };
+
+            (id, annotation, store)
+        },
+        _ => return Err(LE::from_kind(LEK::ExistingLinkTypeWrong)),
+    };
+
+    let origin = Origin {
+        store: match store {
+            Some(store) => store,
+            None        => Origin::of(owner)?.store,
+        },
+        id: PathBuf::from(id),
+        read_only: false,
+    };
+
+    let base = owner.base().cloned().unwrap_or_else(PathBuf::new);
+    let id   = match origin.id_in(&base)? {
+        Some(id) => id.without_base(),
+        None     => {
+            debug!("{:?} links into {:?}, which is not mounted", owner, origin.store);
+            return Ok(None);
+        },
+    };
+
+    Ok(Some(match annotation {
+        Some(annotation) => Link::Annotated { link: id, annotation: annotation },
+        None             => Link::Id { link: id },
+    }))
+}
+
+fn process_rw_result(links: Result<Option<Value>>, owner: &StoreId) -> Result<LinkIter> {
     let links = match links {
         Err(e) => {
             debug!("RW action on store failed. Generating LinkError");
@@ -551,50 +609,16 @@ fn process_rw_result(links: Result<Option<Value>>) -> Result<LinkIter> {
         return Err(LEK::ExistingLinkTypeWrong.into());
     }
 
-    let links : Vec<Link> = links.into_iter()
-        .map(|link| {
-            debug!("Matching the link: {:?}", link);
-            match link {
-                Value::String(s) => StoreId::new_baseless(PathBuf::from(s))
-                    .map(|s| Link::Id { link: s })
-                    .map_err(From::from)
-                    ,
-                Value::Table(mut tab) => {
-                    debug!("Destructuring table");
-                    if !tab.contains_key("link")
-                    || !tab.contains_key("annotation") {
-                        debug!("Things missing... returning Error instance");
-                        Err(LE::from_kind(LEK::LinkParserError))
-                    } else {
-                        let link = tab.remove("link")
-                            .ok_or(LE::from_kind(LEK::LinkParserFieldMissingError))?;
-
-                        let anno = tab.remove("annotation")
-                            .ok_or(LE::from_kind(LEK::LinkParserFieldMissingError))?;
-
-                        debug!("Ok, here we go with building a Link::Annotated");
-                        match (link, anno) {
-                            (Value::String(link), Value::String(anno)) => {
-                                StoreId::new_baseless(PathBuf::from(link))
-                                    .map_err(From::from)
-                                    .map(|link| {
-                                        Link::Annotated {
-                                            link: link,
-                                            annotation: anno,
-                                        }
-                                    })
-                            },
-                            _ => Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
-                        }
-                    }
-                }
-                _ => unreachable!(),
-            }
-        })
-        .collect::<Result<Vec<Link>>>()?;
+    let mut resolved = vec![];
+    for link in links {
+        debug!("Matching the link: {:?}", link);
+        if let Some(link) = resolve(owner, link)? {
+            resolved.push(link);
+        }
+    }
 
     debug!("Ok, the RW action was successful, returning link vector now!");
-    Ok(LinkIter::new(links))
+    Ok(LinkIter::new(resolved))
 }
 
 pub mod store_check {
@@ -1001,5 +1025,92 @@ mod test {
         }
     }
 
+    fn get_mounting_store() -> Store {
+        use std::env;
+        use libimagstore::file_abstraction::FileAbstraction;
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        use libimagstore::file_abstraction::Mount;
+        use libimagstore::file_abstraction::MountFileAbstraction;
+        use libimagstore::store::Entry;
+        use libimagstore::storeid::StoreId;
+
+        // Not "/" like the other tests, as the mounts are registered per store location
+        let location = env::temp_dir();
+
+        // The in-memory backend keeps the entries with their id, so they have to be the ids in
+        // the mounting store
+        let mount = |prefix: &str, mount_location: &str, read_only: bool| {
+            let backend = InMemoryFileAbstraction::new();
+            let id      = StoreId::new(Some(location.clone()), PathBuf::from(prefix).join("meeting"));
+            let meeting = Entry::new(id.unwrap());
+            let _       = backend
+                .new_instance(PathBuf::from(mount_location).join("meeting"))
+                .write_file_content(&meeting)
+                .unwrap();
+
+            Mount::new(String::from(prefix), PathBuf::from(mount_location), Box::new(backend), read_only)
+                .unwrap()
+        };
+
+        let backend  = MountFileAbstraction::new(Box::new(InMemoryFileAbstraction::new()),
+                                                 location.clone(),
+                                                 vec![
+                                                     mount("team", "/team-store", false),
+                                                     mount("archive", "/archive-store", true),
+                                                 ]);
+        Store::new_with_backend(location, &None, Box::new(backend)).unwrap()
+    }
+
+    #[test]
+    fn test_link_across_stores() {
+        use std::fs;
+        use toml::Value;
+        use toml_query::read::TomlValueReadExt;
+
+        setup_logging();
+        let store       = get_mounting_store();
+        let mut note    = store.create(PathBuf::from("test_link_across_stores")).unwrap();
+        let mut meeting = store.get(PathBuf::from("team/meeting")).unwrap().unwrap();
+
+        assert!(note.add_internal_link(&mut meeting).is_ok());
+
+        // Links are written with the id in the store the linked entry is kept in
+        let link  = note.get_header().read("links.internal.[0]").unwrap().cloned().unwrap();
+        assert_eq!(Some("meeting"), link.get("link").and_then(Value::as_str));
+        assert_eq!(Some("/team-store"), link.get("store").and_then(Value::as_str));
+
+        let link  = meeting.get_header().read("links.internal.[0]").unwrap().cloned().unwrap();
+        let store_location = fs::canonicalize(store.path()).unwrap();
+        assert_eq!(Some("test_link_across_stores"), link.get("link").and_then(Value::as_str));
+        assert_eq!(store_location.to_str(), link.get("store").and_then(Value::as_str));
+
+        let note_links = note.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(1, note_links.len());
+        assert!(note_links[0].clone().with_base(store.path().clone()).eq_store_id(meeting.get_location()));
+
+        let meeting_links = meeting.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(1, meeting_links.len());
+        assert!(meeting_links[0].clone().with_base(store.path().clone()).eq_store_id(note.get_location()));
+
+        assert!(note.remove_internal_link(&mut meeting).is_ok());
+        assert_eq!(0, note.get_internal_links().unwrap().count());
+        assert_eq!(0, meeting.get_internal_links().unwrap().count());
+    }
+
+    #[test]
+    fn test_link_into_read_only_mount() {
+        setup_logging();
+        let store       = get_mounting_store();
+        let mut note    = store.create(PathBuf::from("test_link_into_read_only_mount")).unwrap();
+        let mut meeting = store.get(PathBuf::from("archive/meeting")).unwrap().unwrap();
+
+        assert!(note.add_internal_link(&mut meeting).is_ok());
+        assert_eq!(1, note.get_internal_links().unwrap().count());
+        assert_eq!(0, meeting.get_internal_links().unwrap().count());
+
+        assert!(note.remove_internal_link(&mut meeting).is_ok());
+        assert_eq!(0, note.get_internal_links().unwrap().count());
+    }
+
 }
 
@@ -56,4 +56,6 @@ module_entry_path_mod!("links");
 pub mod error;
 pub mod external;
 pub mod internal;
+pub mod migration;
+pub mod schema;
 
@@ -0,0 +1,112 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Migrations of the headers written by libimagentrylink
+//!
+//! Before imag 0.4.0, internal links were stored in `imag.links`, which is reserved for the store
+//! itself. They are located in `links.internal` since then.
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use toml_query::insert::TomlValueInsertExt;
+
+use libimagstore::migration::MigrationRegistry;
+use libimagstore::store::Entry;
+use libimagstore::store::Result;
+use libimagstore::error::StoreError as SE;
+use libimagstore::error::StoreErrorKind as SEK;
+
+/// Register all migrations of libimagentrylink in `registry`
+pub fn register_migrations(registry: &mut MigrationRegistry) -> Result<()> {
+    registry.register("links", "0.0.0", "0.4.0", move_links_out_of_imag_section)
+}
+
+fn move_links_out_of_imag_section(entry: &mut Entry) -> Result<()> {
+    // toml-query refuses to delete non-empty arrays, so we remove the links by hand
+    let old_links = match entry.get_header_mut() {
+        &mut Value::Table(ref mut tbl) => match tbl.get_mut("imag") {
+            Some(&mut Value::Table(ref mut imag)) => imag.remove("links"),
+            _                                     => None,
+        },
+        _ => None,
+    };
+
+    let old_links = match old_links {
+        None                      => return Ok(()),
+        Some(Value::Array(links)) => links,
+        Some(_)                   => {
+            return Err(SE::from_kind(SEK::ConfigTypeError("imag.links", "Array")));
+        },
+    };
+
+    let mut links = match entry.get_header().read("links.internal")? {
+        Some(&Value::Array(ref links)) => links.clone(),
+        _                              => vec![],
+    };
+
+    for link in old_links {
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+
+    entry.get_header_mut()
+        .insert("links.internal", Value::Array(links))
+        .map(|_| ())
+        .map_err(SE::from)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::read::TomlValueReadExt;
+
+    use libimagstore::migration::MigrationRegistry;
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::register_migrations;
+
+    #[test]
+    fn test_links_are_moved() {
+        let id        = StoreId::new_baseless(PathBuf::from("test")).unwrap();
+        let mut entry = Entry::from_str(id, r#"---
+[imag]
+version = "0.3.0"
+links = ["a", "b"]
+
+[links]
+internal = ["b"]
+---
+"#).unwrap();
+
+        let mut registry = MigrationRegistry::new();
+        register_migrations(&mut registry).unwrap();
+        assert_eq!(1, registry.migrate(&mut entry).unwrap().len());
+
+        let header = entry.get_header();
+        assert!(header.read("imag.links").unwrap().is_none());
+        assert_eq!(Some(&Value::Array(vec![Value::String("b".into()), Value::String("a".into())])),
+                   header.read("links.internal").unwrap());
+        assert_eq!(Some(&Value::String("0.4.0".into())), header.read("imag.version").unwrap());
+    }
+
+}
@@ -45,5 +45,6 @@ extern crate libimagentryedit;
 
 pub mod error;
 pub mod builtin;
+pub mod config;
 pub mod viewer;
 
@@ -0,0 +1,254 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The store hook keeping the full-text index up to date
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+use libimagstore::error::StoreErrorKind as SEK;
+use libimagstore::error::ResultExt;
+use libimagstore::error::Result as StoreResult;
+use libimagstore::hook::Hook;
+use libimagstore::hook::HookData;
+use libimagstore::hook::HookPosition;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use error::FullTextErrorKind as FTEK;
+use error::Result;
+use index::FullTextIndex;
+use index::local_id;
+
+/// A hook which updates the full-text index of the store incrementally
+///
+/// It has to be registered for the post-update, post-delete and post-move positions, which is
+/// what `register()` does. The index is loaded on first use, only the index files of the entries
+/// which changed are written back (through the backend of the store).
+///
+/// Concurrent imag processes do not see each others index changes, but as only the files of the
+/// changed entries are written, they do not overwrite them either. `FullTextIndex::build()`
+/// recreates the index from scratch.
+///
+/// The index is not encrypted, so entries of the excluded (encrypted) collections are not indexed.
+#[derive(Debug, Clone)]
+pub struct FullTextIndexHook {
+    index: Arc<Mutex<Option<FullTextIndex>>>,
+    excluded: Vec<String>,
+}
+
+impl FullTextIndexHook {
+
+    pub fn new() -> FullTextIndexHook {
+        FullTextIndexHook {
+            index: Arc::new(Mutex::new(None)),
+            excluded: vec![],
+        }
+    }
+
+    /// Do not index the entries of `collections`
+    pub fn with_excluded_collections(mut self, collections: Vec<String>) -> FullTextIndexHook {
+        self.excluded = collections;
+        self
+    }
+
+    fn is_excluded(&self, id: &StoreId) -> bool {
+        self.excluded.iter().any(|c| id.is_in_collection(&[c.as_str()]))
+    }
+
+    /// Run `f` on the index and persist the entries it returns
+    fn with_index<F>(&self, store: &Store, f: F) -> Result<()>
+        where F: FnOnce(&mut FullTextIndex) -> Result<Vec<String>>
+    {
+        let mut guard = self.index.lock().map_err(|_| FTEK::IndexWriteError)?;
+        if guard.is_none() {
+            *guard = Some(FullTextIndex::load(store)?);
+        }
+
+        match guard.as_mut() {
+            Some(index) => {
+                for id in f(index)? {
+                    let _ = index.save_entry(store, &id)?;
+                }
+                Ok(())
+            },
+            None => Ok(()), // loaded above
+        }
+    }
+
+}
+
+impl Hook for FullTextIndexHook {
+
+    fn name(&self) -> &'static str {
+        "fulltext"
+    }
+
+    /// The index cannot be updated without the store, see `execute_in_store()`
+    fn execute(&self, _: HookPosition, _: &HookData) -> StoreResult<()> {
+        Ok(())
+    }
+
+    fn execute_in_store(&self, store: &Store, position: HookPosition, data: &HookData)
+        -> StoreResult<()>
+    {
+        let removed = |index: &mut FullTextIndex, id: String| {
+            if index.remove(&id) { vec![id] } else { vec![] }
+        };
+
+        let res = match (position, data) {
+            (HookPosition::PostUpdate, &HookData::Entry(entry)) => {
+                self.with_index(store, |index| {
+                    let id = local_id(entry.get_location())?;
+                    if self.is_excluded(entry.get_location()) {
+                        // The entry might have been indexed before its collection was encrypted
+                        Ok(removed(index, id))
+                    } else if index.add(entry)? {
+                        Ok(vec![id])
+                    } else {
+                        Ok(vec![])
+                    }
+                })
+            },
+            (HookPosition::PostDelete, &HookData::Id(id)) => {
+                self.with_index(store, |index| local_id(id).map(|id| removed(index, id)))
+            },
+            (HookPosition::PostMoveById, &HookData::Move(old, new)) => {
+                self.with_index(store, |index| {
+                    let old = local_id(old)?;
+                    if self.is_excluded(new) {
+                        return Ok(removed(index, old));
+                    }
+                    let new = local_id(new)?;
+                    if index.rename(&old, &new) { Ok(vec![old, new]) } else { Ok(vec![]) }
+                })
+            },
+            _ => Ok(()),
+        };
+
+        res.chain_err(|| SEK::IoError)
+    }
+
+}
+
+/// Whether the full-text index is kept up to date
+///
+/// The index is opt-in, as it has to be written on each change of an entry: it is only
+/// maintained if `store.hooks.fulltext.enabled` is set to `true`.
+pub fn is_enabled(config: Option<&Value>) -> bool {
+    config
+        .and_then(|c| c.read("store.hooks.fulltext.enabled").ok())
+        .and_then(|v| v.and_then(Value::as_bool))
+        .unwrap_or(false)
+}
+
+/// Register the full-text index hook in the store
+///
+/// The encrypted collections of the store are excluded from the index.
+pub fn register(store: &mut Store) -> StoreResult<()> {
+    let hook = FullTextIndexHook::new()
+        .with_excluded_collections(store.encrypted_collections().to_vec());
+    for position in &[HookPosition::PostUpdate, HookPosition::PostDelete, HookPosition::PostMoveById] {
+        store.register_hook(*position, Box::new(hook.clone()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use libimagstore::store::Store;
+    use libimagstore::file_abstraction::InMemoryFileAbstraction;
+    use libimagstore::storeid::StoreId;
+
+    use super::*;
+    use query::Query;
+
+    #[test]
+    fn test_hook_updates_index() {
+        let dir = TempDir::new("imag-fulltext-hook").unwrap();
+
+        let backend   = Box::new(InMemoryFileAbstraction::new());
+        let mut store = Store::new_with_backend(dir.path().to_path_buf(), &None, backend).unwrap();
+        register(&mut store).unwrap();
+
+        {
+            let mut entry = store.create(PathBuf::from("note")).unwrap();
+            *entry.get_content_mut() = String::from("full text search");
+        }
+
+        let search = |q: &str| {
+            FullTextIndex::load(&store)
+                .unwrap()
+                .search(&Query::parse(q).unwrap())
+                .into_iter()
+                .map(|r| r.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(search("\"text search\""), vec!["note"]);
+
+        // The index is written through the backend of the store, one file per entry
+        assert!(!dir.path().join(".fulltext").exists());
+        let files = store.hidden_ids(StoreId::new_baseless(PathBuf::from(".fulltext")).unwrap());
+        assert_eq!(1, files.unwrap().len());
+
+        let old = StoreId::new_baseless(PathBuf::from("note")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("moved")).unwrap();
+        store.move_by_id(old, new).unwrap();
+        assert_eq!(search("text"), vec!["moved"]);
+
+        store.delete(PathBuf::from("moved")).unwrap();
+        assert!(search("text").is_empty());
+    }
+
+    #[test]
+    fn test_hook_leaves_out_encrypted_collections() {
+        use toml::de::from_str as toml_from_str;
+
+        let dir = TempDir::new("imag-fulltext-crypt").unwrap();
+
+        let config    = toml_from_str("[store.encryption]\ncollections = [\"diary\"]").unwrap();
+        let backend   = Box::new(InMemoryFileAbstraction::new());
+        let mut store = Store::new_with_backend(dir.path().to_path_buf(), &Some(config), backend).unwrap();
+        register(&mut store).unwrap();
+
+        for id in &["diary/today", "notes/today"] {
+            let mut entry = store.create(PathBuf::from(id)).unwrap();
+            *entry.get_content_mut() = String::from("secret");
+        }
+
+        let found = FullTextIndex::load(&store)
+            .unwrap()
+            .search(&Query::parse("secret").unwrap())
+            .into_iter()
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["notes/today"]);
+        assert_eq!(1, FullTextIndex::build(&store).unwrap().len());
+    }
+
+}
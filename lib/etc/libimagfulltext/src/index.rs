@@ -0,0 +1,580 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The inverted index
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::Bound;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::path::PathBuf;
+
+use serde_json;
+use toml::Value;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use error::FullTextErrorKind as FTEK;
+use error::ResultExt;
+use error::Result;
+use query::Query;
+use tokenizer::tokenize_from;
+use tokenizer::Token;
+
+/// The hidden directory of the store the index is persisted in
+///
+/// Each indexed entry has a file of its own in there, so an update of the index only rewrites the
+/// files of the entries which changed.
+pub const INDEX_DIR : &'static str = ".fulltext";
+
+/// A search result: the id of the matching entry and its score
+///
+/// The id is relative to the store, as returned by `StoreId::local()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub id: String,
+    pub score: f64,
+}
+
+/// What the index knows about an indexed entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Document {
+    /// Number of tokens in the entry
+    length: usize,
+
+    /// Hash of the indexed text, to skip re-indexing unchanged entries
+    fingerprint: u64,
+}
+
+/// What is persisted for an indexed entry: the document and the positions of its terms
+#[derive(Debug, Serialize, Deserialize)]
+struct DocumentFile {
+    document: Document,
+    terms: BTreeMap<String, Vec<usize>>,
+}
+
+/// A full-text index over the content and the string header values of entries
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FullTextIndex {
+    documents: BTreeMap<String, Document>,
+
+    /// term -> entry -> positions of the term in the entry
+    postings: BTreeMap<String, BTreeMap<String, Vec<usize>>>,
+}
+
+impl FullTextIndex {
+
+    pub fn new() -> FullTextIndex {
+        FullTextIndex::default()
+    }
+
+    /// Build a new index from all entries of the store
+    ///
+    /// Entries of encrypted collections are not indexed, as the index is not encrypted.
+    pub fn build(store: &Store) -> Result<FullTextIndex> {
+        let mut index = FullTextIndex::new();
+        for id in store.entries()? {
+            if store.is_encrypted(&id) {
+                continue;
+            }
+            let _ = index.add(&store.get_copy(id)?)?;
+        }
+        Ok(index)
+    }
+
+    /// Load the index from the store, returning an empty index if there is none
+    pub fn load(store: &Store) -> Result<FullTextIndex> {
+        let mut index = FullTextIndex::new();
+        for file_id in store.hidden_ids(index_dir_id()?)? {
+            let id = file_id
+                .local()
+                .strip_prefix(INDEX_DIR)
+                .map_err(|_| FTEK::IndexReadError)?
+                .to_str()
+                .map(String::from)
+                .ok_or(FTEK::IndexReadError)?;
+
+            let file : DocumentFile = match store.get_hidden(file_id)? {
+                Some(entry) => serde_json::from_str(entry.get_content())
+                    .chain_err(|| FTEK::IndexReadError)?,
+                None        => continue, // removed concurrently
+            };
+
+            for (term, positions) in file.terms {
+                let _ = index.postings
+                    .entry(term)
+                    .or_insert_with(BTreeMap::new)
+                    .insert(id.clone(), positions);
+            }
+            let _ = index.documents.insert(id, file.document);
+        }
+        Ok(index)
+    }
+
+    /// Persist the whole index in the store
+    ///
+    /// The files of entries which are not in the index (anymore) are removed.
+    pub fn save(&self, store: &Store) -> Result<()> {
+        for file_id in store.hidden_ids(index_dir_id()?)? {
+            let indexed = file_id
+                .local()
+                .strip_prefix(INDEX_DIR)
+                .ok()
+                .and_then(|id| id.to_str())
+                .map(|id| self.documents.contains_key(id))
+                .unwrap_or(false);
+
+            if !indexed {
+                let _ = store.delete_hidden(file_id)?;
+            }
+        }
+
+        for id in self.documents.keys() {
+            let _ = self.save_entry(store, id)?;
+        }
+        Ok(())
+    }
+
+    /// Persist the index of the entry `id` in the store, or remove it if `id` is not indexed
+    pub fn save_entry(&self, store: &Store, id: &str) -> Result<()> {
+        let file_id = index_file_id(id)?;
+
+        match self.documents.get(id) {
+            Some(document) => {
+                let terms = self.postings
+                    .iter()
+                    .filter_map(|(term, docs)| docs.get(id).map(|p| (term.clone(), p.clone())))
+                    .collect();
+                let file  = DocumentFile { document: document.clone(), terms: terms };
+
+                let mut entry = Entry::new(file_id);
+                *entry.get_content_mut() = serde_json::to_string(&file)
+                    .chain_err(|| FTEK::IndexWriteError)?;
+                store.write_hidden(&entry).chain_err(|| FTEK::IndexWriteError)
+            },
+            None => {
+                if store.get_hidden(file_id.clone())?.is_some() {
+                    store.delete_hidden(file_id).chain_err(|| FTEK::IndexWriteError)
+                } else {
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Number of entries in the index
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Add an entry to the index, replacing the previously indexed version of the entry
+    ///
+    /// Returns whether the index changed, which is not the case if the entry was already indexed
+    /// with the same text.
+    pub fn add(&mut self, entry: &Entry) -> Result<bool> {
+        let id        = local_id(entry.get_location())?;
+        let mut texts = vec![];
+        collect_header_strings(entry.get_header(), &mut texts);
+        texts.push(entry.get_content());
+
+        let fingerprint = {
+            let mut hasher = DefaultHasher::new();
+            texts.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        if self.documents.get(&id).map(|d| d.fingerprint == fingerprint).unwrap_or(false) {
+            return Ok(false);
+        }
+        let _ = self.remove(&id);
+
+        let mut tokens = vec![];
+        for text in texts {
+            // Leave a gap between the fields, so phrases do not match across them
+            let offset = tokens.last().map(|t: &Token| t.position + 2).unwrap_or(0);
+            tokens.append(&mut tokenize_from(text, offset));
+        }
+
+        let document = Document { length: tokens.len(), fingerprint: fingerprint };
+        self.documents.insert(id.clone(), document);
+        for token in tokens {
+            self.postings
+                .entry(token.term)
+                .or_insert_with(BTreeMap::new)
+                .entry(id.clone())
+                .or_insert_with(Vec::new)
+                .push(token.position);
+        }
+
+        Ok(true)
+    }
+
+    /// Remove an entry from the index, returning whether it was indexed
+    pub fn remove(&mut self, id: &str) -> bool {
+        if self.documents.remove(id).is_none() {
+            return false;
+        }
+
+        let emptied = self.postings
+            .iter_mut()
+            .filter_map(|(term, docs)| {
+                let _ = docs.remove(id);
+                if docs.is_empty() { Some(term.clone()) } else { None }
+            })
+            .collect::<Vec<String>>();
+
+        for term in emptied {
+            let _ = self.postings.remove(&term);
+        }
+        true
+    }
+
+    /// Re-key an indexed entry after it was moved
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        match self.documents.remove(old) {
+            None => false,
+            Some(document) => {
+                self.documents.insert(String::from(new), document);
+                for docs in self.postings.values_mut() {
+                    if let Some(positions) = docs.remove(old) {
+                        docs.insert(String::from(new), positions);
+                    }
+                }
+                true
+            },
+        }
+    }
+
+    /// Search the index, returning the matching entries, best match first
+    pub fn search(&self, query: &Query) -> Vec<SearchResult> {
+        let mut results = self.eval(query)
+            .into_iter()
+            .map(|(id, score)| SearchResult { id: id, score: score })
+            .collect::<Vec<_>>();
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(::std::cmp::Ordering::Equal)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        results
+    }
+
+    fn eval(&self, query: &Query) -> BTreeMap<String, f64> {
+        match *query {
+            Query::Term(ref term)      => self.eval_term(term),
+            Query::Prefix(ref prefix)  => self.eval_prefix(prefix),
+            Query::Phrase(ref terms)   => self.eval_phrase(terms),
+            Query::Or(ref queries)     => {
+                let mut result = BTreeMap::new();
+                for (id, score) in queries.iter().flat_map(|q| self.eval(q)) {
+                    *result.entry(id).or_insert(0.0) += score;
+                }
+                result
+            },
+            Query::And(ref queries)    => {
+                let (negative, positive) : (Vec<&Query>, Vec<&Query>) = queries
+                    .iter()
+                    .partition(|q| match **q { Query::Not(_) => true, _ => false });
+
+                let mut result = if positive.is_empty() {
+                    self.all()
+                } else {
+                    let mut results = positive.into_iter().map(|q| self.eval(q));
+                    let first       = results.next().unwrap_or_else(BTreeMap::new);
+                    results.fold(first, |acc, other| {
+                        acc.into_iter()
+                            .filter_map(|(id, score)| other.get(&id).map(|s| (id, score + s)))
+                            .collect()
+                    })
+                };
+
+                for q in negative {
+                    // `q` is a `Query::Not`, so evaluating it yields the entries not excluded
+                    let allowed = self.eval(q);
+                    result = result
+                        .into_iter()
+                        .filter(|&(ref id, _)| allowed.contains_key(id))
+                        .collect();
+                }
+                result
+            },
+            Query::Not(ref q)          => {
+                let excluded = self.eval(q);
+                self.all().into_iter().filter(|&(ref id, _)| !excluded.contains_key(id)).collect()
+            },
+        }
+    }
+
+    fn all(&self) -> BTreeMap<String, f64> {
+        self.documents.keys().map(|id| (id.clone(), 0.0)).collect()
+    }
+
+    fn idf(&self, docs: &BTreeMap<String, Vec<usize>>) -> f64 {
+        (1.0 + (self.documents.len() as f64) / (docs.len() as f64)).ln()
+    }
+
+    /// Length normalisation, so long entries do not win just by containing more terms
+    fn norm(&self, id: &str) -> f64 {
+        self.documents
+            .get(id)
+            .map(|d| 1.0 / (1.0 + (::std::cmp::max(d.length, 1) as f64).ln()))
+            .unwrap_or(1.0)
+    }
+
+    fn eval_term(&self, term: &str) -> BTreeMap<String, f64> {
+        self.postings
+            .get(term)
+            .map(|docs| {
+                let idf = self.idf(docs);
+                docs.iter()
+                    .map(|(id, positions)| {
+                        (id.clone(), tf_weight(positions.len()) * idf * self.norm(id))
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(BTreeMap::new)
+    }
+
+    fn eval_prefix(&self, prefix: &str) -> BTreeMap<String, f64> {
+        let mut result = BTreeMap::new();
+        let range = self.postings
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|&(term, _)| term.starts_with(prefix));
+
+        for (_, docs) in range {
+            let idf = self.idf(docs);
+            for (id, positions) in docs {
+                *result.entry(id.clone()).or_insert(0.0) +=
+                    tf_weight(positions.len()) * idf * self.norm(id);
+            }
+        }
+        result
+    }
+
+    fn eval_phrase(&self, terms: &[String]) -> BTreeMap<String, f64> {
+        let postings = match terms.iter().map(|t| self.postings.get(t)).collect::<Option<Vec<_>>>() {
+            Some(p) => p,
+            None    => return BTreeMap::new(), // one of the terms is not indexed at all
+        };
+
+        let idf = postings.iter().map(|docs| self.idf(docs)).sum::<f64>();
+        let mut result = BTreeMap::new();
+
+        for (id, first_positions) in postings[0] {
+            let rest = match postings[1..].iter().map(|d| d.get(id)).collect::<Option<Vec<_>>>() {
+                Some(r) => r,
+                None    => continue,
+            };
+
+            let rest = rest
+                .into_iter()
+                .map(|positions| positions.iter().cloned().collect::<BTreeSet<usize>>())
+                .collect::<Vec<_>>();
+
+            let count = first_positions
+                .iter()
+                .filter(|&&start| {
+                    rest.iter()
+                        .enumerate()
+                        .all(|(i, positions)| positions.contains(&(start + i + 1)))
+                })
+                .count();
+
+            if count > 0 {
+                result.insert(id.clone(), tf_weight(count) * idf * self.norm(id));
+            }
+        }
+        result
+    }
+
+}
+
+fn tf_weight(count: usize) -> f64 {
+    1.0 + (count as f64).ln()
+}
+
+fn index_dir_id() -> Result<StoreId> {
+    StoreId::new_baseless(PathBuf::from(INDEX_DIR)).map_err(From::from)
+}
+
+/// The id of the file the index of the entry `id` is persisted in
+fn index_file_id(id: &str) -> Result<StoreId> {
+    StoreId::new_baseless(PathBuf::from(INDEX_DIR).join(id)).map_err(From::from)
+}
+
+/// The key an entry is indexed with
+pub fn local_id(id: &StoreId) -> Result<String> {
+    id.clone().without_base().to_str().map_err(From::from)
+}
+
+/// Collect all string values of the header, except the ones in the `imag` section
+fn collect_header_strings<'a>(header: &'a Value, texts: &mut Vec<&'a str>) {
+    fn collect<'a>(value: &'a Value, texts: &mut Vec<&'a str>) {
+        match *value {
+            Value::String(ref s) => texts.push(s),
+            Value::Array(ref a)  => for v in a { collect(v, texts) },
+            Value::Table(ref t)  => for v in t.values() { collect(v, texts) },
+            _                    => {},
+        }
+    }
+
+    if let Value::Table(ref t) = *header {
+        for (k, v) in t {
+            if k != "imag" {
+                collect(v, texts);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::*;
+    use query::Query;
+
+    fn entry(id: &str, header: Option<&str>, content: &str) -> Entry {
+        let id        = StoreId::new_baseless(PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id);
+        if let Some(header) = header {
+            let h : Value = ::toml::de::from_str(header).unwrap();
+            for (k, v) in h.as_table().unwrap().clone() {
+                entry.get_header_mut().as_table_mut().unwrap().insert(k, v);
+            }
+        }
+        *entry.get_content_mut() = String::from(content);
+        entry
+    }
+
+    fn index() -> FullTextIndex {
+        let mut index = FullTextIndex::new();
+        let _ = index.add(&entry("a", None, "the quick brown fox jumps over the lazy dog")).unwrap();
+        let _ = index.add(&entry("b", None, "a quick brown dog, a quick dog")).unwrap();
+        let _ = index.add(&entry("c", Some("[todo]\ntitle = \"Buy dog food\""), "shopping list")).unwrap();
+        index
+    }
+
+    fn search(index: &FullTextIndex, q: &str) -> Vec<String> {
+        index.search(&Query::parse(q).unwrap()).into_iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn test_term_search_is_ranked() {
+        let index = index();
+        assert_eq!(search(&index, "quick"), vec!["b", "a"]);
+        assert_eq!(search(&index, "dog").len(), 3);
+        assert!(search(&index, "cat").is_empty());
+    }
+
+    #[test]
+    fn test_header_strings_are_indexed() {
+        let index = index();
+        assert_eq!(search(&index, "food"), vec!["c"]);
+        assert!(search(&index, "title").is_empty());
+    }
+
+    #[test]
+    fn test_phrase_search() {
+        let index = index();
+        assert_eq!(search(&index, "\"brown fox\""), vec!["a"]);
+        assert_eq!(search(&index, "\"quick brown\"").len(), 2);
+        assert!(search(&index, "\"fox brown\"").is_empty());
+
+        // phrases do not match across fields
+        assert!(search(&index, "\"food shopping\"").is_empty());
+    }
+
+    #[test]
+    fn test_prefix_search() {
+        let index = index();
+        assert_eq!(search(&index, "sho*"), vec!["c"]);
+        assert_eq!(search(&index, "ju* OR la*"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_boolean_search() {
+        let index = index();
+        assert_eq!(search(&index, "dog -fox"), vec!["b", "c"]);
+        assert_eq!(search(&index, "NOT dog"), Vec::<String>::new());
+        let mut any = search(&index, "fox OR food");
+        any.sort();
+        assert_eq!(any, vec!["a", "c"]);
+        assert_eq!(search(&index, "quick AND (fox OR lazy)"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_update_remove_rename() {
+        let mut index = index();
+        assert!(!index.add(&entry("b", None, "a quick brown dog, a quick dog")).unwrap());
+        assert!(index.add(&entry("a", None, "nothing to see")).unwrap());
+        assert_eq!(search(&index, "fox"), Vec::<String>::new());
+        assert_eq!(search(&index, "nothing"), vec!["a"]);
+
+        assert!(index.rename("a", "d"));
+        assert_eq!(search(&index, "nothing"), vec!["d"]);
+
+        assert!(index.remove("d"));
+        assert!(!index.remove("d"));
+        assert!(search(&index, "nothing").is_empty());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        use libimagstore::store::Store;
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+
+        let backend   = Box::new(InMemoryFileAbstraction::new());
+        let store     = Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap();
+        let mut index = index();
+        index.save(&store).unwrap();
+
+        let loaded = FullTextIndex::load(&store).unwrap();
+        assert_eq!(loaded.len(), index.len());
+        assert_eq!(search(&loaded, "\"brown fox\""), vec!["a"]);
+
+        // Only the changed entry is written
+        assert!(index.remove("a"));
+        index.save_entry(&store, "a").unwrap();
+        let loaded = FullTextIndex::load(&store).unwrap();
+        assert_eq!(loaded.len(), index.len());
+        assert!(search(&loaded, "\"brown fox\"").is_empty());
+
+        FullTextIndex::new().save(&store).unwrap();
+        assert!(FullTextIndex::load(&store).unwrap().is_empty());
+    }
+
+}
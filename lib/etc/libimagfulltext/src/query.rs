@@ -0,0 +1,308 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The query language of the full-text index
+//!
+//! A query is a sequence of terms, which all have to match (implicit `AND`). Terms can be
+//! combined with `AND`, `OR` and `NOT` (or a leading `-`) and grouped with parentheses. A term
+//! ending in `*` matches all terms with that prefix and `"some words"` matches a phrase.
+//!
+//! `AND` binds stronger than `OR`, so `a b OR c` means `(a AND b) OR c`.
+
+use std::str::FromStr;
+
+use error::FullTextErrorKind as FTEK;
+use error::FullTextError as FTE;
+use error::Result;
+use tokenizer::tokenize;
+
+/// A parsed query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// A single term
+    Term(String),
+
+    /// All terms starting with the string
+    Prefix(String),
+
+    /// The terms, directly following each other
+    Phrase(Vec<String>),
+
+    /// All subqueries have to match
+    And(Vec<Query>),
+
+    /// Any of the subqueries has to match
+    Or(Vec<Query>),
+
+    /// The subquery must not match
+    Not(Box<Query>),
+}
+
+impl Query {
+
+    /// Parse a query string
+    pub fn parse(s: &str) -> Result<Query> {
+        let tokens = lex(s)?;
+        if tokens.is_empty() {
+            return Err(parse_error("empty query"));
+        }
+
+        let mut parser = Parser { tokens: tokens, pos: 0 };
+        let query      = parser.parse_or()?;
+
+        match parser.peek() {
+            None       => Ok(query),
+            Some(&QToken::Close) => Err(parse_error("unbalanced ')'")),
+            Some(t)    => Err(parse_error(&format!("unexpected {:?}", t))),
+        }
+    }
+
+}
+
+impl FromStr for Query {
+    type Err = FTE;
+
+    fn from_str(s: &str) -> Result<Query> {
+        Query::parse(s)
+    }
+}
+
+fn parse_error(reason: &str) -> FTE {
+    FTE::from_kind(FTEK::QueryParseError(String::from(reason)))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum QToken {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Quoted(String),
+    Word(String),
+}
+
+fn lex(s: &str) -> Result<Vec<QToken>> {
+    let mut tokens = vec![];
+    let mut chars  = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => continue,
+            '(' => tokens.push(QToken::Open),
+            ')' => tokens.push(QToken::Close),
+            '-' => tokens.push(QToken::Not),
+            '"' => {
+                let mut phrase = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    phrase.push(c);
+                }
+
+                if !closed {
+                    return Err(parse_error("unbalanced '\"'"));
+                }
+                tokens.push(QToken::Quoted(phrase));
+            },
+            c => {
+                let mut word = String::new();
+                word.push(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' || next == '"' {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+
+                tokens.push(match word.as_ref() {
+                    "AND" => QToken::And,
+                    "OR"  => QToken::Or,
+                    "NOT" => QToken::Not,
+                    _     => QToken::Word(word),
+                });
+            },
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<QToken>,
+    pos: usize,
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&QToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<QToken> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut queries = vec![self.parse_and()?];
+        while let Some(&QToken::Or) = self.peek() {
+            let _ = self.next();
+            queries.push(self.parse_and()?);
+        }
+
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut queries = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                None | Some(&QToken::Close) | Some(&QToken::Or) => break,
+                Some(&QToken::And) => { let _ = self.next(); },
+                Some(_) => {},
+            }
+            queries.push(self.parse_unary()?);
+        }
+
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn parse_unary(&mut self) -> Result<Query> {
+        match self.peek() {
+            Some(&QToken::Not) => {
+                let _ = self.next();
+                self.parse_unary().map(Box::new).map(Query::Not)
+            },
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Query> {
+        match self.next() {
+            Some(QToken::Open) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(QToken::Close) => Ok(query),
+                    _                   => Err(parse_error("unbalanced '('")),
+                }
+            },
+            Some(QToken::Quoted(phrase)) => terms_to_query(tokenize(&phrase), &phrase),
+            Some(QToken::Word(word)) => {
+                if word.ends_with('*') {
+                    let mut prefix = &word[..];
+                    while prefix.ends_with('*') {
+                        prefix = &prefix[..prefix.len() - 1];
+                    }
+                    let mut terms = tokenize(prefix);
+                    if terms.len() != 1 {
+                        return Err(parse_error(&format!("invalid prefix '{}'", word)));
+                    }
+                    Ok(Query::Prefix(terms.remove(0)))
+                } else {
+                    terms_to_query(tokenize(&word), &word)
+                }
+            },
+            Some(t) => Err(parse_error(&format!("unexpected {:?}", t))),
+            None    => Err(parse_error("unexpected end of query")),
+        }
+    }
+
+}
+
+fn flatten<F>(mut queries: Vec<Query>, f: F) -> Query
+    where F: FnOnce(Vec<Query>) -> Query
+{
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        f(queries)
+    }
+}
+
+fn terms_to_query(mut terms: Vec<String>, original: &str) -> Result<Query> {
+    match terms.len() {
+        0 => Err(parse_error(&format!("no searchable term in '{}'", original))),
+        1 => Ok(Query::Term(terms.remove(0))),
+        _ => Ok(Query::Phrase(terms)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn term(s: &str) -> Query {
+        Query::Term(String::from(s))
+    }
+
+    #[test]
+    fn test_parse_single_term() {
+        assert_eq!(Query::parse("Foo").unwrap(), term("foo"));
+    }
+
+    #[test]
+    fn test_parse_implicit_and() {
+        assert_eq!(Query::parse("foo bar").unwrap(), Query::And(vec![term("foo"), term("bar")]));
+        assert_eq!(Query::parse("foo AND bar").unwrap(), Query::And(vec![term("foo"), term("bar")]));
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let q = Query::parse("a b OR c").unwrap();
+        assert_eq!(q, Query::Or(vec![Query::And(vec![term("a"), term("b")]), term("c")]));
+
+        let q = Query::parse("a (b OR c)").unwrap();
+        assert_eq!(q, Query::And(vec![term("a"), Query::Or(vec![term("b"), term("c")])]));
+    }
+
+    #[test]
+    fn test_parse_not() {
+        let expected = Query::And(vec![term("a"), Query::Not(Box::new(term("b")))]);
+        assert_eq!(Query::parse("a -b").unwrap(), expected);
+        assert_eq!(Query::parse("a NOT b").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_phrase_and_prefix() {
+        let q = Query::parse("\"Hello World\" imag*").unwrap();
+        let expected = Query::And(vec![
+            Query::Phrase(vec![String::from("hello"), String::from("world")]),
+            Query::Prefix(String::from("imag")),
+        ]);
+        assert_eq!(q, expected);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("(a b").is_err());
+        assert!(Query::parse("a b)").is_err());
+        assert!(Query::parse("\"a b").is_err());
+        assert!(Query::parse("a OR").is_err());
+        assert!(Query::parse("*").is_err());
+    }
+
+}
@@ -0,0 +1,72 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Splitting text into the terms which are put into the full-text index
+
+/// A term and its position in the tokenized text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub term: String,
+    pub position: usize,
+}
+
+/// Split a text into lowercase, alphanumeric terms
+///
+/// Everything which is not alphanumeric separates terms, so "foo-bar" yields "foo" and "bar".
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_lowercase())
+        .collect()
+}
+
+/// Tokenize a text, numbering the tokens starting at `offset`
+pub fn tokenize_from(text: &str, offset: usize) -> Vec<Token> {
+    tokenize(text)
+        .into_iter()
+        .enumerate()
+        .map(|(i, term)| Token { term: term, position: offset + i })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_splits_and_lowercases() {
+        let tokens = tokenize("Hello, World! imag-grep is 2 FAST");
+        assert_eq!(tokens, vec!["hello", "world", "imag", "grep", "is", "2", "fast"]);
+    }
+
+    #[test]
+    fn test_tokenize_empty() {
+        assert!(tokenize("").is_empty());
+        assert!(tokenize(" -- !? ").is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_from_offset() {
+        let tokens = tokenize_from("a b", 10);
+        assert_eq!(tokens[0], Token { term: String::from("a"), position: 10 });
+        assert_eq!(tokens[1], Token { term: String::from("b"), position: 11 });
+    }
+
+}
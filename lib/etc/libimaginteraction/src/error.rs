@@ -48,6 +48,11 @@ error_chain! {
             display("Error while selecting id")
         }
 
+        IdReadError         {
+            description("Error while reading ids from stdin")
+            display("Error while reading ids from stdin")
+        }
+
         ConfigError         {
             description("Configuration error")
             display("Configuration error")
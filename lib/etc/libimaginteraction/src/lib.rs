@@ -36,6 +36,7 @@
 )]
 
 extern crate interactor;
+extern crate atty;
 extern crate ansi_term;
 #[macro_use] extern crate lazy_static;
 extern crate regex;
@@ -17,8 +17,13 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::io::BufRead;
+use std::io::stdin;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
 
+use atty::Stream;
 use clap::{Arg, ArgMatches};
 
 use libimagstore::storeid::StoreId;
@@ -49,22 +54,63 @@ pub fn id_argument_long() -> &'static str {
     "id"
 }
 
+/// Get the ids passed with `--id`, or the ids piped into the command if there are none
 pub fn get_id(matches: &ArgMatches) -> Result<Vec<StoreId>> {
-    matches
-        .values_of(id_argument_name())
-        .ok_or(IE::from_kind(IEK::IdMissingError))
-        .chain_err(|| IEK::CLIError)
-        .and_then(|vals| {
-            vals.into_iter()
-                .fold(Ok(vec![]), |acc, elem| {
-                    acc.and_then(|mut v| {
-                        let elem = StoreId::new_baseless(PathBuf::from(String::from(elem)));
-                        let elem = elem.chain_err(|| IEK::StoreIdParsingError)?;
-                        v.push(elem);
-                        Ok(v)
-                    })
+    match matches.values_of(id_argument_name()) {
+        Some(vals) => vals
+            .into_iter()
+            .fold(Ok(vec![]), |acc, elem| {
+                acc.and_then(|mut v| {
+                    let elem = StoreId::new_baseless(PathBuf::from(String::from(elem)));
+                    let elem = elem.chain_err(|| IEK::StoreIdParsingError)?;
+                    v.push(elem);
+                    Ok(v)
                 })
+            }),
+
+        None => ids_from_stdin()?
+            .ok_or(IE::from_kind(IEK::IdMissingError))
+            .chain_err(|| IEK::CLIError),
+    }
+}
+
+lazy_static! {
+    // stdin can be read only once, so the ids are kept for later calls
+    static ref STDIN_IDS: Mutex<Option<Option<Vec<StoreId>>>> = Mutex::new(None);
+}
+
+/// Get the ids piped into the command, one id per line, as printed with `--ids-out`
+///
+/// Returns `None` if stdin is a terminal or no ids were piped in. Stdin is read on the first call,
+/// later calls return the same ids.
+pub fn ids_from_stdin() -> Result<Option<Vec<StoreId>>> {
+    let mut cache = STDIN_IDS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if cache.is_none() {
+        let ids = if atty::is(Stream::Stdin) {
+            None
+        } else {
+            let stdin = stdin();
+            let ids   = read_ids(stdin.lock())?;
+            if ids.is_empty() { None } else { Some(ids) }
+        };
+
+        *cache = Some(ids);
+    }
+
+    Ok(cache.clone().unwrap_or(None))
+}
+
+/// Read one id per line from `reader`, ignoring empty lines
+fn read_ids<R: BufRead>(reader: R) -> Result<Vec<StoreId>> {
+    reader
+        .lines()
+        .filter(|line| line.as_ref().map(|l| !l.trim().is_empty()).unwrap_or(true))
+        .map(|line| {
+            let line = line.chain_err(|| IEK::IdReadError)?;
+            StoreId::new_baseless(PathBuf::from(line.trim())).chain_err(|| IEK::StoreIdParsingError)
         })
+        .collect()
 }
 
 pub fn get_or_select_id(matches: &ArgMatches, store_path: &PathBuf) -> Result<Vec<StoreId>> {
@@ -80,3 +126,25 @@ pub fn get_or_select_id(matches: &ArgMatches, store_path: &PathBuf) -> Result<Ve
         })
 }
 
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::read_ids;
+
+    #[test]
+    fn test_read_ids() {
+        let input = "notes/a\n\n  diary/2018/01/01  \n";
+        let ids   = read_ids(input.as_bytes()).unwrap();
+
+        assert_eq!(2, ids.len());
+        assert_eq!(&PathBuf::from("notes/a"), ids[0].local());
+        assert_eq!(&PathBuf::from("diary/2018/01/01"), ids[1].local());
+    }
+
+    #[test]
+    fn test_read_ids_absolute() {
+        assert!(read_ids("/tmp/store/notes/a\n".as_bytes()).is_err());
+    }
+
+}
@@ -0,0 +1,48 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The header schemas and migrations of all modules of imag
+//!
+//! Commands which check or migrate the entries of all modules (like `imag-store verify`,
+//! `imag-store migrate` and `imag-diagnostics`) register them from here, so there is only one
+//! list of them. Modules which register a schema or migrations have to be added here.
+
+extern crate libimagstore;
+extern crate libimagentrylink;
+extern crate libimagentryattachment;
+extern crate libimaghabit;
+extern crate libimagtodo;
+
+use libimagstore::migration::MigrationRegistry;
+use libimagstore::store::Result;
+
+/// Register the header schemas of all modules
+pub fn register_schemas() -> Result<()> {
+    libimagentrylink::schema::register_schemas()?;
+    libimagentryattachment::schema::register_schemas()?;
+    libimaghabit::schema::register_schemas()?;
+    libimagtodo::schema::register_schemas()
+}
+
+/// Build the registry of the migrations of all modules
+pub fn migration_registry() -> Result<MigrationRegistry> {
+    let mut registry = MigrationRegistry::new();
+    libimagentrylink::migration::register_migrations(&mut registry)?;
+    Ok(registry)
+}
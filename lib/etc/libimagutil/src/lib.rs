@@ -46,6 +46,7 @@ pub mod debug_result;
 pub mod edit;
 pub mod info_result;
 pub mod key_value_split;
+pub mod merge;
 pub mod variants;
 pub mod warn_exit;
 pub mod warn_result;
@@ -0,0 +1,196 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Line based three-way merge of texts
+
+/// The result of a three-way merge
+#[derive(Clone, Debug, PartialEq)]
+pub struct Merge {
+    text: String,
+    conflicts: usize,
+}
+
+impl Merge {
+
+    /// The merged text, with conflict markers around conflicting changes
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    /// The number of conflicting changes
+    pub fn conflicts(&self) -> usize {
+        self.conflicts
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts != 0
+    }
+
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`
+///
+/// Changes which only one side made are taken, changes both sides made in the same region are
+/// marked like git does it:
+///
+/// ```text
+/// <<<<<<< ours
+/// our lines
+/// =======
+/// their lines
+/// >>>>>>> theirs
+/// ```
+pub fn three_way_merge(base: &str, ours: &str, theirs: &str) -> Merge {
+    let trailing_newline = ours.ends_with('\n') || theirs.ends_with('\n');
+
+    let base   : Vec<&str> = base.lines().collect();
+    let ours   : Vec<&str> = ours.lines().collect();
+    let theirs : Vec<&str> = theirs.lines().collect();
+
+    let in_ours   = matching_lines(&base, &ours);
+    let in_theirs = matching_lines(&base, &theirs);
+
+    let mut lines     : Vec<&str> = vec![];
+    let mut conflicts = 0;
+    let (mut b, mut o, mut t) = (0, 0, 0);
+
+    loop {
+        // The next base line which is unchanged on both sides separates the chunks
+        let sync = (b..base.len()).filter_map(|i| match (in_ours[i], in_theirs[i]) {
+            (Some(oi), Some(ti)) => Some((i, oi, ti)),
+            _                    => None,
+        }).next();
+
+        let (bi, oi, ti) = sync.unwrap_or((base.len(), ours.len(), theirs.len()));
+
+        let base_chunk   = &base[b..bi];
+        let ours_chunk   = &ours[o..oi];
+        let theirs_chunk = &theirs[t..ti];
+
+        if ours_chunk == base_chunk {
+            lines.extend_from_slice(theirs_chunk);
+        } else if theirs_chunk == base_chunk || ours_chunk == theirs_chunk {
+            lines.extend_from_slice(ours_chunk);
+        } else {
+            conflicts += 1;
+            lines.push("<<<<<<< ours");
+            lines.extend_from_slice(ours_chunk);
+            lines.push("=======");
+            lines.extend_from_slice(theirs_chunk);
+            lines.push(">>>>>>> theirs");
+        }
+
+        match sync {
+            Some(_) => {
+                lines.push(base[bi]);
+                b = bi + 1;
+                o = oi + 1;
+                t = ti + 1;
+            },
+            None => break,
+        }
+    }
+
+    let mut text = lines.join("\n");
+    if !text.is_empty() && trailing_newline {
+        text.push('\n');
+    }
+
+    Merge { text: text, conflicts: conflicts }
+}
+
+/// For each line of `a`, the index of the matching line in `b`, if the line is part of the longest
+/// common subsequence of `a` and `b`
+fn matching_lines(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut matches = vec![None; a.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            matches[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod test {
+    use super::three_way_merge;
+
+    #[test]
+    fn test_unchanged() {
+        let merge = three_way_merge("a\nb\n", "a\nb\n", "a\nb\n");
+        assert_eq!("a\nb\n", merge.text());
+        assert!(!merge.has_conflicts());
+    }
+
+    #[test]
+    fn test_changes_of_both_sides() {
+        let base   = "one\ntwo\nthree\nfour\n";
+        let ours   = "one\n2\nthree\nfour\n";
+        let theirs = "one\ntwo\nthree\nfour\nfive\n";
+
+        let merge = three_way_merge(base, ours, theirs);
+        assert_eq!("one\n2\nthree\nfour\nfive\n", merge.text());
+        assert_eq!(0, merge.conflicts());
+    }
+
+    #[test]
+    fn test_same_change_on_both_sides() {
+        let merge = three_way_merge("a\nb\nc", "a\nB\nc", "a\nB\nc");
+        assert_eq!("a\nB\nc", merge.text());
+        assert!(!merge.has_conflicts());
+    }
+
+    #[test]
+    fn test_conflict() {
+        let merge = three_way_merge("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+        assert_eq!(1, merge.conflicts());
+        assert_eq!("a\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nc\n", merge.text());
+    }
+
+    #[test]
+    fn test_deletion_and_change_elsewhere() {
+        let merge = three_way_merge("a\nb\nc\nd\n", "a\nc\nd\n", "a\nb\nc\nD\n");
+        assert_eq!("a\nc\nD\n", merge.text());
+        assert!(!merge.has_conflicts());
+    }
+
+}